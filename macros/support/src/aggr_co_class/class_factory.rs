@@ -6,23 +6,27 @@ use syn::ItemStruct;
 // it leads to an infinite loop.
 pub fn generate(struct_item: &ItemStruct) -> HelperTokenStream {
     let base_interface_idents =
-        crate::co_class::class_factory::get_class_factory_base_interface_idents();
+        crate::co_class::class_factory::get_class_factory_base_interface_idents(false);
     let aggr_map = crate::co_class::class_factory::get_class_factory_aggr_map();
 
     let struct_ident = &struct_item.ident;
-    let class_factory_ident = crate::utils::class_factory_ident(&struct_ident);
+    let class_factory_ident = crate::utils::class_factory_ident(struct_ident);
 
-    let struct_definition =
-        crate::co_class::class_factory::gen_class_factory_struct_definition(&class_factory_ident);
+    let struct_definition = crate::co_class::class_factory::gen_class_factory_struct_definition(
+        &class_factory_ident,
+        false,
+    );
     let lock_server = crate::co_class::class_factory::gen_lock_server();
     let iunknown_impl = crate::co_class::class_factory::gen_iunknown_impl(
         &base_interface_idents,
         &aggr_map,
         &class_factory_ident,
+        false,
     );
     let class_factory_impl = crate::co_class::class_factory::gen_class_factory_impl(
         &base_interface_idents,
         &class_factory_ident,
+        false,
     );
 
     quote! {
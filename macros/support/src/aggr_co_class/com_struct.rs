@@ -10,13 +10,15 @@ use syn::{Ident, ItemStruct};
 pub fn generate(
     aggr_map: &HashMap<Ident, Vec<Ident>>,
     base_interface_idents: &[Ident],
+    threading_model: &Option<Ident>,
     struct_item: &ItemStruct,
 ) -> HelperTokenStream {
     let struct_ident = &struct_item.ident;
     let vis = &struct_item.vis;
 
     let base_fields = co_class::com_struct::gen_base_fields(base_interface_idents);
-    let ref_count_field = co_class::com_struct::gen_ref_count_field();
+    let ref_count_field =
+        co_class::com_struct::gen_ref_count_field(crate::utils::is_free_threaded(threading_model));
     let user_fields = co_class::com_struct::gen_user_fields(struct_item);
     let aggregate_fields = co_class::com_struct::gen_aggregate_fields(aggr_map);
 
@@ -87,10 +87,7 @@ pub fn gen_inner_release(
 
     let release_decrement = crate::co_class::iunknown_impl::gen_release_decrement(&ref_count_ident);
     let release_assign_new_count_to_var =
-        crate::co_class::iunknown_impl::gen_release_assign_new_count_to_var(
-            &ref_count_ident,
-            &ref_count_ident,
-        );
+        crate::co_class::iunknown_impl::gen_release_assign_new_count_to_var(&ref_count_ident);
     let release_new_count_var_zero_check =
         crate::co_class::iunknown_impl::gen_new_count_var_zero_check(&ref_count_ident);
     let release_drops = crate::co_class::iunknown_impl::gen_release_drops(
@@ -107,6 +104,7 @@ pub fn gen_inner_release(
             if #release_new_count_var_zero_check {
                 #non_delegating_iunknown_drop
                 #release_drops
+                com::registration::unlock_module();
             }
 
             #ref_count_ident
@@ -117,7 +115,7 @@ pub fn gen_inner_release(
 fn gen_non_delegating_iunknown_drop() -> HelperTokenStream {
     let non_delegating_iunknown_field_ident = crate::utils::non_delegating_iunknown_field_ident();
     quote!(
-        Box::from_raw(self.#non_delegating_iunknown_field_ident as *mut <dyn com::interfaces::iunknown::IUnknown as com::ComInterface>::VTable);
+        drop(Box::from_raw(self.#non_delegating_iunknown_field_ident as *mut <dyn com::interfaces::iunknown::IUnknown as com::ComInterface>::VTable));
     )
 }
 
@@ -155,7 +153,7 @@ fn gen_inner_query_interface(
 }
 
 /// For an aggregable object, we have to do more work here. We need to
-/// instantiate the non-delegating IUnknown vtable. The unsafe extern "stdcall"
+/// instantiate the non-delegating IUnknown vtable. The unsafe extern "system"
 /// methods belonging to the non-delegating IUnknown vtable are also defined here.
 fn gen_allocate_fn(
     aggr_map: &HashMap<Ident, Vec<Ident>>,
@@ -189,7 +187,7 @@ fn gen_allocate_fn(
     quote!(
         fn allocate(#allocate_parameters) -> Box<#struct_ident> {
             // Non-delegating methods.
-            unsafe extern "stdcall" fn non_delegatingegating_query_interface(
+            unsafe extern "system" fn non_delegatingegating_query_interface(
                 this: *mut *const <dyn com::interfaces::iunknown::IUnknown as com::ComInterface>::VTable,
                 riid: *const com::sys::IID,
                 ppv: *mut *mut std::ffi::c_void,
@@ -198,14 +196,14 @@ fn gen_allocate_fn(
                 (*this).inner_query_interface(riid, ppv)
             }
 
-            unsafe extern "stdcall" fn non_delegatingegating_add_ref(
+            unsafe extern "system" fn non_delegatingegating_add_ref(
                 this: *mut *const <dyn com::interfaces::iunknown::IUnknown as com::ComInterface>::VTable,
             ) -> u32 {
                 let this = this.sub(#non_delegating_iunknown_offset) as *mut #struct_ident;
                 (*this).inner_add_ref()
             }
 
-            unsafe extern "stdcall" fn non_delegatingegating_release(
+            unsafe extern "system" fn non_delegatingegating_release(
                 this: *mut *const <dyn com::interfaces::iunknown::IUnknown as com::ComInterface>::VTable,
             ) -> u32 {
                 let this = this.sub(#non_delegating_iunknown_offset) as *mut #struct_ident;
@@ -231,6 +229,7 @@ fn gen_allocate_fn(
                 #aggregate_fields
                 #user_fields
             };
+            com::registration::lock_module();
             Box::new(out)
         }
     )
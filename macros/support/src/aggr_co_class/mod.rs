@@ -12,15 +12,57 @@ mod iunknown_impl;
 pub fn expand_aggr_co_class(input: &ItemStruct, attr_args: &AttributeArgs) -> TokenStream {
     let base_interface_idents = crate::utils::base_interface_idents(attr_args);
     let aggr_interface_idents = crate::utils::get_aggr_map(attr_args);
-
-    let mut out: Vec<TokenStream> = Vec::new();
-    out.push(com_struct::generate(&aggr_interface_idents, &base_interface_idents, input).into());
-    out.push(
-        com_struct_impl::generate(&base_interface_idents, &aggr_interface_idents, input).into(),
+    let persist_field = crate::utils::persist_stream_field(attr_args);
+    let support_error_info = crate::utils::has_flag(attr_args, "support_error_info");
+    let agile = crate::utils::has_flag(attr_args, "agile");
+    assert!(
+        !crate::utils::has_flag(attr_args, "weak"),
+        "Aggregatable co_classes do not support weak references."
+    );
+    assert!(
+        !crate::utils::has_flag(attr_args, "singleton"),
+        "Aggregatable co_classes cannot be singletons, since every instance has its own outer object."
+    );
+    assert!(
+        !crate::utils::has_flag(attr_args, "licensed"),
+        "Aggregatable co_classes do not support licensing."
     );
-    out.push(crate::co_class::co_class_impl::generate(input).into());
-    out.push(iunknown_impl::generate(input).into());
-    out.push(class_factory::generate(input).into());
+    let cache_class_object = crate::utils::has_flag(attr_args, "cache_class_object");
+    let threading_model =
+        crate::co_class::agile::threading_model(agile, crate::utils::threading_model(attr_args));
+
+    // Interfaces with a vpointer on the object, including those added by options
+    let mut interface_idents = base_interface_idents.clone();
+    interface_idents.extend(crate::co_class::agile::interface_idents(agile, input));
+
+    let out: Vec<TokenStream> = vec![
+        com_struct::generate(
+            &aggr_interface_idents,
+            &interface_idents,
+            &threading_model,
+            input,
+        )
+        .into(),
+        com_struct_impl::generate(&interface_idents, &aggr_interface_idents, input).into(),
+        crate::co_class::co_class_impl::generate(
+            &threading_model,
+            false,
+            cache_class_object,
+            input,
+        )
+        .into(),
+        iunknown_impl::generate(input).into(),
+        class_factory::generate(input).into(),
+        crate::co_class::persist_stream::generate(&base_interface_idents, &persist_field, input)
+            .into(),
+        crate::co_class::support_error_info::generate(
+            &base_interface_idents,
+            support_error_info,
+            input,
+        )
+        .into(),
+        crate::co_class::agile::generate(&base_interface_idents, agile, input).into(),
+    ];
 
     TokenStream::from_iter(out)
 }
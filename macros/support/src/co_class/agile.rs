@@ -0,0 +1,123 @@
+use proc_macro2::TokenStream as HelperTokenStream;
+use quote::{format_ident, quote};
+use syn::{Ident, ItemStruct};
+
+/// The interfaces added by the `agile` option, under names private to the class so that
+/// the base interface generators can use them whether or not the user imported them.
+pub fn interface_idents(agile: bool, struct_item: &ItemStruct) -> Vec<Ident> {
+    if !agile {
+        return Vec::new();
+    }
+    let struct_ident = &struct_item.ident;
+    vec![
+        format_ident!("__{}IMarshal", struct_ident),
+        format_ident!("__{}IAgileObject", struct_ident),
+    ]
+}
+
+/// The threading model of an agile class defaults to "both", since its objects are
+/// called directly from every apartment.
+pub fn threading_model(agile: bool, threading_model: Option<Ident>) -> Option<Ident> {
+    if !agile {
+        return threading_model;
+    }
+    match threading_model {
+        Some(model) if model == "Apartment" => {
+            panic!("An agile co_class cannot use the apartment threading model.")
+        }
+        Some(model) => Some(model),
+        None => Some(format_ident!("Both")),
+    }
+}
+
+/// Generates `IMarshal` and `IAgileObject` for a co_class with the `agile` option.
+/// `IMarshal` is implemented by `com::agile::FreeThreadedMarshaler`.
+pub fn generate(
+    base_interface_idents: &[Ident],
+    agile: bool,
+    struct_item: &ItemStruct,
+) -> HelperTokenStream {
+    if !agile {
+        return quote!();
+    }
+    assert!(
+        !base_interface_idents
+            .iter()
+            .any(|base| base == "IMarshal" || base == "IAgileObject"),
+        "An agile co_class implements IMarshal and IAgileObject itself, remove them from implements(...)."
+    );
+    let struct_ident = &struct_item.ident;
+    let idents = interface_idents(agile, struct_item);
+    let imarshal_ident = &idents[0];
+    let iagile_object_ident = &idents[1];
+
+    quote!(
+        use com::interfaces::{IAgileObject as #iagile_object_ident, IMarshal as #imarshal_ident};
+
+        impl com::interfaces::IMarshal for #struct_ident {
+            unsafe fn get_unmarshal_class(
+                &self,
+                _riid: *const com::sys::IID,
+                _pv: *mut std::ffi::c_void,
+                dest_context: u32,
+                _pv_dest_context: *mut std::ffi::c_void,
+                _mshlflags: u32,
+                pclsid: *mut com::sys::CLSID,
+            ) -> com::sys::HRESULT {
+                com::agile::FreeThreadedMarshaler::get_unmarshal_class(dest_context, pclsid)
+            }
+
+            unsafe fn get_marshal_size_max(
+                &self,
+                _riid: *const com::sys::IID,
+                _pv: *mut std::ffi::c_void,
+                dest_context: u32,
+                _pv_dest_context: *mut std::ffi::c_void,
+                _mshlflags: u32,
+                psize: *mut u32,
+            ) -> com::sys::HRESULT {
+                com::agile::FreeThreadedMarshaler::get_marshal_size_max(dest_context, psize)
+            }
+
+            unsafe fn marshal_interface(
+                &self,
+                stream: *mut com::interfaces::istream::IStreamVPtr,
+                riid: *const com::sys::IID,
+                _pv: *mut std::ffi::c_void,
+                dest_context: u32,
+                _pv_dest_context: *mut std::ffi::c_void,
+                mshlflags: u32,
+            ) -> com::sys::HRESULT {
+                com::agile::FreeThreadedMarshaler::marshal_interface(
+                    self,
+                    stream,
+                    riid,
+                    dest_context,
+                    mshlflags,
+                )
+            }
+
+            unsafe fn unmarshal_interface(
+                &self,
+                stream: *mut com::interfaces::istream::IStreamVPtr,
+                riid: *const com::sys::IID,
+                ppv: *mut *mut std::ffi::c_void,
+            ) -> com::sys::HRESULT {
+                com::agile::FreeThreadedMarshaler::unmarshal_interface(stream, riid, ppv)
+            }
+
+            unsafe fn release_marshal_data(
+                &self,
+                stream: *mut com::interfaces::istream::IStreamVPtr,
+            ) -> com::sys::HRESULT {
+                com::agile::FreeThreadedMarshaler::release_marshal_data(stream)
+            }
+
+            unsafe fn disconnect_object(&self, _reserved: u32) -> com::sys::HRESULT {
+                com::sys::S_OK
+            }
+        }
+
+        impl com::interfaces::IAgileObject for #struct_ident {}
+    )
+}
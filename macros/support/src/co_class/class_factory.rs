@@ -3,12 +3,23 @@ use quote::{format_ident, quote};
 use std::collections::HashMap;
 use syn::ItemStruct;
 
-fn get_iclass_factory_interface_ident() -> Ident {
-    format_ident!("IClassFactory")
+/// The interface of the class factory and its path. Licensed classes get an `IClassFactory2`.
+fn get_iclass_factory_interface(licensed: bool) -> (Ident, HelperTokenStream) {
+    if licensed {
+        (
+            format_ident!("IClassFactory2"),
+            quote!(com::interfaces::iclass_factory2::IClassFactory2),
+        )
+    } else {
+        (
+            format_ident!("IClassFactory"),
+            quote!(com::interfaces::iclass_factory::IClassFactory),
+        )
+    }
 }
 
-pub fn get_class_factory_base_interface_idents() -> Vec<Ident> {
-    vec![get_iclass_factory_interface_ident()]
+pub fn get_class_factory_base_interface_idents(licensed: bool) -> Vec<Ident> {
+    vec![get_iclass_factory_interface(licensed).0]
 }
 
 pub fn get_class_factory_aggr_map() -> HashMap<Ident, Vec<Ident>> {
@@ -17,20 +28,29 @@ pub fn get_class_factory_aggr_map() -> HashMap<Ident, Vec<Ident>> {
 
 // We manually generate a ClassFactory without macros, otherwise
 // it leads to an infinite loop.
-pub fn generate(struct_item: &ItemStruct) -> HelperTokenStream {
+pub fn generate(struct_item: &ItemStruct, singleton: bool, licensed: bool) -> HelperTokenStream {
     // Manually define base_interface_idents and aggr_map usually obtained by
     // parsing attributes.
 
-    let base_interface_idents = get_class_factory_base_interface_idents();
+    let base_interface_idents = get_class_factory_base_interface_idents(licensed);
     let aggr_map = get_class_factory_aggr_map();
 
     let struct_ident = &struct_item.ident;
-    let class_factory_ident = crate::utils::class_factory_ident(&struct_ident);
+    let class_factory_ident = crate::utils::class_factory_ident(struct_ident);
 
-    let struct_definition = gen_class_factory_struct_definition(&class_factory_ident);
+    let struct_definition = gen_class_factory_struct_definition(&class_factory_ident, licensed);
+    let create = gen_create(struct_ident, singleton);
+    let license_check = gen_license_check(struct_ident, licensed);
     let lock_server = gen_lock_server();
-    let iunknown_impl = gen_iunknown_impl(&base_interface_idents, &aggr_map, &class_factory_ident);
-    let class_factory_impl = gen_class_factory_impl(&base_interface_idents, &class_factory_ident);
+    let iunknown_impl = gen_iunknown_impl(
+        &base_interface_idents,
+        &aggr_map,
+        &class_factory_ident,
+        licensed,
+    );
+    let class_factory_impl =
+        gen_class_factory_impl(&base_interface_idents, &class_factory_ident, licensed);
+    let class_factory2_impl = gen_class_factory2_impl(struct_ident, &create, licensed);
 
     quote! {
         #struct_definition
@@ -45,37 +65,124 @@ pub fn generate(struct_item: &ItemStruct) -> HelperTokenStream {
                 // Bringing trait into scope to access IUnknown methods.
                 use com::interfaces::iunknown::IUnknown;
 
-                if aggr != std::ptr::null_mut() {
-                    return com::sys::CLASS_E_NOAGGREGATION;
-                }
-
-                let mut instance = #struct_ident::new();
-                instance.add_ref();
-                let hr = instance.query_interface(riid, ppv);
-                instance.release();
-
-                core::mem::forget(instance);
-                hr
+                #license_check
+                #create
             }
 
             #lock_server
         }
 
+        #class_factory2_impl
+
         #iunknown_impl
 
         #class_factory_impl
     }
 }
 
+/// Licensed classes are only created through `CreateInstance` on licensed machines
+fn gen_license_check(struct_ident: &Ident, licensed: bool) -> HelperTokenStream {
+    if !licensed {
+        return quote!();
+    }
+
+    quote! {
+        let hr = com::license::check_license::<#struct_ident>();
+        if com::sys::FAILED(hr) {
+            *ppv = std::ptr::null_mut::<std::ffi::c_void>();
+            return hr;
+        }
+    }
+}
+
+/// `IClassFactory2` for licensed classes, delegating to their `com::license::License`
+fn gen_class_factory2_impl(
+    struct_ident: &Ident,
+    create: &HelperTokenStream,
+    licensed: bool,
+) -> HelperTokenStream {
+    if !licensed {
+        return quote!();
+    }
+    let class_factory_ident = crate::utils::class_factory_ident(struct_ident);
+
+    quote! {
+        impl com::interfaces::IClassFactory2 for #class_factory_ident {
+            unsafe fn get_lic_info(&self, lic_info: *mut com::sys::LICINFO) -> com::sys::HRESULT {
+                com::license::get_lic_info::<#struct_ident>(lic_info)
+            }
+
+            unsafe fn request_lic_key(
+                &self,
+                _reserved: u32,
+                key: *mut com::sys::BSTR,
+            ) -> com::sys::HRESULT {
+                com::license::request_lic_key::<#struct_ident>(key)
+            }
+
+            unsafe fn create_instance_lic(
+                &self,
+                aggr: *mut *const <dyn com::interfaces::iunknown::IUnknown as com::ComInterface>::VTable,
+                _reserved: *mut *const <dyn com::interfaces::iunknown::IUnknown as com::ComInterface>::VTable,
+                riid: *const com::sys::IID,
+                key: com::sys::BSTR,
+                ppv: *mut *mut std::ffi::c_void,
+            ) -> com::sys::HRESULT {
+                // Bringing trait into scope to access IUnknown methods.
+                use com::interfaces::iunknown::IUnknown;
+
+                let hr = com::license::check_key::<#struct_ident>(key);
+                if com::sys::FAILED(hr) {
+                    *ppv = std::ptr::null_mut::<std::ffi::c_void>();
+                    return hr;
+                }
+                #create
+            }
+        }
+    }
+}
+
+/// Create an instance, or hand out the cached one for a singleton class
+fn gen_create(struct_ident: &Ident, singleton: bool) -> HelperTokenStream {
+    let aggregation_check = quote! {
+        if aggr != std::ptr::null_mut() {
+            return com::sys::CLASS_E_NOAGGREGATION;
+        }
+    };
+
+    if singleton {
+        return quote! {
+            #aggregation_check
+            let cache = <#struct_ident as com::CoClass>::instance_cache()
+                .expect("singleton classes have an instance cache");
+            cache.query(#struct_ident::new, riid, ppv)
+        };
+    }
+
+    quote! {
+        #aggregation_check
+        let mut instance = #struct_ident::new();
+        instance.add_ref();
+        let hr = instance.query_interface(riid, ppv);
+        instance.release();
+
+        core::mem::forget(instance);
+        hr
+    }
+}
+
 // Can't use gen_base_fields here, since user might not have imported IClassFactory.
-pub fn gen_class_factory_struct_definition(class_factory_ident: &Ident) -> HelperTokenStream {
-    let ref_count_field = super::com_struct::gen_ref_count_field();
-    let interface_ident = get_iclass_factory_interface_ident();
+pub fn gen_class_factory_struct_definition(
+    class_factory_ident: &Ident,
+    licensed: bool,
+) -> HelperTokenStream {
+    let ref_count_field = super::com_struct::gen_ref_count_field(false);
+    let (interface_ident, interface_path) = get_iclass_factory_interface(licensed);
     let vptr_field_ident = crate::utils::vptr_field_ident(&interface_ident);
     quote! {
         #[repr(C)]
         pub struct #class_factory_ident {
-            #vptr_field_ident: *const <dyn com::interfaces::iclass_factory::IClassFactory as com::ComInterface>::VTable,
+            #vptr_field_ident: *const <dyn #interface_path as com::ComInterface>::VTable,
             #ref_count_field
         }
     }
@@ -83,9 +190,8 @@ pub fn gen_class_factory_struct_definition(class_factory_ident: &Ident) -> Helpe
 
 pub fn gen_lock_server() -> HelperTokenStream {
     quote! {
-        // TODO: Implement correctly
-        unsafe fn lock_server(&self, _increment: com::sys::BOOL) -> com::sys::HRESULT {
-            com::sys::S_OK
+        unsafe fn lock_server(&self, increment: com::sys::BOOL) -> com::sys::HRESULT {
+            com::registration::lock_server(increment)
         }
     }
 }
@@ -94,10 +200,16 @@ pub fn gen_iunknown_impl(
     base_interface_idents: &[Ident],
     aggr_map: &HashMap<Ident, Vec<Ident>>,
     class_factory_ident: &Ident,
+    licensed: bool,
 ) -> HelperTokenStream {
-    let query_interface = gen_query_interface();
+    let query_interface = gen_query_interface(licensed);
     let add_ref = super::iunknown_impl::gen_add_ref();
-    let release = gen_release(&base_interface_idents, &aggr_map, class_factory_ident);
+    let release = gen_release(
+        base_interface_idents,
+        aggr_map,
+        class_factory_ident,
+        licensed,
+    );
     quote! {
         impl com::interfaces::IUnknown for #class_factory_ident {
             #query_interface
@@ -111,14 +223,14 @@ pub fn gen_release(
     base_interface_idents: &[Ident],
     aggr_map: &HashMap<Ident, Vec<Ident>>,
     struct_ident: &Ident,
+    licensed: bool,
 ) -> HelperTokenStream {
     let ref_count_ident = crate::utils::ref_count_ident();
+    let interface_path = get_iclass_factory_interface(licensed).1;
 
     let release_decrement = super::iunknown_impl::gen_release_decrement(&ref_count_ident);
-    let release_assign_new_count_to_var = super::iunknown_impl::gen_release_assign_new_count_to_var(
-        &ref_count_ident,
-        &ref_count_ident,
-    );
+    let release_assign_new_count_to_var =
+        super::iunknown_impl::gen_release_assign_new_count_to_var(&ref_count_ident);
     let release_new_count_var_zero_check =
         super::iunknown_impl::gen_new_count_var_zero_check(&ref_count_ident);
     let release_drops =
@@ -126,7 +238,7 @@ pub fn gen_release(
 
     quote! {
         unsafe fn release(&self) -> u32 {
-            use com::interfaces::iclass_factory::IClassFactory;
+            use #interface_path;
 
             #release_decrement
             #release_assign_new_count_to_var
@@ -139,8 +251,9 @@ pub fn gen_release(
     }
 }
 
-fn gen_query_interface() -> HelperTokenStream {
-    let vptr_field_ident = crate::utils::vptr_field_ident(&get_iclass_factory_interface_ident());
+fn gen_query_interface(licensed: bool) -> HelperTokenStream {
+    let (interface_ident, interface_path) = get_iclass_factory_interface(licensed);
+    let vptr_field_ident = crate::utils::vptr_field_ident(&interface_ident);
 
     quote! {
         unsafe fn query_interface(&self, riid: *const com::sys::IID, ppv: *mut *mut std::ffi::c_void) -> com::sys::HRESULT {
@@ -148,7 +261,7 @@ fn gen_query_interface() -> HelperTokenStream {
             use com::interfaces::iunknown::IUnknown;
 
             let riid = &*riid;
-            if riid == &<dyn com::interfaces::iunknown::IUnknown as com::ComInterface>::IID || riid == &<dyn com::interfaces::iclass_factory::IClassFactory as com::ComInterface>::IID {
+            if riid == &<dyn com::interfaces::iunknown::IUnknown as com::ComInterface>::IID || <dyn #interface_path as com::ComInterface>::is_iid_in_inheritance_chain(riid) {
                 *ppv = &self.#vptr_field_ident as *const _ as *mut std::ffi::c_void;
                 self.add_ref();
                 com::sys::NOERROR
@@ -163,7 +276,9 @@ fn gen_query_interface() -> HelperTokenStream {
 pub fn gen_class_factory_impl(
     base_interface_idents: &[Ident],
     class_factory_ident: &Ident,
+    licensed: bool,
 ) -> HelperTokenStream {
+    let interface_path = get_iclass_factory_interface(licensed).1;
     let ref_count_field = super::com_struct_impl::gen_allocate_ref_count_field();
     let base_fields = super::com_struct_impl::gen_allocate_base_fields(base_interface_idents);
     let base_inits =
@@ -172,7 +287,7 @@ pub fn gen_class_factory_impl(
     quote! {
         impl #class_factory_ident {
             pub(crate) fn new() -> Box<#class_factory_ident> {
-                use com::interfaces::iclass_factory::IClassFactory;
+                use #interface_path;
 
                 // allocate directly since no macros generated an `allocate` function
                 #base_inits
@@ -1,12 +1,77 @@
 use proc_macro2::TokenStream as HelperTokenStream;
 use quote::quote;
-use syn::ItemStruct;
+use syn::{Fields, Ident, ItemStruct};
 
-pub fn generate(struct_item: &ItemStruct) -> HelperTokenStream {
+pub fn generate(
+    threading_model: &Option<Ident>,
+    singleton: bool,
+    cache_class_object: bool,
+    struct_item: &ItemStruct,
+) -> HelperTokenStream {
     let struct_ident = &struct_item.ident;
+    let threading_model_const = match threading_model {
+        Some(model) => quote! {
+            const THREADING_MODEL: Option<com::ThreadingModel> = Some(com::ThreadingModel::#model);
+        },
+        None => quote!(),
+    };
+    let class_object_cache_fn = if cache_class_object {
+        gen_cache_fn(quote!(class_object_cache), quote!(class_object))
+    } else {
+        quote!()
+    };
+    let instance_cache_fn = if singleton {
+        gen_cache_fn(quote!(instance_cache), quote!(instance))
+    } else {
+        quote!()
+    };
+    let thread_safety_check = if crate::utils::is_free_threaded(threading_model) {
+        gen_thread_safety_check(struct_item)
+    } else {
+        quote!()
+    };
 
     quote! {
         unsafe impl com::CoClass for #struct_ident {
+            #threading_model_const
+            #class_object_cache_fn
+            #instance_cache_fn
         }
+
+        #thread_safety_check
+    }
+}
+
+/// A `CoClass` function returning a cache private to the class
+fn gen_cache_fn(fn_ident: HelperTokenStream, constructor: HelperTokenStream) -> HelperTokenStream {
+    quote! {
+        fn #fn_ident() -> Option<&'static com::registration::ObjectCache> {
+            static CACHE: com::registration::ObjectCache =
+                com::registration::ObjectCache::#constructor();
+            Some(&CACHE)
+        }
+    }
+}
+
+/// Objects that can be called from any thread may only hold `Send + Sync` fields
+///
+/// Once checked, the class is marked as `FreeThreaded` so it can implement agile interfaces.
+fn gen_thread_safety_check(struct_item: &ItemStruct) -> HelperTokenStream {
+    let struct_ident = &struct_item.ident;
+    let field_types = match &struct_item.fields {
+        Fields::Named(f) => f.named.iter().map(|field| &field.ty),
+        _ => panic!("Found non Named fields in struct."),
+    };
+
+    quote! {
+        const _: () = {
+            fn assert_thread_safe<T: Send + Sync + ?Sized>() {}
+            #[allow(dead_code)]
+            fn assert_fields_thread_safe() {
+                #(assert_thread_safe::<#field_types>();)*
+            }
+        };
+
+        unsafe impl com::FreeThreaded for #struct_ident {}
     }
 }
@@ -8,18 +8,22 @@ use syn::{Fields, Ident, ItemStruct};
 /// pub struct _ {
 ///     ..base interface vpointers..
 ///     ..ref count..
+///     ..weak reference control block..
 ///     ..init struct..
 /// }
 pub fn generate(
     aggr_map: &HashMap<Ident, Vec<Ident>>,
     base_interface_idents: &[Ident],
+    threading_model: &Option<Ident>,
+    weak: bool,
     struct_item: &ItemStruct,
 ) -> HelperTokenStream {
     let struct_ident = &struct_item.ident;
     let vis = &struct_item.vis;
 
     let base_fields = gen_base_fields(base_interface_idents);
-    let ref_count_field = gen_ref_count_field();
+    let ref_count_field = gen_ref_count_field(crate::utils::is_free_threaded(threading_model));
+    let weak_reference_field = super::weak_reference::gen_field(weak);
     let user_fields = gen_user_fields(struct_item);
     let aggregate_fields = gen_aggregate_fields(aggr_map);
 
@@ -28,6 +32,7 @@ pub fn generate(
         #vis struct #struct_ident {
             #base_fields
             #ref_count_field
+            #weak_reference_field
             #aggregate_fields
             #user_fields
         }
@@ -36,19 +41,24 @@ pub fn generate(
 
 pub fn gen_base_fields(base_interface_idents: &[Ident]) -> HelperTokenStream {
     let bases_interface_idents = base_interface_idents.iter().map(|base| {
-        let field_ident = crate::utils::vptr_field_ident(&base);
+        let field_ident = crate::utils::vptr_field_ident(base);
         quote!(#field_ident: *const <dyn #base as com::ComInterface>::VTable)
     });
     quote!(#(#bases_interface_idents,)*)
 }
 
-pub fn gen_ref_count_field() -> HelperTokenStream {
+/// The reference count is atomic for classes that can be called from any thread
+pub fn gen_ref_count_field(free_threaded: bool) -> HelperTokenStream {
     let ref_count_ident = crate::utils::ref_count_ident();
-    quote!(#ref_count_ident: std::cell::Cell<u32>,)
+    if free_threaded {
+        quote!(#ref_count_ident: std::sync::atomic::AtomicU32,)
+    } else {
+        quote!(#ref_count_ident: std::cell::Cell<u32>,)
+    }
 }
 
 pub fn gen_aggregate_fields(aggr_map: &HashMap<Ident, Vec<Ident>>) -> HelperTokenStream {
-    let aggregates = aggr_map.iter().map(|(aggr_field_ident, _)| {
+    let aggregates = aggr_map.keys().map(|aggr_field_ident| {
         quote!(
             #aggr_field_ident: *mut *const <dyn com::interfaces::iunknown::IUnknown as com::ComInterface>::VTable
         )
@@ -6,11 +6,12 @@ use syn::{Fields, Ident, ItemStruct};
 pub fn generate(
     aggr_map: &HashMap<Ident, Vec<Ident>>,
     base_interface_idents: &[Ident],
+    weak: bool,
     struct_item: &ItemStruct,
 ) -> HelperTokenStream {
     let struct_ident = &struct_item.ident;
 
-    let allocate_fn = gen_allocate_fn(aggr_map, base_interface_idents, struct_item);
+    let allocate_fn = gen_allocate_fn(aggr_map, base_interface_idents, weak, struct_item);
     let set_aggregate_fns = gen_set_aggregate_fns(aggr_map);
     let get_class_object_fn = gen_get_class_object_fn(struct_item);
 
@@ -27,6 +28,7 @@ pub fn generate(
 pub fn gen_allocate_fn(
     aggr_map: &HashMap<Ident, Vec<Ident>>,
     base_interface_idents: &[Ident],
+    weak: bool,
     struct_item: &ItemStruct,
 ) -> HelperTokenStream {
     let struct_ident = &struct_item.ident;
@@ -39,6 +41,7 @@ pub fn gen_allocate_fn(
     // Syntax for instantiating the fields of the struct.
     let base_fields = gen_allocate_base_fields(base_interface_idents);
     let ref_count_field = gen_allocate_ref_count_field();
+    let weak_reference_field = super::weak_reference::gen_allocate_field(weak);
     let user_fields = gen_allocate_user_fields(struct_item);
     let aggregate_fields = gen_allocate_aggregate_fields(aggr_map);
 
@@ -50,9 +53,11 @@ pub fn gen_allocate_fn(
             let out = #struct_ident {
                 #base_fields
                 #ref_count_field
+                #weak_reference_field
                 #aggregate_fields
                 #user_fields
             };
+            com::registration::lock_module();
             Box::new(out)
         }
     )
@@ -68,7 +73,7 @@ pub fn gen_allocate_function_parameters_signature(struct_item: &ItemStruct) -> H
 }
 
 pub fn gen_allocate_aggregate_fields(aggr_map: &HashMap<Ident, Vec<Ident>>) -> HelperTokenStream {
-    let aggregate_inits = aggr_map.iter().map(|(aggr_field_ident, _)| {
+    let aggregate_inits = aggr_map.keys().map(|aggr_field_ident| {
         quote!(
             #aggr_field_ident: std::ptr::null_mut()
         )
@@ -95,7 +100,7 @@ pub fn gen_allocate_user_fields(struct_item: &ItemStruct) -> HelperTokenStream {
 pub fn gen_allocate_ref_count_field() -> HelperTokenStream {
     let ref_count_ident = crate::utils::ref_count_ident();
     quote!(
-        #ref_count_ident: std::cell::Cell::new(0),
+        #ref_count_ident: Default::default(),
     )
 }
 
@@ -117,7 +122,7 @@ pub fn gen_allocate_base_inits(
     let mut offset_count: usize = 0;
     let base_inits = base_interface_idents.iter().map(|base| {
         let vtable_var_ident = format_ident!("{}_vtable", base.to_string().to_lowercase());
-        let vptr_field_ident = crate::utils::vptr_field_ident(&base);
+        let vptr_field_ident = crate::utils::vptr_field_ident(base);
 
         let out = quote!(
             let #vtable_var_ident = com::vtable!(#struct_ident: #base, #offset_count);
@@ -135,7 +140,7 @@ pub fn gen_allocate_base_inits(
 /// class object.
 pub fn gen_get_class_object_fn(struct_item: &ItemStruct) -> HelperTokenStream {
     let struct_ident = &struct_item.ident;
-    let class_factory_ident = crate::utils::class_factory_ident(&struct_ident);
+    let class_factory_ident = crate::utils::class_factory_ident(struct_ident);
 
     quote!(
         pub fn get_class_object() -> Box<#class_factory_ident> {
@@ -148,7 +153,7 @@ pub fn gen_set_aggregate_fns(aggr_map: &HashMap<Ident, Vec<Ident>>) -> HelperTok
     let mut fns = Vec::new();
     for (aggr_field_ident, aggr_base_interface_idents) in aggr_map.iter() {
         for base in aggr_base_interface_idents {
-            let set_aggregate_fn_ident = crate::utils::set_aggregate_fn_ident(&base);
+            let set_aggregate_fn_ident = crate::utils::set_aggregate_fn_ident(base);
             fns.push(quote!(
                 fn #set_aggregate_fn_ident(&mut self, aggr: com::ComPtr<dyn com::interfaces::iunknown::IUnknown>) {
                     // FaTODO: What happens if we are overwriting an existing aggregate?
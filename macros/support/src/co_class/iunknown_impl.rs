@@ -39,9 +39,7 @@ pub fn gen_add_ref() -> HelperTokenStream {
 pub fn gen_add_ref_implementation() -> HelperTokenStream {
     let ref_count_ident = crate::utils::ref_count_ident();
     quote!(
-        let value = self.#ref_count_ident.get().checked_add(1).expect("Overflow of reference count");
-        self.#ref_count_ident.set(value);
-        value
+        com::refcount::RefCount::increment(&self.#ref_count_ident)
     )
 }
 
@@ -53,8 +51,7 @@ pub fn gen_release(
     let ref_count_ident = crate::utils::ref_count_ident();
 
     let release_decrement = gen_release_decrement(&ref_count_ident);
-    let release_assign_new_count_to_var =
-        gen_release_assign_new_count_to_var(&ref_count_ident, &ref_count_ident);
+    let release_assign_new_count_to_var = gen_release_assign_new_count_to_var(&ref_count_ident);
     let release_new_count_var_zero_check = gen_new_count_var_zero_check(&ref_count_ident);
     let release_drops = gen_release_drops(base_interface_idents, aggr_map, struct_ident);
 
@@ -64,6 +61,7 @@ pub fn gen_release(
             #release_assign_new_count_to_var
             if #release_new_count_var_zero_check {
                 #release_drops
+                com::registration::unlock_module();
             }
 
             #ref_count_ident
@@ -88,7 +86,7 @@ pub fn gen_release_drops(
 }
 
 fn gen_aggregate_drops(aggr_map: &HashMap<Ident, Vec<Ident>>) -> HelperTokenStream {
-    let aggregate_drops = aggr_map.iter().map(|(aggr_field_ident, _)| {
+    let aggregate_drops = aggr_map.keys().map(|aggr_field_ident| {
         quote!(
             if !self.#aggr_field_ident.is_null() {
                 let mut aggr_interface_ptr = com::ComPtr::<dyn com::interfaces::iunknown::IUnknown>::new(self.#aggr_field_ident as *mut _);
@@ -102,9 +100,9 @@ fn gen_aggregate_drops(aggr_map: &HashMap<Ident, Vec<Ident>>) -> HelperTokenStre
 
 fn gen_vptr_drops(base_interface_idents: &[Ident]) -> HelperTokenStream {
     let vptr_drops = base_interface_idents.iter().map(|base| {
-        let vptr_field_ident = crate::utils::vptr_field_ident(&base);
+        let vptr_field_ident = crate::utils::vptr_field_ident(base);
         quote!(
-            Box::from_raw(self.#vptr_field_ident as *mut <dyn #base as com::ComInterface>::VTable);
+            drop(Box::from_raw(self.#vptr_field_ident as *mut <dyn #base as com::ComInterface>::VTable));
         )
     });
 
@@ -113,23 +111,19 @@ fn gen_vptr_drops(base_interface_idents: &[Ident]) -> HelperTokenStream {
 
 fn gen_com_object_drop(struct_ident: &Ident) -> HelperTokenStream {
     quote!(
-        Box::from_raw(self as *const _ as *mut #struct_ident);
+        drop(Box::from_raw(self as *const _ as *mut #struct_ident));
     )
 }
 
 pub fn gen_release_decrement(ref_count_ident: &Ident) -> HelperTokenStream {
     quote!(
-        let value = self.#ref_count_ident.get().checked_sub(1).expect("Underflow of reference count");
-        self.#ref_count_ident.set(value);
+        let value = com::refcount::RefCount::decrement(&self.#ref_count_ident);
     )
 }
 
-pub fn gen_release_assign_new_count_to_var(
-    ref_count_ident: &Ident,
-    new_count_ident: &Ident,
-) -> HelperTokenStream {
+pub fn gen_release_assign_new_count_to_var(new_count_ident: &Ident) -> HelperTokenStream {
     quote!(
-        let #new_count_ident = self.#ref_count_ident.get();
+        let #new_count_ident = value;
     )
 }
 
@@ -177,7 +171,7 @@ pub fn gen_base_match_arms(base_interface_idents: &[Ident]) -> HelperTokenStream
     let base_match_arms = base_interface_idents.iter().map(|base| {
         let match_condition =
             quote!(<dyn #base as com::ComInterface>::is_iid_in_inheritance_chain(riid));
-        let vptr_field_ident = crate::utils::vptr_field_ident(&base);
+        let vptr_field_ident = crate::utils::vptr_field_ident(base);
 
         quote!(
             else if #match_condition {
@@ -3,27 +3,51 @@ use syn::{AttributeArgs, ItemStruct};
 
 use std::iter::FromIterator;
 
+pub mod agile;
 pub mod class_factory;
 pub mod co_class_impl;
 pub mod com_struct;
 pub mod com_struct_impl;
 pub mod iunknown_impl;
+pub mod persist_stream;
+pub mod support_error_info;
+pub mod weak_reference;
 
 pub fn expand_co_class(input: &ItemStruct, attr_args: &AttributeArgs) -> TokenStream {
     let base_interface_idents = crate::utils::base_interface_idents(attr_args);
     let aggr_interface_idents = crate::utils::get_aggr_map(attr_args);
-
-    let mut out: Vec<TokenStream> = Vec::new();
-    out.push(com_struct::generate(&aggr_interface_idents, &base_interface_idents, input).into());
-
-    out.push(
-        com_struct_impl::generate(&aggr_interface_idents, &base_interface_idents, input).into(),
-    );
-
-    out.push(co_class_impl::generate(input).into());
-
-    out.push(iunknown_impl::generate(&base_interface_idents, &aggr_interface_idents, input).into());
-    out.push(class_factory::generate(input).into());
+    let persist_field = crate::utils::persist_stream_field(attr_args);
+    let support_error_info = crate::utils::has_flag(attr_args, "support_error_info");
+    let agile = crate::utils::has_flag(attr_args, "agile");
+    let weak = crate::utils::has_flag(attr_args, "weak");
+    let singleton = crate::utils::has_flag(attr_args, "singleton");
+    let cache_class_object = crate::utils::has_flag(attr_args, "cache_class_object");
+    let licensed = crate::utils::has_flag(attr_args, "licensed");
+    let threading_model = agile::threading_model(agile, crate::utils::threading_model(attr_args));
+
+    // Interfaces with a vpointer on the object, including those added by options
+    let mut interface_idents = base_interface_idents.clone();
+    interface_idents.extend(agile::interface_idents(agile, input));
+    interface_idents.extend(weak_reference::interface_idents(weak, input));
+
+    let out: Vec<TokenStream> = vec![
+        com_struct::generate(
+            &aggr_interface_idents,
+            &interface_idents,
+            &threading_model,
+            weak,
+            input,
+        )
+        .into(),
+        com_struct_impl::generate(&aggr_interface_idents, &interface_idents, weak, input).into(),
+        co_class_impl::generate(&threading_model, singleton, cache_class_object, input).into(),
+        iunknown_impl::generate(&interface_idents, &aggr_interface_idents, input).into(),
+        class_factory::generate(input, singleton, licensed).into(),
+        persist_stream::generate(&base_interface_idents, &persist_field, input).into(),
+        support_error_info::generate(&base_interface_idents, support_error_info, input).into(),
+        agile::generate(&base_interface_idents, agile, input).into(),
+        weak_reference::generate(&base_interface_idents, weak, input).into(),
+    ];
 
     TokenStream::from_iter(out)
 }
@@ -0,0 +1,56 @@
+use proc_macro2::TokenStream as HelperTokenStream;
+use quote::quote;
+use syn::{Ident, ItemStruct};
+
+/// Generates `IPersist` and `IPersistStream` for a co_class with the `persist_stream(field)`
+/// option by delegating to the `com::persist::PersistedState` in `field`.
+pub fn generate(
+    base_interface_idents: &[Ident],
+    persist_field: &Option<Ident>,
+    struct_item: &ItemStruct,
+) -> HelperTokenStream {
+    let persist_field = match persist_field {
+        Some(field) => field,
+        None => return quote!(),
+    };
+    assert!(
+        base_interface_idents
+            .iter()
+            .any(|base| base == "IPersistStream"),
+        "persist_stream requires IPersistStream to be listed in implements(...)."
+    );
+    let struct_ident = &struct_item.ident;
+
+    quote!(
+        impl com::interfaces::IPersist for #struct_ident {
+            unsafe fn get_class_id(&self, class_id: *mut com::sys::CLSID) -> com::sys::HRESULT {
+                self.#persist_field.get_class_id(class_id)
+            }
+        }
+
+        impl com::interfaces::IPersistStream for #struct_ident {
+            unsafe fn is_dirty(&self) -> com::sys::HRESULT {
+                self.#persist_field.is_dirty()
+            }
+
+            unsafe fn load(
+                &self,
+                stream: *mut com::interfaces::istream::IStreamVPtr,
+            ) -> com::sys::HRESULT {
+                self.#persist_field.load(stream)
+            }
+
+            unsafe fn save(
+                &self,
+                stream: *mut com::interfaces::istream::IStreamVPtr,
+                clear_dirty: com::sys::BOOL,
+            ) -> com::sys::HRESULT {
+                self.#persist_field.save(stream, clear_dirty)
+            }
+
+            unsafe fn get_size_max(&self, pcb_size: *mut u64) -> com::sys::HRESULT {
+                self.#persist_field.get_size_max(pcb_size)
+            }
+        }
+    )
+}
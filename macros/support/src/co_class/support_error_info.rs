@@ -0,0 +1,41 @@
+use proc_macro2::TokenStream as HelperTokenStream;
+use quote::quote;
+use syn::{Ident, ItemStruct};
+
+/// Generates `ISupportErrorInfo` for a co_class with the `support_error_info` option.
+/// Error information is reported for every other interface in implements(...).
+pub fn generate(
+    base_interface_idents: &[Ident],
+    support_error_info: bool,
+    struct_item: &ItemStruct,
+) -> HelperTokenStream {
+    if !support_error_info {
+        return quote!();
+    }
+    assert!(
+        base_interface_idents
+            .iter()
+            .any(|base| base == "ISupportErrorInfo"),
+        "support_error_info requires ISupportErrorInfo to be listed in implements(...)."
+    );
+    let struct_ident = &struct_item.ident;
+    let supported = base_interface_idents
+        .iter()
+        .filter(|base| *base != "ISupportErrorInfo");
+
+    quote!(
+        impl com::interfaces::ISupportErrorInfo for #struct_ident {
+            unsafe fn interface_supports_error_info(
+                &self,
+                riid: *const com::sys::IID,
+            ) -> com::sys::HRESULT {
+                let riid = &*riid;
+                if false #(|| <dyn #supported as com::ComInterface>::is_iid_in_inheritance_chain(riid))* {
+                    com::sys::S_OK
+                } else {
+                    com::sys::S_FALSE
+                }
+            }
+        }
+    )
+}
@@ -0,0 +1,73 @@
+use proc_macro2::TokenStream as HelperTokenStream;
+use quote::{format_ident, quote};
+use syn::{Ident, ItemStruct};
+
+/// The interface added by the `weak` option, under a name private to the class so that
+/// the base interface generators can use it whether or not the user imported it.
+pub fn interface_idents(weak: bool, struct_item: &ItemStruct) -> Vec<Ident> {
+    if !weak {
+        return Vec::new();
+    }
+    vec![format_ident!("__{}IWeakReferenceSource", struct_item.ident)]
+}
+
+/// The field holding the weak reference control block, after the reference count.
+pub fn gen_field(weak: bool) -> HelperTokenStream {
+    if !weak {
+        return quote!();
+    }
+    let weak_reference_ident = crate::utils::weak_reference_field_ident();
+    quote!(#weak_reference_ident: com::weak::WeakReferenceSlot,)
+}
+
+pub fn gen_allocate_field(weak: bool) -> HelperTokenStream {
+    if !weak {
+        return quote!();
+    }
+    let weak_reference_ident = crate::utils::weak_reference_field_ident();
+    quote!(#weak_reference_ident: Default::default(),)
+}
+
+/// Generates `IWeakReferenceSource` for a co_class with the `weak` option.
+/// The control block only upgrades while the reference count of the object is not zero.
+pub fn generate(
+    base_interface_idents: &[Ident],
+    weak: bool,
+    struct_item: &ItemStruct,
+) -> HelperTokenStream {
+    if !weak {
+        return quote!();
+    }
+    assert!(
+        !base_interface_idents
+            .iter()
+            .any(|base| base == "IWeakReferenceSource"),
+        "A weak co_class implements IWeakReferenceSource itself, remove it from implements(...)."
+    );
+    let struct_ident = &struct_item.ident;
+    let interface_ident = &interface_idents(weak, struct_item)[0];
+    let ref_count_ident = crate::utils::ref_count_ident();
+    let weak_reference_ident = crate::utils::weak_reference_field_ident();
+
+    quote!(
+        use com::interfaces::IWeakReferenceSource as #interface_ident;
+
+        impl com::interfaces::IWeakReferenceSource for #struct_ident {
+            unsafe fn get_weak_reference(
+                &self,
+                weak_reference: *mut *mut com::interfaces::iweak_reference::IWeakReferenceVPtr,
+            ) -> com::sys::HRESULT {
+                unsafe fn try_add_ref(object: *mut std::ffi::c_void) -> bool {
+                    let object = &*(object as *const #struct_ident);
+                    com::refcount::RefCount::try_increment(&object.#ref_count_ident)
+                }
+
+                self.#weak_reference_ident.get_weak_reference(
+                    self as *const _ as *mut std::ffi::c_void,
+                    try_add_ref,
+                    weak_reference,
+                )
+            }
+        }
+    )
+}
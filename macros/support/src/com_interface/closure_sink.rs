@@ -0,0 +1,62 @@
+use proc_macro2::TokenStream as HelperTokenStream;
+use quote::{format_ident, quote};
+use syn::{FnArg, ItemTrait, ReturnType, TraitItem, TypeParamBound};
+
+/// Implement an interface for `com::interfaces::connection_point::ClosureSink`
+///
+/// The sink holds a tuple with one closure per method, in declaration order. Only
+/// interfaces deriving directly from `IUnknown` are supported as the closures cannot
+/// cover the methods of other parent interfaces.
+pub fn generate(interface: &ItemTrait) -> HelperTokenStream {
+    let base_interface = match interface.supertraits.first() {
+        Some(TypeParamBound::Trait(t)) => t.path.segments.last(),
+        _ => None,
+    };
+    let derives_from_iunknown = match base_interface {
+        Some(segment) => segment.ident == "IUnknown",
+        None => false,
+    };
+    if !derives_from_iunknown {
+        return quote!();
+    }
+
+    let interface_ident = &interface.ident;
+    let mut handler_params = Vec::new();
+    let mut handler_bounds = Vec::new();
+    let mut methods = Vec::new();
+    for (index, trait_item) in interface.items.iter().enumerate() {
+        let method = match trait_item {
+            TraitItem::Method(m) => m,
+            _ => panic!("COM interfaces may only contain methods"),
+        };
+        let handler_param = format_ident!("__F{}", index);
+        let handler_index = syn::Index::from(index);
+        let param_types = method.sig.inputs.iter().filter_map(|param| match param {
+            FnArg::Receiver(_) => None,
+            FnArg::Typed(t) => Some(&t.ty),
+        });
+        let return_type = match &method.sig.output {
+            ReturnType::Default => quote!(()),
+            ReturnType::Type(_, ty) => quote!(#ty),
+        };
+        let (method_sig, args) = crate::utils::sig_with_arg_idents(&method.sig);
+
+        handler_bounds.push(quote!(#handler_param: Fn(#(#param_types),*) -> #return_type));
+        methods.push(quote! {
+            #method_sig {
+                (self.handlers().#handler_index)(#(#args),*)
+            }
+        });
+        handler_params.push(handler_param);
+    }
+
+    quote! {
+        impl <#(#handler_params),*> #interface_ident
+            for com::interfaces::connection_point::ClosureSink<dyn #interface_ident, (#(#handler_params,)*)>
+        where
+            #(#handler_bounds),*
+        {
+            #(#methods)*
+        }
+    }
+}
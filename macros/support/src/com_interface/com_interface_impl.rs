@@ -4,17 +4,28 @@ use proc_macro2::TokenStream as HelperTokenStream;
 use quote::quote;
 use syn::{ItemTrait, TypeParamBound};
 
-pub fn generate(trait_item: &ItemTrait) -> HelperTokenStream {
+/// Implement `ComInterface` and `ProductionComInterface` for the interface
+///
+/// Only free threaded classes may implement agile interfaces, since their `ComRc`s are
+/// `Send` and `Sync`. Interfaces inheriting from an agile one build its VTable through
+/// `ProductionComInterface` as well, so the bound applies to them too.
+pub fn generate(trait_item: &ItemTrait, agile: bool) -> HelperTokenStream {
     let interface_ident = &trait_item.ident;
     let vtable_ident = vtable::ident(&interface_ident.to_string());
     let iid_ident = iid::ident(interface_ident);
-    let vtable_macro = vtable_macro::ident(&interface_ident);
+    let vtable_macro = vtable_macro::ident(interface_ident);
     let parent = if let Some(TypeParamBound::Trait(t)) = trait_item.supertraits.first() {
         quote! { #t }
     } else {
         quote! { #interface_ident }
     };
 
+    let class_bound = if agile {
+        quote! { #interface_ident + com::FreeThreaded }
+    } else {
+        quote! { #interface_ident }
+    };
+
     quote! {
         unsafe impl com::ComInterface for dyn #interface_ident {
             type VTable = #vtable_ident;
@@ -23,7 +34,7 @@ pub fn generate(trait_item: &ItemTrait) -> HelperTokenStream {
 
         }
 
-        impl <C: #interface_ident> com::ProductionComInterface<C> for dyn #interface_ident {
+        impl <C: #class_bound> com::ProductionComInterface<C> for dyn #interface_ident {
             fn vtable<O: com::offset::Offset>() -> Self::VTable {
                 #vtable_macro!(C, O)
             }
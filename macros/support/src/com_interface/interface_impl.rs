@@ -1,8 +1,8 @@
 use super::vptr;
 
 use proc_macro2::{Ident, TokenStream as HelperTokenStream};
-use quote::{format_ident, quote, ToTokens};
-use syn::{FnArg, ItemTrait, TraitItem, TraitItemMethod};
+use quote::{format_ident, quote};
+use syn::{FnArg, ItemTrait, ReturnType, TraitItem, TraitItemMethod, Type};
 
 pub fn generate(interface: &ItemTrait) -> HelperTokenStream {
     let interface_ident = &interface.ident;
@@ -29,7 +29,7 @@ pub fn generate(interface: &ItemTrait) -> HelperTokenStream {
 }
 
 fn gen_impl_method(interface_ident: &Ident, method: &TraitItemMethod) -> HelperTokenStream {
-    let method_sig = &method.sig;
+    let (method_sig, args) = crate::utils::sig_with_arg_idents(&method.sig);
     let vptr_ident = vptr::ident(&interface_ident.to_string());
     let method_ident = format_ident!(
         "{}",
@@ -37,20 +37,184 @@ fn gen_impl_method(interface_ident: &Ident, method: &TraitItemMethod) -> HelperT
     );
     let interface_ptr_ident = format_ident!("interface_ptr");
 
-    let mut params = Vec::new();
-    for param in method.sig.inputs.iter() {
-        match param {
-            FnArg::Receiver(_n) => params.push(quote!(#interface_ptr_ident)),
-            // TODO: This may go wrong, I am using everything on the LHS.
-            FnArg::Typed(n) => params.push(n.pat.to_token_stream()),
-        }
-    }
-
     quote!(
         #[allow(missing_docs)]
         #method_sig {
             let #interface_ptr_ident = self.as_raw() as *mut #vptr_ident;
-            ((**#interface_ptr_ident).#method_ident)(#(#params),*)
+            ((**#interface_ptr_ident).#method_ident)(#interface_ptr_ident, #(#args),*)
         }
     )
 }
+
+/// A parameter of a method taking wide strings
+enum WideParam {
+    /// A `PCWSTR` in-param, passed as `&str` to the wrapper
+    In,
+    /// A `*mut PWSTR` out-param, passed as `&mut Option<CoTaskMemString>` to the wrapper
+    Out,
+    /// Any other parameter, passed through
+    Other,
+}
+
+fn wide_param(ty: &Type) -> WideParam {
+    fn is_named(ty: &Type, name: &str) -> bool {
+        match ty {
+            Type::Path(p) => p
+                .path
+                .segments
+                .last()
+                .map_or(false, |segment| segment.ident == name),
+            _ => false,
+        }
+    }
+
+    match ty {
+        _ if is_named(ty, "PCWSTR") => WideParam::In,
+        Type::Ptr(p) if p.mutability.is_some() && is_named(&p.elem, "PWSTR") => WideParam::Out,
+        _ => WideParam::Other,
+    }
+}
+
+/// Provided methods taking `&str` for the `PCWSTR` params of each method, and
+/// `&mut Option<CoTaskMemString>` for its `*mut PWSTR` out-params
+///
+/// A method `set_name(&self, name: PCWSTR)` gets a `set_name_str(&self, name: &str)`,
+/// which passes a temporary null terminated UTF-16 copy of `name`. The wrappers require
+/// `Self: Sized`, so they are available on `ComRc` and `ComPtr` without affecting the
+/// VTable or the implementations of the interface.
+pub fn gen_wide_string_methods(interface: &ItemTrait) -> Vec<TraitItem> {
+    let mut wrappers = Vec::new();
+    for trait_item in &interface.items {
+        let method = match trait_item {
+            TraitItem::Method(m) => m,
+            _ => continue,
+        };
+
+        let mut has_wide_params = false;
+        let mut params = Vec::new();
+        let mut conversions = Vec::new();
+        let mut args = Vec::new();
+        let mut out_conversions = Vec::new();
+        // The arguments are bound to generated names, as the declared patterns may not be
+        // identifiers
+        let (method_sig, idents) = crate::utils::sig_with_arg_idents(&method.sig);
+        let typed_params = method_sig.inputs.iter().filter_map(|param| match param {
+            FnArg::Receiver(_) => None,
+            FnArg::Typed(t) => Some(t),
+        });
+        for (param, ident) in typed_params.zip(&idents) {
+            let ty = &param.ty;
+            match wide_param(ty) {
+                WideParam::In => {
+                    has_wide_params = true;
+                    params.push(quote!(#ident: &str));
+                    conversions.push(quote! {
+                        let #ident = #ident.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+                    });
+                    args.push(quote!(#ident.as_ptr()));
+                }
+                WideParam::Out => {
+                    has_wide_params = true;
+                    params.push(quote!(#ident: &mut Option<com::alloc::CoTaskMemString>));
+                    let raw = format_ident!("{}_raw", ident);
+                    conversions.push(quote! {
+                        let mut #raw = std::ptr::null_mut::<u16>();
+                    });
+                    args.push(quote!(&mut #raw));
+                    out_conversions.push(quote! {
+                        *#ident = com::alloc::CoTaskMemString::from_raw(#raw);
+                    });
+                }
+                WideParam::Other => {
+                    params.push(quote!(#ident: #ty));
+                    args.push(quote!(#ident));
+                }
+            }
+        }
+        if !has_wide_params {
+            continue;
+        }
+
+        let method_ident = &method.sig.ident;
+        let wrapper_ident = format_ident!("{}_str", method_ident);
+        let output = &method.sig.output;
+        let doc = format!(
+            "Calls `{}` with Rust strings in place of wide strings",
+            method_ident
+        );
+        wrappers.push(syn::parse_quote! {
+            #[doc = #doc]
+            unsafe fn #wrapper_ident(&self, #(#params),*) #output
+            where
+                Self: Sized,
+            {
+                #(#conversions)*
+                let result = self.#method_ident(#(#args),*);
+                #(#out_conversions)*
+                result
+            }
+        });
+    }
+    wrappers
+}
+
+/// Whether `method` returns a `HRESULT`
+pub fn returns_hresult(method: &TraitItemMethod) -> bool {
+    match &method.sig.output {
+        ReturnType::Type(_, ty) => match &**ty {
+            Type::Path(p) => p
+                .path
+                .segments
+                .last()
+                .map_or(false, |segment| segment.ident == "HRESULT"),
+            _ => false,
+        },
+        ReturnType::Default => false,
+    }
+}
+
+/// Provided methods turning the failing `HRESULT`s of each method into a `ComError`
+///
+/// A method `eat(&self) -> HRESULT` gets a `try_eat(&self) -> Result<HRESULT, ComError>`,
+/// which attaches the error information the server set if the object supports
+/// `ISupportErrorInfo` for the interface. Like the wide string wrappers they require
+/// `Self: Sized`.
+pub fn gen_checked_methods(interface: &ItemTrait) -> Vec<TraitItem> {
+    let interface_ident = &interface.ident;
+    let mut wrappers = Vec::new();
+    for trait_item in &interface.items {
+        let method = match trait_item {
+            TraitItem::Method(m) if returns_hresult(m) => m,
+            _ => continue,
+        };
+
+        let (method_sig, args) = crate::utils::sig_with_arg_idents(&method.sig);
+        let params = method_sig.inputs.iter().skip(1);
+        let method_ident = &method.sig.ident;
+        let wrapper_ident = format_ident!("try_{}", method_ident);
+        let doc = format!(
+            "Calls `{}`, turning a failing `HRESULT` into a `ComError`",
+            method_ident
+        );
+        wrappers.push(syn::parse_quote! {
+            #[doc = #doc]
+            #[allow(clippy::too_many_arguments)]
+            unsafe fn #wrapper_ident(&self, #(#params),*) -> Result<com::sys::HRESULT, com::error::ComError>
+            where
+                Self: Sized,
+            {
+                let hr = self.#method_ident(#(#args),*);
+                if com::sys::FAILED(hr) {
+                    Err(com::error::ComError::from_call(
+                        self,
+                        &<dyn #interface_ident as com::ComInterface>::IID,
+                        hr,
+                    ))
+                } else {
+                    Ok(hr)
+                }
+            }
+        });
+    }
+    wrappers
+}
@@ -0,0 +1,101 @@
+use super::vtable;
+use proc_macro2::TokenStream as HelperTokenStream;
+use quote::{quote, quote_spanned};
+use syn::{Attribute, ItemTrait, Lit, LitInt, Meta, NestedMeta, TraitItem, TypeParamBound};
+
+/// Where a method sits in the VTable, from its `#[skip_slots(N)]` and `#[slot(N)]`
+/// attributes
+#[derive(Default)]
+pub struct MethodLayout {
+    /// The number of reserved slots before the method
+    pub skipped: usize,
+    /// The VTable slot the method is expected at
+    slot: Option<LitInt>,
+}
+
+/// Remove the `#[skip_slots(N)]` and `#[slot(N)]` attributes from the methods of
+/// `interface`, returning the layout of every method in order
+pub fn take_attrs(interface: &mut ItemTrait) -> Vec<MethodLayout> {
+    let methods = interface.items.iter_mut().filter_map(|item| match item {
+        TraitItem::Method(m) => Some(m),
+        _ => None,
+    });
+    methods
+        .map(|method| {
+            let mut layout = MethodLayout::default();
+            method.attrs.retain(|attr| {
+                if attr.path.is_ident("skip_slots") {
+                    layout.skipped += int_arg(attr)
+                        .and_then(|count| count.base10_parse::<usize>().ok())
+                        .expect(
+                            "Expected #[skip_slots(N)] with the number of VTable slots to reserve",
+                        );
+                } else if attr.path.is_ident("slot") {
+                    layout.slot = Some(
+                        int_arg(attr)
+                            .expect("Expected #[slot(N)] with the index of the VTable slot"),
+                    );
+                } else {
+                    return true;
+                }
+                false
+            });
+            layout
+        })
+        .collect()
+}
+
+fn int_arg(attr: &Attribute) -> Option<LitInt> {
+    match attr.parse_meta() {
+        Ok(Meta::List(list)) if list.nested.len() == 1 => match list.nested.first() {
+            Some(NestedMeta::Lit(Lit::Int(int))) => Some(int.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Assert at compile time that the VTable is made of a function pointer for every
+/// inherited, reserved and declared method, and that its slots are those `slots = N`
+/// and `#[slot(N)]` expect
+pub fn generate(
+    interface: &ItemTrait,
+    total: Option<&LitInt>,
+    layouts: &[MethodLayout],
+) -> HelperTokenStream {
+    let vtable_ident = vtable::ident(&interface.ident.to_string());
+    let inherited = match interface.supertraits.first() {
+        Some(TypeParamBound::Trait(base)) => quote! {
+            std::mem::size_of::<<dyn #base as com::ComInterface>::VTable>() / PTR
+        },
+        _ => quote!(0),
+    };
+
+    let mut methods = 0;
+    let mut slots = Vec::new();
+    for layout in layouts {
+        methods += layout.skipped;
+        if let Some(slot) = &layout.slot {
+            slots.push(quote_spanned! {slot.span()=>
+                let _: [(); 1] = [(); (INHERITED + #methods == #slot) as usize];
+            });
+        }
+        methods += 1;
+    }
+    let total = total.map(|total| {
+        quote_spanned! {total.span()=>
+            let _: [(); 1] = [(); (INHERITED + #methods == #total) as usize];
+        }
+    });
+
+    quote! {
+        #[allow(clippy::eq_op, clippy::identity_op)]
+        const _: () = {
+            const PTR: usize = std::mem::size_of::<usize>();
+            const INHERITED: usize = #inherited;
+            let _: [(); 1] = [(); (std::mem::size_of::<#vtable_ident>() == (INHERITED + #methods) * PTR) as usize];
+            #total
+            #(#slots)*
+        };
+    }
+}
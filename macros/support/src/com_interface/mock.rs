@@ -0,0 +1,142 @@
+use proc_macro2::{Ident, TokenStream as HelperTokenStream};
+use quote::{format_ident, quote};
+use syn::{FnArg, ItemTrait, ReturnType, TraitItem, TraitItemMethod};
+
+/// Generate a `Mock{Interface}` type for an interface trait
+///
+/// The mock type only holds the expectations. The COM object itself is a
+/// `com::mock::MockObject` which implements the interface by forwarding each call
+/// to the expectation of the same name.
+pub fn generate(interface: &ItemTrait) -> HelperTokenStream {
+    let interface_ident = &interface.ident;
+    let vis = &interface.vis;
+    let mock_ident = ident(interface_ident);
+    let parent_mock = quote! {
+        <<dyn #interface_ident as com::ComInterface>::Super as com::mock::MockInterface>::Mock
+    };
+
+    let mut expect_methods = Vec::new();
+    let mut impl_methods = Vec::new();
+    for trait_item in &interface.items {
+        match trait_item {
+            TraitItem::Method(m) => {
+                expect_methods.push(gen_expect_method(m));
+                impl_methods.push(gen_impl_method(m));
+            }
+            _ => panic!("COM interfaces may only contain methods"),
+        }
+    }
+
+    quote! {
+        #[allow(missing_docs)]
+        #[derive(Clone)]
+        #vis struct #mock_ident {
+            parent: #parent_mock,
+        }
+
+        #[allow(missing_docs)]
+        impl #mock_ident {
+            pub fn new() -> Self {
+                <Self as com::mock::MockHandle>::from_mock(com::mock::Mock::new())
+            }
+
+            #(#expect_methods)*
+        }
+
+        impl Default for #mock_ident {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl com::mock::MockHandle for #mock_ident {
+            fn from_mock(mock: com::mock::Mock) -> Self {
+                #mock_ident {
+                    parent: com::mock::MockHandle::from_mock(mock),
+                }
+            }
+        }
+
+        impl std::ops::Deref for #mock_ident {
+            type Target = #parent_mock;
+
+            fn deref(&self) -> &Self::Target {
+                &self.parent
+            }
+        }
+
+        impl com::mock::MockInterface for dyn #interface_ident {
+            type Mock = #mock_ident;
+        }
+
+        // Generic so that mocks of agile interfaces, which a `MockObject` can't implement,
+        // only fail to compile when converted
+        impl<I> std::convert::From<#mock_ident> for com::ComRc<I>
+        where
+            I: com::mock::MockInterface<Mock = #mock_ident>
+                + com::ProductionComInterface<com::mock::MockObject<I>>
+                + ?Sized,
+        {
+            fn from(mock: #mock_ident) -> Self {
+                com::mock::MockObject::<I>::create(&mock)
+            }
+        }
+
+        impl <I: com::ComInterface + ?Sized> #interface_ident for com::mock::MockObject<I> {
+            #(#impl_methods)*
+        }
+    }
+}
+
+pub fn ident(interface_ident: &Ident) -> Ident {
+    format_ident!("Mock{}", interface_ident)
+}
+
+/// The signature of a method as a closure type, e.g. `dyn FnMut(u32) -> HRESULT`
+fn gen_handler_type(method: &TraitItemMethod) -> HelperTokenStream {
+    let params = method.sig.inputs.iter().filter_map(|param| match param {
+        FnArg::Receiver(_) => None,
+        FnArg::Typed(t) => Some(&t.ty),
+    });
+    let return_type = match &method.sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+
+    quote!(dyn FnMut(#(#params),*) -> #return_type)
+}
+
+fn gen_expect_method(method: &TraitItemMethod) -> HelperTokenStream {
+    let method_ident = &method.sig.ident;
+    let method_name = method_ident.to_string();
+    let expect_ident = format_ident!("expect_{}", method_ident);
+    let handler_type = gen_handler_type(method);
+
+    quote! {
+        pub fn #expect_ident(&self) -> com::mock::Expectation<'_, #handler_type> {
+            com::mock::Mock::expect(self, #method_name)
+        }
+    }
+}
+
+fn gen_impl_method(method: &TraitItemMethod) -> HelperTokenStream {
+    let (method_sig, args) = crate::utils::sig_with_arg_idents(&method.sig);
+    let method_name = method.sig.ident.to_string();
+    let handler_type = gen_handler_type(method);
+    let return_type = match &method.sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+
+    quote! {
+        #method_sig {
+            #[allow(unused_imports)]
+            use com::mock::{DefaultValue as _, UnexpectedValue as _};
+            com::mock::MockObject::mock(self).call::<#handler_type, _>(
+                #method_name,
+                || (&com::mock::Unexpected::<#return_type>(std::marker::PhantomData)).value(),
+                |handler| handler(#(#args),*),
+            )
+        }
+    }
+}
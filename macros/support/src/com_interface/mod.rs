@@ -1,16 +1,24 @@
 extern crate proc_macro;
 
+mod closure_sink;
 mod com_interface_impl;
 mod iid;
 mod interface_impl;
+mod layout;
+#[cfg(feature = "mock")]
+mod mock;
+mod proxy;
 mod vptr;
 mod vtable;
 mod vtable_macro;
 
 use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as HelperTokenStream, TokenTree};
 
-use quote::ToTokens;
-use syn::{ItemStruct, ItemTrait};
+use quote::{quote, ToTokens};
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::{ItemStruct, ItemTrait, Lit, Meta, NestedMeta, Token};
 
 use std::iter::FromIterator;
 
@@ -18,19 +26,84 @@ use std::iter::FromIterator;
 
 // Expansion entry point
 pub fn expand_com_interface(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let input = syn::parse_macro_input!(item as ItemTrait);
+    let mut input = syn::parse_macro_input!(item as ItemTrait);
+    let (iid, options) = split_attr(attr);
+    let mut agile = false;
+    let mut slots = None;
+    for option in &options {
+        match option {
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("agile") => agile = true,
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("slots") => match &nv.lit {
+                Lit::Int(count) => slots = Some(count.clone()),
+                _ => panic!("Expected `slots = N` with the number of VTable slots"),
+            },
+            _ => panic!("Unknown [com_interface] option, expected `agile` or `slots = N`"),
+        }
+    }
+    let layouts = layout::take_attrs(&mut input);
 
-    let mut out: Vec<TokenStream> = Vec::new();
-    out.push(input.to_token_stream().into());
-    out.push(vtable::generate(&input).into());
-    out.push(vptr::generate(&input.ident).into());
-    out.push(interface_impl::generate(&input).into());
-    out.push(com_interface_impl::generate(&input).into());
-    out.push(iid::generate(&attr, &input.ident).into());
+    // The trait as declared, plus wrappers for the methods taking wide strings and
+    // returning a `HRESULT`
+    let mut interface = input.clone();
+    interface
+        .items
+        .extend(interface_impl::gen_wide_string_methods(&input));
+    if input.ident != "IUnknown" {
+        interface
+            .items
+            .extend(interface_impl::gen_checked_methods(&input));
+    }
+
+    #[cfg_attr(not(feature = "mock"), allow(unused_mut))]
+    let mut out: Vec<TokenStream> = vec![
+        interface.to_token_stream().into(),
+        vtable::generate(&input, &layouts).into(),
+        layout::generate(&input, slots.as_ref(), &layouts).into(),
+        vptr::generate(&input.ident).into(),
+        interface_impl::generate(&input).into(),
+        com_interface_impl::generate(&input, agile).into(),
+        iid::generate(&iid, &input.ident).into(),
+        closure_sink::generate(&input).into(),
+        proxy::generate(&input).into(),
+        gen_agile_impl(agile, &input).into(),
+    ];
+
+    #[cfg(feature = "mock")]
+    {
+        if input.ident != "IUnknown" {
+            out.push(mock::generate(&input).into());
+        }
+    }
 
     TokenStream::from_iter(out)
 }
 
+/// Split the attribute into the IID and the options following it, e.g. `"...", agile`
+fn split_attr(attr: TokenStream) -> (TokenStream, Punctuated<NestedMeta, Token![,]>) {
+    let mut tokens = HelperTokenStream::from(attr).into_iter();
+    let iid: HelperTokenStream = tokens
+        .by_ref()
+        .take_while(|token| match token {
+            TokenTree::Punct(p) => p.as_char() != ',',
+            _ => true,
+        })
+        .collect();
+    let options = Punctuated::<NestedMeta, Token![,]>::parse_terminated
+        .parse2(tokens.collect())
+        .expect("[com_interface] options must follow the IID, e.g. `agile`");
+    (iid.into(), options)
+}
+
+fn gen_agile_impl(agile: bool, interface: &ItemTrait) -> HelperTokenStream {
+    if !agile {
+        return quote!();
+    }
+    let interface_ident = &interface.ident;
+    quote! {
+        unsafe impl com::AgileInterface for dyn #interface_ident {}
+    }
+}
+
 pub fn expand_derive(input: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(input as ItemStruct);
     vtable_macro::generate(&input).into()
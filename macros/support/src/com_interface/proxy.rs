@@ -0,0 +1,54 @@
+use proc_macro2::TokenStream as HelperTokenStream;
+use quote::quote;
+use syn::{ItemTrait, TraitItem};
+
+/// Implement an interface for `com::agile::Proxy`, which only exists on the portable runtime
+///
+/// Every method runs on the thread owning the proxied object. Methods returning
+/// `HRESULT` return `RPC_E_DISCONNECTED` if that thread is gone. Others have no way to
+/// report it and abort the process, since they are called through the VTable and must
+/// not unwind.
+pub fn generate(interface: &ItemTrait) -> HelperTokenStream {
+    let interface_ident = &interface.ident;
+    if interface_ident == "IUnknown" {
+        return quote!();
+    }
+
+    let mut methods = Vec::new();
+    for trait_item in &interface.items {
+        let method = match trait_item {
+            TraitItem::Method(m) => m,
+            _ => panic!("COM interfaces may only contain methods"),
+        };
+        let method_ident = &method.sig.ident;
+        let (method_sig, args) = crate::utils::sig_with_arg_idents(&method.sig);
+        let unwrap = if super::interface_impl::returns_hresult(method) {
+            quote!(.unwrap_or_else(|hr| hr))
+        } else {
+            quote!(.unwrap_or_else(|_| std::process::abort()))
+        };
+
+        methods.push(quote! {
+            #method_sig {
+                // The arguments and the result are raw COM types, which the owning
+                // thread uses only for the duration of the call
+                let args = com::agile::AssertSend::new((#(#args,)*));
+                self.call(move |target| {
+                    let (#(#args,)*) = args.into_inner();
+                    com::agile::AssertSend::new(
+                        <com::ComRc<T> as #interface_ident>::#method_ident(target, #(#args),*)
+                    )
+                })
+                .map(com::agile::AssertSend::into_inner)
+                #unwrap
+            }
+        });
+    }
+
+    quote! {
+        #[cfg(not(windows))]
+        impl <T: #interface_ident + com::ComInterface + ?Sized> #interface_ident for com::agile::Proxy<T> {
+            #(#methods)*
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use super::layout::MethodLayout;
 use super::vptr;
 use proc_macro2::{Ident, TokenStream as HelperTokenStream};
 use quote::{format_ident, quote};
@@ -7,23 +8,24 @@ use syn::{FnArg, ItemTrait, TraitItem, TraitItemMethod, Type, TypeParamBound};
 /// Generate an VTable for an interface trait
 ///
 /// * `interface` is a trait representing a COM interface. This trait must either be
-/// IUnknown and have no super traits or some other trait that has a parent trait
-pub fn generate(interface: &ItemTrait) -> HelperTokenStream {
+///   IUnknown and have no super traits or some other trait that has a parent trait
+/// * `layouts` are the slots reserved before each method with `#[skip_slots(N)]`
+pub fn generate(interface: &ItemTrait, layouts: &[MethodLayout]) -> HelperTokenStream {
     let interface_ident = &interface.ident;
     let vtable_ident = ident(&interface_ident.to_string());
     let base_field = if interface_ident.to_string().to_uppercase() == "IUNKNOWN" {
         assert!(
-            interface.supertraits.len() == 0,
+            interface.supertraits.is_empty(),
             "IUnknown is a reserved interface"
         );
         quote! {}
     } else {
         assert!(
-            !(interface.supertraits.len() > 1),
+            interface.supertraits.len() <= 1,
             "Multiple inheritance is not supported in COM interfaces"
         );
         assert!(
-            interface.supertraits.len() != 0,
+            !interface.supertraits.is_empty(),
             "All interfaces must inherit from another COM interface"
         );
 
@@ -43,7 +45,7 @@ pub fn generate(interface: &ItemTrait) -> HelperTokenStream {
             pub #base_field_ident: <dyn #base_interface_path as com::ComInterface>::VTable,
         }
     };
-    let methods = gen_vtable_methods(&interface);
+    let methods = gen_vtable_methods(interface, layouts);
 
     quote!(
         #[allow(non_snake_case, missing_docs)]
@@ -64,11 +66,25 @@ fn base_field_ident(base_interface_name: &str) -> Ident {
     format_ident!("{}_base", crate::utils::camel_to_snake(base_interface_name))
 }
 
-fn gen_vtable_methods(interface: &ItemTrait) -> HelperTokenStream {
+/// The name of the field of a reserved slot, numbered like `#[slot(N)]` but without
+/// the inherited slots
+fn reserved_field_ident(index: usize) -> Ident {
+    format_ident!("Reserved{}", index)
+}
+
+fn gen_vtable_methods(interface: &ItemTrait, layouts: &[MethodLayout]) -> HelperTokenStream {
     let mut methods: Vec<HelperTokenStream> = Vec::new();
+    let mut layouts = layouts.iter();
     for trait_item in &interface.items {
         match trait_item {
-            TraitItem::Method(m) => methods.push(gen_vtable_method(&interface.ident, m)),
+            TraitItem::Method(m) => {
+                let skipped = layouts.next().map(|layout| layout.skipped).unwrap_or(0);
+                for _ in 0..skipped {
+                    let field_ident = reserved_field_ident(methods.len());
+                    methods.push(quote!(pub #field_ident: com::ReservedSlot,));
+                }
+                methods.push(gen_vtable_method(&interface.ident, m))
+            }
             _ => panic!("Interface traits currently only support methods"),
         };
     }
@@ -102,7 +118,7 @@ fn gen_vtable_function_signature(
     let return_type = &method.sig.output;
 
     quote!(
-        unsafe extern "stdcall" fn(#params) #return_type
+        unsafe extern "system" fn(#params) #return_type
     )
 }
 
@@ -126,7 +142,7 @@ fn gen_raw_params(interface_ident: &Ident, method: &TraitItemMethod) -> HelperTo
                 ));
             }
             FnArg::Typed(t) => {
-                params.push(gen_raw_type(&*t.ty));
+                params.push(gen_raw_type(&t.ty));
             }
         }
     }
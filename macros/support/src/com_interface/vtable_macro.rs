@@ -28,7 +28,7 @@ pub fn ident(struct_ident: &Ident) -> Ident {
 }
 
 fn gen_parent_vtable_binding(item: &ItemStruct) -> HelperTokenStream {
-    let parent = item.fields.iter().nth(0);
+    let parent = item.fields.iter().next();
     if let Some(parent) = parent {
         let is_base = parent
             .ident
@@ -102,7 +102,7 @@ fn gen_vtable_function(
     let return_type = &fun.output;
     quote! {
         #[allow(missing_docs)]
-        unsafe extern "stdcall" fn #function_ident<C: #interface_ident, O: com::offset::Offset>(#(#params)*) #return_type {
+        unsafe extern "system" fn #function_ident<C: #interface_ident, O: com::offset::Offset>(#(#params)*) #return_type {
             let this = arg0.sub(O::VALUE) as *const C as *mut C;
             (*this).#method_name(#(#args)*)
         }
@@ -120,6 +120,18 @@ fn gen_initialized_vtable(item: &ItemStruct) -> HelperTokenStream {
     }
 }
 
+/// Whether a field is a slot reserved with `#[skip_slots(N)]`, typed `com::ReservedSlot`
+fn is_reserved_slot(ty: &Type) -> bool {
+    match ty {
+        Type::Path(p) => p
+            .path
+            .segments
+            .last()
+            .map_or(false, |segment| segment.ident == "ReservedSlot"),
+        _ => false,
+    }
+}
+
 fn gen_vtable_method_initialization(item: &ItemStruct) -> HelperTokenStream {
     let mut methods = Vec::new();
     for field in &item.fields {
@@ -131,6 +143,8 @@ fn gen_vtable_method_initialization(item: &ItemStruct) -> HelperTokenStream {
         let function_ident = if method_ident.to_string().ends_with("_base") {
             let parent = format_ident!("parent_vtable",);
             quote! { #parent, }
+        } else if is_reserved_slot(&field.ty) {
+            quote! { com::reserved_slot, }
         } else {
             let function_ident = format_ident!(
                 "{}_{}",
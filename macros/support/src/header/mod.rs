@@ -0,0 +1,451 @@
+//! Generating C and C++ headers from [`Metadata`]
+//!
+//! The header has the same shape as the ones MIDL generates: C++ gets a struct with pure
+//! virtual methods deriving from the parent interface, C gets a `{Interface}Vtbl` struct
+//! holding every method including the inherited ones, and each IID is defined as a
+//! `static const GUID`.
+//!
+//! ```rust,ignore
+//! use com_macros_support::header;
+//! use com_macros_support::metadata::Metadata;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut metadata = Metadata::new();
+//!     metadata.parse_file("src/lib.rs")?;
+//!     let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+//!     header::write(&metadata, out_dir.join("animal.h"))
+//! }
+//! ```
+//!
+//! On Windows the header includes `objbase.h`. Elsewhere it declares the few types and
+//! macros it needs, including `IUnknown`, which matches the portable runtime of `com`.
+//!
+//! The GUIDs are not defined with `DEFINE_GUID`, which on Windows only declares them
+//! unless `initguid.h` is included first. Defining them in every translation unit
+//! spares consumers from picking the one that includes `initguid.h`.
+//!
+//! [`Metadata`]: ../metadata/struct.Metadata.html
+
+use crate::idl::type_name;
+use crate::metadata::{Direction, Interface, Metadata, Method, Param, TypeRef};
+
+use std::fmt::Write;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+const PORTABLE_PRELUDE: &str = r#"#ifdef _WIN32
+#include <objbase.h>
+#else
+#include <stddef.h>
+#include <stdint.h>
+
+#ifndef COM_RS_PORTABLE_TYPES
+#define COM_RS_PORTABLE_TYPES
+typedef int32_t HRESULT;
+typedef int32_t BOOL;
+typedef unsigned char boolean;
+typedef uint8_t BYTE;
+typedef int16_t SHORT;
+typedef uint16_t USHORT;
+typedef int32_t LONG;
+typedef uint32_t ULONG;
+typedef int64_t LONGLONG;
+typedef uint64_t ULONGLONG;
+typedef intptr_t LONG_PTR;
+typedef size_t SIZE_T;
+typedef uint16_t WCHAR;
+typedef WCHAR *BSTR;
+typedef const WCHAR *LPCWSTR;
+typedef WCHAR *LPWSTR;
+typedef struct GUID {
+    uint32_t Data1;
+    uint16_t Data2;
+    uint16_t Data3;
+    uint8_t Data4[8];
+} GUID;
+typedef GUID IID;
+typedef GUID CLSID;
+
+#define STDMETHODCALLTYPE
+
+#if defined(__cplusplus) && !defined(CINTERFACE)
+struct IUnknown {
+    virtual HRESULT STDMETHODCALLTYPE QueryInterface(const IID *riid, void **ppvObject) = 0;
+    virtual ULONG STDMETHODCALLTYPE AddRef(void) = 0;
+    virtual ULONG STDMETHODCALLTYPE Release(void) = 0;
+};
+#else
+typedef struct IUnknown IUnknown;
+typedef struct IUnknownVtbl {
+    HRESULT (STDMETHODCALLTYPE *QueryInterface)(IUnknown *This, const IID *riid, void **ppvObject);
+    ULONG (STDMETHODCALLTYPE *AddRef)(IUnknown *This);
+    ULONG (STDMETHODCALLTYPE *Release)(IUnknown *This);
+} IUnknownVtbl;
+struct IUnknown {
+    const struct IUnknownVtbl *lpVtbl;
+};
+#endif
+#endif /* COM_RS_PORTABLE_TYPES */
+#endif /* _WIN32 */
+
+#ifndef COM_RS_DEFINE_GUID
+#define COM_RS_DEFINE_GUID(name, l, w1, w2, b1, b2, b3, b4, b5, b6, b7, b8) \
+    static const GUID name = { l, w1, w2, { b1, b2, b3, b4, b5, b6, b7, b8 } }
+#endif
+"#;
+
+/// The header declaring the interfaces and CLSIDs of `metadata`
+///
+/// `guard` names the include guard. Fails if an interface derives from an interface
+/// other than `IUnknown` that is not part of `metadata`, as its methods are unknown.
+pub fn generate(metadata: &Metadata, guard: &str) -> std::io::Result<String> {
+    let mut header = String::new();
+    header.push_str("/* Generated from the Rust declarations, do not edit */\n\n");
+    writeln!(header, "#ifndef {0}\n#define {0}\n", guard).unwrap();
+    header.push_str(PORTABLE_PRELUDE);
+
+    let interfaces = metadata.sorted_interfaces();
+    header.push_str("\n#if defined(__cplusplus) && !defined(CINTERFACE)\n");
+    for interface in &interfaces {
+        writeln!(header, "struct {};", interface.name).unwrap();
+    }
+    header.push_str("#else\n");
+    for interface in &interfaces {
+        writeln!(header, "typedef struct {0} {0};", interface.name).unwrap();
+    }
+    header.push_str("#endif\n");
+
+    for interface in &interfaces {
+        header.push('\n');
+        gen_interface(&mut header, metadata, interface)?;
+    }
+
+    let classes = metadata
+        .classes
+        .iter()
+        .filter_map(|class| class.clsid.as_ref().map(|clsid| (&class.name, clsid)))
+        .collect::<Vec<_>>();
+    if !classes.is_empty() {
+        header.push('\n');
+    }
+    for (name, clsid) in classes {
+        gen_guid(&mut header, &format!("CLSID_{}", name), clsid)?;
+    }
+
+    writeln!(header, "\n#endif /* {} */", guard).unwrap();
+    Ok(header)
+}
+
+/// Write the header declaring the interfaces and CLSIDs of `metadata` to `path`
+///
+/// The include guard is derived from the file name, e.g. `ANIMAL_H` for `animal.h`.
+pub fn write(metadata: &Metadata, path: impl AsRef<Path>) -> std::io::Result<()> {
+    let path = path.as_ref();
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    std::fs::write(path, generate(metadata, &guard_name(&file_name))?)
+}
+
+fn guard_name(file_name: &str) -> String {
+    let guard = file_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    match guard.chars().next() {
+        Some(c) if !c.is_ascii_digit() => guard,
+        _ => format!("_{}", guard),
+    }
+}
+
+fn gen_interface(
+    header: &mut String,
+    metadata: &Metadata,
+    interface: &Interface,
+) -> std::io::Result<()> {
+    gen_guid(header, &format!("IID_{}", interface.name), &interface.iid)?;
+
+    header.push_str("\n#if defined(__cplusplus) && !defined(CINTERFACE)\n");
+    match &interface.base {
+        Some(base) => writeln!(header, "struct {} : public {} {{", interface.name, base),
+        None => writeln!(header, "struct {} {{", interface.name),
+    }
+    .unwrap();
+    for method in &interface.methods {
+        writeln!(
+            header,
+            "    virtual {} STDMETHODCALLTYPE {}({}) = 0;",
+            c_type(&method.ret),
+            method.com_name(),
+            param_list(None, &method.params)
+        )
+        .unwrap();
+    }
+    header.push_str("};\n#else\n");
+
+    writeln!(header, "typedef struct {}Vtbl {{", interface.name).unwrap();
+    for method in vtable_methods(metadata, interface)? {
+        writeln!(
+            header,
+            "    {} (STDMETHODCALLTYPE *{})({});",
+            c_type(&method.ret),
+            method.com_name(),
+            param_list(Some(&interface.name), &method.params)
+        )
+        .unwrap();
+    }
+    writeln!(
+        header,
+        "}} {0}Vtbl;\nstruct {0} {{\n    const struct {0}Vtbl *lpVtbl;\n}};\n#endif",
+        interface.name
+    )
+    .unwrap();
+    Ok(())
+}
+
+/// The methods of `interface` in VTable order, starting with those of `IUnknown`
+fn vtable_methods(metadata: &Metadata, interface: &Interface) -> std::io::Result<Vec<Method>> {
+    let mut chain = vec![interface];
+    let mut base = interface.base.as_deref();
+    while let Some(name) = base {
+        if name == "IUnknown" {
+            break;
+        }
+        let parent = match metadata.interface(name) {
+            Some(parent) if !chain.iter().any(|i| i.name == name) => parent,
+            Some(_) => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("`{}` derives from itself", name),
+                ))
+            }
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "the methods of `{}`, the parent of `{}`, are unknown",
+                        name, interface.name
+                    ),
+                ))
+            }
+        };
+        chain.push(parent);
+        base = parent.base.as_deref();
+    }
+
+    let mut methods = iunknown_methods();
+    for interface in chain.into_iter().rev() {
+        methods.extend(interface.methods.iter().cloned());
+    }
+    Ok(methods)
+}
+
+fn iunknown_methods() -> Vec<Method> {
+    let param = |name: &str, ty: TypeRef, direction| Param {
+        name: name.to_owned(),
+        ty,
+        direction,
+    };
+    let ptr = |mutable, pointee| TypeRef::Ptr {
+        mutable,
+        pointee: Box::new(pointee),
+    };
+    vec![
+        Method {
+            name: "query_interface".to_owned(),
+            params: vec![
+                param(
+                    "riid",
+                    ptr(false, TypeRef::Named("IID".to_owned())),
+                    Direction::In,
+                ),
+                param(
+                    "ppvObject",
+                    ptr(true, ptr(true, TypeRef::Void)),
+                    Direction::Out,
+                ),
+            ],
+            ret: TypeRef::Named("HRESULT".to_owned()),
+        },
+        Method {
+            name: "add_ref".to_owned(),
+            params: Vec::new(),
+            ret: TypeRef::U32,
+        },
+        Method {
+            name: "release".to_owned(),
+            params: Vec::new(),
+            ret: TypeRef::U32,
+        },
+    ]
+}
+
+/// The parameters of a method, preceded by `This` in C
+fn param_list(this: Option<&str>, params: &[Param]) -> String {
+    let params = this
+        .map(|this| format!("{} *This", this))
+        .into_iter()
+        .chain(
+            params
+                .iter()
+                .map(|param| declaration(&param.ty, &param.name)),
+        )
+        .collect::<Vec<_>>();
+    if params.is_empty() {
+        "void".to_owned()
+    } else {
+        params.join(", ")
+    }
+}
+
+/// The C spelling of a type, with pointers written the way MIDL writes them
+fn c_type(ty: &TypeRef) -> String {
+    match ty {
+        TypeRef::Ptr { mutable, pointee } => {
+            let pointee = c_type(pointee);
+            let space = if pointee.ends_with('*') { "" } else { " " };
+            let constness = if *mutable { "" } else { "const " };
+            format!("{}{}{}*", constness, pointee, space)
+        }
+        _ => type_name(ty),
+    }
+}
+
+/// A parameter declaration, e.g. `ULONG *lives`
+fn declaration(ty: &TypeRef, name: &str) -> String {
+    let ty = c_type(ty);
+    if ty.ends_with('*') {
+        format!("{}{}", ty, name)
+    } else {
+        format!("{} {}", ty, name)
+    }
+}
+
+fn gen_guid(header: &mut String, name: &str, guid: &str) -> std::io::Result<()> {
+    let invalid = || {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("`{}` of `{}` is not a GUID", guid, name),
+        )
+    };
+    let fields = crate::utils::guid_fields(guid).ok_or_else(invalid)?;
+    let fields = fields
+        .iter()
+        .map(|field| format!("0x{}", field))
+        .collect::<Vec<_>>();
+    writeln!(
+        header,
+        "COM_RS_DEFINE_GUID({}, {});",
+        name,
+        fields.join(", ")
+    )
+    .unwrap();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"
+        use com::sys::{CLSID, HRESULT, PCWSTR};
+
+        pub const CLSID_CAT_CLASS: CLSID = CLSID {
+            data1: 0xC5F4_5CBC,
+            data2: 0x4439,
+            data3: 0x418C,
+            data4: [0xA9, 0xF9, 0x05, 0xAC, 0x67, 0x52, 0x5E, 0x43],
+        };
+
+        #[com_interface("EFF8970E-C50F-45E0-9284-291CE5A6F771")]
+        pub trait IAnimal: IUnknown {
+            unsafe fn eat(&self) -> HRESULT;
+        }
+
+        #[com_interface("F5353C58-CFD9-4204-8D92-D274C7578B53")]
+        pub trait ICat: IAnimal {
+            unsafe fn set_name(&self, name: PCWSTR) -> HRESULT;
+            unsafe fn lives(&self, lives: *mut u32);
+        }
+
+        #[co_class(implements(ICat, IAnimal))]
+        pub struct BritishShortHairCat {}
+
+        com::inproc_dll_module![(CLSID_CAT_CLASS, BritishShortHairCat),];
+    "#;
+
+    #[test]
+    fn interfaces_and_classes() {
+        let mut metadata = Metadata::new();
+        metadata.parse_source(SOURCE).unwrap();
+        let header = generate(&metadata, "ANIMAL_H").unwrap();
+
+        assert!(header.contains("#ifndef ANIMAL_H\n#define ANIMAL_H\n"));
+        assert!(header.contains(
+            "COM_RS_DEFINE_GUID(IID_IAnimal, 0xEFF8970E, 0xC50F, 0x45E0, \
+             0x92, 0x84, 0x29, 0x1C, 0xE5, 0xA6, 0xF7, 0x71);\n"
+        ));
+        assert!(header.contains(
+            "struct ICat : public IAnimal {\n    \
+             virtual HRESULT STDMETHODCALLTYPE SetName(LPCWSTR name) = 0;\n    \
+             virtual void STDMETHODCALLTYPE Lives(ULONG *lives) = 0;\n};\n"
+        ));
+        assert!(header.contains(
+            "typedef struct ICatVtbl {\n    \
+             HRESULT (STDMETHODCALLTYPE *QueryInterface)(ICat *This, const IID *riid, void **ppvObject);\n    \
+             ULONG (STDMETHODCALLTYPE *AddRef)(ICat *This);\n    \
+             ULONG (STDMETHODCALLTYPE *Release)(ICat *This);\n    \
+             HRESULT (STDMETHODCALLTYPE *Eat)(ICat *This);\n    \
+             HRESULT (STDMETHODCALLTYPE *SetName)(ICat *This, LPCWSTR name);\n    \
+             void (STDMETHODCALLTYPE *Lives)(ICat *This, ULONG *lives);\n\
+             } ICatVtbl;\n"
+        ));
+        assert!(header.contains("COM_RS_DEFINE_GUID(CLSID_BritishShortHairCat, 0xC5F45CBC,"));
+        assert_eq!(guard_name("2d-shapes.h"), "_2D_SHAPES_H");
+    }
+
+    #[test]
+    fn reserved_slots() {
+        let mut metadata = Metadata::new();
+        metadata
+            .parse_source(
+                r#"
+                #[com_interface("00000000-0000-0000-0000-000000000001")]
+                pub trait IMirror: IUnknown {
+                    #[skip_slots(2)]
+                    unsafe fn third(&self) -> HRESULT;
+                }
+                "#,
+            )
+            .unwrap();
+        let header = generate(&metadata, "MIRROR_H").unwrap();
+        assert!(header.contains(
+            "struct IMirror : public IUnknown {\n    \
+             virtual void STDMETHODCALLTYPE Reserved0(void) = 0;\n    \
+             virtual void STDMETHODCALLTYPE Reserved1(void) = 0;\n    \
+             virtual HRESULT STDMETHODCALLTYPE Third(void) = 0;\n};\n"
+        ));
+    }
+
+    #[test]
+    fn unknown_parent() {
+        let mut metadata = Metadata::new();
+        metadata
+            .parse_source(
+                r#"
+                #[com_interface("00000000-0000-0000-0000-000000000002")]
+                pub trait IChild: IDispatch {}
+                "#,
+            )
+            .unwrap();
+        let error = generate(&metadata, "CHILD_H").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,459 @@
+//! Generating MIDL compatible IDL from [`Metadata`]
+//!
+//! A build script keeps the IDL consumed by C++ in sync with the Rust declarations:
+//!
+//! ```rust,ignore
+//! use com_macros_support::idl;
+//! use com_macros_support::metadata::{Library, Metadata};
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut metadata = Metadata::new();
+//!     metadata.parse_file("src/lib.rs")?;
+//!     let library = Library::new("AnimalLib", "C5F45CBC-4439-418C-A9F9-05AC67525E43");
+//!     let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+//!     idl::write(&metadata, Some(&library), out_dir.join("animal.idl"))
+//! }
+//! ```
+//!
+//! The enums, structs and typedefs of `metadata` are declared first. Every interface gets
+//! a forward declaration and a definition. Classes with a known CLSID become coclasses of
+//! the library, if there is one.
+//!
+//! Types the IDL can't resolve, i.e. other types than these and the ones `oaidl.idl`
+//! declares, fail the generation.
+//!
+//! [`Metadata`]: ../metadata/struct.Metadata.html
+
+use crate::metadata::{
+    Direction, Enum, Interface, Library, Metadata, Method, Struct, TypeRef, Typedef,
+};
+
+use std::fmt::Write;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// The types declared by `oaidl.idl` that parameters may use, by their Rust names
+const IMPORTED_TYPES: &[&str] = &[
+    "BOOL",
+    "BSTR",
+    "CLSID",
+    "FILETIME",
+    "GUID",
+    "HRESULT",
+    "HWND",
+    "IID",
+    "PCWSTR",
+    "PWSTR",
+    "VARIANT",
+    "VARIANT_BOOL",
+];
+
+/// The IDL for the interfaces, classes and types of `metadata`
+pub fn generate(metadata: &Metadata, library: Option<&Library>) -> std::io::Result<String> {
+    check_types(metadata)?;
+
+    let mut idl = String::new();
+    idl.push_str("// Generated from the Rust declarations, do not edit\n\n");
+    idl.push_str("import \"oaidl.idl\";\nimport \"ocidl.idl\";\n\n");
+
+    for item in &metadata.enums {
+        gen_enum(&mut idl, item);
+        idl.push('\n');
+    }
+    for item in &metadata.structs {
+        gen_struct(&mut idl, item);
+        idl.push('\n');
+    }
+    for item in &metadata.typedefs {
+        gen_typedef(&mut idl, item);
+        idl.push('\n');
+    }
+
+    let interfaces = metadata.sorted_interfaces();
+    for interface in &interfaces {
+        writeln!(idl, "interface {};", interface.name).unwrap();
+    }
+    for interface in &interfaces {
+        idl.push('\n');
+        gen_interface(&mut idl, interface);
+    }
+
+    if let Some(library) = library {
+        idl.push('\n');
+        gen_library(&mut idl, metadata, library);
+    }
+    Ok(idl)
+}
+
+/// Write the IDL for the interfaces, classes and types of `metadata` to `path`
+pub fn write(
+    metadata: &Metadata,
+    library: Option<&Library>,
+    path: impl AsRef<Path>,
+) -> std::io::Result<()> {
+    std::fs::write(path, generate(metadata, library)?)
+}
+
+/// Fail if a type used by `metadata` is neither declared by it nor imported
+fn check_types(metadata: &Metadata) -> std::io::Result<()> {
+    fn check(metadata: &Metadata, ty: &TypeRef, user: &str) -> std::io::Result<()> {
+        match ty {
+            TypeRef::Named(name) => {
+                let known = IMPORTED_TYPES.contains(&name.as_str())
+                    || metadata.enums.iter().any(|e| &e.name == name)
+                    || metadata.structs.iter().any(|s| &s.name == name)
+                    || metadata.typedefs.iter().any(|t| &t.name == name);
+                if known {
+                    Ok(())
+                } else {
+                    Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "`{}`, used by `{}`, is not declared and cannot be written to IDL",
+                            name, user
+                        ),
+                    ))
+                }
+            }
+            TypeRef::Ptr { pointee, .. } => check(metadata, pointee, user),
+            TypeRef::Array { element, .. } => check(metadata, element, user),
+            _ => Ok(()),
+        }
+    }
+
+    for interface in &metadata.interfaces {
+        for method in &interface.methods {
+            let user = format!("{}::{}", interface.name, method.name);
+            check(metadata, &method.ret, &user)?;
+            for param in &method.params {
+                check(metadata, &param.ty, &user)?;
+            }
+        }
+    }
+    for item in &metadata.structs {
+        for field in &item.fields {
+            check(metadata, &field.ty, &item.name)?;
+        }
+    }
+    for item in &metadata.typedefs {
+        check(metadata, &item.ty, &item.name)?;
+    }
+    Ok(())
+}
+
+fn gen_enum(idl: &mut String, item: &Enum) {
+    writeln!(idl, "typedef enum {}\n{{", item.name).unwrap();
+    let variants = item
+        .variants
+        .iter()
+        .map(|(name, value)| format!("    {} = {}", name, value))
+        .collect::<Vec<_>>();
+    writeln!(idl, "{}\n}} {};", variants.join(",\n"), item.name).unwrap();
+}
+
+fn gen_struct(idl: &mut String, item: &Struct) {
+    writeln!(idl, "typedef struct {}\n{{", item.name).unwrap();
+    for field in &item.fields {
+        writeln!(idl, "    {};", declaration(&field.ty, &field.name)).unwrap();
+    }
+    writeln!(idl, "}} {};", item.name).unwrap();
+}
+
+fn gen_typedef(idl: &mut String, item: &Typedef) {
+    writeln!(idl, "typedef {};", declaration(&item.ty, &item.name)).unwrap();
+}
+
+/// Declare `name` as a `ty`, which for arrays follows the name
+fn declaration(ty: &TypeRef, name: &str) -> String {
+    match ty {
+        TypeRef::Array { element, len } => {
+            format!("{}[{}]", declaration(element, name), len)
+        }
+        ty => format!("{} {}", type_name(ty), name),
+    }
+}
+
+fn gen_interface(idl: &mut String, interface: &Interface) {
+    // MIDL cannot marshal untyped memory, nor report the failure of a call that does not
+    // return a `HRESULT`, so such interfaces are local only
+    let hresult = TypeRef::Named("HRESULT".to_owned());
+    let local = interface.methods.iter().any(|method| {
+        method.ret != hresult || method.params.iter().any(|param| points_to_void(&param.ty))
+    });
+
+    idl.push_str("[\n    object,\n");
+    if local {
+        idl.push_str("    local,\n");
+    }
+    writeln!(idl, "    uuid({}),", interface.iid).unwrap();
+    idl.push_str("    pointer_default(unique)\n]\n");
+    match &interface.base {
+        Some(base) => writeln!(idl, "interface {} : {}", interface.name, base).unwrap(),
+        None => writeln!(idl, "interface {}", interface.name).unwrap(),
+    }
+    idl.push_str("{\n");
+    for method in &interface.methods {
+        writeln!(idl, "    {};", gen_method(method)).unwrap();
+    }
+    idl.push_str("};\n");
+}
+
+fn gen_method(method: &Method) -> String {
+    let params = method
+        .params
+        .iter()
+        .map(|param| {
+            let direction = match param.direction {
+                Direction::In => "in",
+                Direction::Out => "out",
+                Direction::InOut => "in, out",
+                Direction::Retval => "out, retval",
+            };
+            format!("[{}] {} {}", direction, type_name(&param.ty), param.name)
+        })
+        .collect::<Vec<_>>();
+    format!(
+        "{} {}({})",
+        type_name(&method.ret),
+        method.com_name(),
+        params.join(", ")
+    )
+}
+
+fn gen_library(idl: &mut String, metadata: &Metadata, library: &Library) {
+    writeln!(idl, "[\n    uuid({}),", library.uuid).unwrap();
+    if let Some(help_string) = &library.help_string {
+        writeln!(
+            idl,
+            "    helpstring(\"{}\"),",
+            help_string.replace('"', "\\\"")
+        )
+        .unwrap();
+    }
+    writeln!(
+        idl,
+        "    version({}.{})\n]\nlibrary {}\n{{",
+        library.version.0, library.version.1, library.name
+    )
+    .unwrap();
+    idl.push_str("    importlib(\"stdole2.tlb\");\n");
+    for class in &metadata.classes {
+        let clsid = match &class.clsid {
+            Some(clsid) => clsid,
+            None => continue,
+        };
+        writeln!(
+            idl,
+            "\n    [uuid({})]\n    coclass {}\n    {{",
+            clsid, class.name
+        )
+        .unwrap();
+        for (index, interface) in class.interfaces.iter().enumerate() {
+            let default = if index == 0 { "[default] " } else { "" };
+            writeln!(idl, "        {}interface {};", default, interface).unwrap();
+        }
+        idl.push_str("    };\n");
+    }
+    idl.push_str("};\n");
+}
+
+fn points_to_void(ty: &TypeRef) -> bool {
+    match ty {
+        TypeRef::Ptr { pointee, .. } => match **pointee {
+            TypeRef::Void => true,
+            ref pointee => points_to_void(pointee),
+        },
+        _ => false,
+    }
+}
+
+/// The IDL spelling of a type
+pub fn type_name(ty: &TypeRef) -> String {
+    match ty {
+        TypeRef::Void => "void".to_owned(),
+        TypeRef::Bool => "boolean".to_owned(),
+        TypeRef::I8 => "signed char".to_owned(),
+        TypeRef::U8 => "BYTE".to_owned(),
+        TypeRef::I16 => "SHORT".to_owned(),
+        TypeRef::U16 => "USHORT".to_owned(),
+        TypeRef::I32 => "LONG".to_owned(),
+        TypeRef::U32 => "ULONG".to_owned(),
+        TypeRef::I64 => "LONGLONG".to_owned(),
+        TypeRef::U64 => "ULONGLONG".to_owned(),
+        TypeRef::Isize => "LONG_PTR".to_owned(),
+        TypeRef::Usize => "SIZE_T".to_owned(),
+        TypeRef::F32 => "float".to_owned(),
+        TypeRef::F64 => "double".to_owned(),
+        TypeRef::Interface(name) => name.clone(),
+        TypeRef::Named(name) => match name.as_str() {
+            "PCWSTR" => "LPCWSTR".to_owned(),
+            "PWSTR" => "LPWSTR".to_owned(),
+            _ => name.clone(),
+        },
+        TypeRef::Ptr {
+            mutable: true,
+            pointee,
+        } => format!("{}*", type_name(pointee)),
+        TypeRef::Ptr {
+            mutable: false,
+            pointee,
+        } => format!("const {}*", type_name(pointee)),
+        TypeRef::Array { element, len } => format!("{}[{}]", type_name(element), len),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"
+        use com::sys::{BSTR, CLSID, HRESULT, IID, PCWSTR};
+
+        pub const CLSID_CAT_CLASS: CLSID = CLSID {
+            data1: 0xC5F4_5CBC,
+            data2: 0x4439,
+            data3: 0x418C,
+            data4: [0xA9, 0xF9, 0x05, 0xAC, 0x67, 0x52, 0x5E, 0x43],
+        };
+
+        #[com_interface("EFF8970E-C50F-45E0-9284-291CE5A6F771")]
+        pub trait IAnimal: IUnknown {
+            unsafe fn eat(&self) -> HRESULT;
+        }
+
+        mod cat {
+            #[com::com_interface("F5353C58-CFD9-4204-8D92-D274C7578B53")]
+            pub trait ICat: IAnimal {
+                unsafe fn set_name(&self, name: PCWSTR) -> HRESULT;
+                unsafe fn name(&self, name: *mut BSTR) -> HRESULT;
+                unsafe fn lives(&self) -> u32;
+                unsafe fn query(&self, riid: *const IID, ppv: *mut *mut c_void) -> HRESULT;
+                unsafe fn adopt(&self, friend: *mut IAnimalVPtr, age: u8);
+            }
+        }
+
+        #[co_class(implements(ICat, IAnimal))]
+        pub struct BritishShortHairCat {
+            num_owners: u32,
+        }
+
+        com::inproc_dll_module![(CLSID_CAT_CLASS, BritishShortHairCat),];
+    "#;
+
+    #[test]
+    fn interfaces_and_classes() {
+        let mut metadata = Metadata::new();
+        metadata.parse_source(SOURCE).unwrap();
+        let library = Library::new("AnimalLib", "9BB1E3D1-57E1-4D1C-B2A6-A8B4D93A2E10");
+        let idl = generate(&metadata, Some(&library)).unwrap();
+
+        assert!(idl.contains("interface IAnimal;\ninterface ICat;\n"));
+        assert!(idl.contains(
+            "[\n    object,\n    uuid(EFF8970E-C50F-45E0-9284-291CE5A6F771),\n    \
+             pointer_default(unique)\n]\ninterface IAnimal : IUnknown\n{\n    HRESULT Eat();\n};\n"
+        ));
+        assert!(idl.contains("    object,\n    local,\n    uuid(F5353C58"));
+        assert!(idl.contains("    HRESULT SetName([in] LPCWSTR name);\n"));
+        assert!(idl.contains("    HRESULT Name([out] BSTR* name);\n"));
+        assert!(idl.contains("    ULONG Lives();\n"));
+        assert!(idl.contains("    HRESULT Query([in] const IID* riid, [out] void** ppv);\n"));
+        assert!(idl.contains("    void Adopt([in] IAnimal* friend, [in] BYTE age);\n"));
+        assert!(idl.contains(
+            "    [uuid(C5F45CBC-4439-418C-A9F9-05AC67525E43)]\n    coclass BritishShortHairCat\n    \
+             {\n        [default] interface ICat;\n        interface IAnimal;\n    };\n"
+        ));
+    }
+
+    #[test]
+    fn parents_come_first() {
+        let mut metadata = Metadata::new();
+        metadata
+            .parse_source(
+                r#"
+                #[com_interface("00000000-0000-0000-0000-000000000002")]
+                pub trait IChild: IParent {}
+                #[com_interface("00000000-0000-0000-0000-000000000001")]
+                pub trait IParent: IUnknown {}
+                "#,
+            )
+            .unwrap();
+        let names = metadata
+            .sorted_interfaces()
+            .iter()
+            .map(|interface| interface.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["IParent", "IChild"]);
+        // Without a library there are no coclasses
+        assert!(!generate(&metadata, None).unwrap().contains("library"));
+    }
+
+    #[test]
+    fn methods_without_hresult_are_local() {
+        let mut metadata = Metadata::new();
+        metadata
+            .parse_source(
+                r#"
+                #[com_interface("00000000-0000-0000-0000-000000000001")]
+                pub trait ICounter: IUnknown {
+                    unsafe fn count(&self) -> u32;
+                }
+                "#,
+            )
+            .unwrap();
+        let idl = generate(&metadata, None).unwrap();
+        assert!(
+            idl.contains("    object,\n    local,\n    uuid(00000000-0000-0000-0000-000000000001)")
+        );
+    }
+
+    #[test]
+    fn types() {
+        let mut metadata = Metadata::new();
+        metadata
+            .parse_source(
+                r#"
+                #[com_interface("EFF8970E-C50F-45E0-9284-291CE5A6F771")]
+                pub trait IAnimal: IUnknown {
+                    unsafe fn eat(&self, food: *mut FOOD) -> HRESULT;
+                }
+                "#,
+            )
+            .unwrap();
+        assert_eq!(
+            generate(&metadata, None).unwrap_err().to_string(),
+            "`FOOD`, used by `IAnimal::eat`, is not declared and cannot be written to IDL"
+        );
+
+        // Declared types come before the interfaces using them
+        metadata.enums.push(Enum {
+            name: "FOOD_KIND".to_owned(),
+            variants: vec![("FK_FISH".to_owned(), 0), ("FK_MOUSE".to_owned(), 1)],
+        });
+        metadata.structs.push(Struct {
+            name: "MEAL".to_owned(),
+            fields: vec![
+                crate::metadata::Field {
+                    name: "kind".to_owned(),
+                    ty: TypeRef::Named("FOOD_KIND".to_owned()),
+                },
+                crate::metadata::Field {
+                    name: "grams".to_owned(),
+                    ty: TypeRef::Array {
+                        element: Box::new(TypeRef::U32),
+                        len: 2,
+                    },
+                },
+            ],
+        });
+        metadata.typedefs.push(Typedef {
+            name: "FOOD".to_owned(),
+            ty: TypeRef::Named("MEAL".to_owned()),
+        });
+        let idl = generate(&metadata, None).unwrap();
+        assert!(idl.contains(
+            "typedef enum FOOD_KIND\n{\n    FK_FISH = 0,\n    FK_MOUSE = 1\n} FOOD_KIND;\n\n\
+             typedef struct MEAL\n{\n    FOOD_KIND kind;\n    ULONG grams[2];\n} MEAL;\n\n\
+             typedef MEAL FOOD;\n\ninterface IAnimal;\n"
+        ));
+    }
+}
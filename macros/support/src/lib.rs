@@ -3,4 +3,9 @@ extern crate proc_macro;
 pub mod aggr_co_class;
 pub mod co_class;
 pub mod com_interface;
+pub mod header;
+pub mod idl;
+pub mod metadata;
+pub mod rust;
+pub mod tlb;
 mod utils;
@@ -0,0 +1,1148 @@
+//! Reading [`Metadata`] from MIDL interface definitions
+//!
+//! Only the declarations needed to call and implement interfaces are read: interfaces,
+//! coclasses, enums, structs, typedefs and the constants they use. Preprocessor lines,
+//! `cpp_quote`, `midl_pragma`, dispinterfaces and modules are skipped. Methods with a
+//! `call_as` attribute are left out, as only their `local` counterpart is in the VTable.
+
+use super::{
+    Class, Direction, Enum, Field, Interface, Metadata, Method, Param, Struct, TypeRef, Typedef,
+};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// The interfaces declared by the system IDL files, which are not read
+const SYSTEM_INTERFACES: &[&str] = &[
+    "IUnknown",
+    "IClassFactory",
+    "IClassFactory2",
+    "IDispatch",
+    "IEnumString",
+    "IEnumUnknown",
+    "IEnumVARIANT",
+    "IErrorInfo",
+    "IErrorLog",
+    "IMalloc",
+    "IMoniker",
+    "IBindCtx",
+    "IPersist",
+    "IPersistStream",
+    "IPropertyBag",
+    "IRecordInfo",
+    "ISequentialStream",
+    "IServiceProvider",
+    "IStorage",
+    "IStream",
+    "ITypeInfo",
+    "ITypeLib",
+    "IConnectionPoint",
+    "IConnectionPointContainer",
+    "IEnumConnectionPoints",
+    "IEnumConnections",
+];
+
+impl Metadata {
+    /// Read the declarations of an IDL file and of the files it imports
+    ///
+    /// Imports which are not next to the file, like `oaidl.idl`, are skipped. The
+    /// interfaces they declare are expected to be available to the generated code.
+    pub fn parse_idl_file(&mut self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        if self.files.iter().any(|file| file == path) {
+            return Ok(());
+        }
+        let source = std::fs::read_to_string(path)?;
+        self.files.push(path.to_owned());
+        let imports = self
+            .read_idl(&source)
+            .map_err(|e| Error::new(e.kind(), format!("{}:{}", path.display(), e)))?;
+
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for import in imports {
+            let import = dir.join(import);
+            if import.is_file() {
+                self.parse_idl_file(import)?;
+            }
+        }
+        self.resolve_idl_types();
+        Ok(())
+    }
+
+    /// Read the declarations of IDL source code
+    ///
+    /// Imports are skipped.
+    pub fn parse_idl(&mut self, source: &str) -> std::io::Result<()> {
+        self.read_idl(source)?;
+        self.resolve_idl_types();
+        Ok(())
+    }
+
+    /// Read the declarations of `source`, returning the files it imports
+    fn read_idl(&mut self, source: &str) -> std::io::Result<Vec<String>> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+            metadata: self,
+            imports: Vec::new(),
+            anonymous: 0,
+        };
+        while parser.peek().is_some() {
+            parser.item()?;
+        }
+        Ok(parser.imports)
+    }
+
+    /// Name structs and enums by their typedef rather than their tag, and tell interfaces
+    /// apart from other types
+    fn resolve_idl_types(&mut self) {
+        let interfaces = &self.idl_interfaces;
+        let tags = &self.idl_tags;
+        let resolve = |ty: &mut TypeRef| resolve_type(ty, interfaces, tags);
+        for interface in &mut self.interfaces {
+            for method in &mut interface.methods {
+                resolve(&mut method.ret);
+                for param in &mut method.params {
+                    resolve(&mut param.ty);
+                }
+            }
+        }
+        for field in self.structs.iter_mut().flat_map(|s| s.fields.iter_mut()) {
+            resolve(&mut field.ty);
+        }
+        for typedef in &mut self.typedefs {
+            resolve(&mut typedef.ty);
+        }
+    }
+}
+
+fn resolve_type(ty: &mut TypeRef, interfaces: &BTreeSet<String>, tags: &BTreeMap<String, String>) {
+    match ty {
+        TypeRef::Named(name) => {
+            if let Some(typedef) = tags.get(name.as_str()) {
+                *name = typedef.clone();
+            }
+            if interfaces.contains(name.as_str()) || SYSTEM_INTERFACES.contains(&name.as_str()) {
+                *ty = TypeRef::Interface(name.clone());
+            }
+        }
+        TypeRef::Ptr { pointee, .. } => resolve_type(pointee, interfaces, tags),
+        TypeRef::Array { element, .. } => resolve_type(element, interfaces, tags),
+        _ => {}
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(String),
+    Str(String),
+    Punct(char),
+}
+
+fn tokenize(source: &str) -> std::io::Result<Vec<(Token, usize)>> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    let mut line = 1;
+    let mut line_start = true;
+    let unterminated = |what: &str, line: usize| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("{}: unterminated {}", line, what),
+        )
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let start_line = line;
+        let token = match c {
+            '\n' => {
+                line += 1;
+                line_start = true;
+                i += 1;
+                continue;
+            }
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '#' if line_start => {
+                while i < chars.len() && chars[i] != '\n' {
+                    if chars[i] == '\\' && chars.get(i + 1) == Some(&'\n') {
+                        line += 1;
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                continue;
+            }
+            '/' if chars.get(i + 1) == Some(&'/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(unterminated("comment", start_line)),
+                        Some('*') if chars.get(i + 1) == Some(&'/') => break,
+                        Some('\n') => line += 1,
+                        Some(_) => {}
+                    }
+                    i += 1;
+                }
+                i += 2;
+                continue;
+            }
+            '"' => {
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None | Some('\n') => return Err(unterminated("string", start_line)),
+                        Some('"') => break,
+                        Some('\\') if i + 1 < chars.len() => {
+                            value.push(chars[i + 1]);
+                            i += 1;
+                        }
+                        Some(&c) => value.push(c),
+                    }
+                    i += 1;
+                }
+                i += 1;
+                Token::Str(value)
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let text = chars[start..i].iter().collect::<String>();
+                if c.is_ascii_digit() {
+                    Token::Number(text)
+                } else {
+                    Token::Ident(text)
+                }
+            }
+            c => {
+                i += 1;
+                Token::Punct(c)
+            }
+        };
+        line_start = false;
+        tokens.push((token, start_line));
+    }
+    Ok(tokens)
+}
+
+/// An attribute in square brackets, e.g. `size_is(count)`
+struct Attr {
+    name: String,
+    args: Vec<Token>,
+}
+
+fn has_attr(attrs: &[Attr], name: &str) -> bool {
+    attrs.iter().any(|attr| attr.name == name)
+}
+
+/// The GUID of a `uuid` attribute, in upper case
+fn uuid(attrs: &[Attr]) -> Option<String> {
+    let attr = attrs.iter().find(|attr| attr.name == "uuid")?;
+    let uuid = attr
+        .args
+        .iter()
+        .map(|token| match token {
+            Token::Ident(text) | Token::Number(text) | Token::Str(text) => text.clone(),
+            Token::Punct(c) => c.to_string(),
+        })
+        .collect::<String>();
+    Some(uuid.to_uppercase())
+}
+
+fn direction(attrs: &[Attr]) -> Direction {
+    match (has_attr(attrs, "in"), has_attr(attrs, "out")) {
+        (true, true) => Direction::InOut,
+        (_, true) if has_attr(attrs, "retval") => Direction::Retval,
+        (_, true) => Direction::Out,
+        _ => Direction::In,
+    }
+}
+
+/// The start of a declaration: the type before any `*`
+struct Specifier {
+    base: TypeRef,
+    constant: bool,
+    /// Whether the type is a UTF-16 character, making `const WCHAR*` a `PCWSTR`
+    wide_char: bool,
+}
+
+/// The operators of binary expressions from the lowest to the highest precedence
+const OPERATORS: &[&[&str]] = &[
+    &["|"],
+    &["^"],
+    &["&"],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    metadata: &'a mut Metadata,
+    imports: Vec<String>,
+    /// The number of structs and enums without a tag so far
+    anonymous: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.pos + offset).map(|(token, _)| token)
+    }
+
+    fn error(&self, message: String) -> Error {
+        let line = self
+            .tokens
+            .get(self.pos)
+            .or_else(|| self.tokens.last())
+            .map_or(1, |(_, line)| *line);
+        Error::new(ErrorKind::InvalidData, format!("{}: {}", line, message))
+    }
+
+    fn next(&mut self) -> std::io::Result<Token> {
+        match self.tokens.get(self.pos) {
+            Some((token, _)) => {
+                self.pos += 1;
+                Ok(token.clone())
+            }
+            None => Err(self.error("unexpected end of file".to_owned())),
+        }
+    }
+
+    fn is_ident(&self, name: &str) -> bool {
+        match self.peek() {
+            Some(Token::Ident(ident)) => ident == name,
+            _ => false,
+        }
+    }
+
+    fn eat_ident(&mut self, name: &str) -> bool {
+        let found = self.is_ident(name);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn is_punct(&self, c: char) -> bool {
+        self.peek() == Some(&Token::Punct(c))
+    }
+
+    fn eat_punct(&mut self, c: char) -> bool {
+        let found = self.is_punct(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_punct(&mut self, c: char) -> std::io::Result<()> {
+        if self.eat_punct(c) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("`{}`", c)))
+        }
+    }
+
+    fn ident(&mut self) -> std::io::Result<String> {
+        match self.peek() {
+            Some(Token::Ident(ident)) => {
+                let ident = ident.clone();
+                self.pos += 1;
+                Ok(ident)
+            }
+            _ => Err(self.unexpected("a name")),
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> Error {
+        let found = match self.peek() {
+            Some(Token::Ident(text)) | Some(Token::Number(text)) => format!("`{}`", text),
+            Some(Token::Str(text)) => format!("\"{}\"", text),
+            Some(Token::Punct(c)) => format!("`{}`", c),
+            None => "the end of the file".to_owned(),
+        };
+        self.error(format!("expected {}, found {}", expected, found))
+    }
+
+    /// The tokens up to the bracket matching the one just read
+    fn balanced(&mut self, open: char, close: char) -> std::io::Result<Vec<Token>> {
+        let mut tokens = Vec::new();
+        let mut depth = 1;
+        loop {
+            let token = self.next()?;
+            if token == Token::Punct(open) {
+                depth += 1;
+            } else if token == Token::Punct(close) {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(tokens);
+                }
+            }
+            tokens.push(token);
+        }
+    }
+
+    fn attributes(&mut self) -> std::io::Result<Vec<Attr>> {
+        let mut attrs = Vec::new();
+        while self.eat_punct('[') {
+            loop {
+                let name = self.ident()?;
+                let args = if self.eat_punct('(') {
+                    self.balanced('(', ')')?
+                } else {
+                    Vec::new()
+                };
+                attrs.push(Attr { name, args });
+                if !self.eat_punct(',') {
+                    break;
+                }
+            }
+            self.expect_punct(']')?;
+        }
+        Ok(attrs)
+    }
+
+    fn item(&mut self) -> std::io::Result<()> {
+        if self.eat_punct(';') {
+            return Ok(());
+        }
+        if self.eat_ident("import") {
+            loop {
+                match self.next()? {
+                    Token::Str(file) => self.imports.push(file),
+                    _ => return Err(self.error("expected a file name".to_owned())),
+                }
+                if !self.eat_punct(',') {
+                    break;
+                }
+            }
+            return self.expect_punct(';');
+        }
+        if self.eat_ident("importlib")
+            || self.eat_ident("cpp_quote")
+            || self.eat_ident("midl_pragma")
+        {
+            self.expect_punct('(')?;
+            self.balanced('(', ')')?;
+            self.eat_punct(';');
+            return Ok(());
+        }
+
+        let attrs = self.attributes()?;
+        let keyword = self.ident()?;
+        match keyword.as_str() {
+            "interface" => self.interface(&attrs),
+            "coclass" => self.coclass(&attrs),
+            "library" => {
+                self.ident()?;
+                self.expect_punct('{')?;
+                while !self.eat_punct('}') {
+                    if self.peek().is_none() {
+                        return Err(self.unexpected("`}`"));
+                    }
+                    self.item()?;
+                }
+                Ok(())
+            }
+            "dispinterface" | "module" => {
+                self.ident()?;
+                if self.eat_punct('{') {
+                    self.balanced('{', '}')?;
+                }
+                Ok(())
+            }
+            "typedef" => self.typedef(),
+            "struct" | "enum" | "union" => {
+                self.pos -= 1;
+                self.specifier()?;
+                self.expect_punct(';')
+            }
+            "const" => self.constant(),
+            _ => {
+                self.pos -= 1;
+                Err(self.unexpected("a declaration"))
+            }
+        }
+    }
+
+    fn interface(&mut self, attrs: &[Attr]) -> std::io::Result<()> {
+        let name = self.ident()?;
+        self.metadata.idl_interfaces.insert(name.clone());
+        if self.eat_punct(';') {
+            return Ok(());
+        }
+        let base = if self.eat_punct(':') {
+            Some(self.ident()?)
+        } else {
+            None
+        };
+        let iid =
+            uuid(attrs).ok_or_else(|| self.error(format!("interface `{}` has no uuid", name)))?;
+        self.expect_punct('{')?;
+
+        let mut methods = Vec::new();
+        while !self.eat_punct('}') {
+            let declaration = ["typedef", "struct", "enum", "union", "const", "cpp_quote"]
+                .iter()
+                .any(|keyword| self.is_ident(keyword));
+            if declaration || self.is_punct(';') {
+                self.item()?;
+                continue;
+            }
+            let attrs = self.attributes()?;
+            let ret = self.type_()?;
+            let method_name = self.ident()?;
+            self.expect_punct('(')?;
+            let params = self.params()?;
+            self.expect_punct(';')?;
+            if has_attr(&attrs, "call_as") {
+                continue;
+            }
+            let prefix = ["propget", "propput", "propputref"]
+                .iter()
+                .find(|prefix| has_attr(&attrs, prefix))
+                .map_or("", |prefix| &prefix[4..]);
+            let method_name = crate::utils::camel_to_snake(&method_name);
+            methods.push(Method {
+                name: if prefix.is_empty() {
+                    method_name
+                } else {
+                    format!("{}_{}", prefix, method_name)
+                },
+                params,
+                ret,
+            });
+        }
+
+        self.metadata.interfaces.push(Interface {
+            name,
+            iid,
+            base,
+            methods,
+        });
+        Ok(())
+    }
+
+    fn params(&mut self) -> std::io::Result<Vec<Param>> {
+        let mut params = Vec::new();
+        if self.is_ident("void") && self.peek_at(1) == Some(&Token::Punct(')')) {
+            self.pos += 1;
+        }
+        while !self.eat_punct(')') {
+            let attrs = self.attributes()?;
+            let direction = direction(&attrs);
+            let mut ty = self.type_()?;
+            let name = match self.peek() {
+                Some(Token::Ident(_)) => self.ident()?,
+                _ => format!("arg{}", params.len()),
+            };
+            // Arrays are passed as pointers to their first element
+            while self.eat_punct('[') {
+                self.balanced('[', ']')?;
+                ty = TypeRef::Ptr {
+                    mutable: direction != Direction::In,
+                    pointee: Box::new(ty),
+                };
+            }
+            params.push(Param {
+                name,
+                ty,
+                direction,
+            });
+            if !self.eat_punct(',') {
+                self.expect_punct(')')?;
+                break;
+            }
+        }
+        Ok(params)
+    }
+
+    fn coclass(&mut self, attrs: &[Attr]) -> std::io::Result<()> {
+        let name = self.ident()?;
+        self.expect_punct('{')?;
+        let mut interfaces = Vec::new();
+        while !self.eat_punct('}') {
+            let attrs = self.attributes()?;
+            if !self.eat_ident("interface") && !self.eat_ident("dispinterface") {
+                return Err(self.unexpected("`interface`"));
+            }
+            let interface = self.ident()?;
+            self.expect_punct(';')?;
+            // Source interfaces are implemented by the clients
+            if has_attr(&attrs, "source") {
+                continue;
+            }
+            if has_attr(&attrs, "default") {
+                interfaces.insert(0, interface);
+            } else {
+                interfaces.push(interface);
+            }
+        }
+        self.metadata.classes.push(Class {
+            name,
+            clsid: uuid(attrs),
+            interfaces,
+        });
+        Ok(())
+    }
+
+    fn typedef(&mut self) -> std::io::Result<()> {
+        self.attributes()?;
+        let specifier = self.specifier()?;
+        loop {
+            let ty = self.pointers(&specifier);
+            let name = self.ident()?;
+            let ty = self.arrays(ty)?;
+            if !self.name_definition(&ty, &name) && ty != TypeRef::Named(name.clone()) {
+                self.metadata.typedefs.push(Typedef { name, ty });
+            }
+            if !self.eat_punct(',') {
+                break;
+            }
+        }
+        self.expect_punct(';')
+    }
+
+    /// Name the struct or enum `ty` after a typedef, returning whether there was one
+    fn name_definition(&mut self, ty: &TypeRef, name: &str) -> bool {
+        let tag = match ty {
+            TypeRef::Named(tag) if tag != name => tag.clone(),
+            _ => return false,
+        };
+        let metadata = &mut *self.metadata;
+        let definition = metadata
+            .structs
+            .iter_mut()
+            .map(|s| &mut s.name)
+            .chain(metadata.enums.iter_mut().map(|e| &mut e.name))
+            .find(|definition| **definition == tag);
+        match definition {
+            Some(definition) => {
+                *definition = name.to_owned();
+                metadata.idl_tags.insert(tag, name.to_owned());
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn constant(&mut self) -> std::io::Result<()> {
+        self.type_()?;
+        let name = self.ident()?;
+        self.expect_punct('=')?;
+        match self.peek() {
+            Some(Token::Str(_)) => {
+                self.pos += 1;
+            }
+            _ => {
+                let value = self.expr()?;
+                self.metadata.idl_constants.insert(name, value);
+            }
+        }
+        self.expect_punct(';')
+    }
+
+    fn type_(&mut self) -> std::io::Result<TypeRef> {
+        let specifier = self.specifier()?;
+        Ok(self.pointers(&specifier))
+    }
+
+    fn specifier(&mut self) -> std::io::Result<Specifier> {
+        let mut constant = false;
+        while self.eat_ident("const") {
+            constant = true;
+        }
+        let (base, wide_char) = self.base_type()?;
+        while self.eat_ident("const") {
+            constant = true;
+        }
+        Ok(Specifier {
+            base,
+            constant,
+            wide_char,
+        })
+    }
+
+    fn pointers(&mut self, specifier: &Specifier) -> TypeRef {
+        let mut ty = specifier.base.clone();
+        let mut first = true;
+        while self.eat_punct('*') {
+            ty = if first && specifier.constant && specifier.wide_char {
+                TypeRef::Named("PCWSTR".to_owned())
+            } else {
+                TypeRef::Ptr {
+                    mutable: !(first && specifier.constant),
+                    pointee: Box::new(ty),
+                }
+            };
+            first = false;
+            while self.eat_ident("const") {}
+        }
+        ty
+    }
+
+    fn arrays(&mut self, ty: TypeRef) -> std::io::Result<TypeRef> {
+        let mut lens = Vec::new();
+        while self.eat_punct('[') {
+            // A conformant array at the end of a struct takes no space
+            let len = if self.is_punct(']') { 0 } else { self.expr()? };
+            self.expect_punct(']')?;
+            if len < 0 {
+                return Err(self.error(format!("array of {} elements", len)));
+            }
+            lens.push(len as usize);
+        }
+        Ok(lens
+            .into_iter()
+            .rev()
+            .fold(ty, |element, len| TypeRef::Array {
+                element: Box::new(element),
+                len,
+            }))
+    }
+
+    fn base_type(&mut self) -> std::io::Result<(TypeRef, bool)> {
+        const INTEGERS: &[&str] = &[
+            "char",
+            "small",
+            "short",
+            "int",
+            "long",
+            "hyper",
+            "__int8",
+            "__int16",
+            "__int32",
+            "__int64",
+            "__int3264",
+        ];
+        let unsigned = if self.eat_ident("unsigned") {
+            Some(true)
+        } else if self.eat_ident("signed") {
+            Some(false)
+        } else {
+            None
+        };
+        if unsigned.is_some() || INTEGERS.iter().any(|name| self.is_ident(name)) {
+            return Ok((self.integer(unsigned.unwrap_or(false)), false));
+        }
+
+        let name = self.ident()?;
+        if name == "struct" || name == "enum" || name == "union" {
+            return self.definition(&name).map(|ty| (ty, false));
+        }
+        let named = |name: &str| TypeRef::Named(name.to_owned());
+        let reference = |name: &str| TypeRef::Ptr {
+            mutable: false,
+            pointee: Box::new(named(name)),
+        };
+        let ty = match name.as_str() {
+            "void" | "VOID" => TypeRef::Void,
+            "boolean" => TypeRef::Bool,
+            "byte" | "BYTE" | "UCHAR" => TypeRef::U8,
+            "CHAR" => TypeRef::I8,
+            "SHORT" => TypeRef::I16,
+            "USHORT" | "WORD" => TypeRef::U16,
+            "WCHAR" | "OLECHAR" | "wchar_t" => return Ok((TypeRef::U16, true)),
+            "INT" | "LONG" | "INT32" | "LONG32" => TypeRef::I32,
+            "UINT" | "ULONG" | "DWORD" | "UINT32" | "ULONG32" | "DWORD32" => TypeRef::U32,
+            "LONGLONG" | "INT64" | "LONG64" => TypeRef::I64,
+            "ULONGLONG" | "UINT64" | "ULONG64" | "DWORD64" | "DWORDLONG" => TypeRef::U64,
+            "LONG_PTR" | "INT_PTR" | "SSIZE_T" => TypeRef::Isize,
+            "ULONG_PTR" | "UINT_PTR" | "DWORD_PTR" | "SIZE_T" => TypeRef::Usize,
+            "float" | "FLOAT" => TypeRef::F32,
+            "double" | "DOUBLE" => TypeRef::F64,
+            "LPCWSTR" | "LPCOLESTR" | "PCWSTR" => named("PCWSTR"),
+            "LPWSTR" | "LPOLESTR" | "PWSTR" => named("PWSTR"),
+            "REFIID" => reference("IID"),
+            "REFCLSID" => reference("CLSID"),
+            "REFGUID" => reference("GUID"),
+            "LPVOID" | "PVOID" => TypeRef::Ptr {
+                mutable: true,
+                pointee: Box::new(TypeRef::Void),
+            },
+            _ => TypeRef::Named(name),
+        };
+        Ok((ty, false))
+    }
+
+    /// An integer type after `signed` or `unsigned`, if any
+    fn integer(&mut self, unsigned: bool) -> TypeRef {
+        let bits = if self.eat_ident("char") || self.eat_ident("small") || self.eat_ident("__int8")
+        {
+            8
+        } else if self.eat_ident("short") || self.eat_ident("__int16") {
+            self.eat_ident("int");
+            16
+        } else if self.eat_ident("long") {
+            let bits = if self.eat_ident("long") { 64 } else { 32 };
+            self.eat_ident("int");
+            bits
+        } else if self.eat_ident("hyper") || self.eat_ident("__int64") {
+            64
+        } else if self.eat_ident("__int3264") {
+            0
+        } else {
+            let _ = self.eat_ident("int") || self.eat_ident("__int32");
+            32
+        };
+        match (bits, unsigned) {
+            (8, false) => TypeRef::I8,
+            (8, true) => TypeRef::U8,
+            (16, false) => TypeRef::I16,
+            (16, true) => TypeRef::U16,
+            (32, false) => TypeRef::I32,
+            (32, true) => TypeRef::U32,
+            (64, false) => TypeRef::I64,
+            (64, true) => TypeRef::U64,
+            (_, false) => TypeRef::Isize,
+            (_, true) => TypeRef::Usize,
+        }
+    }
+
+    /// A struct or enum, defining it if it has a body
+    fn definition(&mut self, keyword: &str) -> std::io::Result<TypeRef> {
+        let tag = match self.peek() {
+            Some(Token::Ident(_)) => Some(self.ident()?),
+            _ => None,
+        };
+        if !self.eat_punct('{') {
+            return match tag {
+                Some(tag) => Ok(TypeRef::Named(tag)),
+                None => Err(self.unexpected("`{`")),
+            };
+        }
+        let name = tag.unwrap_or_else(|| {
+            self.anonymous += 1;
+            format!("__unnamed_{}_{}", keyword, self.anonymous)
+        });
+        match keyword {
+            "struct" => {
+                let fields = self.fields()?;
+                self.metadata.structs.push(Struct {
+                    name: name.clone(),
+                    fields,
+                });
+            }
+            "enum" => {
+                let variants = self.variants()?;
+                self.metadata.enums.push(Enum {
+                    name: name.clone(),
+                    variants,
+                });
+            }
+            _ => return Err(self.error(format!("`{}` is not supported", keyword))),
+        }
+        Ok(TypeRef::Named(name))
+    }
+
+    fn fields(&mut self) -> std::io::Result<Vec<Field>> {
+        let mut fields = Vec::new();
+        while !self.eat_punct('}') {
+            self.attributes()?;
+            let specifier = self.specifier()?;
+            loop {
+                let ty = self.pointers(&specifier);
+                let name = self.ident()?;
+                let ty = self.arrays(ty)?;
+                fields.push(Field { name, ty });
+                if !self.eat_punct(',') {
+                    break;
+                }
+            }
+            self.expect_punct(';')?;
+        }
+        Ok(fields)
+    }
+
+    fn variants(&mut self) -> std::io::Result<Vec<(String, i64)>> {
+        let mut variants = Vec::new();
+        let mut next = 0;
+        while !self.eat_punct('}') {
+            self.attributes()?;
+            let name = self.ident()?;
+            let value = if self.eat_punct('=') {
+                self.expr()?
+            } else {
+                next
+            };
+            self.metadata.idl_constants.insert(name.clone(), value);
+            variants.push((name, value));
+            next = value.wrapping_add(1);
+            if !self.eat_punct(',') {
+                self.expect_punct('}')?;
+                break;
+            }
+        }
+        Ok(variants)
+    }
+
+    fn expr(&mut self) -> std::io::Result<i64> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> std::io::Result<i64> {
+        if level == OPERATORS.len() {
+            return self.unary();
+        }
+        let mut value = self.binary(level + 1)?;
+        while let Some(operator) = self.operator(OPERATORS[level]) {
+            let rhs = self.binary(level + 1)?;
+            value = match operator {
+                "|" => value | rhs,
+                "^" => value ^ rhs,
+                "&" => value & rhs,
+                "<<" => value.wrapping_shl(rhs as u32),
+                ">>" => value.wrapping_shr(rhs as u32),
+                "+" => value.wrapping_add(rhs),
+                "-" => value.wrapping_sub(rhs),
+                "*" => value.wrapping_mul(rhs),
+                _ if rhs == 0 => return Err(self.error("division by zero".to_owned())),
+                "/" => value.wrapping_div(rhs),
+                _ => value.wrapping_rem(rhs),
+            };
+        }
+        Ok(value)
+    }
+
+    /// The operator of `operators` that comes next, if any
+    fn operator(&mut self, operators: &[&'static str]) -> Option<&'static str> {
+        let operator = operators.iter().find(|operator| {
+            operator
+                .chars()
+                .enumerate()
+                .all(|(i, c)| self.peek_at(i) == Some(&Token::Punct(c)))
+        })?;
+        self.pos += operator.len();
+        Some(operator)
+    }
+
+    fn unary(&mut self) -> std::io::Result<i64> {
+        match self.next()? {
+            Token::Punct('-') => Ok(self.unary()?.wrapping_neg()),
+            Token::Punct('~') => Ok(!self.unary()?),
+            Token::Punct('+') => self.unary(),
+            Token::Punct('(') => {
+                let value = self.expr()?;
+                self.expect_punct(')')?;
+                Ok(value)
+            }
+            Token::Number(number) => parse_number(&number)
+                .ok_or_else(|| self.error(format!("`{}` is not a number", number))),
+            Token::Ident(name) => match self.metadata.idl_constants.get(&name) {
+                Some(value) => Ok(*value),
+                None => Err(self.error(format!("unknown constant `{}`", name))),
+            },
+            _ => {
+                self.pos -= 1;
+                Err(self.unexpected("a number"))
+            }
+        }
+    }
+}
+
+/// A decimal, hexadecimal or octal integer literal with an optional `U` or `L` suffix
+fn parse_number(number: &str) -> Option<i64> {
+    let digits = number.trim_end_matches(|c| c == 'u' || c == 'U' || c == 'l' || c == 'L');
+    let value = if digits.starts_with("0x") || digits.starts_with("0X") {
+        u64::from_str_radix(&digits[2..], 16).ok()?
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        u64::from_str_radix(&digits[1..], 8).ok()?
+    } else {
+        digits.parse::<u64>().ok()?
+    };
+    Some(value as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"
+        // The animals of the zoo
+        import "oaidl.idl";
+        #define MAX_NAME 32
+
+        interface ICat;
+
+        const ULONG NAME_LEN = 16;
+
+        typedef [v1_enum] enum tagANIMAL_KIND {
+            AK_CAT = 1,
+            AK_DOG,
+            AK_BIRD = 0x10 | (1 << 8),
+            AK_ANY = -1,
+        } ANIMAL_KIND;
+
+        typedef struct tagFOOD {
+            ULONG calories;
+            ANIMAL_KIND forKind;
+            WCHAR name[NAME_LEN];
+            struct tagFOOD *next;
+        } FOOD, *LPFOOD;
+
+        [
+            object,
+            uuid(eff8970e-c50f-45e0-9284-291ce5a6f771),
+            pointer_default(unique)
+        ]
+        interface IAnimal : IUnknown
+        {
+            HRESULT Eat([in] const FOOD *food, [in] unsigned long count);
+            [propget] HRESULT Name([out, retval] BSTR *name);
+            [local] HRESULT Friend([in] REFIID riid, [out, iid_is(riid)] void **ppv);
+            [call_as(Friend)] HRESULT RemoteFriend([in] REFIID riid, [out] IUnknown **ppv);
+            HRESULT Kin([in] ICat *cat, [in, out] long *age);
+            ULONG Lives(void);
+            void Greet([in, string] const WCHAR *greeting, [in] BYTE data[4]);
+        };
+
+        [uuid(F5353C58-CFD9-4204-8D92-D274C7578B53), object]
+        interface ICat : IAnimal
+        {
+            HRESULT Purr();
+        };
+
+        [uuid(9BB1E3D1-57E1-4D1C-B2A6-A8B4D93A2E10), version(1.0)]
+        library ZooLib
+        {
+            importlib("stdole2.tlb");
+            [uuid(C5F45CBC-4439-418C-A9F9-05AC67525E43)]
+            coclass Cat
+            {
+                interface IAnimal;
+                [default] interface ICat;
+                [source] interface IAnimalEvents;
+            };
+        };
+    "#;
+
+    fn ptr(mutable: bool, pointee: TypeRef) -> TypeRef {
+        TypeRef::Ptr {
+            mutable,
+            pointee: Box::new(pointee),
+        }
+    }
+
+    fn named(name: &str) -> TypeRef {
+        TypeRef::Named(name.to_owned())
+    }
+
+    #[test]
+    fn declarations() {
+        let mut metadata = Metadata::new();
+        metadata.parse_idl(SOURCE).unwrap();
+
+        assert_eq!(
+            metadata.enums,
+            [Enum {
+                name: "ANIMAL_KIND".to_owned(),
+                variants: vec![
+                    ("AK_CAT".to_owned(), 1),
+                    ("AK_DOG".to_owned(), 2),
+                    ("AK_BIRD".to_owned(), 0x110),
+                    ("AK_ANY".to_owned(), -1),
+                ],
+            }]
+        );
+        assert_eq!(metadata.structs.len(), 1);
+        let food = &metadata.structs[0];
+        assert_eq!(food.name, "FOOD");
+        let types = food.fields.iter().map(|f| &f.ty).collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            [
+                &TypeRef::U32,
+                &named("ANIMAL_KIND"),
+                &TypeRef::Array {
+                    element: Box::new(TypeRef::U16),
+                    len: 16
+                },
+                &ptr(true, named("FOOD")),
+            ]
+        );
+        assert_eq!(
+            metadata.typedefs,
+            [Typedef {
+                name: "LPFOOD".to_owned(),
+                ty: ptr(true, named("FOOD")),
+            }]
+        );
+
+        let animal = metadata.interface("IAnimal").unwrap();
+        assert_eq!(animal.iid, "EFF8970E-C50F-45E0-9284-291CE5A6F771");
+        assert_eq!(animal.base.as_deref(), Some("IUnknown"));
+        let names = animal
+            .methods
+            .iter()
+            .map(|m| m.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["eat", "get_name", "friend", "kin", "lives", "greet"]
+        );
+
+        let eat = &animal.methods[0];
+        assert_eq!(eat.ret, named("HRESULT"));
+        assert_eq!(eat.params[0].ty, ptr(false, named("FOOD")));
+        assert_eq!(eat.params[1].ty, TypeRef::U32);
+        let name = &animal.methods[1].params[0];
+        assert_eq!(name.direction, Direction::Retval);
+        assert_eq!(name.ty, ptr(true, named("BSTR")));
+        let friend = &animal.methods[2].params;
+        assert_eq!(friend[0].ty, ptr(false, named("IID")));
+        assert_eq!(friend[1].ty, ptr(true, ptr(true, TypeRef::Void)));
+        assert_eq!(friend[1].direction, Direction::Out);
+        let kin = &animal.methods[3].params;
+        assert_eq!(kin[0].ty, ptr(true, TypeRef::Interface("ICat".to_owned())));
+        assert_eq!(kin[1].direction, Direction::InOut);
+        assert_eq!(animal.methods[4].params, []);
+        assert_eq!(animal.methods[4].ret, TypeRef::U32);
+        let greet = &animal.methods[5].params;
+        assert_eq!(greet[0].ty, named("PCWSTR"));
+        assert_eq!(greet[1].ty, ptr(false, TypeRef::U8));
+
+        assert_eq!(metadata.sorted_interfaces()[1].name, "ICat");
+        assert_eq!(
+            metadata.classes,
+            [Class {
+                name: "Cat".to_owned(),
+                clsid: Some("C5F45CBC-4439-418C-A9F9-05AC67525E43".to_owned()),
+                interfaces: vec!["ICat".to_owned(), "IAnimal".to_owned()],
+            }]
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let mut metadata = Metadata::new();
+        let error = metadata
+            .parse_idl("interface IFoo;\n[uuid(00000000-0000-0000-0000-000000000001)]\ninterface IBar : IUnknown { HRESULT Baz(int) }")
+            .unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "3: expected `;`, found `}`");
+
+        let error = metadata
+            .parse_idl("typedef union { int a; } U;")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "1: `union` is not supported");
+    }
+}
@@ -0,0 +1,324 @@
+//! The COM interfaces and classes declared in Rust sources
+//!
+//! Build scripts read the `#[com_interface]` traits and `#[co_class]` structs of a crate
+//! into [`Metadata`], which the generators in this crate turn into files for other
+//! languages:
+//!
+//! ```rust,ignore
+//! let mut metadata = Metadata::new();
+//! metadata.parse_file("src/lib.rs")?;
+//! for file in metadata.files() {
+//!     println!("cargo:rerun-if-changed={}", file.display());
+//! }
+//! ```
+//!
+//! Modules declared with `mod name;` are followed to their files. The CLSID of a class
+//! is found through `inproc_dll_module!` and the `CLSID` constant it names, or set with
+//! [`Metadata::set_class_id`].
+//!
+//! Metadata is also read from IDL with [`Metadata::parse_idl_file`], which additionally
+//! fills in the enums, structs and typedefs the interfaces use, and from type libraries
+//! with [`Metadata::parse_tlb_file`].
+
+mod idl;
+mod parse;
+mod tlb;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// The interfaces and classes read from Rust sources
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    /// The interfaces in the order they were declared
+    pub interfaces: Vec<Interface>,
+    /// The classes in the order they were declared
+    pub classes: Vec<Class>,
+    /// The enums read from IDL or type libraries
+    pub enums: Vec<Enum>,
+    /// The structs read from IDL or type libraries
+    pub structs: Vec<Struct>,
+    /// The typedefs read from IDL or type libraries, other than those naming an enum or
+    /// struct
+    pub typedefs: Vec<Typedef>,
+    /// The files read so far
+    files: Vec<PathBuf>,
+    /// The GUID constants by name, as GUID strings
+    guids: BTreeMap<String, String>,
+    /// The names of the CLSID constants by class name, from `inproc_dll_module!`
+    class_id_consts: BTreeMap<String, String>,
+    /// The interfaces declared in IDL, including forward declarations
+    idl_interfaces: BTreeSet<String>,
+    /// The names of IDL structs and enums by their tag, e.g. `ANIMAL` for `tagANIMAL`
+    idl_tags: BTreeMap<String, String>,
+    /// The values of IDL constants and enumerators
+    idl_constants: BTreeMap<String, i64>,
+}
+
+/// A `#[com_interface]` trait
+#[derive(Clone, Debug, PartialEq)]
+pub struct Interface {
+    /// The name of the trait
+    pub name: String,
+    /// The IID, e.g. `EFF8970E-C50F-45E0-9284-291CE5A6F771`
+    pub iid: String,
+    /// The name of the parent interface, `None` for `IUnknown` itself
+    pub base: Option<String>,
+    /// The methods in VTable order, without those of the parent interface
+    pub methods: Vec<Method>,
+}
+
+/// A method of an interface
+#[derive(Clone, Debug, PartialEq)]
+pub struct Method {
+    /// The name of the Rust method, e.g. `get_name`
+    pub name: String,
+    /// The parameters after `&self`
+    pub params: Vec<Param>,
+    /// The return type
+    pub ret: TypeRef,
+}
+
+impl Method {
+    /// The name of the COM method, e.g. `GetName`
+    pub fn com_name(&self) -> String {
+        crate::utils::snake_to_camel(&self.name)
+    }
+}
+
+/// A parameter of a method
+#[derive(Clone, Debug, PartialEq)]
+pub struct Param {
+    /// The name of the parameter
+    pub name: String,
+    /// The type of the parameter
+    pub ty: TypeRef,
+    /// Whether the caller or the callee fills in the parameter
+    pub direction: Direction,
+}
+
+/// Whether the caller or the callee fills in a parameter
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Direction {
+    /// Passed by the caller
+    In,
+    /// Written through a pointer by the callee
+    Out,
+    /// Passed by the caller and updated through a pointer by the callee
+    InOut,
+    /// Written through a pointer by the callee, and the value languages hiding the
+    /// `HRESULT` return instead
+    Retval,
+}
+
+/// The type of a parameter or return value
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeRef {
+    /// `()` or `c_void`
+    Void,
+    /// `bool`
+    Bool,
+    /// `i8`
+    I8,
+    /// `u8`
+    U8,
+    /// `i16`
+    I16,
+    /// `u16`
+    U16,
+    /// `i32`
+    I32,
+    /// `u32`
+    U32,
+    /// `i64`
+    I64,
+    /// `u64`
+    U64,
+    /// `isize`
+    Isize,
+    /// `usize`
+    Usize,
+    /// `f32`
+    F32,
+    /// `f64`
+    F64,
+    /// An interface, from its `{Interface}VPtr`
+    Interface(String),
+    /// Any other type by the last segment of its path, e.g. `HRESULT` or `BSTR`
+    Named(String),
+    /// A raw pointer
+    Ptr {
+        /// Whether it is `*mut` rather than `*const`
+        mutable: bool,
+        /// The type pointed to
+        pointee: Box<TypeRef>,
+    },
+    /// A fixed size array
+    Array {
+        /// The type of the elements
+        element: Box<TypeRef>,
+        /// The number of elements
+        len: usize,
+    },
+}
+
+/// An enum, whose values are 32 bit integers
+#[derive(Clone, Debug, PartialEq)]
+pub struct Enum {
+    /// The name of the enum, e.g. `ANIMAL_KIND`
+    pub name: String,
+    /// The names and values of the enumerators in the order they were declared
+    pub variants: Vec<(String, i64)>,
+}
+
+/// A struct with C layout
+#[derive(Clone, Debug, PartialEq)]
+pub struct Struct {
+    /// The name of the struct
+    pub name: String,
+    /// The fields in the order they were declared
+    pub fields: Vec<Field>,
+}
+
+/// A field of a struct
+#[derive(Clone, Debug, PartialEq)]
+pub struct Field {
+    /// The name of the field
+    pub name: String,
+    /// The type of the field
+    pub ty: TypeRef,
+}
+
+/// Another name for a type
+#[derive(Clone, Debug, PartialEq)]
+pub struct Typedef {
+    /// The new name
+    pub name: String,
+    /// The type named
+    pub ty: TypeRef,
+}
+
+/// A `#[co_class]` struct
+#[derive(Clone, Debug, PartialEq)]
+pub struct Class {
+    /// The name of the struct
+    pub name: String,
+    /// The CLSID, if known
+    pub clsid: Option<String>,
+    /// The interfaces of `implements(...)`, the first one being the default
+    pub interfaces: Vec<String>,
+}
+
+/// The type library holding the classes
+#[derive(Clone, Debug, PartialEq)]
+pub struct Library {
+    /// The name of the library, e.g. `AnimalLib`
+    pub name: String,
+    /// The LIBID
+    pub uuid: String,
+    /// The major and minor version
+    pub version: (u16, u16),
+    /// A description of the library
+    pub help_string: Option<String>,
+}
+
+impl Library {
+    /// A library with version 1.0 and no description
+    pub fn new(name: &str, uuid: &str) -> Self {
+        Library {
+            name: name.to_owned(),
+            uuid: uuid.to_owned(),
+            version: (1, 0),
+            help_string: None,
+        }
+    }
+}
+
+impl Metadata {
+    /// No interfaces or classes
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the interfaces and classes of a file and the modules it declares
+    pub fn parse_file(&mut self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)?;
+        let file = syn::parse_file(&source).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), e),
+            )
+        })?;
+        self.files.push(path.to_owned());
+        let module_dir = parse::module_dir(path);
+        self.parse_items(&file.items, Some(&module_dir))?;
+        self.resolve_class_ids();
+        Ok(())
+    }
+
+    /// Read the interfaces and classes of Rust source code
+    ///
+    /// Modules declared with `mod name;` are skipped.
+    pub fn parse_source(&mut self, source: &str) -> syn::Result<()> {
+        let file = syn::parse_file(source)?;
+        self.parse_items(&file.items, None)
+            .map_err(|e| syn::Error::new(proc_macro2::Span::call_site(), e))?;
+        self.resolve_class_ids();
+        Ok(())
+    }
+
+    /// Set the CLSID of a class, e.g. `8B4EAF50-6D72-4E93-C4B5-A09182736455`
+    pub fn set_class_id(&mut self, class: &str, clsid: &str) {
+        if let Some(class) = self.classes.iter_mut().find(|c| c.name == class) {
+            class.clsid = Some(clsid.to_owned());
+        }
+    }
+
+    /// The interface named `name`
+    pub fn interface(&self, name: &str) -> Option<&Interface> {
+        self.interfaces.iter().find(|i| i.name == name)
+    }
+
+    /// The files read so far
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// The interfaces in an order where every parent interface declared in the sources
+    /// comes before its children
+    pub fn sorted_interfaces(&self) -> Vec<&Interface> {
+        let mut sorted: Vec<&Interface> = Vec::new();
+        let mut pending: Vec<&Interface> = self.interfaces.iter().collect();
+        while !pending.is_empty() {
+            let before = pending.len();
+            pending.retain(|interface| {
+                let ready = match &interface.base {
+                    Some(base) => {
+                        self.interface(base).is_none() || sorted.iter().any(|i| &i.name == base)
+                    }
+                    None => true,
+                };
+                if ready {
+                    sorted.push(interface);
+                }
+                !ready
+            });
+            if pending.len() == before {
+                // A cycle, which the compiler rejects anyway
+                sorted.append(&mut pending);
+            }
+        }
+        sorted
+    }
+
+    fn resolve_class_ids(&mut self) {
+        let (guids, class_id_consts) = (&self.guids, &self.class_id_consts);
+        for class in self.classes.iter_mut().filter(|c| c.clsid.is_none()) {
+            class.clsid = class_id_consts
+                .get(&class.name)
+                .and_then(|name| guids.get(name))
+                .cloned();
+        }
+    }
+}
@@ -0,0 +1,344 @@
+//! Reading [`Metadata`] from the items of Rust source files
+
+use super::{Class, Direction, Interface, Metadata, Method, Param, TypeRef};
+
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use quote::ToTokens;
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::{
+    Attribute, Expr, FnArg, Item, ItemConst, ItemStruct, ItemTrait, Lit, NestedMeta, ReturnType,
+    Token, TraitItem, Type, TypeParamBound,
+};
+
+/// The directory holding the files of the modules declared in the file at `path`
+pub fn module_dir(path: &Path) -> PathBuf {
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some("lib.rs") | Some("main.rs") | Some("mod.rs") => parent.to_owned(),
+        _ => match path.file_stem() {
+            Some(stem) => parent.join(stem),
+            None => parent.to_owned(),
+        },
+    }
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// The attribute `name`, whatever path it is used through
+fn find_attr<'a>(attrs: &'a [Attribute], name: &str) -> Option<&'a Attribute> {
+    attrs.iter().find(|attr| {
+        attr.path
+            .segments
+            .last()
+            .map_or(false, |segment| segment.ident == name)
+    })
+}
+
+fn is_dll_module(mac: &syn::Macro) -> bool {
+    mac.path
+        .segments
+        .last()
+        .map_or(false, |segment| segment.ident == "inproc_dll_module")
+}
+
+fn last_ident(path: &syn::Path) -> Option<String> {
+    path.segments
+        .last()
+        .map(|segment| segment.ident.to_string())
+}
+
+impl Metadata {
+    pub(super) fn parse_items(
+        &mut self,
+        items: &[Item],
+        module_dir: Option<&Path>,
+    ) -> std::io::Result<()> {
+        for item in items {
+            match item {
+                Item::Trait(item) => {
+                    if let Some(attr) = find_attr(&item.attrs, "com_interface") {
+                        let interface = parse_interface(attr, item)?;
+                        self.interfaces.push(interface);
+                    }
+                }
+                Item::Struct(item) => {
+                    let attr = find_attr(&item.attrs, "co_class")
+                        .or_else(|| find_attr(&item.attrs, "aggr_co_class"));
+                    if let Some(attr) = attr {
+                        let class = parse_class(attr, item)?;
+                        self.classes.push(class);
+                    }
+                }
+                Item::Const(item) => {
+                    if let Some(guid) = parse_guid_const(item) {
+                        self.guids.insert(item.ident.to_string(), guid);
+                    }
+                }
+                Item::Macro(item) if is_dll_module(&item.mac) => {
+                    self.parse_dll_module(&item.mac)?;
+                }
+                Item::Mod(item) => {
+                    let name = item.ident.to_string();
+                    let dir = module_dir.map(|dir| dir.join(&name));
+                    match &item.content {
+                        Some((_, items)) => self.parse_items(items, dir.as_deref())?,
+                        None => {
+                            if let Some(dir) = dir {
+                                self.parse_module_file(&dir)?;
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the file of the module whose files are in `dir`
+    fn parse_module_file(&mut self, dir: &Path) -> std::io::Result<()> {
+        let file = dir.with_extension("rs");
+        let path = if file.is_file() {
+            file
+        } else {
+            dir.join("mod.rs")
+        };
+        let source = std::fs::read_to_string(&path)?;
+        let file = syn::parse_file(&source)
+            .map_err(|e| invalid_data(format!("{}: {}", path.display(), e)))?;
+        self.files.push(path);
+        self.parse_items(&file.items, Some(dir))
+    }
+
+    /// Record the CLSID constant of each class of `inproc_dll_module![(CLSID, Class), ...]`
+    ///
+    /// A leading `type_lib(...)` is skipped.
+    fn parse_dll_module(&mut self, mac: &syn::Macro) -> std::io::Result<()> {
+        let entries = Punctuated::<Expr, Token![,]>::parse_terminated
+            .parse2(mac.tokens.clone())
+            .map_err(|e| invalid_data(format!("inproc_dll_module!: {}", e)))?;
+        for entry in entries {
+            let class = match entry {
+                Expr::Tuple(class) => class,
+                _ => continue,
+            };
+            let mut elems = class.elems.iter().map(|elem| match elem {
+                Expr::Path(p) => last_ident(&p.path),
+                _ => None,
+            });
+            if let (Some(Some(clsid)), Some(Some(class))) = (elems.next(), elems.next()) {
+                self.class_id_consts.insert(class, clsid);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_interface(attr: &Attribute, item: &ItemTrait) -> std::io::Result<Interface> {
+    let iid = attr
+        .tokens
+        .clone()
+        .into_iter()
+        .flat_map(|token| match token {
+            proc_macro2::TokenTree::Group(group) => group.stream().into_iter().collect(),
+            token => vec![token],
+        })
+        .find_map(
+            |token| match syn::parse2::<Lit>(token.into_token_stream()) {
+                Ok(Lit::Str(iid)) => Some(iid.value()),
+                _ => None,
+            },
+        )
+        .ok_or_else(|| invalid_data(format!("{} has no IID", item.ident)))?;
+
+    let base = item.supertraits.first().and_then(|bound| match bound {
+        TypeParamBound::Trait(t) => last_ident(&t.path),
+        _ => None,
+    });
+
+    let mut methods = Vec::new();
+    for trait_item in &item.items {
+        let method = match trait_item {
+            TraitItem::Method(m) => m,
+            _ => continue,
+        };
+        // The slots reserved with `#[skip_slots(N)]` are described as methods without
+        // parameters, named after the VTable fields standing in for them
+        for attr in method
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("skip_slots"))
+        {
+            let count = attr
+                .parse_args::<syn::LitInt>()
+                .and_then(|count| count.base10_parse::<usize>())
+                .map_err(|e| invalid_data(format!("{}: {}", item.ident, e)))?;
+            for _ in 0..count {
+                methods.push(Method {
+                    name: format!("reserved{}", methods.len()),
+                    params: Vec::new(),
+                    ret: TypeRef::Void,
+                });
+            }
+        }
+        let params = method
+            .sig
+            .inputs
+            .iter()
+            .filter_map(|param| match param {
+                FnArg::Receiver(_) => None,
+                FnArg::Typed(t) => {
+                    let ty = type_ref(&t.ty);
+                    Some(Param {
+                        name: t.pat.to_token_stream().to_string(),
+                        direction: direction(&ty),
+                        ty,
+                    })
+                }
+            })
+            .collect();
+        let ret = match &method.sig.output {
+            ReturnType::Default => TypeRef::Void,
+            ReturnType::Type(_, ty) => type_ref(ty),
+        };
+        methods.push(Method {
+            name: method.sig.ident.to_string(),
+            params,
+            ret,
+        });
+    }
+
+    Ok(Interface {
+        name: item.ident.to_string(),
+        iid,
+        base,
+        methods,
+    })
+}
+
+fn parse_class(attr: &Attribute, item: &ItemStruct) -> std::io::Result<Class> {
+    let args = attr
+        .parse_args_with(Punctuated::<NestedMeta, Token![,]>::parse_terminated)
+        .map_err(|e| invalid_data(format!("{}: {}", item.ident, e)))?;
+    let args = args.into_iter().collect::<Vec<_>>();
+    let interfaces = crate::utils::base_interface_idents(&args)
+        .iter()
+        .map(|ident| ident.to_string())
+        .collect();
+    Ok(Class {
+        name: item.ident.to_string(),
+        clsid: None,
+        interfaces,
+    })
+}
+
+/// The GUID string of `const NAME: CLSID = CLSID { data1: ..., ... }`
+fn parse_guid_const(item: &ItemConst) -> Option<String> {
+    match &*item.ty {
+        Type::Path(p) => match last_ident(&p.path)?.as_str() {
+            "CLSID" | "IID" | "GUID" => {}
+            _ => return None,
+        },
+        _ => return None,
+    }
+    let fields = match &*item.expr {
+        Expr::Struct(s) => &s.fields,
+        _ => return None,
+    };
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|field| field.member.to_token_stream().to_string() == name)
+            .map(|field| &field.expr)
+    };
+    let int = |expr: &Expr| match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Int(int) => int.base10_parse::<u32>().ok(),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    let data1 = int(field("data1")?)?;
+    let data2 = int(field("data2")?)?;
+    let data3 = int(field("data3")?)?;
+    let data4 = match field("data4")? {
+        Expr::Array(array) => array.elems.iter().map(int).collect::<Option<Vec<_>>>()?,
+        _ => return None,
+    };
+    if data4.len() != 8 {
+        return None;
+    }
+    Some(format!(
+        "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
+        data1,
+        data2,
+        data3,
+        data4[0],
+        data4[1],
+        data4[2],
+        data4[3],
+        data4[4],
+        data4[5],
+        data4[6],
+        data4[7]
+    ))
+}
+
+fn type_ref(ty: &Type) -> TypeRef {
+    match ty {
+        Type::Ptr(p) => TypeRef::Ptr {
+            mutable: p.mutability.is_some(),
+            pointee: Box::new(type_ref(&p.elem)),
+        },
+        Type::Paren(p) => type_ref(&p.elem),
+        Type::Group(g) => type_ref(&g.elem),
+        Type::Tuple(t) if t.elems.is_empty() => TypeRef::Void,
+        Type::Path(p) => {
+            let name = match last_ident(&p.path) {
+                Some(name) => name,
+                None => return TypeRef::Named(ty.to_token_stream().to_string()),
+            };
+            match name.as_str() {
+                "c_void" => TypeRef::Void,
+                "bool" => TypeRef::Bool,
+                "i8" => TypeRef::I8,
+                "u8" => TypeRef::U8,
+                "i16" => TypeRef::I16,
+                "u16" => TypeRef::U16,
+                "i32" => TypeRef::I32,
+                "u32" => TypeRef::U32,
+                "i64" => TypeRef::I64,
+                "u64" => TypeRef::U64,
+                "isize" => TypeRef::Isize,
+                "usize" => TypeRef::Usize,
+                "f32" => TypeRef::F32,
+                "f64" => TypeRef::F64,
+                _ if name.len() > 4 && name.ends_with("VPtr") => {
+                    TypeRef::Interface(name[..name.len() - 4].to_owned())
+                }
+                _ => TypeRef::Named(name),
+            }
+        }
+        _ => TypeRef::Named(ty.to_token_stream().to_string()),
+    }
+}
+
+/// Pointers to anything but an interface or untyped memory are written by the callee
+fn direction(ty: &TypeRef) -> Direction {
+    match ty {
+        TypeRef::Ptr {
+            mutable: true,
+            pointee,
+        } => match **pointee {
+            TypeRef::Void | TypeRef::Interface(_) => Direction::In,
+            _ => Direction::Out,
+        },
+        _ => Direction::In,
+    }
+}
@@ -0,0 +1,322 @@
+//! Reading [`Metadata`] from type libraries
+//!
+//! Interfaces, dual interfaces, coclasses, enums, records and aliases are read. Unions,
+//! modules and dispinterfaces without a VTable are skipped. Type libraries do not record
+//! whether a pointer is `const`, so all pointers are `*mut`, except that `[in]` strings
+//! become `PCWSTR`.
+
+use super::{
+    Class, Direction, Enum, Field, Interface, Metadata, Method, Param, Struct, TypeRef, Typedef,
+};
+use crate::tlb::{self, HRef, TypeDesc, TypeInfo, TypeKind, TypeLib};
+
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// The interfaces of `stdole2.tlb` and `oaidl.idl` other type libraries import, by IID
+const IMPORTED_INTERFACES: &[(&str, &str)] = &[
+    ("00000000-0000-0000-C000-000000000046", "IUnknown"),
+    ("00020400-0000-0000-C000-000000000046", "IDispatch"),
+    ("00020401-0000-0000-C000-000000000046", "ITypeInfo"),
+    ("00020402-0000-0000-C000-000000000046", "ITypeLib"),
+    ("00020404-0000-0000-C000-000000000046", "IEnumVARIANT"),
+];
+
+impl Metadata {
+    /// Read the interfaces, classes and types of a `.tlb` file
+    pub fn parse_tlb_file(&mut self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let type_lib = TypeLib::read(path)?;
+        self.files.push(path.to_owned());
+        self.add_type_lib(&type_lib)
+            .map_err(|e| Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+    }
+
+    /// Add the interfaces, classes and types of a type library
+    pub fn add_type_lib(&mut self, type_lib: &TypeLib) -> std::io::Result<()> {
+        let types = Types { type_lib };
+        for info in &type_lib.types {
+            match info.kind {
+                TypeKind::Interface => self.interfaces.push(types.interface(info)?),
+                TypeKind::Dispatch if info.is_dual() => {
+                    self.interfaces.push(types.interface(info)?)
+                }
+                TypeKind::CoClass => self.classes.push(types.class(info)?),
+                TypeKind::Enum => self.enums.push(Enum {
+                    name: info.name.clone(),
+                    variants: info
+                        .variables
+                        .iter()
+                        .map(|var| (var.name.clone(), var.value.unwrap_or_default()))
+                        .collect(),
+                }),
+                // The GUID struct type libraries declare themselves is the one of com::sys
+                TypeKind::Record if info.name == "GUID" => {}
+                TypeKind::Record => self.structs.push(Struct {
+                    name: info.name.clone(),
+                    fields: info
+                        .variables
+                        .iter()
+                        .map(|var| {
+                            Ok(Field {
+                                name: var.name.clone(),
+                                ty: types.type_ref(&var.ty)?,
+                            })
+                        })
+                        .collect::<std::io::Result<_>>()?,
+                }),
+                TypeKind::Alias => {
+                    if let Some(ty) = &info.alias {
+                        self.typedefs.push(Typedef {
+                            name: info.name.clone(),
+                            ty: types.type_ref(ty)?,
+                        });
+                    }
+                }
+                TypeKind::Dispatch | TypeKind::Module | TypeKind::Union => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Types<'a> {
+    type_lib: &'a TypeLib,
+}
+
+impl Types<'_> {
+    fn interface(&self, info: &TypeInfo) -> std::io::Result<Interface> {
+        let base = match info.impl_types.first() {
+            Some(base) => Some(self.type_name(&base.href)?),
+            None => None,
+        };
+        let mut functions = info.functions.iter().collect::<Vec<_>>();
+        functions.sort_by_key(|function| function.vtable_slot);
+
+        let mut methods = Vec::with_capacity(functions.len());
+        for function in functions {
+            let prefix = match function.invoke_kind {
+                tlb::INVOKE_PROPERTYGET => "get_",
+                tlb::INVOKE_PROPERTYPUT => "put_",
+                tlb::INVOKE_PROPERTYPUTREF => "putref_",
+                _ => "",
+            };
+            let params = function
+                .params
+                .iter()
+                .enumerate()
+                .map(|(index, param)| {
+                    let direction = direction(param.flags);
+                    let ty = match param.ty {
+                        TypeDesc::Base(tlb::VT_LPWSTR) if direction == Direction::In => {
+                            TypeRef::Named("PCWSTR".to_owned())
+                        }
+                        ref ty => self.type_ref(ty)?,
+                    };
+                    Ok(Param {
+                        name: param
+                            .name
+                            .clone()
+                            .unwrap_or_else(|| format!("arg{}", index)),
+                        ty,
+                        direction,
+                    })
+                })
+                .collect::<std::io::Result<_>>()?;
+            methods.push(Method {
+                name: format!("{}{}", prefix, crate::utils::camel_to_snake(&function.name)),
+                params,
+                ret: self.type_ref(&function.ret)?,
+            });
+        }
+
+        Ok(Interface {
+            name: info.name.clone(),
+            iid: self.guid(info)?,
+            base,
+            methods,
+        })
+    }
+
+    fn class(&self, info: &TypeInfo) -> std::io::Result<Class> {
+        let mut interfaces = Vec::new();
+        for impl_type in &info.impl_types {
+            if impl_type.flags & tlb::IMPLTYPEFLAG_FSOURCE != 0 {
+                continue;
+            }
+            let name = self.type_name(&impl_type.href)?;
+            if impl_type.flags & tlb::IMPLTYPEFLAG_FDEFAULT != 0 {
+                interfaces.insert(0, name);
+            } else {
+                interfaces.push(name);
+            }
+        }
+        Ok(Class {
+            name: info.name.clone(),
+            clsid: Some(self.guid(info)?),
+            interfaces,
+        })
+    }
+
+    fn guid(&self, info: &TypeInfo) -> std::io::Result<String> {
+        info.guid
+            .clone()
+            .ok_or_else(|| invalid_data(format!("{} has no GUID", info.name)))
+    }
+
+    fn type_name(&self, href: &HRef) -> std::io::Result<String> {
+        match href {
+            HRef::Local(_) => self
+                .type_lib
+                .type_info(href)
+                .map(|info| info.name.clone())
+                .ok_or_else(|| invalid_data("a reference to a missing type".to_owned())),
+            HRef::External { guid } => IMPORTED_INTERFACES
+                .iter()
+                .find(|(iid, _)| iid == guid)
+                .map(|(_, name)| (*name).to_owned())
+                .ok_or_else(|| {
+                    invalid_data(format!("{} is imported from another type library", guid))
+                }),
+        }
+    }
+
+    fn type_ref(&self, ty: &TypeDesc) -> std::io::Result<TypeRef> {
+        let named = |name: &str| TypeRef::Named(name.to_owned());
+        let pointer = |pointee| TypeRef::Ptr {
+            mutable: true,
+            pointee: Box::new(pointee),
+        };
+        Ok(match ty {
+            TypeDesc::Base(vt) => match *vt {
+                0 | tlb::VT_VOID => TypeRef::Void,
+                tlb::VT_I1 => TypeRef::I8,
+                tlb::VT_UI1 => TypeRef::U8,
+                tlb::VT_I2 => TypeRef::I16,
+                tlb::VT_UI2 => TypeRef::U16,
+                tlb::VT_I4 | tlb::VT_INT | tlb::VT_ERROR => TypeRef::I32,
+                tlb::VT_UI4 | tlb::VT_UINT => TypeRef::U32,
+                // A currency amount is a 64 bit integer scaled by 10000
+                tlb::VT_I8 | tlb::VT_CY => TypeRef::I64,
+                tlb::VT_UI8 => TypeRef::U64,
+                tlb::VT_INT_PTR => TypeRef::Isize,
+                tlb::VT_UINT_PTR => TypeRef::Usize,
+                tlb::VT_R4 => TypeRef::F32,
+                tlb::VT_R8 | tlb::VT_DATE => TypeRef::F64,
+                tlb::VT_BOOL => named("VARIANT_BOOL"),
+                tlb::VT_BSTR => named("BSTR"),
+                tlb::VT_HRESULT => named("HRESULT"),
+                tlb::VT_VARIANT => named("VARIANT"),
+                tlb::VT_DECIMAL => named("DECIMAL"),
+                tlb::VT_LPWSTR => named("PWSTR"),
+                tlb::VT_LPSTR => pointer(TypeRef::I8),
+                tlb::VT_UNKNOWN => pointer(TypeRef::Interface("IUnknown".to_owned())),
+                tlb::VT_DISPATCH => pointer(TypeRef::Interface("IDispatch".to_owned())),
+                vt => return Err(invalid_data(format!("unsupported variant type {}", vt))),
+            },
+            TypeDesc::Ptr(pointee) => pointer(self.type_ref(pointee)?),
+            TypeDesc::SafeArray(_) => pointer(named("SAFEARRAY")),
+            TypeDesc::CArray { element, len } => TypeRef::Array {
+                element: Box::new(self.type_ref(element)?),
+                len: *len,
+            },
+            TypeDesc::UserDefined(href) => {
+                let name = self.type_name(href)?;
+                let interface = match self.type_lib.type_info(href) {
+                    Some(info) => {
+                        info.kind == TypeKind::Interface || info.kind == TypeKind::Dispatch
+                    }
+                    None => true,
+                };
+                if interface {
+                    TypeRef::Interface(name)
+                } else {
+                    TypeRef::Named(name)
+                }
+            }
+        })
+    }
+}
+
+fn direction(flags: u32) -> Direction {
+    if flags & tlb::PARAMFLAG_FRETVAL != 0 {
+        Direction::Retval
+    } else if flags & tlb::PARAMFLAG_FOUT != 0 && flags & tlb::PARAMFLAG_FIN != 0 {
+        Direction::InOut
+    } else if flags & tlb::PARAMFLAG_FOUT != 0 {
+        Direction::Out
+    } else {
+        Direction::In
+    }
+}
+
+fn invalid_data(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zoo() {
+        let type_lib = TypeLib::parse(&crate::tlb::tests::zoo()).unwrap();
+        let mut metadata = Metadata::new();
+        metadata.add_type_lib(&type_lib).unwrap();
+
+        let animal = metadata.interface("IAnimal").unwrap();
+        assert_eq!(animal.base.as_deref(), Some("IUnknown"));
+        let names = animal
+            .methods
+            .iter()
+            .map(|method| method.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["eat", "get_name", "put_name", "meet", "call"]);
+        assert_eq!(animal.methods[1].params[0].direction, Direction::Retval);
+        assert_eq!(
+            metadata.classes,
+            [Class {
+                name: "Cat".to_owned(),
+                clsid: Some("C5F45CBC-4439-418C-A9F9-05AC67525E43".to_owned()),
+                interfaces: vec!["IAnimal".to_owned(), "IUnknown".to_owned()],
+            }]
+        );
+        assert_eq!(metadata.enums[0].variants[1], ("AK_ANY".to_owned(), -1));
+
+        let rust = crate::rust::generate(&metadata);
+        assert!(rust.contains(
+            "#[com::com_interface(\"EFF8970E-C50F-45E0-9284-291CE5A6F771\")]\n\
+             pub trait IAnimal: com::interfaces::iunknown::IUnknown {\n    \
+             unsafe fn eat(&self, grams: i32) -> com::sys::HRESULT;\n    \
+             unsafe fn get_name(&self, name: *mut com::sys::BSTR) -> com::sys::HRESULT;\n    \
+             unsafe fn put_name(&self, name: com::sys::BSTR) -> com::sys::HRESULT;\n    \
+             unsafe fn meet(&self, kind: ANIMAL_KIND, other: *mut IAnimalVPtr, \
+             same: *mut com::sys::VARIANT_BOOL) -> com::sys::HRESULT;\n    \
+             unsafe fn call(&self, name: com::sys::PCWSTR, \
+             keeper: *mut com::interfaces::iunknown::IUnknownVPtr) -> com::sys::HRESULT;\n}\n"
+        ));
+        assert!(rust.contains(
+            "pub struct FEEDING {\n    pub kind: ANIMAL_KIND,\n    pub grams: u32,\n    \
+             pub note: [u8; 8],\n}\n"
+        ));
+        assert!(rust.contains("pub type LPANIMAL = *mut IAnimalVPtr;\n"));
+        assert!(rust.contains(
+            "pub trait IKeeper: IDispatch {\n    \
+             unsafe fn feed(&self, animal: LPANIMAL, feeding: *mut FEEDING) -> com::sys::HRESULT;\n}\n"
+        ));
+        assert!(rust.contains("pub const CLSID_CAT: com::sys::CLSID"));
+    }
+
+    #[test]
+    fn unknown_import() {
+        let mut type_lib = TypeLib::parse(&crate::tlb::tests::zoo()).unwrap();
+        type_lib.types[1].impl_types[0].href = HRef::External {
+            guid: "00000000-0000-0000-0000-000000000001".to_owned(),
+        };
+        let error = Metadata::new().add_type_lib(&type_lib).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "00000000-0000-0000-0000-000000000001 is imported from another type library"
+        );
+    }
+}
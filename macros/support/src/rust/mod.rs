@@ -0,0 +1,359 @@
+//! Generating Rust `#[com_interface]` traits from [`Metadata`]
+//!
+//! A build script turns the IDL of existing interfaces into Rust, so the parameters are
+//! never translated by hand:
+//!
+//! ```rust,ignore
+//! use com_macros_support::metadata::Metadata;
+//! use com_macros_support::rust;
+//!
+//! fn main() -> std::io::Result<()> {
+//!     let mut metadata = Metadata::new();
+//!     metadata.parse_idl_file("idl/animal.idl")?;
+//!     for file in metadata.files() {
+//!         println!("cargo:rerun-if-changed={}", file.display());
+//!     }
+//!     let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+//!     rust::write(&metadata, out_dir.join("animal.rs"))
+//! }
+//! ```
+//!
+//! Type libraries are read the same way with `metadata.parse_tlb_file("zoo.tlb")`.
+//!
+//! The crate then includes the generated items into a module of its own:
+//!
+//! ```rust,ignore
+//! pub mod animal {
+//!     include!(concat!(env!("OUT_DIR"), "/animal.rs"));
+//! }
+//! ```
+//!
+//! Types and interfaces provided by `com` are named by their full path. Other interfaces
+//! the generated ones refer to, like `IDispatch`, are expected to be in scope. Enums
+//! become a type alias of `i32` with a constant for each enumerator, and each class with
+//! a CLSID gets a `CLSID_{CLASS_NAME}` constant.
+//!
+//! [`Metadata`]: ../metadata/struct.Metadata.html
+
+use crate::metadata::{Enum, Interface, Metadata, Struct, TypeRef, Typedef};
+use crate::utils::camel_to_snake;
+
+use std::fmt::Write;
+use std::path::Path;
+
+/// The types of `com::sys` that parameters may use
+const SYS_TYPES: &[&str] = &[
+    "BOOL",
+    "BSTR",
+    "CLSID",
+    "COSERVERINFO",
+    "FILETIME",
+    "GUID",
+    "HKEY",
+    "HRESULT",
+    "HWND",
+    "IID",
+    "LICINFO",
+    "MSG",
+    "MULTI_QI",
+    "PCWSTR",
+    "POINT",
+    "PWSTR",
+    "STATSTG",
+    "VARIANT_BOOL",
+];
+
+/// The interfaces of `com::interfaces`
+const COM_INTERFACES: &[&str] = &[
+    "IUnknown",
+    "IClassFactory",
+    "IClassFactory2",
+    "IConnectionPoint",
+    "IConnectionPointContainer",
+    "IEnumConnectionPoints",
+    "IEnumConnections",
+    "IEnumUnknown",
+    "IErrorInfo",
+    "IMalloc",
+    "IPersist",
+    "IPersistStream",
+    "ISequentialStream",
+    "IStream",
+    "ITypeInfo",
+    "ITypeLib",
+];
+
+/// The Rust items for the interfaces, classes and types of `metadata`
+pub fn generate(metadata: &Metadata) -> String {
+    let generator = Generator { metadata };
+    let mut rust = String::new();
+    rust.push_str("// Generated from the interface definitions, do not edit\n");
+
+    for item in &metadata.enums {
+        rust.push('\n');
+        generator.gen_enum(&mut rust, item);
+    }
+    for item in &metadata.structs {
+        rust.push('\n');
+        generator.gen_struct(&mut rust, item);
+    }
+    if !metadata.typedefs.is_empty() {
+        rust.push('\n');
+    }
+    for item in &metadata.typedefs {
+        generator.gen_typedef(&mut rust, item);
+    }
+    for interface in metadata.sorted_interfaces() {
+        // Provided by `com`, and the root of every other interface
+        if interface.base.is_none() {
+            continue;
+        }
+        rust.push('\n');
+        generator.gen_interface(&mut rust, interface);
+    }
+    for class in &metadata.classes {
+        if let Some(clsid) = &class.clsid {
+            let name = format!("CLSID_{}", camel_to_snake(&class.name).to_uppercase());
+            rust.push('\n');
+            gen_guid(&mut rust, &name, clsid);
+        }
+    }
+    rust
+}
+
+/// Write the Rust items for the interfaces, classes and types of `metadata` to `path`
+pub fn write(metadata: &Metadata, path: impl AsRef<Path>) -> std::io::Result<()> {
+    std::fs::write(path, generate(metadata))
+}
+
+struct Generator<'a> {
+    metadata: &'a Metadata,
+}
+
+impl Generator<'_> {
+    fn gen_enum(&self, rust: &mut String, item: &Enum) {
+        allow_non_camel_case(rust, &item.name);
+        writeln!(rust, "pub type {} = i32;", item.name).unwrap();
+        for (name, value) in &item.variants {
+            if name.chars().any(char::is_lowercase) {
+                rust.push_str("#[allow(non_upper_case_globals)]\n");
+            }
+            // Enumerators are 32 bit, whatever the value is written as
+            writeln!(
+                rust,
+                "pub const {}: {} = {};",
+                name, item.name, *value as i32
+            )
+            .unwrap();
+        }
+    }
+
+    fn gen_struct(&self, rust: &mut String, item: &Struct) {
+        rust.push_str("#[repr(C)]\n#[derive(Copy, Clone)]\n");
+        allow_non_camel_case(rust, &item.name);
+        writeln!(rust, "pub struct {} {{", item.name).unwrap();
+        for field in &item.fields {
+            writeln!(
+                rust,
+                "    pub {}: {},",
+                field_name(&field.name),
+                self.type_name(&field.ty)
+            )
+            .unwrap();
+        }
+        rust.push_str("}\n");
+    }
+
+    fn gen_typedef(&self, rust: &mut String, item: &Typedef) {
+        allow_non_camel_case(rust, &item.name);
+        writeln!(
+            rust,
+            "pub type {} = {};",
+            item.name,
+            self.type_name(&item.ty)
+        )
+        .unwrap();
+    }
+
+    fn gen_interface(&self, rust: &mut String, interface: &Interface) {
+        let base = interface.base.as_deref().unwrap_or("IUnknown");
+        writeln!(
+            rust,
+            "#[com::com_interface(\"{}\")]\npub trait {}: {} {{",
+            interface.iid,
+            interface.name,
+            self.interface_path(base)
+        )
+        .unwrap();
+        for method in &interface.methods {
+            let params = method
+                .params
+                .iter()
+                .map(|param| {
+                    format!(
+                        ", {}: {}",
+                        field_name(&param.name),
+                        self.type_name(&param.ty)
+                    )
+                })
+                .collect::<String>();
+            let ret = match method.ret {
+                TypeRef::Void => String::new(),
+                ref ret => format!(" -> {}", self.type_name(ret)),
+            };
+            writeln!(
+                rust,
+                "    unsafe fn {}(&self{}){};",
+                field_name(&method.name),
+                params,
+                ret
+            )
+            .unwrap();
+        }
+        rust.push_str("}\n");
+    }
+
+    /// The path of an interface trait
+    fn interface_path(&self, name: &str) -> String {
+        if self.metadata.interface(name).is_none() && COM_INTERFACES.contains(&name) {
+            format!("com::interfaces::{}::{}", camel_to_snake(name), name)
+        } else {
+            name.to_owned()
+        }
+    }
+
+    /// The Rust spelling of a type
+    fn type_name(&self, ty: &TypeRef) -> String {
+        match ty {
+            TypeRef::Void => "std::ffi::c_void".to_owned(),
+            TypeRef::Bool => "bool".to_owned(),
+            TypeRef::I8 => "i8".to_owned(),
+            TypeRef::U8 => "u8".to_owned(),
+            TypeRef::I16 => "i16".to_owned(),
+            TypeRef::U16 => "u16".to_owned(),
+            TypeRef::I32 => "i32".to_owned(),
+            TypeRef::U32 => "u32".to_owned(),
+            TypeRef::I64 => "i64".to_owned(),
+            TypeRef::U64 => "u64".to_owned(),
+            TypeRef::Isize => "isize".to_owned(),
+            TypeRef::Usize => "usize".to_owned(),
+            TypeRef::F32 => "f32".to_owned(),
+            TypeRef::F64 => "f64".to_owned(),
+            TypeRef::Interface(name) => format!("{}VPtr", self.interface_path(name)),
+            TypeRef::Named(name) => {
+                let local = self.metadata.enums.iter().any(|e| &e.name == name)
+                    || self.metadata.structs.iter().any(|s| &s.name == name)
+                    || self.metadata.typedefs.iter().any(|t| &t.name == name);
+                if !local && SYS_TYPES.contains(&name.as_str()) {
+                    format!("com::sys::{}", name)
+                } else {
+                    name.clone()
+                }
+            }
+            TypeRef::Ptr { mutable, pointee } => format!(
+                "*{} {}",
+                if *mutable { "mut" } else { "const" },
+                self.type_name(pointee)
+            ),
+            TypeRef::Array { element, len } => format!("[{}; {}]", self.type_name(element), len),
+        }
+    }
+}
+
+fn allow_non_camel_case(rust: &mut String, name: &str) {
+    if name.contains('_') || name.starts_with(char::is_lowercase) {
+        rust.push_str("#[allow(non_camel_case_types)]\n");
+    }
+}
+
+/// The snake case name of a field, parameter or method, e.g. `ppv_object` for `ppvObject`
+fn field_name(name: &str) -> String {
+    const KEYWORDS: &[&str] = &[
+        "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+        "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+        "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+        "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+        "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+    ];
+    let name = camel_to_snake(name);
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("{}_", name)
+    } else {
+        name
+    }
+}
+
+fn gen_guid(rust: &mut String, name: &str, guid: &str) {
+    let fields = match crate::utils::guid_fields(guid) {
+        Some(fields) => fields,
+        None => return,
+    };
+    let data4 = fields[3..]
+        .iter()
+        .map(|byte| format!("0x{}", byte))
+        .collect::<Vec<_>>();
+    writeln!(
+        rust,
+        "pub const {}: com::sys::CLSID = com::sys::CLSID {{\n    data1: 0x{},\n    \
+         data2: 0x{},\n    data3: 0x{},\n    data4: [{}],\n}};",
+        name,
+        fields[0],
+        fields[1],
+        fields[2],
+        data4.join(", ")
+    )
+    .unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn items() {
+        let mut metadata = Metadata::new();
+        metadata
+            .parse_idl(
+                r#"
+                typedef enum tagKIND { K_one = 1, K_TWO } KIND;
+                typedef struct POINT { LONG xPos; KIND kind; BYTE type[2]; } POINT, *PPOINT;
+
+                [object, uuid(4f1e2d3c-5b6a-4798-8a7b-6c5d4e3f2a1b)]
+                interface IShape : IUnknown
+                {
+                    HRESULT Move([in] const POINT *to, [in] REFIID riid);
+                    [propget] HRESULT Name([out, retval] BSTR *pName);
+                    void Stream([in] IStream *stream, [in] IDispatch *dispatch);
+                };
+
+                [uuid(C5F45CBC-4439-418C-A9F9-05AC67525E43)]
+                coclass ShapeFactory { interface IShape; };
+                "#,
+            )
+            .unwrap();
+        let rust = generate(&metadata);
+
+        assert!(rust.contains(
+            "pub type KIND = i32;\n\
+             #[allow(non_upper_case_globals)]\npub const K_one: KIND = 1;\n\
+             pub const K_TWO: KIND = 2;\n"
+        ));
+        assert!(rust.contains(
+            "#[repr(C)]\n#[derive(Copy, Clone)]\npub struct POINT {\n    pub x_pos: i32,\n    \
+             pub kind: KIND,\n    pub type_: [u8; 2],\n}\n"
+        ));
+        assert!(rust.contains("pub type PPOINT = *mut POINT;\n"));
+        assert!(rust.contains(
+            "#[com::com_interface(\"4F1E2D3C-5B6A-4798-8A7B-6C5D4E3F2A1B\")]\n\
+             pub trait IShape: com::interfaces::iunknown::IUnknown {\n    \
+             unsafe fn move_(&self, to: *const POINT, riid: *const com::sys::IID) -> com::sys::HRESULT;\n    \
+             unsafe fn get_name(&self, p_name: *mut com::sys::BSTR) -> com::sys::HRESULT;\n    \
+             unsafe fn stream(&self, stream: *mut com::interfaces::istream::IStreamVPtr, \
+             dispatch: *mut IDispatchVPtr);\n}\n"
+        ));
+        assert!(rust.contains(
+            "pub const CLSID_SHAPE_FACTORY: com::sys::CLSID = com::sys::CLSID {\n    \
+             data1: 0xC5F45CBC,\n"
+        ));
+    }
+}
@@ -0,0 +1,1309 @@
+//! Reading type libraries in the MSFT format written by MIDL
+//!
+//! [`TypeLib::read`] parses a `.tlb` file without loading it through `oleaut32`, so type
+//! libraries can be read on any platform, e.g. from a build script:
+//!
+//! ```rust,ignore
+//! use com_macros_support::tlb::TypeLib;
+//!
+//! let type_lib = TypeLib::read("zoo.tlb")?;
+//! for type_info in &type_lib.types {
+//!     println!("{:?} {} {:?}", type_info.kind, type_info.name, type_info.guid);
+//! }
+//! ```
+//!
+//! [`Metadata::parse_tlb_file`] reads the interfaces, classes and types of a type library
+//! into [`Metadata`] instead, for the generators.
+//!
+//! The older SLTG format and type libraries embedded in the resources of a DLL are not
+//! supported.
+//!
+//! [`generate`] and [`write`] go the other way, writing the type library of [`Metadata`]
+//! that late-bound clients and the type library marshaler need:
+//!
+//! ```rust,ignore
+//! use com_macros_support::metadata::{Library, Metadata};
+//! use com_macros_support::tlb;
+//!
+//! let mut metadata = Metadata::new();
+//! metadata.parse_file("src/lib.rs")?;
+//! let library = Library::new("AnimalLib", "C5F45CBC-4439-418C-A9F9-05AC67525E43");
+//! let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+//! tlb::write(&metadata, &library, out_dir.join("animal.tlb"))?;
+//! ```
+//!
+//! [`generate`]: fn.generate.html
+//! [`write`]: fn.write.html
+//! [`Metadata`]: ../metadata/struct.Metadata.html
+//! [`Metadata::parse_tlb_file`]: ../metadata/struct.Metadata.html#method.parse_tlb_file
+
+mod writer;
+
+pub use writer::{generate, write};
+
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// `i16`
+pub const VT_I2: u16 = 2;
+/// `i32`
+pub const VT_I4: u16 = 3;
+/// `f32`
+pub const VT_R4: u16 = 4;
+/// `f64`
+pub const VT_R8: u16 = 5;
+/// A currency amount, scaled by 10000
+pub const VT_CY: u16 = 6;
+/// A date as the days since December 30, 1899
+pub const VT_DATE: u16 = 7;
+/// `BSTR`
+pub const VT_BSTR: u16 = 8;
+/// `IDispatch*`
+pub const VT_DISPATCH: u16 = 9;
+/// `SCODE`
+pub const VT_ERROR: u16 = 10;
+/// `VARIANT_BOOL`
+pub const VT_BOOL: u16 = 11;
+/// `VARIANT`
+pub const VT_VARIANT: u16 = 12;
+/// `IUnknown*`
+pub const VT_UNKNOWN: u16 = 13;
+/// `DECIMAL`
+pub const VT_DECIMAL: u16 = 14;
+/// `i8`
+pub const VT_I1: u16 = 16;
+/// `u8`
+pub const VT_UI1: u16 = 17;
+/// `u16`
+pub const VT_UI2: u16 = 18;
+/// `u32`
+pub const VT_UI4: u16 = 19;
+/// `i64`
+pub const VT_I8: u16 = 20;
+/// `u64`
+pub const VT_UI8: u16 = 21;
+/// C `int`
+pub const VT_INT: u16 = 22;
+/// C `unsigned int`
+pub const VT_UINT: u16 = 23;
+/// `void`
+pub const VT_VOID: u16 = 24;
+/// `HRESULT`
+pub const VT_HRESULT: u16 = 25;
+/// A pointer, see [`TypeDesc::Ptr`](enum.TypeDesc.html#variant.Ptr)
+pub const VT_PTR: u16 = 26;
+/// A `SAFEARRAY`, see [`TypeDesc::SafeArray`](enum.TypeDesc.html#variant.SafeArray)
+pub const VT_SAFEARRAY: u16 = 27;
+/// A C array, see [`TypeDesc::CArray`](enum.TypeDesc.html#variant.CArray)
+pub const VT_CARRAY: u16 = 28;
+/// A type of a type library, see [`TypeDesc::UserDefined`](enum.TypeDesc.html#variant.UserDefined)
+pub const VT_USERDEFINED: u16 = 29;
+/// A null terminated ANSI string
+pub const VT_LPSTR: u16 = 30;
+/// A null terminated UTF-16 string
+pub const VT_LPWSTR: u16 = 31;
+/// `isize`
+pub const VT_INT_PTR: u16 = 37;
+/// `usize`
+pub const VT_UINT_PTR: u16 = 38;
+
+/// A parameter passed by the caller
+pub const PARAMFLAG_FIN: u32 = 0x1;
+/// A parameter written by the callee
+pub const PARAMFLAG_FOUT: u32 = 0x2;
+/// The parameter is the return value for languages hiding the `HRESULT`
+pub const PARAMFLAG_FRETVAL: u32 = 0x8;
+
+/// A method
+pub const INVOKE_FUNC: u32 = 1;
+/// A property getter
+pub const INVOKE_PROPERTYGET: u32 = 2;
+/// A property setter
+pub const INVOKE_PROPERTYPUT: u32 = 4;
+/// A property setter taking a reference
+pub const INVOKE_PROPERTYPUTREF: u32 = 8;
+
+/// The interface is the default one of a coclass
+pub const IMPLTYPEFLAG_FDEFAULT: u32 = 0x1;
+/// The interface is an outgoing interface of a coclass
+pub const IMPLTYPEFLAG_FSOURCE: u32 = 0x2;
+
+/// A dispinterface which can also be called through its VTable
+pub const TYPEFLAG_FDUAL: u32 = 0x40;
+
+/// "MSFT" in little endian
+const MSFT_MAGIC: u32 = 0x5446_534D;
+/// "SLTG" in little endian
+const SLTG_MAGIC: u32 = 0x4754_4C53;
+const HEADER_SIZE: usize = 0x54;
+/// The header is followed by the string offset of the help DLL
+const HELP_DLL_FLAG: u32 = 0x100;
+const SYS_WIN64: u32 = 3;
+const TYPE_INFO_SIZE: usize = 0x64;
+/// The imported type is found by its GUID rather than its index
+const IMPINFO_OFFSET_IS_GUID: i32 = 0x0001_0000;
+const VT_TYPEMASK: i32 = 0x0fff;
+const VAR_CONST: i16 = 2;
+/// Nesting of pointers and arrays beyond which a type library is taken to be corrupt
+const MAX_TYPE_DEPTH: usize = 32;
+
+const IDISPATCH_GUID: &str = "00020400-0000-0000-C000-000000000046";
+
+/// The segments in the order of the segment directory
+#[derive(Copy, Clone)]
+enum Segment {
+    TypeInfoTab = 0,
+    ImpInfo = 1,
+    ImpFiles = 2,
+    RefTab = 3,
+    GuidHash = 4,
+    GuidTab = 5,
+    NameHash = 6,
+    NameTab = 7,
+    StringTab = 8,
+    TypedescTab = 9,
+    ArrayDesc = 10,
+    CustData = 11,
+}
+const SEGMENT_COUNT: usize = 15;
+
+/// A type library
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeLib {
+    /// The name of the library, e.g. `ZooLib`
+    pub name: String,
+    /// The LIBID, e.g. `9BB1E3D1-57E1-4D1C-B2A6-A8B4D93A2E10`
+    pub guid: String,
+    /// The major and minor version
+    pub version: (u16, u16),
+    /// The locale of the names and documentation
+    pub lcid: u32,
+    /// A description of the library
+    pub help_string: Option<String>,
+    /// The types in the order of the library
+    pub types: Vec<TypeInfo>,
+}
+
+/// A type of a type library
+#[derive(Clone, Debug, PartialEq)]
+pub struct TypeInfo {
+    /// The name of the type
+    pub name: String,
+    /// The IID, CLSID or other GUID of the type, if it has one
+    pub guid: Option<String>,
+    /// The kind of type
+    pub kind: TypeKind,
+    /// The `TYPEFLAG_*` flags
+    pub flags: u32,
+    /// The major and minor version
+    pub version: (u16, u16),
+    /// A description of the type
+    pub help_string: Option<String>,
+    /// The methods and property accessors in the order they were declared
+    pub functions: Vec<FuncDesc>,
+    /// The enumerators, fields or constants in the order they were declared
+    pub variables: Vec<VarDesc>,
+    /// The parent of an interface, or the interfaces of a coclass
+    pub impl_types: Vec<ImplType>,
+    /// The type named by an alias
+    pub alias: Option<TypeDesc>,
+}
+
+impl TypeInfo {
+    /// Whether the type is a dispinterface which can also be called through its VTable
+    pub fn is_dual(&self) -> bool {
+        self.kind == TypeKind::Dispatch && self.flags & TYPEFLAG_FDUAL != 0
+    }
+}
+
+/// The kind of a type
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TypeKind {
+    /// An enumeration
+    Enum,
+    /// A structure
+    Record,
+    /// A module of static functions and constants
+    Module,
+    /// An interface with a VTable
+    Interface,
+    /// A dispinterface, called through `IDispatch::Invoke` unless it is dual
+    Dispatch,
+    /// A creatable class
+    CoClass,
+    /// Another name for a type
+    Alias,
+    /// A union
+    Union,
+}
+
+/// A method or property accessor
+#[derive(Clone, Debug, PartialEq)]
+pub struct FuncDesc {
+    /// The name of the method or property
+    pub name: String,
+    /// The DISPID of the function
+    pub member_id: i32,
+    /// Whether it is a method or property accessor (`INVOKE_*`)
+    pub invoke_kind: u32,
+    /// Whether it is virtual, static or dispatch only (`FUNC_*`)
+    pub func_kind: u32,
+    /// The index of the function in the VTable, counting inherited functions
+    pub vtable_slot: usize,
+    /// The return type
+    pub ret: TypeDesc,
+    /// The parameters
+    pub params: Vec<ParamDesc>,
+}
+
+/// A parameter of a function
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParamDesc {
+    /// The name of the parameter, if it has one
+    pub name: Option<String>,
+    /// The type of the parameter
+    pub ty: TypeDesc,
+    /// The `PARAMFLAG_*` flags
+    pub flags: u32,
+}
+
+/// An enumerator, field or constant
+#[derive(Clone, Debug, PartialEq)]
+pub struct VarDesc {
+    /// The name of the variable
+    pub name: String,
+    /// The DISPID of the variable
+    pub member_id: i32,
+    /// The type of the variable
+    pub ty: TypeDesc,
+    /// The value of an integer constant
+    pub value: Option<i64>,
+}
+
+/// An interface inherited or implemented by a type
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImplType {
+    /// The interface
+    pub href: HRef,
+    /// The `IMPLTYPEFLAG_*` flags
+    pub flags: u32,
+}
+
+/// The type of a parameter, variable or alias
+#[derive(Clone, Debug, PartialEq)]
+pub enum TypeDesc {
+    /// A type without parameters (`VT_*`), e.g. `VT_I4` or `VT_BSTR`
+    Base(u16),
+    /// A pointer
+    Ptr(Box<TypeDesc>),
+    /// A `SAFEARRAY` of the type
+    SafeArray(Box<TypeDesc>),
+    /// A fixed size array
+    CArray {
+        /// The type of the elements
+        element: Box<TypeDesc>,
+        /// The number of elements, over all dimensions
+        len: usize,
+    },
+    /// A type of this or another type library
+    UserDefined(HRef),
+}
+
+/// A reference to a type
+#[derive(Clone, Debug, PartialEq)]
+pub enum HRef {
+    /// The type at the index in [`TypeLib::types`](struct.TypeLib.html#structfield.types)
+    Local(usize),
+    /// A type imported from another type library, by its GUID
+    External {
+        /// The GUID of the type
+        guid: String,
+    },
+}
+
+impl TypeLib {
+    /// Read the type library of a `.tlb` file
+    pub fn read(path: impl AsRef<Path>) -> std::io::Result<TypeLib> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)?;
+        Self::parse(&data).map_err(|e| Error::new(e.kind(), format!("{}: {}", path.display(), e)))
+    }
+
+    /// Read a type library from its bytes
+    pub fn parse(data: &[u8]) -> std::io::Result<TypeLib> {
+        let reader = Reader::new(data)?;
+        let count = reader.i32(0x20)?;
+        if count < 0 {
+            return Err(invalid_data("the number of types is negative"));
+        }
+        let version = reader.u32(0x18)?;
+        Ok(TypeLib {
+            name: reader.name(reader.i32(0x38)?)?,
+            guid: reader.guid(reader.i32(0x08)?)?,
+            version: (version as u16, (version >> 16) as u16),
+            lcid: reader.u32(0x10)?,
+            help_string: reader.string(reader.i32(0x24)?)?,
+            types: (0..count as usize)
+                .map(|index| reader.type_info(index))
+                .collect::<std::io::Result<_>>()?,
+        })
+    }
+
+    /// The type referenced by `href`, if it is in this library
+    pub fn type_info(&self, href: &HRef) -> Option<&TypeInfo> {
+        match href {
+            HRef::Local(index) => self.types.get(*index),
+            HRef::External { .. } => None,
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    /// The file offsets of the segments, negative for missing ones
+    segments: [i32; SEGMENT_COUNT],
+    /// The size of the function pointers of the VTables
+    ptr_size: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> std::io::Result<Self> {
+        let mut reader = Reader {
+            data,
+            segments: [-1; SEGMENT_COUNT],
+            ptr_size: 4,
+        };
+        match reader.u32(0) {
+            Ok(MSFT_MAGIC) => {}
+            Ok(SLTG_MAGIC) => return Err(invalid_data("SLTG type libraries are not supported")),
+            _ => return Err(invalid_data("not an MSFT type library")),
+        }
+        let var_flags = reader.u32(0x14)?;
+        if var_flags & 0xf == SYS_WIN64 {
+            reader.ptr_size = 8;
+        }
+
+        let mut directory = HEADER_SIZE + reader.i32(0x20)?.max(0) as usize * 4;
+        if var_flags & HELP_DLL_FLAG != 0 {
+            directory += 4;
+        }
+        // Every entry ends with the same marker, which tells whether the directory was found
+        if reader.i32(directory + 12)? != 0x0f {
+            return Err(invalid_data("the segment directory is missing"));
+        }
+        for (index, segment) in reader.segments.iter_mut().enumerate() {
+            *segment = read_i32(data, directory + index * 16)?;
+        }
+        Ok(reader)
+    }
+
+    fn bytes(&self, offset: usize, len: usize) -> std::io::Result<&'a [u8]> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| invalid_data("the type library is truncated"))
+    }
+
+    fn i16(&self, offset: usize) -> std::io::Result<i16> {
+        let bytes = self.bytes(offset, 2)?;
+        Ok(i16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u16(&self, offset: usize) -> std::io::Result<u16> {
+        self.i16(offset).map(|value| value as u16)
+    }
+
+    fn i32(&self, offset: usize) -> std::io::Result<i32> {
+        read_i32(self.data, offset)
+    }
+
+    fn u32(&self, offset: usize) -> std::io::Result<u32> {
+        self.i32(offset).map(|value| value as u32)
+    }
+
+    /// The file offset of `offset` into `segment`
+    fn segment(&self, segment: Segment, offset: i32) -> std::io::Result<usize> {
+        let start = self.segments[segment as usize];
+        if start < 0 || offset < 0 {
+            return Err(invalid_data("a reference to a missing table entry"));
+        }
+        Ok(start as usize + offset as usize)
+    }
+
+    fn name(&self, offset: i32) -> std::io::Result<String> {
+        let at = self.segment(Segment::NameTab, offset)?;
+        let len = self.u32(at + 8)? as usize & 0xff;
+        Ok(latin1(self.bytes(at + 12, len)?))
+    }
+
+    fn string(&self, offset: i32) -> std::io::Result<Option<String>> {
+        if offset < 0 {
+            return Ok(None);
+        }
+        let at = self.segment(Segment::StringTab, offset)?;
+        let len = self.u16(at)? as usize;
+        Ok(Some(latin1(self.bytes(at + 2, len)?)))
+    }
+
+    fn guid(&self, offset: i32) -> std::io::Result<String> {
+        let at = self.segment(Segment::GuidTab, offset)?;
+        let bytes = self.bytes(at, 16)?;
+        let data4 = bytes[8..]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect::<String>();
+        Ok(format!(
+            "{:08X}-{:04X}-{:04X}-{}-{}",
+            read_i32(bytes, 0)? as u32,
+            self.u16(at + 4)?,
+            self.u16(at + 6)?,
+            &data4[..4],
+            &data4[4..]
+        ))
+    }
+
+    fn href(&self, href: i32) -> std::io::Result<HRef> {
+        if href < 0 {
+            return Err(invalid_data("a reference to a missing type"));
+        }
+        if href & 1 == 0 {
+            return Ok(HRef::Local(href as usize / TYPE_INFO_SIZE));
+        }
+        let at = self.segment(Segment::ImpInfo, href & !3)?;
+        if self.i32(at)? & IMPINFO_OFFSET_IS_GUID == 0 {
+            return Err(invalid_data("a type imported by index rather than GUID"));
+        }
+        Ok(HRef::External {
+            guid: self.guid(self.i32(at + 8)?)?,
+        })
+    }
+
+    /// The type encoded as a `VT_*` if negative, or else an offset into the type table
+    fn type_desc(&self, encoded: i32, depth: usize) -> std::io::Result<TypeDesc> {
+        if encoded < 0 {
+            return Ok(TypeDesc::Base((encoded & VT_TYPEMASK) as u16));
+        }
+        if depth == MAX_TYPE_DEPTH {
+            return Err(invalid_data("a type is nested too deeply"));
+        }
+        let at = self.segment(Segment::TypedescTab, encoded)?;
+        let vt = (i32::from(self.u16(at)?) & VT_TYPEMASK) as u16;
+        let target = self.i32(at + 4)?;
+        Ok(match vt {
+            VT_PTR => TypeDesc::Ptr(Box::new(self.type_desc(target, depth + 1)?)),
+            VT_SAFEARRAY => TypeDesc::SafeArray(Box::new(self.type_desc(target, depth + 1)?)),
+            VT_CARRAY => {
+                let at = self.segment(Segment::ArrayDesc, target)?;
+                let element = self.type_desc(self.i32(at)?, depth + 1)?;
+                let mut len = 1usize;
+                for dimension in 0..self.u16(at + 4)? as usize {
+                    let elements = self.u32(at + 8 + dimension * 8)? as usize;
+                    len = len.saturating_mul(elements);
+                }
+                TypeDesc::CArray {
+                    element: Box::new(element),
+                    len,
+                }
+            }
+            VT_USERDEFINED => TypeDesc::UserDefined(self.href(target)?),
+            vt => TypeDesc::Base(vt),
+        })
+    }
+
+    fn type_info(&self, index: usize) -> std::io::Result<TypeInfo> {
+        let at = self.segment(Segment::TypeInfoTab, (index * TYPE_INFO_SIZE) as i32)?;
+        let kind = match self.u32(at)? & 0xf {
+            0 => TypeKind::Enum,
+            1 => TypeKind::Record,
+            2 => TypeKind::Module,
+            3 => TypeKind::Interface,
+            4 => TypeKind::Dispatch,
+            5 => TypeKind::CoClass,
+            6 => TypeKind::Alias,
+            7 => TypeKind::Union,
+            kind => return Err(invalid_data(&format!("unknown kind of type {}", kind))),
+        };
+        let elements = self.u32(at + 0x18)? as usize;
+        let guid = match self.i32(at + 0x2c)? {
+            offset if offset < 0 => None,
+            offset => Some(self.guid(offset)?),
+        };
+        let version = self.u32(at + 0x38)?;
+        let impl_count = self.i16(at + 0x4c)?.max(0) as usize;
+        let data_type = self.i32(at + 0x54)?;
+
+        let mut impl_types = Vec::new();
+        let mut alias = None;
+        match kind {
+            TypeKind::CoClass => {
+                let mut offset = data_type;
+                while impl_types.len() < impl_count && offset >= 0 {
+                    let record = self.segment(Segment::RefTab, offset)?;
+                    impl_types.push(ImplType {
+                        href: self.href(self.i32(record)?)?,
+                        flags: self.u32(record + 4)?,
+                    });
+                    offset = self.i32(record + 12)?;
+                }
+            }
+            TypeKind::Interface | TypeKind::Dispatch if data_type != -1 => {
+                impl_types.push(ImplType {
+                    href: self.href(data_type)?,
+                    flags: 0,
+                });
+            }
+            // Dispinterfaces without a parent of their own derive from IDispatch
+            TypeKind::Dispatch => impl_types.push(ImplType {
+                href: HRef::External {
+                    guid: IDISPATCH_GUID.to_owned(),
+                },
+                flags: 0,
+            }),
+            TypeKind::Alias => alias = Some(self.type_desc(data_type, 0)?),
+            _ => {}
+        }
+
+        let (functions, variables) =
+            self.members(self.i32(at + 4)?, elements & 0xffff, elements >> 16)?;
+        Ok(TypeInfo {
+            name: self.name(self.i32(at + 0x34)?)?,
+            guid,
+            kind,
+            flags: self.u32(at + 0x30)?,
+            version: (version as u16, (version >> 16) as u16),
+            help_string: self.string(self.i32(at + 0x3c)?)?,
+            functions,
+            variables,
+            impl_types,
+            alias,
+        })
+    }
+
+    /// The functions and variables of the member block at `offset`
+    ///
+    /// The records are followed by the member IDs, the name offsets and the record offsets
+    /// of all functions and then all variables.
+    fn members(
+        &self,
+        offset: i32,
+        funcs: usize,
+        vars: usize,
+    ) -> std::io::Result<(Vec<FuncDesc>, Vec<VarDesc>)> {
+        if funcs + vars == 0 {
+            return Ok((Vec::new(), Vec::new()));
+        }
+        if offset < 0 {
+            return Err(invalid_data("the members of a type are missing"));
+        }
+        let records = offset as usize + 4;
+        let arrays = records + self.u32(offset as usize)? as usize;
+        let count = funcs + vars;
+        let member_id = |index: usize| self.i32(arrays + index * 4);
+        let name = |index: usize| self.i32(arrays + (count + index) * 4);
+        let record = |index: usize| -> std::io::Result<usize> {
+            let offset = self.i32(arrays + (2 * count + index) * 4)?;
+            if offset < 0 {
+                return Err(invalid_data("a member record is missing"));
+            }
+            Ok(records + offset as usize)
+        };
+
+        let mut functions: Vec<FuncDesc> = Vec::with_capacity(funcs);
+        for index in 0..funcs {
+            let at = record(index)?;
+            let len = self.u32(at)? as usize & 0xffff;
+            let fkccic = self.u32(at + 16)?;
+            let param_count = self.i16(at + 20)?.max(0) as usize;
+            let params_at = (at + len)
+                .checked_sub(param_count * 12)
+                .ok_or_else(|| invalid_data("a function record is too short"))?;
+            let params = (0..param_count)
+                .map(|param| {
+                    let at = params_at + param * 12;
+                    let name = match self.i32(at + 4)? {
+                        offset if offset < 0 => None,
+                        offset => Some(self.name(offset)?),
+                    };
+                    Ok(ParamDesc {
+                        name,
+                        ty: self.type_desc(self.i32(at)?, 0)?,
+                        flags: self.u32(at + 8)?,
+                    })
+                })
+                .collect::<std::io::Result<_>>()?;
+            // The second accessor of a property may leave out the name
+            let name = match (name(index)?, functions.last()) {
+                (-1, Some(previous)) => previous.name.clone(),
+                (offset, _) => self.name(offset)?,
+            };
+            functions.push(FuncDesc {
+                name,
+                member_id: member_id(index)?,
+                invoke_kind: (fkccic >> 3) & 0xf,
+                func_kind: fkccic & 0x7,
+                vtable_slot: (self.u16(at + 12)? & !1) as usize / self.ptr_size,
+                ret: self.type_desc(self.i32(at + 4)?, 0)?,
+                params,
+            });
+        }
+
+        let mut variables = Vec::with_capacity(vars);
+        for index in funcs..count {
+            let at = record(index)?;
+            let value = if self.i16(at + 12)? == VAR_CONST {
+                self.constant(self.i32(at + 16)?)?
+            } else {
+                None
+            };
+            variables.push(VarDesc {
+                name: self.name(name(index)?)?,
+                member_id: member_id(index)?,
+                ty: self.type_desc(self.i32(at + 4)?, 0)?,
+                value,
+            });
+        }
+        Ok((functions, variables))
+    }
+
+    /// The value of an integer constant, either packed into `value` with its `VT_*` in
+    /// bits 26 to 30, or at that offset into the custom data
+    fn constant(&self, value: i32) -> std::io::Result<Option<i64>> {
+        if value < 0 {
+            return Ok(Some(i64::from(value & 0x03ff_ffff)));
+        }
+        let at = self.segment(Segment::CustData, value)?;
+        let int = || self.i32(at + 2);
+        Ok(Some(match self.u16(at)? {
+            VT_I1 => i64::from(int()? as i8),
+            VT_UI1 => i64::from(int()? as u8),
+            VT_I2 | VT_BOOL => i64::from(int()? as i16),
+            VT_UI2 => i64::from(int()? as u16),
+            VT_I4 | VT_INT | VT_ERROR | VT_HRESULT => i64::from(int()?),
+            VT_UI4 | VT_UINT => i64::from(int()? as u32),
+            VT_I8 | VT_UI8 => {
+                let bytes = self.bytes(at + 2, 8)?;
+                let mut value = [0; 8];
+                value.copy_from_slice(bytes);
+                i64::from_le_bytes(value)
+            }
+            _ => return Ok(None),
+        }))
+    }
+}
+
+fn read_i32(data: &[u8], offset: usize) -> std::io::Result<i32> {
+    offset
+        .checked_add(4)
+        .and_then(|end| data.get(offset..end))
+        .map(|bytes| i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| invalid_data("the type library is truncated"))
+}
+
+/// Names are stored in the code page of the library, which is Latin-1 for the names of
+/// interfaces and methods in practice
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&byte| char::from(byte)).collect()
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    const IUNKNOWN_GUID: &str = "00000000-0000-0000-C000-000000000046";
+    const FUNC_PUREVIRTUAL: i32 = 1;
+    const CC_STDCALL: i32 = 4;
+
+    /// A type library written the way MIDL lays out the MSFT format
+    #[derive(Default)]
+    struct Image {
+        segments: Vec<Vec<u8>>,
+        type_infos: Vec<Vec<u8>>,
+        members: Vec<Option<Vec<u8>>>,
+    }
+
+    fn push_i16(buffer: &mut Vec<u8>, value: i16) {
+        buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn push_i32(buffer: &mut Vec<u8>, value: i32) {
+        buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn pad(buffer: &mut Vec<u8>) {
+        while buffer.len() % 4 != 0 {
+            buffer.push(0x57);
+        }
+    }
+
+    /// A `VT_*` type stored in place of a type table offset
+    fn base(vt: u16) -> i32 {
+        (0x8000_0000 | u32::from(vt) << 16 | u32::from(vt)) as i32
+    }
+
+    /// A small constant stored in place of a custom data offset
+    fn packed(vt: u16, value: u32) -> i32 {
+        (0x8000_0000 | u32::from(vt) << 26 | value) as i32
+    }
+
+    fn guid_bytes(guid: &str) -> [u8; 16] {
+        writer::guid_bytes(guid).unwrap()
+    }
+
+    fn func(ret: i32, slot: i16, invoke_kind: i32, params: &[(i32, i32, i32)]) -> Vec<u8> {
+        let mut record = Vec::new();
+        push_i32(&mut record, 24 + params.len() as i32 * 12);
+        push_i32(&mut record, ret);
+        push_i32(&mut record, 0);
+        push_i16(&mut record, slot * 4);
+        push_i16(&mut record, 0);
+        push_i32(
+            &mut record,
+            FUNC_PUREVIRTUAL | invoke_kind << 3 | CC_STDCALL << 8,
+        );
+        push_i16(&mut record, params.len() as i16);
+        push_i16(&mut record, 0);
+        for &(ty, name, flags) in params {
+            push_i32(&mut record, ty);
+            push_i32(&mut record, name);
+            push_i32(&mut record, flags);
+        }
+        record
+    }
+
+    fn var(ty: i32, kind: i16, value: i32) -> Vec<u8> {
+        let mut record = Vec::new();
+        push_i32(&mut record, 20);
+        push_i32(&mut record, ty);
+        push_i32(&mut record, 0);
+        push_i16(&mut record, kind);
+        push_i16(&mut record, 4);
+        push_i32(&mut record, value);
+        record
+    }
+
+    /// The member block of `(member ID, name offset, record)`
+    fn members(records: &[(i32, i32, Vec<u8>)]) -> Vec<u8> {
+        let mut block = Vec::new();
+        push_i32(
+            &mut block,
+            records.iter().map(|record| record.2.len() as i32).sum(),
+        );
+        let mut offsets = Vec::new();
+        for (_, _, record) in records {
+            offsets.push(block.len() as i32 - 4);
+            block.extend_from_slice(record);
+        }
+        for &(member_id, _, _) in records {
+            push_i32(&mut block, member_id);
+        }
+        for &(_, name, _) in records {
+            push_i32(&mut block, name);
+        }
+        for offset in offsets {
+            push_i32(&mut block, offset);
+        }
+        block
+    }
+
+    impl Image {
+        fn new() -> Self {
+            Image {
+                segments: vec![Vec::new(); SEGMENT_COUNT],
+                ..Image::default()
+            }
+        }
+
+        fn name(&mut self, name: &str) -> i32 {
+            let table = &mut self.segments[Segment::NameTab as usize];
+            let offset = table.len() as i32;
+            push_i32(table, -1);
+            push_i32(table, -1);
+            push_i32(table, name.len() as i32 | 0x3800_0000);
+            table.extend_from_slice(name.as_bytes());
+            pad(table);
+            offset
+        }
+
+        fn string(&mut self, string: &str) -> i32 {
+            let table = &mut self.segments[Segment::StringTab as usize];
+            let offset = table.len() as i32;
+            push_i16(table, string.len() as i16);
+            table.extend_from_slice(string.as_bytes());
+            pad(table);
+            offset
+        }
+
+        fn guid(&mut self, guid: [u8; 16]) -> i32 {
+            let table = &mut self.segments[Segment::GuidTab as usize];
+            let offset = table.len() as i32;
+            table.extend_from_slice(&guid);
+            push_i32(table, -1);
+            push_i32(table, -1);
+            offset
+        }
+
+        fn type_desc(&mut self, vt: u16, target: i32) -> i32 {
+            let table = &mut self.segments[Segment::TypedescTab as usize];
+            let offset = table.len() as i32;
+            push_i16(table, vt as i16);
+            push_i16(table, 0);
+            push_i32(table, target);
+            offset
+        }
+
+        fn array(&mut self, element: i32, len: i32) -> i32 {
+            let table = &mut self.segments[Segment::ArrayDesc as usize];
+            let offset = table.len() as i32;
+            push_i32(table, element);
+            push_i16(table, 1);
+            push_i16(table, 0);
+            push_i32(table, len);
+            push_i32(table, 0);
+            self.type_desc(VT_CARRAY, offset)
+        }
+
+        fn import(&mut self, guid: &str) -> i32 {
+            let guid = self.guid(guid_bytes(guid));
+            let table = &mut self.segments[Segment::ImpInfo as usize];
+            let offset = table.len() as i32;
+            push_i32(table, IMPINFO_OFFSET_IS_GUID | 3 << 24);
+            push_i32(table, 0);
+            push_i32(table, guid);
+            offset | 1
+        }
+
+        fn impl_types(&mut self, impl_types: &[(i32, i32)]) -> i32 {
+            let table = &mut self.segments[Segment::RefTab as usize];
+            let first = table.len() as i32;
+            for (index, &(href, flags)) in impl_types.iter().enumerate() {
+                let next = if index + 1 == impl_types.len() {
+                    -1
+                } else {
+                    first + (index as i32 + 1) * 16
+                };
+                push_i32(table, href);
+                push_i32(table, flags);
+                push_i32(table, -1);
+                push_i32(table, next);
+            }
+            first
+        }
+
+        fn const_value(&mut self, value: i32) -> i32 {
+            let table = &mut self.segments[Segment::CustData as usize];
+            let offset = table.len() as i32;
+            push_i16(table, VT_I4 as i16);
+            push_i32(table, value);
+            pad(table);
+            offset
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        fn type_info(
+            &mut self,
+            kind: i32,
+            name: &str,
+            guid: Option<&str>,
+            flags: i32,
+            impl_count: i16,
+            data_type: i32,
+            elements: (usize, usize),
+            block: Option<Vec<u8>>,
+        ) -> i32 {
+            let name = self.name(name);
+            let guid = guid.map_or(-1, |guid| self.guid(guid_bytes(guid)));
+            let mut info = Vec::new();
+            push_i32(&mut info, kind | 0x20);
+            // The member offset is filled in once the layout is known
+            push_i32(&mut info, -1);
+            info.resize(0x18, 0);
+            push_i32(&mut info, (elements.1 << 16 | elements.0) as i32);
+            info.resize(0x2c, 0);
+            push_i32(&mut info, guid);
+            push_i32(&mut info, flags);
+            push_i32(&mut info, name);
+            push_i32(&mut info, 1);
+            push_i32(&mut info, -1);
+            info.resize(0x4c, 0);
+            push_i16(&mut info, impl_count);
+            push_i16(&mut info, 0);
+            push_i32(&mut info, 4);
+            push_i32(&mut info, data_type);
+            push_i32(&mut info, -1);
+            push_i32(&mut info, 0);
+            push_i32(&mut info, 0);
+            assert_eq!(info.len(), TYPE_INFO_SIZE);
+            self.type_infos.push(info);
+            self.members.push(block);
+            ((self.type_infos.len() - 1) * TYPE_INFO_SIZE) as i32
+        }
+
+        fn build(mut self, library: &str, name: &str, help_string: &str) -> Vec<u8> {
+            let guid = self.guid(guid_bytes(library));
+            let name = self.name(name);
+            let help_string = self.string(help_string);
+            for info in &self.type_infos {
+                self.segments[Segment::TypeInfoTab as usize].extend_from_slice(info);
+            }
+
+            let count = self.type_infos.len();
+            let mut data = Vec::new();
+            for value in &[MSFT_MAGIC as i32, 0x0001_0002, guid, 0x409, 0, 1] {
+                push_i32(&mut data, *value);
+            }
+            push_i32(&mut data, 2 << 16 | 1);
+            push_i32(&mut data, 0);
+            push_i32(&mut data, count as i32);
+            push_i32(&mut data, help_string);
+            data.resize(0x38, 0);
+            push_i32(&mut data, name);
+            push_i32(&mut data, -1);
+            data.resize(HEADER_SIZE, 0);
+            for _ in 0..count {
+                push_i32(&mut data, 0);
+            }
+
+            let mut offset = data.len() + SEGMENT_COUNT * 16;
+            for segment in &self.segments {
+                push_i32(
+                    &mut data,
+                    if segment.is_empty() {
+                        -1
+                    } else {
+                        offset as i32
+                    },
+                );
+                push_i32(&mut data, segment.len() as i32);
+                push_i32(&mut data, -1);
+                push_i32(&mut data, 0x0f);
+                offset += segment.len();
+            }
+            let type_info_tab = offset - self.segments.iter().map(Vec::len).sum::<usize>();
+            for segment in &self.segments {
+                data.extend_from_slice(segment);
+            }
+            for (index, block) in self.members.iter().enumerate() {
+                if let Some(block) = block {
+                    let at = type_info_tab + index * TYPE_INFO_SIZE + 4;
+                    let offset = (data.len() as i32).to_le_bytes();
+                    data[at..at + 4].copy_from_slice(&offset);
+                    data.extend_from_slice(block);
+                }
+            }
+            data
+        }
+    }
+
+    /// The type library MIDL makes of
+    ///
+    /// ```idl
+    /// [uuid(9BB1E3D1-57E1-4D1C-B2A6-A8B4D93A2E10), version(1.2), helpstring("Zoo animals")]
+    /// library ZooLib
+    /// {
+    ///     importlib("stdole2.tlb");
+    ///     typedef enum ANIMAL_KIND { AK_CAT = 1, AK_ANY = -1 } ANIMAL_KIND;
+    ///     [object, uuid(EFF8970E-C50F-45E0-9284-291CE5A6F771)]
+    ///     interface IAnimal : IUnknown
+    ///     {
+    ///         HRESULT Eat([in] LONG grams);
+    ///         [propget] HRESULT Name([out, retval] BSTR *name);
+    ///         [propput] HRESULT Name([in] BSTR name);
+    ///         HRESULT Meet([in] ANIMAL_KIND kind, [in] IAnimal *other, [out] VARIANT_BOOL *same);
+    ///         HRESULT Call([in] LPWSTR name, [in] IUnknown *keeper);
+    ///     };
+    ///     typedef IAnimal *LPANIMAL;
+    ///     typedef struct FEEDING { ANIMAL_KIND kind; ULONG grams; BYTE note[8]; } FEEDING;
+    ///     [object, dual, uuid(5A2F3E4D-6C7B-48A9-9B8C-7D6E5F4A3B2C)]
+    ///     interface IKeeper : IDispatch { HRESULT Feed([in] LPANIMAL animal, [in] FEEDING *feeding); };
+    ///     [uuid(C5F45CBC-4439-418C-A9F9-05AC67525E43)]
+    ///     coclass Cat { interface IUnknown; [default] interface IAnimal; [source] interface IAnimal; };
+    /// }
+    /// ```
+    pub(crate) fn zoo() -> Vec<u8> {
+        let mut image = Image::new();
+        let iunknown = image.import(IUNKNOWN_GUID);
+        let idispatch = image.import(IDISPATCH_GUID);
+        let ianimal = TYPE_INFO_SIZE as i32;
+        let lpanimal = 3 * TYPE_INFO_SIZE as i32;
+        let feeding = 4 * TYPE_INFO_SIZE as i32;
+
+        let (cat, any) = (image.name("AK_CAT"), image.name("AK_ANY"));
+        let any_value = image.const_value(-1);
+        let block = members(&[
+            (0, cat, var(base(VT_I4), VAR_CONST, packed(VT_I4, 1))),
+            (0, any, var(base(VT_I4), VAR_CONST, any_value)),
+        ]);
+        image.type_info(0, "ANIMAL_KIND", None, 0, 0, -1, (0, 2), Some(block));
+
+        let names = [
+            "Eat", "grams", "Name", "name", "Meet", "kind", "other", "same",
+        ];
+        let names = names.iter().map(|n| image.name(n)).collect::<Vec<_>>();
+        let (call, keeper) = (image.name("Call"), image.name("keeper"));
+        let bstr_out = image.type_desc(VT_PTR, base(VT_BSTR));
+        let kind = image.type_desc(VT_USERDEFINED, 0);
+        let animal = image.type_desc(VT_USERDEFINED, ianimal);
+        let animal_ptr = image.type_desc(VT_PTR, animal);
+        let bool_out = image.type_desc(VT_PTR, base(VT_BOOL));
+        let hresult = base(VT_HRESULT);
+        let (fin, fout, fretval) = (
+            PARAMFLAG_FIN as i32,
+            PARAMFLAG_FOUT as i32,
+            PARAMFLAG_FRETVAL as i32,
+        );
+        let block = members(&[
+            (
+                0x6001_0000,
+                names[0],
+                func(hresult, 3, 1, &[(base(VT_I4), names[1], fin)]),
+            ),
+            (
+                0x6001_0001,
+                names[2],
+                func(hresult, 4, 2, &[(bstr_out, names[3], fout | fretval)]),
+            ),
+            (
+                0x6001_0001,
+                -1,
+                func(hresult, 5, 4, &[(base(VT_BSTR), names[3], fin)]),
+            ),
+            (
+                0x6001_0003,
+                names[4],
+                func(
+                    hresult,
+                    6,
+                    1,
+                    &[
+                        (kind, names[5], fin),
+                        (animal_ptr, names[6], fin),
+                        (bool_out, names[7], fout),
+                    ],
+                ),
+            ),
+            (
+                0x6001_0004,
+                call,
+                func(
+                    hresult,
+                    7,
+                    1,
+                    &[
+                        (base(VT_LPWSTR), names[3], fin),
+                        (base(VT_UNKNOWN), keeper, fin),
+                    ],
+                ),
+            ),
+        ]);
+        image.type_info(
+            3,
+            "IAnimal",
+            Some("EFF8970E-C50F-45E0-9284-291CE5A6F771"),
+            0,
+            1,
+            iunknown,
+            (5, 0),
+            Some(block),
+        );
+
+        let impl_types = image.impl_types(&[
+            (iunknown, 0),
+            (ianimal, IMPLTYPEFLAG_FDEFAULT as i32),
+            (ianimal, IMPLTYPEFLAG_FSOURCE as i32),
+        ]);
+        image.type_info(
+            5,
+            "Cat",
+            Some("C5F45CBC-4439-418C-A9F9-05AC67525E43"),
+            2,
+            3,
+            impl_types,
+            (0, 0),
+            None,
+        );
+
+        image.type_info(6, "LPANIMAL", None, 0, 0, animal_ptr, (0, 0), None);
+
+        let names = ["kind", "grams", "note"];
+        let names = names.iter().map(|n| image.name(n)).collect::<Vec<_>>();
+        let note = image.array(base(VT_UI1), 8);
+        let block = members(&[
+            (0x4000_0000, names[0], var(kind, 0, 0)),
+            (0x4000_0001, names[1], var(base(VT_UI4), 0, 4)),
+            (0x4000_0002, names[2], var(note, 0, 8)),
+        ]);
+        image.type_info(1, "FEEDING", None, 0, 0, -1, (0, 3), Some(block));
+
+        let names = ["Feed", "animal", "feeding"];
+        let names = names.iter().map(|n| image.name(n)).collect::<Vec<_>>();
+        let alias = image.type_desc(VT_USERDEFINED, lpanimal);
+        let record = image.type_desc(VT_USERDEFINED, feeding);
+        let record_ptr = image.type_desc(VT_PTR, record);
+        let block = members(&[(
+            0x6002_0000,
+            names[0],
+            func(
+                hresult,
+                7,
+                1,
+                &[(alias, names[1], fin), (record_ptr, names[2], fin)],
+            ),
+        )]);
+        image.type_info(
+            4,
+            "IKeeper",
+            Some("5A2F3E4D-6C7B-48A9-9B8C-7D6E5F4A3B2C"),
+            TYPEFLAG_FDUAL as i32 | 0x100,
+            1,
+            idispatch,
+            (1, 0),
+            Some(block),
+        );
+
+        image.build(
+            "9BB1E3D1-57E1-4D1C-B2A6-A8B4D93A2E10",
+            "ZooLib",
+            "Zoo animals",
+        )
+    }
+
+    #[test]
+    fn library() {
+        let type_lib = TypeLib::parse(&zoo()).unwrap();
+        assert_eq!(type_lib.name, "ZooLib");
+        assert_eq!(type_lib.guid, "9BB1E3D1-57E1-4D1C-B2A6-A8B4D93A2E10");
+        assert_eq!(type_lib.version, (1, 2));
+        assert_eq!(type_lib.lcid, 0);
+        assert_eq!(type_lib.help_string.as_deref(), Some("Zoo animals"));
+        let kinds = type_lib
+            .types
+            .iter()
+            .map(|t| (t.name.as_str(), t.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                ("ANIMAL_KIND", TypeKind::Enum),
+                ("IAnimal", TypeKind::Interface),
+                ("Cat", TypeKind::CoClass),
+                ("LPANIMAL", TypeKind::Alias),
+                ("FEEDING", TypeKind::Record),
+                ("IKeeper", TypeKind::Dispatch),
+            ]
+        );
+        assert!(type_lib.types[5].is_dual());
+    }
+
+    #[test]
+    fn interfaces() {
+        let type_lib = TypeLib::parse(&zoo()).unwrap();
+        let animal = &type_lib.types[1];
+        assert_eq!(
+            animal.guid.as_deref(),
+            Some("EFF8970E-C50F-45E0-9284-291CE5A6F771")
+        );
+        assert_eq!(
+            animal.impl_types,
+            [ImplType {
+                href: HRef::External {
+                    guid: IUNKNOWN_GUID.to_owned()
+                },
+                flags: 0,
+            }]
+        );
+        let functions = animal
+            .functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.invoke_kind, f.vtable_slot))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            functions,
+            [
+                ("Eat", INVOKE_FUNC, 3),
+                ("Name", INVOKE_PROPERTYGET, 4),
+                ("Name", INVOKE_PROPERTYPUT, 5),
+                ("Meet", INVOKE_FUNC, 6),
+                ("Call", INVOKE_FUNC, 7),
+            ]
+        );
+        assert_eq!(animal.functions[0].ret, TypeDesc::Base(VT_HRESULT));
+        assert_eq!(animal.functions[1].member_id, 0x6001_0001);
+        assert_eq!(
+            animal.functions[1].params,
+            [ParamDesc {
+                name: Some("name".to_owned()),
+                ty: TypeDesc::Ptr(Box::new(TypeDesc::Base(VT_BSTR))),
+                flags: PARAMFLAG_FOUT | PARAMFLAG_FRETVAL,
+            }]
+        );
+        let meet = &animal.functions[3].params;
+        assert_eq!(meet[0].ty, TypeDesc::UserDefined(HRef::Local(0)));
+        assert_eq!(
+            meet[1].ty,
+            TypeDesc::Ptr(Box::new(TypeDesc::UserDefined(HRef::Local(1))))
+        );
+        assert_eq!(
+            type_lib.type_info(&HRef::Local(1)).map(|t| t.name.as_str()),
+            Some("IAnimal")
+        );
+
+        let keeper = &type_lib.types[5];
+        assert_eq!(keeper.functions[0].vtable_slot, 7);
+        assert_eq!(
+            keeper.impl_types[0].href,
+            HRef::External {
+                guid: IDISPATCH_GUID.to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn other_types() {
+        let type_lib = TypeLib::parse(&zoo()).unwrap();
+        let values = type_lib.types[0]
+            .variables
+            .iter()
+            .map(|v| (v.name.as_str(), v.value))
+            .collect::<Vec<_>>();
+        assert_eq!(values, [("AK_CAT", Some(1)), ("AK_ANY", Some(-1))]);
+
+        let cat = &type_lib.types[2];
+        let flags = cat.impl_types.iter().map(|i| i.flags).collect::<Vec<_>>();
+        assert_eq!(flags, [0, IMPLTYPEFLAG_FDEFAULT, IMPLTYPEFLAG_FSOURCE]);
+        assert_eq!(
+            type_lib.types[3].alias,
+            Some(TypeDesc::Ptr(Box::new(TypeDesc::UserDefined(HRef::Local(
+                1
+            )))))
+        );
+        let feeding = &type_lib.types[4].variables;
+        assert_eq!(feeding[1].ty, TypeDesc::Base(VT_UI4));
+        assert_eq!(feeding[1].value, None);
+        assert_eq!(
+            feeding[2].ty,
+            TypeDesc::CArray {
+                element: Box::new(TypeDesc::Base(VT_UI1)),
+                len: 8
+            }
+        );
+    }
+
+    #[test]
+    fn corrupt() {
+        assert!(TypeLib::parse(b"SLTG\x01\x00\x00\x00")
+            .unwrap_err()
+            .to_string()
+            .contains("SLTG"));
+        let mut data = zoo();
+        data.truncate(data.len() - 8);
+        assert_eq!(
+            TypeLib::parse(&data).unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+        assert!(TypeLib::parse(&data[..0x40]).is_err());
+    }
+}
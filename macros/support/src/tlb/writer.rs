@@ -0,0 +1,1123 @@
+use super::{
+    Segment, HEADER_SIZE, IDISPATCH_GUID, IMPINFO_OFFSET_IS_GUID, IMPLTYPEFLAG_FDEFAULT,
+    INVOKE_FUNC, MSFT_MAGIC, PARAMFLAG_FIN, PARAMFLAG_FOUT, PARAMFLAG_FRETVAL, SEGMENT_COUNT,
+    SYS_WIN64, TYPE_INFO_SIZE, VAR_CONST, VT_BOOL, VT_BSTR, VT_CARRAY, VT_DISPATCH, VT_HRESULT,
+    VT_I1, VT_I2, VT_I4, VT_I8, VT_INT_PTR, VT_LPWSTR, VT_PTR, VT_R4, VT_R8, VT_UI1, VT_UI2,
+    VT_UI4, VT_UI8, VT_UINT_PTR, VT_UNKNOWN, VT_USERDEFINED, VT_VOID,
+};
+use crate::metadata::{
+    Class, Direction, Enum, Interface, Library, Metadata, Method, Struct, TypeRef, Typedef,
+};
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+const SYS_WIN32: u32 = 1;
+const TKIND_ENUM: i32 = 0;
+const TKIND_RECORD: i32 = 1;
+const TKIND_INTERFACE: i32 = 3;
+const TKIND_COCLASS: i32 = 5;
+const TKIND_ALIAS: i32 = 6;
+const TYPEFLAG_FCANCREATE: i32 = 0x2;
+const TYPEFLAG_FDISPATCHABLE: i32 = 0x1000;
+const FUNC_PUREVIRTUAL: i32 = 1;
+const CC_STDCALL: i32 = 4;
+const VAR_PERINSTANCE: i16 = 0;
+const VT_BYREF: i16 = 0x4000;
+/// The sizes of `FUNCDESC`, `ELEMDESC`, `TYPEDESC` and `VARDESC` on 32 bit Windows, which
+/// the records note for `oleaut32` to size its allocations
+const FUNCDESC_SIZE: usize = 52;
+const ELEMDESC_SIZE: usize = 16;
+const TYPEDESC_SIZE: usize = 8;
+const VARDESC_SIZE: i16 = 36;
+const GUID_HASH_BUCKETS: usize = 0x20;
+const NAME_HASH_BUCKETS: usize = 0x80;
+/// The flags of the names of types and of their members
+const TYPE_NAME_FLAGS: u32 = 0x38;
+const MEMBER_NAME_FLAGS: u32 = 0x10;
+/// MIDL writes the segments in this order rather than that of the directory
+const FILE_ORDER: [usize; SEGMENT_COUNT] = [0, 4, 5, 3, 1, 2, 6, 7, 8, 9, 10, 11, 12, 13, 14];
+
+const STDOLE2_GUID: &str = "00020430-0000-0000-C000-000000000046";
+const STDOLE2_FILE: &str = "stdole2.tlb";
+
+/// An interface of `stdole2.tlb`, which type libraries import rather than declare
+struct Import {
+    name: &'static str,
+    iid: &'static str,
+    /// The VTable slots, counting those of `IUnknown`
+    slots: usize,
+}
+
+const IMPORTS: &[Import] = &[
+    Import {
+        name: "IUnknown",
+        iid: "00000000-0000-0000-C000-000000000046",
+        slots: 3,
+    },
+    Import {
+        name: "IDispatch",
+        iid: IDISPATCH_GUID,
+        slots: 7,
+    },
+    Import {
+        name: "ITypeInfo",
+        iid: "00020401-0000-0000-C000-000000000046",
+        slots: 22,
+    },
+    Import {
+        name: "ITypeLib",
+        iid: "00020402-0000-0000-C000-000000000046",
+        slots: 13,
+    },
+    Import {
+        name: "IEnumVARIANT",
+        iid: "00020404-0000-0000-C000-000000000046",
+        slots: 7,
+    },
+];
+
+/// The type library of the interfaces, classes and types of `metadata`, as the bytes of a
+/// `.tlb` file
+///
+/// Interfaces become `TKIND_INTERFACE` types whose methods keep their VTable slots, and
+/// classes with a known CLSID become coclasses whose first interface is the default one.
+/// The enums, structs and typedefs the interfaces use are written too. Interfaces from
+/// `stdole2.tlb`, e.g. `IUnknown` and `IDispatch`, are imported from it; any other type
+/// that is not declared in `metadata` is an error.
+///
+/// The VTable offsets depend on the size of a pointer, which is that of the target when
+/// called from a build script and that of the host otherwise.
+pub fn generate(metadata: &Metadata, library: &Library) -> std::io::Result<Vec<u8>> {
+    let mut writer = Writer::new(metadata, pointer_size());
+    writer.write_types()?;
+    writer.finish(library)
+}
+
+/// Write the type library of the interfaces, classes and types of `metadata` to `path`
+pub fn write(
+    metadata: &Metadata,
+    library: &Library,
+    path: impl AsRef<Path>,
+) -> std::io::Result<()> {
+    std::fs::write(path, generate(metadata, library)?)
+}
+
+/// The size of a pointer, from the target configuration Cargo passes to build scripts
+fn pointer_size() -> usize {
+    match std::env::var("CARGO_CFG_TARGET_POINTER_WIDTH") {
+        Ok(ref width) if width == "64" => 8,
+        Ok(ref width) if width == "32" => 4,
+        _ => std::mem::size_of::<usize>(),
+    }
+}
+
+/// A type of the library, in the order of the type info table
+#[derive(Copy, Clone)]
+enum Type<'a> {
+    Enum(&'a Enum),
+    Struct(&'a Struct),
+    Typedef(&'a Typedef),
+    Interface(&'a Interface),
+    Class(&'a Class, &'a str),
+    /// The `GUID` struct, added once a type uses it
+    Guid,
+}
+
+impl Type<'_> {
+    fn name(&self) -> &str {
+        match self {
+            Type::Enum(e) => &e.name,
+            Type::Struct(s) => &s.name,
+            Type::Typedef(t) => &t.name,
+            Type::Interface(i) => &i.name,
+            Type::Class(c, _) => &c.name,
+            Type::Guid => "GUID",
+        }
+    }
+}
+
+/// The fields of a type info other than its name and members
+struct Layout {
+    kind: i32,
+    flags: i32,
+    guid: i32,
+    impl_count: usize,
+    vtable_slots: usize,
+    size: usize,
+    align: usize,
+    data_type: (i32, i32),
+}
+
+/// The member records of a type, with their member IDs and name offsets
+#[derive(Default)]
+struct Members {
+    functions: Vec<(i32, i32, Vec<u8>)>,
+    variables: Vec<(i32, i32, Vec<u8>)>,
+}
+
+impl Members {
+    /// The member block, whose record offsets count from after its length
+    fn block(&self) -> Vec<u8> {
+        let records = self.functions.iter().chain(&self.variables);
+        let mut block = Vec::new();
+        push_i32(&mut block, records.clone().map(|r| r.2.len() as i32).sum());
+        let mut offsets = Vec::new();
+        for (_, _, record) in records.clone() {
+            offsets.push(block.len() as i32 - 4);
+            block.extend_from_slice(record);
+        }
+        for &(member_id, _, _) in records.clone() {
+            push_i32(&mut block, member_id);
+        }
+        for &(_, name, _) in records {
+            push_i32(&mut block, name);
+        }
+        for offset in offsets {
+            push_i32(&mut block, offset);
+        }
+        block
+    }
+}
+
+struct Writer<'a> {
+    metadata: &'a Metadata,
+    ptr_size: usize,
+    types: Vec<Type<'a>>,
+    segments: Vec<Vec<u8>>,
+    /// The member block of each type written so far
+    blocks: Vec<Vec<u8>>,
+    guid_hash: [i32; GUID_HASH_BUCKETS],
+    name_hash: [i32; NAME_HASH_BUCKETS],
+    guids: HashMap<[u8; 16], i32>,
+    names: HashMap<String, i32>,
+    name_chars: usize,
+    type_descs: HashMap<[u8; 8], i32>,
+    /// The references to imported interfaces by IID
+    imports: HashMap<&'static str, i32>,
+    /// The offset of `stdole2.tlb` in the import file table, once something is imported
+    import_file: Option<i32>,
+}
+
+impl<'a> Writer<'a> {
+    fn new(metadata: &'a Metadata, ptr_size: usize) -> Self {
+        let mut types = Vec::new();
+        types.extend(metadata.enums.iter().map(Type::Enum));
+        types.extend(metadata.structs.iter().map(Type::Struct));
+        types.extend(metadata.typedefs.iter().map(Type::Typedef));
+        // IUnknown itself is imported rather than declared
+        types.extend(
+            metadata
+                .sorted_interfaces()
+                .into_iter()
+                .filter(|interface| interface.base.is_some())
+                .map(Type::Interface),
+        );
+        for class in &metadata.classes {
+            if let Some(clsid) = &class.clsid {
+                types.push(Type::Class(class, clsid));
+            }
+        }
+        Writer {
+            metadata,
+            ptr_size,
+            types,
+            segments: vec![Vec::new(); SEGMENT_COUNT],
+            blocks: Vec::new(),
+            guid_hash: [-1; GUID_HASH_BUCKETS],
+            name_hash: [-1; NAME_HASH_BUCKETS],
+            guids: HashMap::new(),
+            names: HashMap::new(),
+            name_chars: 0,
+            type_descs: HashMap::new(),
+            imports: HashMap::new(),
+            import_file: None,
+        }
+    }
+
+    fn write_types(&mut self) -> std::io::Result<()> {
+        // Types may add the GUID struct as they go
+        let mut index = 0;
+        while index < self.types.len() {
+            self.write_type(index)?;
+            index += 1;
+        }
+        Ok(())
+    }
+
+    fn write_type(&mut self, index: usize) -> std::io::Result<()> {
+        let href = (index * TYPE_INFO_SIZE) as i32;
+        let ty = self.types[index];
+        let name = self.name(ty.name(), href, TYPE_NAME_FLAGS);
+        let mut members = Members::default();
+        let layout = match ty {
+            Type::Enum(e) => self.enumeration(e, href, &mut members),
+            Type::Struct(s) => self.record(s, href, &mut members)?,
+            Type::Typedef(t) => {
+                let (size, align) = self.size_of(&t.ty)?;
+                Layout {
+                    data_type: (self.type_desc(&t.ty)?, 0),
+                    ..self.layout(TKIND_ALIAS, size, align)
+                }
+            }
+            Type::Interface(interface) => self.interface(interface, href, &mut members)?,
+            Type::Class(class, clsid) => self.class(class, clsid, href)?,
+            Type::Guid => self.guid_record(href, &mut members),
+        };
+
+        let mut info = Vec::with_capacity(TYPE_INFO_SIZE);
+        push_i32(
+            &mut info,
+            (index << 16 | layout.align << 11) as i32 | 0x20 | layout.kind,
+        );
+        // The member offset is filled in once the layout of the file is known
+        push_i32(&mut info, -1);
+        for &value in &[0, -1, 3, 0] {
+            push_i32(&mut info, value);
+        }
+        push_i32(
+            &mut info,
+            (members.variables.len() << 16 | members.functions.len()) as i32,
+        );
+        info.resize(0x2c, 0);
+        for &value in &[layout.guid, layout.flags, name, 0, -1, 0, 0, -1] {
+            push_i32(&mut info, value);
+        }
+        push_i16(&mut info, layout.impl_count as i16);
+        push_i16(&mut info, (layout.vtable_slots * self.ptr_size) as i16);
+        push_i32(&mut info, layout.size as i32);
+        push_i32(&mut info, layout.data_type.0);
+        push_i32(&mut info, layout.data_type.1);
+        push_i32(&mut info, 0);
+        push_i32(&mut info, -1);
+        debug_assert_eq!(info.len(), TYPE_INFO_SIZE);
+
+        self.segments[Segment::TypeInfoTab as usize].extend_from_slice(&info);
+        self.blocks.push(members.block());
+        Ok(())
+    }
+
+    fn layout(&self, kind: i32, size: usize, align: usize) -> Layout {
+        Layout {
+            kind,
+            flags: 0,
+            guid: -1,
+            impl_count: 0,
+            vtable_slots: 0,
+            size,
+            align,
+            data_type: (-1, 0),
+        }
+    }
+
+    fn enumeration(&mut self, e: &Enum, href: i32, members: &mut Members) -> Layout {
+        for (index, (name, value)) in e.variants.iter().enumerate() {
+            let name = self.name(name, href, MEMBER_NAME_FLAGS);
+            let value = self.constant(*value);
+            let record = variable(index, base(VT_I4), VAR_CONST, value);
+            members
+                .variables
+                .push((0x4000_0000 + index as i32, name, record));
+        }
+        self.layout(TKIND_ENUM, 4, 4)
+    }
+
+    fn record(&mut self, s: &Struct, href: i32, members: &mut Members) -> std::io::Result<Layout> {
+        let (offsets, size, align) = self.struct_layout(s)?;
+        for (index, (field, offset)) in s.fields.iter().zip(offsets).enumerate() {
+            let name = self.name(&field.name, href, MEMBER_NAME_FLAGS);
+            let ty = self.type_desc(&field.ty)?;
+            let record = variable(index, ty, VAR_PERINSTANCE, offset as i32);
+            members
+                .variables
+                .push((0x4000_0000 + index as i32, name, record));
+        }
+        Ok(self.layout(TKIND_RECORD, size, align))
+    }
+
+    fn guid_record(&mut self, href: i32, members: &mut Members) -> Layout {
+        let data4 = self.array(base(VT_UI1), 8, 8);
+        let fields = [
+            ("Data1", base(VT_UI4), 0),
+            ("Data2", base(VT_UI2), 4),
+            ("Data3", base(VT_UI2), 6),
+            ("Data4", data4, 8),
+        ];
+        for (index, &(name, ty, offset)) in fields.iter().enumerate() {
+            let name = self.name(name, href, MEMBER_NAME_FLAGS);
+            let record = variable(index, ty, VAR_PERINSTANCE, offset);
+            members
+                .variables
+                .push((0x4000_0000 + index as i32, name, record));
+        }
+        self.layout(TKIND_RECORD, 16, 4)
+    }
+
+    fn interface(
+        &mut self,
+        interface: &Interface,
+        href: i32,
+        members: &mut Members,
+    ) -> std::io::Result<Layout> {
+        let base_name = interface.base.as_deref().unwrap_or("IUnknown");
+        let (inherited, level, dispatchable) = self.base_layout(base_name)?;
+        let base_href = self.interface_href(base_name)?;
+        for (index, method) in interface.methods.iter().enumerate() {
+            let name = self.name(&method.com_name(), href, MEMBER_NAME_FLAGS);
+            let record = self.function(method, href, index, inherited + index)?;
+            let member_id = 0x6000_0000 | (level + 1) << 16 | index;
+            members.functions.push((member_id as i32, name, record));
+        }
+        let slots = inherited + interface.methods.len();
+        Ok(Layout {
+            flags: if dispatchable {
+                TYPEFLAG_FDISPATCHABLE
+            } else {
+                0
+            },
+            guid: self.guid(&interface.iid, href)?,
+            impl_count: 1,
+            vtable_slots: slots,
+            data_type: (base_href, (inherited << 16 | (level + 1)) as i32),
+            ..self.layout(TKIND_INTERFACE, self.ptr_size, self.ptr_size)
+        })
+    }
+
+    /// The VTable slots of an interface, the number of interfaces it derives from and
+    /// whether it derives from `IDispatch`
+    fn base_layout(&self, name: &str) -> std::io::Result<(usize, usize, bool)> {
+        if let Some(interface) = self.metadata.interface(name) {
+            if let Some(base) = &interface.base {
+                let (slots, level, dispatchable) = self.base_layout(base)?;
+                return Ok((slots + interface.methods.len(), level + 1, dispatchable));
+            }
+        }
+        match IMPORTS.iter().find(|import| import.name == name) {
+            Some(import) if import.name == "IUnknown" => Ok((import.slots, 0, false)),
+            Some(import) => Ok((import.slots, 1, import.name == "IDispatch")),
+            None => Err(unknown_type(name)),
+        }
+    }
+
+    fn class(&mut self, class: &Class, clsid: &str, href: i32) -> std::io::Result<Layout> {
+        let mut impl_types = Vec::with_capacity(class.interfaces.len());
+        for interface in &class.interfaces {
+            impl_types.push(self.interface_href(interface)?);
+        }
+        let table = &mut self.segments[Segment::RefTab as usize];
+        let first = if impl_types.is_empty() {
+            -1
+        } else {
+            table.len() as i32
+        };
+        for (index, &impl_type) in impl_types.iter().enumerate() {
+            let next = if index + 1 == impl_types.len() {
+                -1
+            } else {
+                table.len() as i32 + 16
+            };
+            let flags = if index == 0 { IMPLTYPEFLAG_FDEFAULT } else { 0 };
+            push_i32(table, impl_type);
+            push_i32(table, flags as i32);
+            push_i32(table, -1);
+            push_i32(table, next);
+        }
+        Ok(Layout {
+            flags: TYPEFLAG_FCANCREATE,
+            guid: self.guid(clsid, href)?,
+            impl_count: impl_types.len(),
+            data_type: (first, 0),
+            ..self.layout(TKIND_COCLASS, self.ptr_size, self.ptr_size)
+        })
+    }
+
+    fn function(
+        &mut self,
+        method: &Method,
+        href: i32,
+        index: usize,
+        slot: usize,
+    ) -> std::io::Result<Vec<u8>> {
+        let ret = self.type_desc(&method.ret)?;
+        let mut desc_size = FUNCDESC_SIZE + self.decoded_size(ret);
+        let mut params = Vec::with_capacity(method.params.len());
+        for param in &method.params {
+            let ty = self.type_desc(&param.ty)?;
+            let name = self.name(&param.name, href, MEMBER_NAME_FLAGS);
+            let flags = match param.direction {
+                Direction::In => PARAMFLAG_FIN,
+                Direction::Out => PARAMFLAG_FOUT,
+                Direction::InOut => PARAMFLAG_FIN | PARAMFLAG_FOUT,
+                Direction::Retval => PARAMFLAG_FOUT | PARAMFLAG_FRETVAL,
+            };
+            desc_size += ELEMDESC_SIZE + self.decoded_size(ty);
+            params.push((ty, name, flags as i32));
+        }
+
+        let mut record = Vec::new();
+        push_i32(&mut record, ((24 + params.len() * 12) | index << 16) as i32);
+        push_i32(&mut record, ret);
+        push_i32(&mut record, 0);
+        push_i16(&mut record, (slot * self.ptr_size) as i16);
+        push_i16(&mut record, desc_size as i16);
+        push_i32(
+            &mut record,
+            FUNC_PUREVIRTUAL | (INVOKE_FUNC as i32) << 3 | CC_STDCALL << 8,
+        );
+        push_i16(&mut record, params.len() as i16);
+        push_i16(&mut record, 0);
+        for (ty, name, flags) in params {
+            push_i32(&mut record, ty);
+            push_i32(&mut record, name);
+            push_i32(&mut record, flags);
+        }
+        Ok(record)
+    }
+
+    /// The size of the `TYPEDESC`s a type needs besides the one of its `ELEMDESC`
+    fn decoded_size(&self, encoded: i32) -> usize {
+        let mut size = 0;
+        let mut encoded = encoded;
+        while encoded >= 0 {
+            let table = &self.segments[Segment::TypedescTab as usize];
+            let at = encoded as usize;
+            size += TYPEDESC_SIZE;
+            if i16::from_le_bytes([table[at], table[at + 1]]) != VT_PTR as i16 {
+                break;
+            }
+            encoded =
+                i32::from_le_bytes([table[at + 4], table[at + 5], table[at + 6], table[at + 7]]);
+        }
+        size
+    }
+
+    /// A type encoded as either a `VT_*` or an offset into the type table
+    fn type_desc(&mut self, ty: &TypeRef) -> std::io::Result<i32> {
+        Ok(match ty {
+            TypeRef::Void => base(VT_VOID),
+            TypeRef::Bool | TypeRef::U8 => base(VT_UI1),
+            TypeRef::I8 => base(VT_I1),
+            TypeRef::I16 => base(VT_I2),
+            TypeRef::U16 => base(VT_UI2),
+            TypeRef::I32 => base(VT_I4),
+            TypeRef::U32 => base(VT_UI4),
+            TypeRef::I64 => base(VT_I8),
+            TypeRef::U64 => base(VT_UI8),
+            TypeRef::Isize => base(VT_INT_PTR),
+            TypeRef::Usize => base(VT_UINT_PTR),
+            TypeRef::F32 => base(VT_R4),
+            TypeRef::F64 => base(VT_R8),
+            TypeRef::Named(name) => match name.as_str() {
+                "HRESULT" => base(VT_HRESULT),
+                "BSTR" => base(VT_BSTR),
+                "BOOL" => base(VT_I4),
+                "VARIANT_BOOL" => base(VT_BOOL),
+                "PCWSTR" | "PWSTR" => base(VT_LPWSTR),
+                "GUID" | "IID" | "CLSID" => {
+                    let href = self.guid_type();
+                    self.user_defined(href)
+                }
+                name => match self.local_type(name) {
+                    Some((href, Type::Enum(_)))
+                    | Some((href, Type::Struct(_)))
+                    | Some((href, Type::Typedef(_))) => self.user_defined(href),
+                    _ => return Err(unknown_type(name)),
+                },
+            },
+            TypeRef::Interface(name) => {
+                let href = self.interface_href(name)?;
+                self.user_defined(href)
+            }
+            TypeRef::Ptr { pointee, .. } => match &**pointee {
+                TypeRef::Interface(name) if name == "IUnknown" => base(VT_UNKNOWN),
+                TypeRef::Interface(name) if name == "IDispatch" => base(VT_DISPATCH),
+                pointee => {
+                    let target = self.type_desc(pointee)?;
+                    let mix = if target < 0 {
+                        (target >> 16) as i16 & 0x0fff | VT_BYREF
+                    } else {
+                        let table = &self.segments[Segment::TypedescTab as usize];
+                        let at = target as usize + 2;
+                        match i16::from_le_bytes([table[at], table[at + 1]]) {
+                            0x7fff => 0x7fff,
+                            _ => 0x7ffe,
+                        }
+                    };
+                    self.add_type_desc(VT_PTR, mix, target)
+                }
+            },
+            TypeRef::Array { element, len } => {
+                let (size, _) = self.size_of(element)?;
+                let element = self.type_desc(element)?;
+                self.array(element, *len, size * len)
+            }
+        })
+    }
+
+    fn user_defined(&mut self, href: i32) -> i32 {
+        self.add_type_desc(VT_USERDEFINED, 0x7fff, href)
+    }
+
+    fn array(&mut self, element: i32, len: usize, size: usize) -> i32 {
+        let table = &mut self.segments[Segment::ArrayDesc as usize];
+        let offset = table.len() as i32;
+        push_i32(table, element);
+        push_i16(table, 1);
+        push_i16(table, size.min(0xffff) as u16 as i16);
+        push_i32(table, len as i32);
+        push_i32(table, 0);
+        self.add_type_desc(VT_CARRAY, 0x7ffe, offset)
+    }
+
+    fn add_type_desc(&mut self, vt: u16, mix: i16, target: i32) -> i32 {
+        let mut entry = [0; 8];
+        entry[..2].copy_from_slice(&vt.to_le_bytes());
+        entry[2..4].copy_from_slice(&mix.to_le_bytes());
+        entry[4..].copy_from_slice(&target.to_le_bytes());
+        let table = &mut self.segments[Segment::TypedescTab as usize];
+        *self.type_descs.entry(entry).or_insert_with(|| {
+            let offset = table.len() as i32;
+            table.extend_from_slice(&entry);
+            offset
+        })
+    }
+
+    /// The reference to the type `name` of this library, and the type
+    fn local_type(&self, name: &str) -> Option<(i32, Type<'a>)> {
+        self.types
+            .iter()
+            .position(|ty| ty.name() == name)
+            .map(|index| ((index * TYPE_INFO_SIZE) as i32, self.types[index]))
+    }
+
+    fn guid_type(&mut self) -> i32 {
+        if let Some(index) = self.types.iter().position(|ty| match ty {
+            Type::Guid => true,
+            _ => false,
+        }) {
+            return (index * TYPE_INFO_SIZE) as i32;
+        }
+        self.types.push(Type::Guid);
+        ((self.types.len() - 1) * TYPE_INFO_SIZE) as i32
+    }
+
+    fn interface_href(&mut self, name: &str) -> std::io::Result<i32> {
+        if let Some((href, Type::Interface(_))) = self.local_type(name) {
+            return Ok(href);
+        }
+        let import = IMPORTS
+            .iter()
+            .find(|import| import.name == name)
+            .ok_or_else(|| unknown_type(name))?;
+        if let Some(&href) = self.imports.get(import.iid) {
+            return Ok(href);
+        }
+
+        let import_file = match self.import_file {
+            Some(offset) => offset,
+            None => {
+                let guid = self.guid(STDOLE2_GUID, 2)?;
+                let table = &mut self.segments[Segment::ImpFiles as usize];
+                let offset = table.len() as i32;
+                push_i32(table, guid);
+                push_i32(table, 0);
+                push_i32(table, 2);
+                push_i16(table, (STDOLE2_FILE.len() << 2 | 1) as i16);
+                table.extend_from_slice(STDOLE2_FILE.as_bytes());
+                pad(table);
+                self.import_file = Some(offset);
+                offset
+            }
+        };
+        let href = self.segments[Segment::ImpInfo as usize].len() as i32 | 1;
+        let guid = self.guid(import.iid, href)?;
+        let table = &mut self.segments[Segment::ImpInfo as usize];
+        push_i32(table, TKIND_INTERFACE << 24 | IMPINFO_OFFSET_IS_GUID);
+        push_i32(table, import_file);
+        push_i32(table, guid);
+        self.imports.insert(import.iid, href);
+        Ok(href)
+    }
+
+    /// The offsets of the fields of a struct with C layout, its size and its alignment
+    fn struct_layout(&self, s: &Struct) -> std::io::Result<(Vec<usize>, usize, usize)> {
+        let (mut offsets, mut size, mut align) = (Vec::with_capacity(s.fields.len()), 0, 1);
+        for field in &s.fields {
+            let (field_size, field_align) = self.size_of(&field.ty)?;
+            size = align_to(size, field_align);
+            offsets.push(size);
+            size += field_size;
+            align = align.max(field_align);
+        }
+        Ok((offsets, align_to(size, align), align))
+    }
+
+    /// The size and alignment of a type in C
+    fn size_of(&self, ty: &TypeRef) -> std::io::Result<(usize, usize)> {
+        let ptr = (self.ptr_size, self.ptr_size);
+        Ok(match ty {
+            TypeRef::Void => (0, 1),
+            TypeRef::Bool | TypeRef::I8 | TypeRef::U8 => (1, 1),
+            TypeRef::I16 | TypeRef::U16 => (2, 2),
+            TypeRef::I32 | TypeRef::U32 | TypeRef::F32 => (4, 4),
+            TypeRef::I64 | TypeRef::U64 | TypeRef::F64 => (8, 8),
+            TypeRef::Isize | TypeRef::Usize | TypeRef::Interface(_) | TypeRef::Ptr { .. } => ptr,
+            TypeRef::Array { element, len } => {
+                let (size, align) = self.size_of(element)?;
+                (size * len, align)
+            }
+            TypeRef::Named(name) => match name.as_str() {
+                "HRESULT" | "BOOL" => (4, 4),
+                "VARIANT_BOOL" => (2, 2),
+                "BSTR" | "PCWSTR" | "PWSTR" => ptr,
+                "GUID" | "IID" | "CLSID" => (16, 4),
+                name => match self.local_type(name) {
+                    Some((_, Type::Enum(_))) => (4, 4),
+                    Some((_, Type::Struct(s))) => {
+                        let (_, size, align) = self.struct_layout(s)?;
+                        (size, align)
+                    }
+                    Some((_, Type::Typedef(t))) => self.size_of(&t.ty)?,
+                    _ => return Err(unknown_type(name)),
+                },
+            },
+        })
+    }
+
+    /// The value of a constant, packed in place of a custom data offset if it fits
+    fn constant(&mut self, value: i64) -> i32 {
+        if (0..=0x03ff_ffff).contains(&value) {
+            return (0x8000_0000 | u32::from(VT_I4) << 26 | value as u32) as i32;
+        }
+        let table = &mut self.segments[Segment::CustData as usize];
+        let offset = table.len() as i32;
+        push_i16(table, VT_I4 as i16);
+        push_i32(table, value as i32);
+        pad(table);
+        offset
+    }
+
+    fn guid(&mut self, guid: &str, href: i32) -> std::io::Result<i32> {
+        let bytes = guid_bytes(guid)?;
+        if let Some(&offset) = self.guids.get(&bytes) {
+            return Ok(offset);
+        }
+        let hash = (0..8)
+            .map(|i| i16::from_le_bytes([bytes[2 * i], bytes[2 * i + 1]]))
+            .fold(0, |hash, word| hash ^ word) as usize
+            & (GUID_HASH_BUCKETS - 1);
+        let table = &mut self.segments[Segment::GuidTab as usize];
+        let offset = table.len() as i32;
+        table.extend_from_slice(&bytes);
+        push_i32(table, href);
+        push_i32(table, self.guid_hash[hash]);
+        self.guid_hash[hash] = offset;
+        self.guids.insert(bytes, offset);
+        Ok(offset)
+    }
+
+    fn name(&mut self, name: &str, href: i32, flags: u32) -> i32 {
+        if let Some(&offset) = self.names.get(name) {
+            return offset;
+        }
+        let hash = name_hash(name);
+        let bucket = hash as usize & (NAME_HASH_BUCKETS - 1);
+        let table = &mut self.segments[Segment::NameTab as usize];
+        let offset = table.len() as i32;
+        push_i32(table, href);
+        push_i32(table, self.name_hash[bucket]);
+        push_i32(
+            table,
+            (name.len() as u32 & 0xff | flags << 8 | hash << 16) as i32,
+        );
+        table.extend_from_slice(name.as_bytes());
+        pad(table);
+        self.name_hash[bucket] = offset;
+        self.names.insert(name.to_owned(), offset);
+        self.name_chars += name.len();
+        offset
+    }
+
+    fn string(&mut self, string: &str) -> i32 {
+        let table = &mut self.segments[Segment::StringTab as usize];
+        let offset = table.len() as i32;
+        push_i16(table, string.len() as i16);
+        table.extend_from_slice(string.as_bytes());
+        while table.len() - (offset as usize) < 8 {
+            table.push(0x57);
+        }
+        pad(table);
+        offset
+    }
+
+    fn finish(mut self, library: &Library) -> std::io::Result<Vec<u8>> {
+        let guid = self.guid(&library.uuid, -2)?;
+        let name = self.name(&library.name, -1, 0);
+        let help_string = match &library.help_string {
+            Some(help_string) => self.string(help_string),
+            None => -1,
+        };
+        let dispatch = self.imports.get(IDISPATCH_GUID).copied().unwrap_or(-1);
+        let syskind = if self.ptr_size == 8 {
+            SYS_WIN64
+        } else {
+            SYS_WIN32
+        };
+        let count = self.types.len();
+
+        self.segments[Segment::GuidHash as usize] = self
+            .guid_hash
+            .iter()
+            .flat_map(|b| b.to_le_bytes().to_vec())
+            .collect();
+        self.segments[Segment::NameHash as usize] = self
+            .name_hash
+            .iter()
+            .flat_map(|b| b.to_le_bytes().to_vec())
+            .collect();
+
+        let mut data = Vec::new();
+        for &value in &[
+            MSFT_MAGIC as i32,
+            0x0001_0002,
+            guid,
+            0x409,
+            0,
+            (0x40 | syskind) as i32,
+            (u32::from(library.version.0) | u32::from(library.version.1) << 16) as i32,
+            0,
+            count as i32,
+            help_string,
+            0,
+            0,
+            self.names.len() as i32,
+            self.name_chars as i32,
+            name,
+            -1,
+            -1,
+            0x20,
+            0x80,
+            dispatch,
+            self.imports.len() as i32,
+        ] {
+            push_i32(&mut data, value);
+        }
+        debug_assert_eq!(data.len(), HEADER_SIZE);
+        for index in 0..count {
+            push_i32(&mut data, (index * TYPE_INFO_SIZE) as i32);
+        }
+
+        let mut offsets = [-1; SEGMENT_COUNT];
+        let mut offset = data.len() + SEGMENT_COUNT * 16;
+        for &segment in FILE_ORDER.iter() {
+            let len = self.segments[segment].len();
+            if len > 0 {
+                offsets[segment] = offset as i32;
+                offset += len;
+            }
+        }
+        // The member blocks follow the segments
+        for (index, block) in self.blocks.iter().enumerate() {
+            let at = index * TYPE_INFO_SIZE + 4;
+            self.segments[Segment::TypeInfoTab as usize][at..at + 4]
+                .copy_from_slice(&(offset as i32).to_le_bytes());
+            offset += block.len();
+        }
+
+        for (segment, &offset) in self.segments.iter().zip(&offsets) {
+            push_i32(&mut data, offset);
+            push_i32(&mut data, segment.len() as i32);
+            push_i32(&mut data, -1);
+            push_i32(&mut data, 0x0f);
+        }
+        for &segment in FILE_ORDER.iter() {
+            data.extend_from_slice(&self.segments[segment]);
+        }
+        for block in &self.blocks {
+            data.extend_from_slice(block);
+        }
+        Ok(data)
+    }
+}
+
+/// A `VT_*` type stored in place of a type table offset
+fn base(vt: u16) -> i32 {
+    (0x8000_0000 | u32::from(vt) << 16 | u32::from(vt)) as i32
+}
+
+fn variable(index: usize, ty: i32, kind: i16, value: i32) -> Vec<u8> {
+    let mut record = Vec::new();
+    push_i32(&mut record, (0x14 | index << 16) as i32);
+    push_i32(&mut record, ty);
+    push_i32(&mut record, 0);
+    push_i16(&mut record, kind);
+    push_i16(&mut record, VARDESC_SIZE);
+    push_i32(&mut record, value);
+    record
+}
+
+/// The bytes of a GUID string as it is laid out in memory
+pub(super) fn guid_bytes(guid: &str) -> std::io::Result<[u8; 16]> {
+    let fields = crate::utils::guid_fields(guid)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, format!("{} is not a GUID", guid)))?;
+    // The fields are known to be hex digits of the right lengths
+    let mut bytes = [0; 16];
+    bytes[..4].copy_from_slice(&u32::from_str_radix(fields[0], 16).unwrap().to_le_bytes());
+    bytes[4..6].copy_from_slice(&u16::from_str_radix(fields[1], 16).unwrap().to_le_bytes());
+    bytes[6..8].copy_from_slice(&u16::from_str_radix(fields[2], 16).unwrap().to_le_bytes());
+    for (byte, field) in bytes[8..].iter_mut().zip(&fields[3..]) {
+        *byte = u8::from_str_radix(field, 16).unwrap();
+    }
+    Ok(bytes)
+}
+
+/// The hash `oleaut32` finds names by, that of `LHashValOfNameSys` for US English
+fn name_hash(name: &str) -> u32 {
+    let hash = name.bytes().fold(0x0dea_dbee_u32, |hash, byte| {
+        hash.wrapping_mul(37)
+            .wrapping_add(u32::from(byte.to_ascii_uppercase()))
+    });
+    (hash % 65599) & 0xffff
+}
+
+fn align_to(offset: usize, align: usize) -> usize {
+    (offset + align - 1) / align * align
+}
+
+fn unknown_type(name: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "{} is not declared and cannot be written to a type library",
+            name
+        ),
+    )
+}
+
+fn push_i16(buffer: &mut Vec<u8>, value: i16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_i32(buffer: &mut Vec<u8>, value: i32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn pad(buffer: &mut Vec<u8>) {
+    while buffer.len() % 4 != 0 {
+        buffer.push(0x57);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tlb::{HRef, TypeDesc, TypeKind, TypeLib};
+
+    const SOURCE: &str = r#"
+        use com::sys::{BSTR, CLSID, HRESULT, IID, PCWSTR};
+
+        pub const CLSID_CAT_CLASS: CLSID = CLSID {
+            data1: 0xC5F4_5CBC,
+            data2: 0x4439,
+            data3: 0x418C,
+            data4: [0xA9, 0xF9, 0x05, 0xAC, 0x67, 0x52, 0x5E, 0x43],
+        };
+
+        #[com_interface("EFF8970E-C50F-45E0-9284-291CE5A6F771")]
+        pub trait IAnimal: IUnknown {
+            unsafe fn eat(&self) -> HRESULT;
+        }
+
+        #[com_interface("F5353C58-CFD9-4204-8D92-D274C7578B53")]
+        pub trait ICat: IAnimal {
+            unsafe fn set_name(&self, name: PCWSTR) -> HRESULT;
+            unsafe fn name(&self, name: *mut BSTR) -> HRESULT;
+            unsafe fn lives(&self) -> u32;
+            unsafe fn query(&self, riid: *const IID, ppv: *mut *mut c_void) -> HRESULT;
+            unsafe fn adopt(&self, friend: *mut IAnimalVPtr, keeper: *mut IUnknownVPtr);
+        }
+
+        #[co_class(implements(ICat, IAnimal))]
+        pub struct BritishShortHairCat {
+            num_owners: u32,
+        }
+
+        com::inproc_dll_module![(CLSID_CAT_CLASS, BritishShortHairCat),];
+    "#;
+
+    fn library() -> Library {
+        Library {
+            help_string: Some("Cats".to_owned()),
+            version: (1, 2),
+            ..Library::new("AnimalLib", "9BB1E3D1-57E1-4D1C-B2A6-A8B4D93A2E10")
+        }
+    }
+
+    fn cats() -> TypeLib {
+        let mut metadata = Metadata::new();
+        metadata.parse_source(SOURCE).unwrap();
+        TypeLib::parse(&generate(&metadata, &library()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn library_and_types() {
+        let type_lib = cats();
+        assert_eq!(type_lib.name, "AnimalLib");
+        assert_eq!(type_lib.guid, "9BB1E3D1-57E1-4D1C-B2A6-A8B4D93A2E10");
+        assert_eq!(type_lib.version, (1, 2));
+        assert_eq!(type_lib.lcid, 0);
+        assert_eq!(type_lib.help_string.as_deref(), Some("Cats"));
+        let kinds = type_lib
+            .types
+            .iter()
+            .map(|t| (t.name.as_str(), t.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                ("IAnimal", TypeKind::Interface),
+                ("ICat", TypeKind::Interface),
+                ("BritishShortHairCat", TypeKind::CoClass),
+                ("GUID", TypeKind::Record),
+            ]
+        );
+
+        let class = &type_lib.types[2];
+        assert_eq!(
+            class.guid.as_deref(),
+            Some("C5F45CBC-4439-418C-A9F9-05AC67525E43")
+        );
+        let impl_types = class
+            .impl_types
+            .iter()
+            .map(|i| (i.href.clone(), i.flags))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            impl_types,
+            [(HRef::Local(1), IMPLTYPEFLAG_FDEFAULT), (HRef::Local(0), 0)]
+        );
+        let fields = type_lib.types[3]
+            .variables
+            .iter()
+            .map(|v| v.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(fields, ["Data1", "Data2", "Data3", "Data4"]);
+    }
+
+    #[test]
+    fn functions() {
+        let type_lib = cats();
+        let animal = &type_lib.types[0];
+        assert_eq!(
+            animal.impl_types[0].href,
+            HRef::External {
+                guid: "00000000-0000-0000-C000-000000000046".to_owned()
+            }
+        );
+        let cat = &type_lib.types[1];
+        assert_eq!(cat.impl_types[0].href, HRef::Local(0));
+        let functions = cat
+            .functions
+            .iter()
+            .map(|f| (f.name.as_str(), f.member_id, f.vtable_slot))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            functions,
+            [
+                ("SetName", 0x6002_0000, 4),
+                ("Name", 0x6002_0001, 5),
+                ("Lives", 0x6002_0002, 6),
+                ("Query", 0x6002_0003, 7),
+                ("Adopt", 0x6002_0004, 8),
+            ]
+        );
+        assert_eq!(cat.functions[0].params[0].ty, TypeDesc::Base(VT_LPWSTR));
+        assert_eq!(cat.functions[1].params[0].flags, PARAMFLAG_FOUT);
+        assert_eq!(cat.functions[2].ret, TypeDesc::Base(VT_UI4));
+        assert_eq!(
+            cat.functions[3].params[0].ty,
+            TypeDesc::Ptr(Box::new(TypeDesc::UserDefined(HRef::Local(3))))
+        );
+        assert_eq!(
+            cat.functions[3].params[1].ty,
+            TypeDesc::Ptr(Box::new(TypeDesc::Ptr(Box::new(TypeDesc::Base(VT_VOID)))))
+        );
+        let adopt = &cat.functions[4];
+        assert_eq!(adopt.ret, TypeDesc::Base(VT_VOID));
+        assert_eq!(
+            adopt.params[0].ty,
+            TypeDesc::Ptr(Box::new(TypeDesc::UserDefined(HRef::Local(0))))
+        );
+        assert_eq!(adopt.params[1].ty, TypeDesc::Base(VT_UNKNOWN));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut metadata = Metadata::new();
+        metadata
+            .parse_idl(
+                r#"
+                typedef enum ANIMAL_KIND { AK_CAT = 1, AK_ANY = -1, AK_BIG = 0x7fffffff } ANIMAL_KIND;
+                typedef struct FEEDING { ANIMAL_KIND kind; BYTE note[3]; double hour; } FEEDING;
+                typedef FEEDING *LPFEEDING;
+                [object, uuid(5A2F3E4D-6C7B-48A9-9B8C-7D6E5F4A3B2C)]
+                interface IKeeper : IDispatch
+                {
+                    HRESULT Feed([in] LPFEEDING feeding, [out, retval] VARIANT_BOOL *fed);
+                };
+                "#,
+            )
+            .unwrap();
+        let data = generate(&metadata, &library()).unwrap();
+        let type_lib = TypeLib::parse(&data).unwrap();
+        assert_eq!(type_lib.types[3].flags, TYPEFLAG_FDISPATCHABLE as u32);
+        assert_eq!(type_lib.types[3].functions[0].vtable_slot, 7);
+        assert_eq!(type_lib.types[3].functions[0].member_id, 0x6002_0000);
+
+        let mut read = Metadata::new();
+        read.add_type_lib(&type_lib).unwrap();
+        assert_eq!(read.enums, metadata.enums);
+        assert_eq!(read.structs, metadata.structs);
+        assert_eq!(read.typedefs, metadata.typedefs);
+        assert_eq!(read.interfaces, metadata.interfaces);
+    }
+
+    #[test]
+    fn unknown_types() {
+        let mut metadata = Metadata::new();
+        metadata
+            .parse_source(
+                r#"
+                #[com_interface("EFF8970E-C50F-45E0-9284-291CE5A6F771")]
+                pub trait IAnimal: IUnknown {
+                    unsafe fn eat(&self, food: *mut FOOD) -> HRESULT;
+                }
+                "#,
+            )
+            .unwrap();
+        assert_eq!(
+            generate(&metadata, &library()).unwrap_err().to_string(),
+            "FOOD is not declared and cannot be written to a type library"
+        );
+    }
+
+    #[test]
+    fn hashes() {
+        // The names of the library are chained off the buckets of their hash
+        let data = generate(&Metadata::new(), &library()).unwrap();
+        let hash = name_hash("AnimalLib");
+        let name_hash_at = read_i32(&data, HEADER_SIZE + Segment::NameHash as usize * 16);
+        let name_at = read_i32(&data, HEADER_SIZE + Segment::NameTab as usize * 16);
+        let bucket = read_i32(&data, name_hash_at as usize + (hash as usize & 0x7f) * 4);
+        assert_eq!(bucket, read_i32(&data, 0x38));
+        assert_eq!(
+            read_i32(&data, name_at as usize + bucket as usize + 8) as u32 >> 16,
+            hash
+        );
+    }
+
+    fn read_i32(data: &[u8], offset: usize) -> i32 {
+        super::super::read_i32(data, offset).unwrap()
+    }
+}
@@ -1,5 +1,5 @@
 use quote::format_ident;
-use syn::{AttributeArgs, Ident, Meta, NestedMeta};
+use syn::{AttributeArgs, Ident, Lit, Meta, NestedMeta};
 
 use std::collections::HashMap;
 
@@ -19,6 +19,10 @@ pub fn ref_count_ident() -> Ident {
     format_ident!("__refcnt")
 }
 
+pub fn weak_reference_field_ident() -> Ident {
+    format_ident!("__weak_reference")
+}
+
 pub fn vptr_field_ident(interface_ident: &Ident) -> Ident {
     format_ident!("__{}vptr", interface_ident.to_string().to_lowercase())
 }
@@ -86,7 +90,7 @@ pub fn get_aggr_map(attr_args: &AttributeArgs) -> HashMap<Ident, Vec<Ident>> {
             let mut aggr_interfaces_idents = Vec::new();
 
             assert!(
-                attr.nested.len() > 0,
+                !attr.nested.is_empty(),
                 "Need to expose at least one interface from aggregated COM object."
             );
 
@@ -115,3 +119,79 @@ pub fn get_aggr_map(attr_args: &AttributeArgs) -> HashMap<Ident, Vec<Ident>> {
 
     aggr_map
 }
+
+/// Parse the argument of the helper attribute persist_stream. E.g. #[persist_stream(state)]
+/// Returns the ident of the struct field holding the persisted state.
+pub fn persist_stream_field(attr_args: &AttributeArgs) -> Option<Ident> {
+    for attr_arg in attr_args {
+        if let NestedMeta::Meta(Meta::List(ref attr)) = attr_arg {
+            if attr
+                .path
+                .segments
+                .last()
+                .expect("Invalid attribute syntax")
+                .ident
+                != "persist_stream"
+            {
+                continue;
+            }
+
+            assert!(
+                attr.nested.len() == 1,
+                "persist_stream takes the name of the field holding the persisted state."
+            );
+            if let Some(NestedMeta::Meta(Meta::Path(p))) = attr.nested.first() {
+                return p.get_ident().cloned();
+            }
+            panic!("persist_stream takes the name of the field holding the persisted state.");
+        }
+    }
+
+    None
+}
+
+/// Whether the bare flag `name` is among the arguments. E.g. #[co_class(..., support_error_info)]
+pub fn has_flag(attr_args: &AttributeArgs, name: &str) -> bool {
+    attr_args.iter().any(|attr_arg| match attr_arg {
+        NestedMeta::Meta(Meta::Path(p)) => p.is_ident(name),
+        _ => false,
+    })
+}
+
+/// Parse the threading option. E.g. #[co_class(..., threading = "free")]
+/// Returns the ident of the matching `com::ThreadingModel` variant.
+pub fn threading_model(attr_args: &AttributeArgs) -> Option<Ident> {
+    for attr_arg in attr_args {
+        if let NestedMeta::Meta(Meta::NameValue(ref attr)) = attr_arg {
+            if !attr.path.is_ident("threading") {
+                continue;
+            }
+
+            let model = match &attr.lit {
+                Lit::Str(s) => s.value(),
+                _ => panic!("threading takes a string, e.g. threading = \"free\"."),
+            };
+            let variant = match model.as_str() {
+                "apartment" => "Apartment",
+                "free" => "Free",
+                "both" => "Both",
+                "neutral" => "Neutral",
+                _ => panic!(
+                    "Unknown threading model {:?}, expected \"apartment\", \"free\", \"both\" or \"neutral\".",
+                    model
+                ),
+            };
+            return Some(format_ident!("{}", variant));
+        }
+    }
+
+    None
+}
+
+/// Whether objects of a class with the given threading model can be called from any thread
+pub fn is_free_threaded(threading_model: &Option<Ident>) -> bool {
+    match threading_model {
+        Some(model) => model != "Apartment",
+        None => false,
+    }
+}
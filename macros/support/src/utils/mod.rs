@@ -1,6 +1,9 @@
 mod idents;
 pub use idents::*;
 
+use quote::format_ident;
+use syn::{FnArg, Ident, Signature};
+
 pub fn snake_to_camel(input: &str) -> String {
     let mut new = String::new();
 
@@ -26,23 +29,55 @@ pub fn camel_to_snake(input: &str) -> String {
         if c.is_uppercase() {
             if seen_lowercase {
                 seen_lowercase = false;
-                new.push_str("_");
+                new.push('_');
             }
             new.push_str(&c.to_lowercase().to_string());
         } else {
             seen_lowercase = true;
-            new.push_str(&c.to_string())
+            new.push(c)
         }
     }
 
     new
 }
 
+/// The hex digits of the fields of a GUID string like `EFF8970E-C50F-45E0-9284-291CE5A6F771`:
+/// `data1`, `data2`, `data3` and the 8 bytes of `data4`
+pub fn guid_fields(guid: &str) -> Option<Vec<&str>> {
+    let parts = guid.split('-').collect::<Vec<_>>();
+    let lengths = parts.iter().map(|part| part.len()).collect::<Vec<_>>();
+    if lengths != [8, 4, 4, 4, 12] || !guid.chars().all(|c| c == '-' || c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut fields = parts[..3].to_vec();
+    for part in &parts[3..] {
+        fields.extend((0..part.len()).step_by(2).map(|i| &part[i..i + 2]));
+    }
+    Some(fields)
+}
+
+/// The signature of a method with its arguments bound to `__arg0`, `__arg1`, ...
+///
+/// Generated methods forward their arguments, which only works for identifiers, not for
+/// patterns like `_` that the declaration may use.
+pub fn sig_with_arg_idents(sig: &Signature) -> (Signature, Vec<Ident>) {
+    let mut sig = sig.clone();
+    let mut args = Vec::new();
+    for param in sig.inputs.iter_mut() {
+        if let FnArg::Typed(t) = param {
+            let ident = format_ident!("__arg{}", args.len());
+            *t.pat = syn::parse_quote!(#ident);
+            args.push(ident);
+        }
+    }
+    (sig, args)
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
     fn test_camel_to_snake() {
-        let result = camel_to_snake("IAnimalVTable".into());
+        let result = camel_to_snake("IAnimalVTable");
         assert_eq!(result, "ianimal_vtable".to_owned());
     }
     use super::*;
@@ -0,0 +1,30 @@
+use com::interfaces::IUnknown;
+use com::sys::HRESULT;
+use com::{co_class, com_interface};
+
+use std::cell::Cell;
+
+#[com_interface("5E2C8A41-0B7D-4F63-A9C2-7D1E3F8B6A05", agile)]
+pub trait ICounter: IUnknown {
+    unsafe fn increment(&self) -> HRESULT;
+}
+
+#[co_class(implements(ICounter), threading = "apartment")]
+pub struct Counter {
+    count: Cell<u32>,
+}
+
+impl Counter {
+    fn new() -> Box<Counter> {
+        Counter::allocate(Cell::new(0))
+    }
+}
+
+impl ICounter for Counter {
+    unsafe fn increment(&self) -> HRESULT {
+        self.count.set(self.count.get() + 1);
+        com::sys::S_OK
+    }
+}
+
+fn main() {}
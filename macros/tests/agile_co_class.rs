@@ -0,0 +1,140 @@
+mod common;
+
+use com::agile::AgileRef;
+use com::interfaces::imarshal::CLSID_IN_PROC_FREE_MARSHALER;
+use com::interfaces::stream::create_memory_stream;
+use com::interfaces::{IAgileObject, IMarshal, IStream, IUnknown};
+use com::runtime::{init_apartment, ApartmentType, StaExecutor};
+use com::sys::{
+    E_FAIL, GUID_NULL, HRESULT, MSHCTX_INPROC, MSHCTX_LOCAL, MSHLFLAGS_NORMAL, STREAM_SEEK_SET,
+    S_OK,
+};
+use com::{co_class, com_interface, CoClass, ComInterface, ComRc, ThreadingModel};
+use common::into_interface;
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+#[com_interface("3E7B9C14-5D2A-4F60-8B31-C4A9E0F5D276")]
+pub trait ICounter: IUnknown {
+    unsafe fn increment(&self) -> HRESULT;
+    unsafe fn count(&self) -> u32;
+}
+
+#[co_class(implements(ICounter), agile)]
+pub struct Counter {
+    count: AtomicU32,
+}
+
+impl Counter {
+    fn new() -> Box<Counter> {
+        Counter::allocate(AtomicU32::new(0))
+    }
+}
+
+impl ICounter for Counter {
+    unsafe fn increment(&self) -> HRESULT {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        S_OK
+    }
+
+    unsafe fn count(&self) -> u32 {
+        self.count.load(Ordering::SeqCst)
+    }
+}
+
+fn create_counter() -> ComRc<dyn ICounter> {
+    into_interface(Counter::new())
+}
+
+fn main() {
+    init_apartment(ApartmentType::Multithreaded).unwrap();
+
+    assert_eq!(
+        <Counter as CoClass>::THREADING_MODEL,
+        Some(ThreadingModel::Both)
+    );
+
+    let counter = create_counter();
+    assert!(counter.get_interface::<dyn IAgileObject>().is_some());
+    let marshal = counter.get_interface::<dyn IMarshal>().unwrap();
+
+    unsafe {
+        let mut clsid = GUID_NULL;
+        assert_eq!(
+            marshal.get_unmarshal_class(
+                &<dyn ICounter as ComInterface>::IID,
+                std::ptr::null_mut(),
+                MSHCTX_INPROC,
+                std::ptr::null_mut(),
+                MSHLFLAGS_NORMAL,
+                &mut clsid,
+            ),
+            S_OK
+        );
+        assert!(clsid == CLSID_IN_PROC_FREE_MARSHALER);
+
+        // The interface pointer itself is marshaled within the process
+        let stream = create_memory_stream(Vec::new());
+        assert_eq!(
+            marshal.marshal_interface(
+                stream.as_raw() as *mut _,
+                &<dyn ICounter as ComInterface>::IID,
+                std::ptr::null_mut(),
+                MSHCTX_INPROC,
+                std::ptr::null_mut(),
+                MSHLFLAGS_NORMAL,
+            ),
+            S_OK
+        );
+        assert_eq!(stream.seek(0, STREAM_SEEK_SET, std::ptr::null_mut()), S_OK);
+        let mut ppv = std::ptr::null_mut();
+        assert_eq!(
+            marshal.unmarshal_interface(
+                stream.as_raw() as *mut _,
+                &<dyn ICounter as ComInterface>::IID,
+                &mut ppv,
+            ),
+            S_OK
+        );
+        let unmarshaled = ComRc::<dyn ICounter>::from_raw(ppv as *mut *mut _);
+        assert_eq!(unmarshaled.as_raw(), counter.as_raw());
+
+        // Other processes would need a proxy
+        assert_eq!(
+            marshal.marshal_interface(
+                stream.as_raw() as *mut _,
+                &<dyn ICounter as ComInterface>::IID,
+                std::ptr::null_mut(),
+                MSHCTX_LOCAL,
+                std::ptr::null_mut(),
+                MSHLFLAGS_NORMAL,
+            ),
+            E_FAIL
+        );
+    }
+
+    // Resolving in another apartment returns the object itself instead of a proxy
+    let sta = StaExecutor::new().unwrap();
+    let agile = sta
+        .handle()
+        .run(|| {
+            let counter = create_counter();
+            let agile = AgileRef::new(&counter).unwrap();
+            (agile, counter.as_raw() as usize)
+        })
+        .unwrap();
+    let (agile, raw) = agile;
+    let resolved = agile.resolve().unwrap();
+    assert_eq!(resolved.as_raw() as usize, raw);
+    unsafe {
+        assert_eq!(resolved.increment(), S_OK);
+        assert_eq!(resolved.count(), 1);
+    }
+
+    // Calls keep working once the creating apartment is gone
+    sta.join().unwrap();
+    unsafe {
+        assert_eq!(resolved.increment(), S_OK);
+        assert_eq!(resolved.count(), 2);
+    }
+}
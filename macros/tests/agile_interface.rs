@@ -0,0 +1,63 @@
+mod common;
+
+use com::interfaces::IUnknown;
+use com::sys::{HRESULT, S_OK};
+use com::{co_class, com_interface, CoClass, ComRc, ThreadingModel};
+use common::into_interface;
+
+use std::sync::atomic::{AtomicU32, Ordering};
+
+#[com_interface("9A1F5E62-7C3D-4B8A-8F21-3E6D0B4C9A17", agile)]
+pub trait ICounter: IUnknown {
+    unsafe fn increment(&self) -> HRESULT;
+    unsafe fn count(&self) -> u32;
+}
+
+#[co_class(implements(ICounter), threading = "free")]
+pub struct Counter {
+    count: AtomicU32,
+}
+
+impl Counter {
+    fn new() -> Box<Counter> {
+        Counter::allocate(AtomicU32::new(0))
+    }
+}
+
+impl ICounter for Counter {
+    unsafe fn increment(&self) -> HRESULT {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        S_OK
+    }
+
+    unsafe fn count(&self) -> u32 {
+        self.count.load(Ordering::SeqCst)
+    }
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+fn main() {
+    assert_send_sync::<ComRc<dyn ICounter>>();
+    assert_eq!(
+        <Counter as CoClass>::THREADING_MODEL,
+        Some(ThreadingModel::Free)
+    );
+
+    let icounter = into_interface::<_, dyn ICounter>(Counter::new());
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let icounter = icounter.clone();
+            std::thread::spawn(move || unsafe {
+                assert_eq!(icounter.increment(), S_OK);
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    unsafe {
+        assert_eq!(icounter.count(), 4);
+    }
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use com::agile::AgileRef;
+use com::runtime::{init_apartment, ApartmentType, StaExecutor};
+use com::sys::{HRESULT, RPC_E_DISCONNECTED, S_OK};
+use com::{co_class, com_interface, ComRc};
+use common::{into_interface, IAnimal};
+
+use std::cell::Cell;
+
+#[com_interface("5A9C4B70-2F61-4E39-A4B6-3D8E0C7F1B22")]
+pub trait ICat: IAnimal {
+    unsafe fn thread(&self, thread: *mut std::thread::ThreadId) -> HRESULT;
+    unsafe fn eaten(&self) -> u32;
+}
+
+#[co_class(implements(ICat))]
+pub struct Cat {
+    eaten: Cell<u32>,
+}
+
+impl Cat {
+    fn new() -> Box<Cat> {
+        Cat::allocate(Cell::new(0))
+    }
+}
+
+impl IAnimal for Cat {
+    unsafe fn eat(&self, amount: u32) -> HRESULT {
+        self.eaten.set(self.eaten.get() + amount);
+        S_OK
+    }
+}
+
+impl ICat for Cat {
+    unsafe fn thread(&self, thread: *mut std::thread::ThreadId) -> HRESULT {
+        *thread = std::thread::current().id();
+        S_OK
+    }
+
+    unsafe fn eaten(&self) -> u32 {
+        self.eaten.get()
+    }
+}
+
+fn create_cat() -> ComRc<dyn ICat> {
+    into_interface(Cat::new())
+}
+
+/// Holds an `AgileRef`, which is revoked from the table when the litter is destroyed
+#[co_class(implements(IAnimal))]
+pub struct Litter {
+    kitten: AgileRef<dyn ICat>,
+}
+
+impl Litter {
+    fn new() -> Box<Litter> {
+        Litter::allocate(AgileRef::new(&create_cat()).unwrap())
+    }
+}
+
+impl IAnimal for Litter {
+    unsafe fn eat(&self, _amount: u32) -> HRESULT {
+        S_OK
+    }
+}
+
+fn main() {
+    init_apartment(ApartmentType::Multithreaded).unwrap();
+
+    let sta = StaExecutor::new().unwrap();
+    let sta_thread = sta.handle().run(|| std::thread::current().id()).unwrap();
+    let agile = sta
+        .handle()
+        .run(|| AgileRef::new(&create_cat()))
+        .unwrap()
+        .unwrap();
+
+    // Calls through the proxy run on the STA thread
+    let cat = agile.resolve().unwrap();
+    unsafe {
+        let mut thread = std::thread::current().id();
+        assert_eq!(cat.thread(&mut thread), S_OK);
+        assert_eq!(thread, sta_thread);
+        assert_eq!(cat.eat(3), S_OK);
+        assert_eq!(cat.eaten(), 3);
+    }
+    assert!(cat.get_interface::<dyn IAnimal>().is_some());
+
+    // In the owning apartment the object itself is returned
+    let agile = std::sync::Arc::new(agile);
+    let owner_agile = agile.clone();
+    let eaten = sta
+        .handle()
+        .run(move || unsafe { owner_agile.resolve().unwrap().eaten() })
+        .unwrap();
+    assert_eq!(eaten, 3);
+
+    // Revoking an object that revokes its own `AgileRef` when destroyed does not deadlock
+    let litter = AgileRef::new(&into_interface::<_, dyn IAnimal>(Litter::new())).unwrap();
+    assert!(litter.resolve().is_ok());
+    drop(litter);
+
+    // Once the STA is gone calls fail
+    sta.join().unwrap();
+    unsafe {
+        assert_eq!(cat.eat(1), RPC_E_DISCONNECTED);
+    }
+}
@@ -0,0 +1,227 @@
+use com::interfaces::IUnknown;
+use com::sys::{E_POINTER, HRESULT, S_OK};
+use com::{co_class, com_interface, ComRc};
+
+use std::cell::Cell;
+
+#[com_interface("4F1E2D3C-5B6A-4798-8A7B-6C5D4E3F2A1B")]
+pub trait IAdder: IUnknown {
+    unsafe fn add(&self, a: i32, b: i32, sum: *mut i32) -> HRESULT;
+}
+
+#[com_interface("5A2F3E4D-6C7B-48A9-9B8C-7D6E5F4A3B2C")]
+pub trait IAccumulator: IAdder {
+    unsafe fn accumulate(&self, value: i64) -> HRESULT;
+    unsafe fn total(&self) -> i64;
+}
+
+#[co_class(implements(IAccumulator))]
+pub struct Calculator {
+    total: Cell<i64>,
+}
+
+impl Calculator {
+    fn new() -> Box<Calculator> {
+        Calculator::allocate(Cell::new(0))
+    }
+}
+
+impl IAdder for Calculator {
+    unsafe fn add(&self, a: i32, b: i32, sum: *mut i32) -> HRESULT {
+        if sum.is_null() {
+            return E_POINTER;
+        }
+        *sum = a + b;
+        S_OK
+    }
+}
+
+impl IAccumulator for Calculator {
+    unsafe fn accumulate(&self, value: i64) -> HRESULT {
+        self.total.set(self.total.get() + value);
+        S_OK
+    }
+
+    unsafe fn total(&self) -> i64 {
+        self.total.get()
+    }
+}
+
+#[cfg(any(target_os = "linux", windows))]
+mod native {
+    use super::IAccumulator;
+    use com::ComRc;
+    use std::ffi::{c_void, CString};
+    use std::path::Path;
+    use std::process::Command;
+
+    #[cfg(target_os = "linux")]
+    mod loader {
+        use std::ffi::{c_void, CStr};
+        use std::os::raw::{c_char, c_int};
+
+        pub const LIBRARY: &str = "so";
+        /// Shared libraries need position independent code, the default on Windows
+        pub const FLAGS: &[&str] = &["-fPIC"];
+        const RTLD_NOW: c_int = 2;
+
+        #[link(name = "dl")]
+        extern "C" {
+            fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+            fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+            fn dlerror() -> *const c_char;
+        }
+
+        pub unsafe fn open(library: *const c_char) -> *mut c_void {
+            let handle = dlopen(library, RTLD_NOW);
+            assert!(
+                !handle.is_null(),
+                "{}",
+                CStr::from_ptr(dlerror()).to_string_lossy()
+            );
+            handle
+        }
+
+        pub unsafe fn symbol(handle: *mut c_void, name: *const c_char) -> *mut c_void {
+            dlsym(handle, name)
+        }
+    }
+
+    #[cfg(windows)]
+    mod loader {
+        use std::ffi::c_void;
+        use std::os::raw::c_char;
+
+        pub const LIBRARY: &str = "dll";
+        pub const FLAGS: &[&str] = &[];
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn LoadLibraryA(filename: *const c_char) -> *mut c_void;
+            fn GetProcAddress(module: *mut c_void, name: *const c_char) -> *mut c_void;
+        }
+
+        pub unsafe fn open(library: *const c_char) -> *mut c_void {
+            let handle = LoadLibraryA(library);
+            assert!(!handle.is_null(), "the library could not be loaded");
+            handle
+        }
+
+        pub unsafe fn symbol(handle: *mut c_void, name: *const c_char) -> *mut c_void {
+            GetProcAddress(handle, name)
+        }
+    }
+
+    /// Calls through the C VTable, querying for the parent interface on the way
+    const C_SOURCE: &str = r#"
+#include "calculator.h"
+
+long long call_from_c(IAccumulator *accumulator) {
+    int sum = 0;
+    IAdder *adder = NULL;
+    long long total;
+    if (accumulator->lpVtbl->Add(accumulator, 2, 3, &sum) != 0) return -1;
+    if (accumulator->lpVtbl->QueryInterface(accumulator, &IID_IAdder, (void **)&adder) != 0) return -2;
+    if (adder->lpVtbl->Add(adder, sum, 10, &sum) != 0) return -3;
+    adder->lpVtbl->Release(adder);
+    if (accumulator->lpVtbl->Accumulate(accumulator, sum) != 0) return -4;
+    total = accumulator->lpVtbl->Total(accumulator);
+    return total;
+}
+"#;
+
+    /// Calls through the C++ virtual methods
+    const CPP_SOURCE: &str = r#"
+#include "calculator.h"
+
+extern "C" long long call_from_cpp(IAccumulator *accumulator) {
+    int sum = 0;
+    if (accumulator->Add(20, 22, &sum) != 0) return -1;
+    accumulator->AddRef();
+    accumulator->Accumulate(sum);
+    accumulator->Release();
+    return accumulator->Total();
+}
+"#;
+
+    /// The first of `names` that runs. Linux hosts are expected to have one of each kind,
+    /// and so are Windows hosts through MinGW, which compiles against `objbase.h`.
+    fn compiler<'a>(names: &[&'a str]) -> Option<&'a str> {
+        names
+            .iter()
+            .copied()
+            .find(|name| Command::new(name).arg("--version").output().is_ok())
+    }
+
+    /// Build `source` into a shared library and look up `symbol`
+    unsafe fn build(
+        compiler: &str,
+        dir: &Path,
+        file: &str,
+        source: &str,
+        symbol: &str,
+    ) -> unsafe extern "C" fn(*mut c_void) -> i64 {
+        let source_path = dir.join(file);
+        let library = dir.join(format!("lib{}.{}", file.replace('.', "_"), loader::LIBRARY));
+        std::fs::write(&source_path, source).unwrap();
+        // Shared libraries must resolve every symbol, so GUIDs that are only declared fail
+        let status = Command::new(compiler)
+            .args(&["-shared", "-Wall", "-Werror"])
+            .args(loader::FLAGS)
+            .arg("-I")
+            .arg(dir)
+            .arg("-o")
+            .arg(&library)
+            .arg(&source_path)
+            .status()
+            .unwrap();
+        assert!(status.success(), "{} failed to compile {}", compiler, file);
+
+        let library = CString::new(library.to_str().unwrap()).unwrap();
+        let handle = loader::open(library.as_ptr());
+        let symbol = CString::new(symbol).unwrap();
+        let function = loader::symbol(handle, symbol.as_ptr());
+        assert!(!function.is_null());
+        std::mem::transmute(function)
+    }
+
+    pub fn run(accumulator: &ComRc<dyn IAccumulator>) {
+        let raw = accumulator.as_raw() as *mut c_void;
+        let mut metadata = com_macros_support::metadata::Metadata::new();
+        metadata.parse_source(include_str!("c_header.rs")).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("com_c_header_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        com_macros_support::header::write(&metadata, dir.join("calculator.h")).unwrap();
+
+        let cc = compiler(&["cc", "gcc", "clang"]).expect("no C compiler found");
+        unsafe {
+            let call = build(cc, &dir, "calculator.c", C_SOURCE, "call_from_c");
+            let total = accumulator.total();
+            assert_eq!(call(raw), total + 15);
+        }
+        let cxx = compiler(&["c++", "g++", "clang++"]).expect("no C++ compiler found");
+        unsafe {
+            let call = build(cxx, &dir, "calculator.cpp", CPP_SOURCE, "call_from_cpp");
+            let total = accumulator.total();
+            assert_eq!(call(raw), total + 42);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
+
+fn main() {
+    let calculator = Calculator::new();
+    let accumulator = unsafe {
+        let mut ppv = std::ptr::null_mut();
+        assert_eq!(
+            calculator.query_interface(&IID_IACCUMULATOR, &mut ppv),
+            S_OK
+        );
+        let _ = Box::into_raw(calculator);
+        ComRc::<dyn IAccumulator>::from_raw(ppv as *mut _)
+    };
+
+    #[cfg(any(target_os = "linux", windows))]
+    native::run(&accumulator);
+}
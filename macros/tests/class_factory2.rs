@@ -0,0 +1,95 @@
+use com::interfaces::{IClassFactory, IClassFactory2, IUnknown};
+use com::license::License;
+use com::sys::{CLASS_E_NOTLICENSED, CLSID, HRESULT, S_OK};
+use com::{co_class, com_interface, ComInterface, ComRc};
+
+use std::cell::Cell;
+
+pub const CLSID_CHART_CLASS: CLSID = CLSID {
+    data1: 0x7A3D_9E4F,
+    data2: 0x5C61,
+    data3: 0x4D82,
+    data4: [0xB3, 0xA4, 0x9F, 0x80, 0x71, 0x62, 0x53, 0x44],
+};
+
+thread_local! {
+    static LICENSED: Cell<bool> = Cell::new(true);
+}
+
+#[com_interface("2B3C4D5E-6F7A-4B8C-9DAE-1F2A3B4C5D6E")]
+pub trait IChart: IUnknown {
+    unsafe fn bars(&self) -> u32;
+}
+
+#[co_class(implements(IChart), licensed)]
+pub struct Chart {}
+
+impl Chart {
+    fn new() -> Box<Chart> {
+        Chart::allocate()
+    }
+}
+
+impl License for Chart {
+    fn is_licensed() -> bool {
+        LICENSED.with(Cell::get)
+    }
+
+    fn runtime_key() -> Option<String> {
+        Some("chart-runtime-key".to_owned())
+    }
+}
+
+impl IChart for Chart {
+    unsafe fn bars(&self) -> u32 {
+        3
+    }
+}
+
+com::inproc_dll_module![(CLSID_CHART_CLASS, Chart),];
+
+fn main() {
+    let mut ppv = std::ptr::null_mut();
+    let hr: HRESULT = DllGetClassObject(
+        &CLSID_CHART_CLASS,
+        &<dyn IClassFactory2 as ComInterface>::IID,
+        &mut ppv,
+    );
+    assert_eq!(hr, S_OK);
+    let factory: ComRc<dyn IClassFactory2> = unsafe { ComRc::from_raw(ppv as *mut *mut _) };
+    assert!(factory.get_interface::<dyn IClassFactory>().is_some());
+
+    // A licensed machine creates instances and hands out the runtime key
+    let lic_info = factory.lic_info().unwrap();
+    assert_ne!(lic_info.f_runtime_key_avail, 0);
+    assert_ne!(lic_info.f_licence_verified, 0);
+    let key = factory.request_key().unwrap();
+    assert_eq!(key, "chart-runtime-key");
+    let chart = factory
+        .get_interface::<dyn IClassFactory>()
+        .unwrap()
+        .get_instance::<dyn IChart>()
+        .unwrap();
+    assert_eq!(unsafe { chart.bars() }, 3);
+
+    // Without a license only the runtime key creates instances
+    LICENSED.with(|licensed| licensed.set(false));
+    assert_eq!(factory.lic_info().unwrap().f_licence_verified, 0);
+    assert_eq!(factory.request_key().err(), Some(CLASS_E_NOTLICENSED));
+    let mut ppv = std::ptr::null_mut();
+    let hr = unsafe {
+        factory.create_instance(
+            std::ptr::null_mut(),
+            &<dyn IChart as ComInterface>::IID,
+            &mut ppv,
+        )
+    };
+    assert_eq!(hr, CLASS_E_NOTLICENSED);
+    assert!(ppv.is_null());
+    assert_eq!(
+        factory.get_instance_lic::<dyn IChart>("wrong-key").err(),
+        Some(CLASS_E_NOTLICENSED)
+    );
+    let chart = factory.get_instance_lic::<dyn IChart>(&key).unwrap();
+    assert_eq!(unsafe { chart.bars() }, 3);
+}
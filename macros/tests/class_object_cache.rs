@@ -0,0 +1,184 @@
+mod common;
+
+use com::interfaces::iclass_factory::IClassFactory;
+use com::interfaces::IUnknown;
+use com::sys::{CLSID, E_UNEXPECTED, HRESULT, S_FALSE, S_OK};
+use com::{co_class, com_interface, ComInterface, ComRc};
+use common::{count_drop, dropped};
+
+use std::cell::Cell;
+
+pub const CLSID_COUNTER_CLASS: CLSID = CLSID {
+    data1: 0x5E1B_7C2D,
+    data2: 0x3A4F,
+    data3: 0x4B60,
+    data4: [0x91, 0x82, 0x7D, 0x6E, 0x5F, 0x40, 0x31, 0x22],
+};
+
+pub const CLSID_SETTINGS_CLASS: CLSID = CLSID {
+    data1: 0x6F2C_8D3E,
+    data2: 0x4B50,
+    data3: 0x4C71,
+    data4: [0xA2, 0x93, 0x8E, 0x7F, 0x60, 0x51, 0x42, 0x33],
+};
+
+pub const CLSID_REENTRANT_CLASS: CLSID = CLSID {
+    data1: 0x7A3D_9E4F,
+    data2: 0x5C61,
+    data3: 0x4D82,
+    data4: [0xB3, 0xA4, 0x9F, 0x80, 0x71, 0x62, 0x53, 0x44],
+};
+
+thread_local! {
+    static REENTERED: Cell<Option<(HRESULT, HRESULT)>> = Cell::new(None);
+}
+
+#[com_interface("1A2B3C4D-5E6F-4A7B-8C9D-0E1F2A3B4C5D")]
+pub trait ICounter: IUnknown {
+    unsafe fn increment(&self) -> u32;
+}
+
+#[co_class(implements(ICounter), cache_class_object)]
+pub struct Counter {
+    count: Cell<u32>,
+}
+
+impl Counter {
+    fn new() -> Box<Counter> {
+        Counter::allocate(Cell::new(0))
+    }
+}
+
+impl ICounter for Counter {
+    unsafe fn increment(&self) -> u32 {
+        self.count.set(self.count.get() + 1);
+        self.count.get()
+    }
+}
+
+#[co_class(implements(ICounter), singleton)]
+pub struct Settings {
+    count: Cell<u32>,
+}
+
+impl Settings {
+    fn new() -> Box<Settings> {
+        Settings::allocate(Cell::new(0))
+    }
+}
+
+impl Drop for Settings {
+    fn drop(&mut self) {
+        count_drop();
+    }
+}
+
+impl ICounter for Settings {
+    unsafe fn increment(&self) -> u32 {
+        self.count.set(self.count.get() + 1);
+        self.count.get()
+    }
+}
+
+/// A singleton creating an instance of its own class while it is being created
+#[co_class(implements(ICounter), singleton)]
+pub struct Reentrant {}
+
+impl Reentrant {
+    fn new() -> Box<Reentrant> {
+        let factory = class_object(&CLSID_REENTRANT_CLASS);
+        let mut ppv = std::ptr::null_mut();
+        let hr = unsafe {
+            factory.create_instance(
+                std::ptr::null_mut(),
+                &<dyn ICounter as ComInterface>::IID,
+                &mut ppv,
+            )
+        };
+        REENTERED.with(|reentered| reentered.set(Some((hr, DllCanUnloadNow()))));
+        Reentrant::allocate()
+    }
+}
+
+impl ICounter for Reentrant {
+    unsafe fn increment(&self) -> u32 {
+        0
+    }
+}
+
+com::inproc_dll_module![
+    (CLSID_COUNTER_CLASS, Counter),
+    (CLSID_SETTINGS_CLASS, Settings),
+    (CLSID_REENTRANT_CLASS, Reentrant)
+];
+
+fn class_object(clsid: &CLSID) -> ComRc<dyn IClassFactory> {
+    let mut ppv = std::ptr::null_mut();
+    let hr: HRESULT = DllGetClassObject(clsid, &<dyn IClassFactory as ComInterface>::IID, &mut ppv);
+    assert_eq!(hr, S_OK);
+    unsafe { ComRc::from_raw(ppv as *mut *mut _) }
+}
+
+fn create(factory: &ComRc<dyn IClassFactory>) -> ComRc<dyn ICounter> {
+    let mut ppv = std::ptr::null_mut();
+    unsafe {
+        let hr = factory.create_instance(
+            std::ptr::null_mut(),
+            &<dyn ICounter as ComInterface>::IID,
+            &mut ppv,
+        );
+        assert_eq!(hr, S_OK);
+        ComRc::from_raw(ppv as *mut *mut _)
+    }
+}
+
+fn main() {
+    // The class object of a cached class is shared
+    let counter_factory = class_object(&CLSID_COUNTER_CLASS);
+    assert_eq!(
+        class_object(&CLSID_COUNTER_CLASS).as_raw(),
+        counter_factory.as_raw()
+    );
+    let counter = create(&counter_factory);
+    assert_ne!(create(&counter_factory).as_raw(), counter.as_raw());
+
+    // Other classes get a new class object, but a singleton always has the same instance
+    let settings_factory = class_object(&CLSID_SETTINGS_CLASS);
+    assert_ne!(
+        class_object(&CLSID_SETTINGS_CLASS).as_raw(),
+        settings_factory.as_raw()
+    );
+    let settings = create(&settings_factory);
+    assert_eq!(create(&settings_factory).as_raw(), settings.as_raw());
+    unsafe {
+        assert_eq!(settings.increment(), 1);
+        assert_eq!(create(&settings_factory).increment(), 2);
+    }
+
+    // Live instances and server locks keep the module loaded
+    assert_eq!(DllCanUnloadNow(), S_FALSE);
+    drop(counter);
+    drop(settings);
+    unsafe {
+        assert_eq!(counter_factory.lock_server(1), S_OK);
+        assert_eq!(DllCanUnloadNow(), S_FALSE);
+        assert_eq!(counter_factory.lock_server(0), S_OK);
+    }
+    assert_eq!(dropped(), 0);
+
+    // Unloading releases the singleton and the cached class object
+    assert_eq!(DllCanUnloadNow(), S_OK);
+    assert_eq!(dropped(), 1);
+    let settings = create(&settings_factory);
+    unsafe {
+        assert_eq!(settings.increment(), 1);
+    }
+    assert_ne!(
+        class_object(&CLSID_COUNTER_CLASS).as_raw(),
+        counter_factory.as_raw()
+    );
+
+    // Creating a singleton from its own constructor fails instead of waiting forever
+    create(&class_object(&CLSID_REENTRANT_CLASS));
+    assert_eq!(REENTERED.with(Cell::get), Some((E_UNEXPECTED, S_FALSE)));
+}
@@ -0,0 +1,47 @@
+//! Fixtures shared by the tests, included with `mod common;`
+
+// Each test only uses some of them
+#![allow(dead_code)]
+
+use com::interfaces::IUnknown;
+use com::sys::{HRESULT, S_OK};
+use com::{ComInterface, ComRc};
+
+use std::cell::Cell;
+
+#[com::com_interface("EFF8970E-C50F-45E0-9284-291CE5A6F771")]
+pub trait IAnimal: IUnknown {
+    unsafe fn eat(&self, amount: u32) -> HRESULT;
+}
+
+thread_local! {
+    static DROPPED: Cell<u32> = Cell::new(0);
+}
+
+/// Count an object as dropped, from its `Drop` implementation
+pub fn count_drop() {
+    DROPPED.with(|dropped| dropped.set(dropped.get() + 1));
+}
+
+/// The number of objects dropped on this thread
+pub fn dropped() -> u32 {
+    DROPPED.with(Cell::get)
+}
+
+/// Query an object for the interface `T`, which it must implement
+pub fn query<T: ComInterface + ?Sized>(object: &dyn IUnknown) -> ComRc<T> {
+    unsafe {
+        let mut ppv = std::ptr::null_mut();
+        assert_eq!(object.query_interface(&T::IID, &mut ppv), S_OK);
+        ComRc::from_raw(ppv as *mut *mut _)
+    }
+}
+
+/// Turn a newly allocated co_class instance into its interface `T`
+///
+/// The returned pointer holds the only reference to the instance.
+pub fn into_interface<C: IUnknown, T: ComInterface + ?Sized>(instance: Box<C>) -> ComRc<T> {
+    let rc = query::<T>(&*instance);
+    let _ = Box::into_raw(instance);
+    rc
+}
@@ -0,0 +1,113 @@
+use com::co_class;
+use com::interfaces::iconnection_point::IConnectionPointVPtr;
+use com::interfaces::ienum_connection_points::IEnumConnectionPointsVPtr;
+use com::interfaces::{ConnectionPoint, IConnectionPoint, IConnectionPointContainer, IUnknown};
+use com::sys::{CONNECT_E_NOCONNECTION, E_NOTIMPL, E_POINTER, HRESULT, IID, S_OK};
+use com::{ComInterface, ComRc};
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+#[com::com_interface("D7F2B0C4-3A8E-4C1F-9E5B-2F6A7C8D9E01")]
+pub trait ICatEvents: IUnknown {
+    unsafe fn on_meow(&self, volume: u32) -> HRESULT;
+    unsafe fn on_sleep(&self) -> HRESULT;
+}
+
+#[co_class(implements(IConnectionPointContainer))]
+pub struct Cat {
+    events: ConnectionPoint<dyn ICatEvents>,
+}
+
+impl IConnectionPointContainer for Cat {
+    unsafe fn enum_connection_points(&self, _ppenum: *mut *mut IEnumConnectionPointsVPtr) -> HRESULT {
+        E_NOTIMPL
+    }
+
+    unsafe fn find_connection_point(
+        &self,
+        riid: *const IID,
+        ppcp: *mut *mut IConnectionPointVPtr,
+    ) -> HRESULT {
+        self.events.find(self, riid, ppcp)
+    }
+}
+
+impl Cat {
+    fn new() -> Box<Cat> {
+        Cat::allocate(ConnectionPoint::new())
+    }
+
+    fn meow(&self, volume: u32) {
+        self.events.fire(|sink| unsafe {
+            sink.on_meow(volume);
+        });
+    }
+}
+
+fn main() {
+    let cat = Cat::new();
+    let container = unsafe {
+        let mut ppv = std::ptr::null_mut();
+        cat.query_interface(&<dyn IConnectionPointContainer as ComInterface>::IID, &mut ppv);
+        ComRc::<dyn IConnectionPointContainer>::from_raw(ppv as *mut *mut _)
+    };
+
+    let loudest = Rc::new(Cell::new(0));
+    let sleeps = Rc::new(Cell::new(0));
+    let connection = {
+        let loudest = loudest.clone();
+        let sleeps = sleeps.clone();
+        container
+            .advise_with::<dyn ICatEvents, _>((
+                move |volume: u32| {
+                    loudest.set(loudest.get().max(volume));
+                    S_OK
+                },
+                move || {
+                    sleeps.set(sleeps.get() + 1);
+                    S_OK
+                },
+            ))
+            .expect("ICatEvents is supported")
+    };
+
+    cat.meow(3);
+    cat.meow(7);
+    cat.events.fire(|sink| unsafe {
+        sink.on_sleep();
+    });
+    assert_eq!(loudest.get(), 7);
+    assert_eq!(sleeps.get(), 1);
+
+    let point = container.connection_point::<dyn ICatEvents>().unwrap();
+    let connections: Vec<_> = unsafe {
+        let mut ppenum = std::ptr::null_mut();
+        assert_eq!(point.enum_connections(&mut ppenum), S_OK);
+        ComRc::<dyn com::interfaces::IEnumConnections>::from_raw(ppenum as *mut *mut _)
+    }
+    .into_iter()
+    .collect();
+    assert_eq!(connections.len(), 1);
+    assert_eq!(connections[0].cookie, connection.cookie());
+
+    drop(connection);
+    cat.meow(10);
+    assert_eq!(loudest.get(), 7);
+    assert!(cat.events.sinks().is_empty());
+    assert_eq!(
+        container.connection_point::<dyn IUnknown>().err(),
+        Some(CONNECT_E_NOCONNECTION)
+    );
+    unsafe {
+        let mut ppcp = std::ptr::null_mut();
+        assert_eq!(
+            container.find_connection_point(std::ptr::null(), &mut ppcp),
+            E_POINTER
+        );
+        assert!(ppcp.is_null());
+    }
+
+    // Leave the reference held by `container` as the only one
+    let _ = Box::into_raw(cat);
+}
@@ -0,0 +1,122 @@
+use com::interfaces::{IClassFactory, IUnknown};
+use com::runtime::{
+    create_instance, create_instance_multi, create_instance_tuple, init_runtime,
+    register_class_object, revoke_class_object,
+};
+use com::sys::{CLSID, E_NOINTERFACE, HRESULT, REGDB_E_CLASSNOTREG, S_OK};
+use com::{co_class, com_interface, ComInterface, ComRc};
+
+use std::cell::Cell;
+
+pub const CLSID_LAMP_CLASS: CLSID = CLSID {
+    data1: 0x8B4E_AF50,
+    data2: 0x6D72,
+    data3: 0x4E93,
+    data4: [0xC4, 0xB5, 0xA0, 0x91, 0x82, 0x73, 0x64, 0x55],
+};
+
+thread_local! {
+    static CREATED: Cell<u32> = Cell::new(0);
+}
+
+#[com_interface("3C4D5E6F-7A8B-4C9D-AEBF-2A3B4C5D6E7F")]
+pub trait ISwitch: IUnknown {
+    unsafe fn toggle(&self) -> bool;
+}
+
+#[com_interface("4D5E6F7A-8B9C-4DAE-BFC0-3B4C5D6E7F80")]
+pub trait IDimmer: IUnknown {
+    unsafe fn level(&self) -> u32;
+}
+
+#[com_interface("5E6F7A8B-9CAD-4EBF-C0D1-4C5D6E7F8091")]
+pub trait IUnsupported: IUnknown {}
+
+#[co_class(implements(ISwitch, IDimmer))]
+pub struct Lamp {
+    on: Cell<bool>,
+}
+
+impl Lamp {
+    fn new() -> Box<Lamp> {
+        CREATED.with(|created| created.set(created.get() + 1));
+        Lamp::allocate(Cell::new(false))
+    }
+}
+
+impl ISwitch for Lamp {
+    unsafe fn toggle(&self) -> bool {
+        self.on.set(!self.on.get());
+        self.on.get()
+    }
+}
+
+impl IDimmer for Lamp {
+    unsafe fn level(&self) -> u32 {
+        if self.on.get() {
+            100
+        } else {
+            0
+        }
+    }
+}
+
+com::inproc_dll_module![(CLSID_LAMP_CLASS, Lamp),];
+
+fn main() {
+    init_runtime().unwrap();
+    assert_eq!(
+        create_instance::<dyn ISwitch>(&CLSID_LAMP_CLASS).err(),
+        Some(REGDB_E_CLASSNOTREG)
+    );
+
+    let mut ppv = std::ptr::null_mut();
+    let hr: HRESULT = DllGetClassObject(
+        &CLSID_LAMP_CLASS,
+        &<dyn IClassFactory as ComInterface>::IID,
+        &mut ppv,
+    );
+    assert_eq!(hr, S_OK);
+    let factory: ComRc<dyn IClassFactory> = unsafe { ComRc::from_raw(ppv as *mut *mut _) };
+    let cookie = register_class_object(&CLSID_LAMP_CLASS, &factory).unwrap();
+
+    // Every interface is queried from the same instance
+    let results = create_instance_multi(
+        &CLSID_LAMP_CLASS,
+        &[
+            <dyn ISwitch as ComInterface>::IID,
+            <dyn IUnsupported as ComInterface>::IID,
+            <dyn IDimmer as ComInterface>::IID,
+        ],
+    )
+    .unwrap();
+    assert_eq!(CREATED.with(Cell::get), 1);
+    assert_eq!(results.len(), 3);
+    assert!(results[0].is_ok());
+    assert_eq!(results[1].as_ref().err(), Some(&E_NOINTERFACE));
+    assert!(results[2].is_ok());
+
+    let (switch, dimmer): (ComRc<dyn ISwitch>, ComRc<dyn IDimmer>) =
+        create_instance_tuple(&CLSID_LAMP_CLASS).unwrap();
+    assert_eq!(CREATED.with(Cell::get), 2);
+    unsafe {
+        assert_eq!(dimmer.level(), 0);
+        assert!(switch.toggle());
+        assert_eq!(dimmer.level(), 100);
+    }
+
+    // The typed variant fails if any interface is missing
+    let missing: Result<(ComRc<dyn ISwitch>, ComRc<dyn IUnsupported>), HRESULT> =
+        create_instance_tuple(&CLSID_LAMP_CLASS);
+    assert_eq!(missing.err(), Some(E_NOINTERFACE));
+    assert_eq!(
+        create_instance_multi(&CLSID_LAMP_CLASS, &[<dyn IUnsupported as ComInterface>::IID]).err(),
+        Some(E_NOINTERFACE)
+    );
+
+    revoke_class_object(cookie).unwrap();
+    assert_eq!(
+        create_instance_tuple::<(ComRc<dyn ISwitch>,)>(&CLSID_LAMP_CLASS).err(),
+        Some(REGDB_E_CLASSNOTREG)
+    );
+}
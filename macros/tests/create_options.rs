@@ -0,0 +1,87 @@
+use com::interfaces::{IClassFactory, IUnknown};
+use com::runtime::{create_instance, init_runtime, revoke_class_object, CreateOptions};
+use com::sys::{CLSID, E_NOTIMPL, HRESULT, REGDB_E_CLASSNOTREG, S_OK};
+use com::{co_class, com_interface, ComInterface, ComRc};
+
+pub const CLSID_PRINTER_CLASS: CLSID = CLSID {
+    data1: 0x9C5F_B061,
+    data2: 0x7E83,
+    data3: 0x4FA4,
+    data4: [0xD5, 0xC6, 0xB1, 0xA2, 0x93, 0x84, 0x75, 0x66],
+};
+
+#[com_interface("6F7A8B9C-ADBE-4FC0-D1E2-5D6E7F8091A2")]
+pub trait IPrinter: IUnknown {
+    unsafe fn pages(&self) -> u32;
+}
+
+#[co_class(implements(IPrinter))]
+pub struct Printer {}
+
+impl Printer {
+    fn new() -> Box<Printer> {
+        Printer::allocate()
+    }
+}
+
+impl IPrinter for Printer {
+    unsafe fn pages(&self) -> u32 {
+        42
+    }
+}
+
+com::inproc_dll_module![(CLSID_PRINTER_CLASS, Printer),];
+
+fn main() {
+    init_runtime().unwrap();
+    let mut ppv = std::ptr::null_mut();
+    let hr: HRESULT = DllGetClassObject(
+        &CLSID_PRINTER_CLASS,
+        &<dyn IClassFactory as ComInterface>::IID,
+        &mut ppv,
+    );
+    assert_eq!(hr, S_OK);
+    let factory: ComRc<dyn IClassFactory> = unsafe { ComRc::from_raw(ppv as *mut *mut _) };
+
+    // The class object is only available from a local server
+    let cookie = CreateOptions::new()
+        .local_server()
+        .register_class_object(&CLSID_PRINTER_CLASS, &factory)
+        .unwrap();
+    assert_eq!(
+        create_instance::<dyn IPrinter>(&CLSID_PRINTER_CLASS).err(),
+        Some(REGDB_E_CLASSNOTREG)
+    );
+
+    // In process servers are tried first, then the local server
+    let options = CreateOptions::new()
+        .inproc_server()
+        .local_server()
+        .activate_64_bit_server();
+    let printer = options
+        .create_instance::<dyn IPrinter>(&CLSID_PRINTER_CLASS)
+        .unwrap();
+    assert_eq!(unsafe { printer.pages() }, 42);
+    assert!(options
+        .get_class_object::<dyn IClassFactory>(&CLSID_PRINTER_CLASS)
+        .is_ok());
+
+    // Errors other than the class not being available stop the fallback
+    let remote = CreateOptions::new()
+        .remote_server("print-server")
+        .local_server();
+    assert_eq!(
+        remote
+            .create_instance::<dyn IPrinter>(&CLSID_PRINTER_CLASS)
+            .err(),
+        Some(E_NOTIMPL)
+    );
+
+    revoke_class_object(cookie).unwrap();
+    assert_eq!(
+        options
+            .create_instance::<dyn IPrinter>(&CLSID_PRINTER_CLASS)
+            .err(),
+        Some(REGDB_E_CLASSNOTREG)
+    );
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use com::error::ErrorInfo;
+use com::interfaces::{ICreateErrorInfo, IErrorInfo, ISupportErrorInfo, IUnknown};
+use com::sys::{E_FAIL, HRESULT, S_FALSE, S_OK};
+use com::{co_class, com_interface, ComInterface};
+use common::into_interface;
+
+#[com_interface("7F7DB1A6-7D3B-4C8D-A3C7-6DBA0E3F8F10")]
+pub trait ICat: IUnknown {
+    unsafe fn ignore_humans(&self) -> HRESULT;
+    unsafe fn eat(&self) -> HRESULT;
+}
+
+#[co_class(implements(ICat, ISupportErrorInfo), support_error_info)]
+pub struct Cat {}
+
+impl Cat {
+    fn new() -> Box<Cat> {
+        Cat::allocate()
+    }
+}
+
+impl ICat for Cat {
+    unsafe fn ignore_humans(&self) -> HRESULT {
+        ErrorInfo::new("Humans can not be ignored")
+            .with_source("Cat")
+            .into_hresult(E_FAIL)
+    }
+
+    unsafe fn eat(&self) -> HRESULT {
+        S_FALSE
+    }
+}
+
+#[co_class(implements(ICat))]
+pub struct Kitten {}
+
+impl Kitten {
+    fn new() -> Box<Kitten> {
+        Kitten::allocate()
+    }
+}
+
+impl ICat for Kitten {
+    unsafe fn ignore_humans(&self) -> HRESULT {
+        ErrorInfo::new("Kittens do not report errors").into_hresult(E_FAIL)
+    }
+
+    unsafe fn eat(&self) -> HRESULT {
+        S_OK
+    }
+}
+
+fn main() {
+    let icat = into_interface::<_, dyn ICat>(Cat::new());
+
+    let support = icat.get_interface::<dyn ISupportErrorInfo>().unwrap();
+    unsafe {
+        assert_eq!(
+            support.interface_supports_error_info(&<dyn ICat as ComInterface>::IID),
+            S_OK
+        );
+        assert_eq!(
+            support.interface_supports_error_info(&<dyn IErrorInfo as ComInterface>::IID),
+            S_FALSE
+        );
+    }
+
+    assert_eq!(icat.call(|cat| unsafe { cat.eat() }), Ok(S_FALSE));
+    let err = icat.call(|cat| unsafe { cat.ignore_humans() }).unwrap_err();
+    assert_eq!(err.hresult(), E_FAIL);
+    assert_eq!(err.description(), Some("Humans can not be ignored"));
+    assert_eq!(err.source(), Some("Cat"));
+    assert!(com::runtime::take_error_info().is_none());
+
+    // The generated wrappers do the same for each method
+    assert_eq!(unsafe { icat.try_eat() }, Ok(S_FALSE));
+    let err = unsafe { icat.try_ignore_humans() }.unwrap_err();
+    assert_eq!(err.description(), Some("Humans can not be ignored"));
+    assert!(com::runtime::take_error_info().is_none());
+
+    // Without `ISupportErrorInfo` the error information is not attached, nor taken
+    let ikitten = into_interface::<_, dyn ICat>(Kitten::new());
+    let err = ikitten
+        .call(|kitten| unsafe { kitten.ignore_humans() })
+        .unwrap_err();
+    assert_eq!(err.hresult(), E_FAIL);
+    assert!(err.info().is_none());
+    assert_eq!(
+        com::runtime::take_error_info().map(|info| info.description),
+        Some("Kittens do not report errors".to_string())
+    );
+
+    // Error objects can be filled in through `ICreateErrorInfo`
+    let create = com::runtime::create_error_info();
+    let description = "Out of treats"
+        .encode_utf16()
+        .chain(Some(0))
+        .collect::<Vec<u16>>();
+    unsafe {
+        assert_eq!(
+            create.set_description(description.as_ptr() as *mut u16),
+            S_OK
+        );
+        assert_eq!(create.set_help_context(7), S_OK);
+    }
+    let info = create.get_interface::<dyn IErrorInfo>().unwrap();
+    let mut help_context = 0;
+    unsafe {
+        assert_eq!(info.get_help_context(&mut help_context), S_OK);
+        assert_eq!(
+            com::sys::SetErrorInfo(0, info.as_raw() as *mut std::ffi::c_void),
+            S_OK
+        );
+    }
+    assert_eq!(help_context, 7);
+    let info = com::runtime::take_error_info().unwrap();
+    assert_eq!(info.description, "Out of treats");
+    assert_eq!(info.help_context, 7);
+}
@@ -0,0 +1,26 @@
+use com::generations::OneOf2;
+use com::interfaces::IUnknown;
+use com::sys::HRESULT;
+use com::{com_interface, ComRc};
+
+#[com_interface("A2B3C4D5-E6F7-4809-9A1B-2C3D4E5F6071")]
+pub trait IGauge: IUnknown {
+    unsafe fn level(&self) -> u32;
+}
+
+#[com_interface("B3C4D5E6-F708-491A-AB2C-3D4E5F607182")]
+pub trait IGauge2: IGauge {
+    unsafe fn reset(&self) -> HRESULT;
+}
+
+#[com_interface("C4D5E6F7-0819-4A2B-BC3D-4E5F60718293")]
+pub trait IGauge3: IGauge2 {
+    unsafe fn generation(&self) -> u32;
+}
+
+fn newest(unknown: &ComRc<dyn IUnknown>) {
+    // IGauge2 is missing from the chain
+    let _ = unknown.best_of::<OneOf2<dyn IGauge3, dyn IGauge>>();
+}
+
+fn main() {}
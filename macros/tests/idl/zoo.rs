@@ -0,0 +1,40 @@
+// Generated from the interface definitions, do not edit
+
+#[allow(non_camel_case_types)]
+pub type ANIMAL_KIND = i32;
+pub const AK_CAT: ANIMAL_KIND = 1;
+pub const AK_DOG: ANIMAL_KIND = 2;
+pub const AK_BIRD: ANIMAL_KIND = 16;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct FEEDING {
+    pub kind: ANIMAL_KIND,
+    pub grams: u32,
+    pub hour: f64,
+}
+
+pub type LPFEEDING = *mut FEEDING;
+
+#[com::com_interface("2D1B3E8A-6F4C-4B57-9E21-0C3A5D7F8E91")]
+pub trait IEnclosure: com::interfaces::iunknown::IUnknown {
+    unsafe fn feed(&self, feedings: *const FEEDING, count: u32) -> com::sys::HRESULT;
+    unsafe fn get_grams(&self, grams: *mut u32) -> com::sys::HRESULT;
+}
+
+#[com::com_interface("3E2C4F9B-705D-4C68-AF32-1D4B6E809FA2")]
+pub trait IAviary: IEnclosure {
+    unsafe fn wingspan(&self, kind: ANIMAL_KIND, age: i16, metres: *mut f64, flies: *mut com::sys::BOOL) -> com::sys::HRESULT;
+}
+
+#[com::com_interface("4F3D5A0C-816E-4D79-B043-2E5C7F91A0B3")]
+pub trait IZookeeper: com::interfaces::iunknown::IUnknown {
+    unsafe fn inspect(&self, enclosure: *mut IEnclosureVPtr, total: *mut u32) -> com::sys::HRESULT;
+}
+
+pub const CLSID_AVIARY: com::sys::CLSID = com::sys::CLSID {
+    data1: 0x6B5F7C2E,
+    data2: 0xA380,
+    data3: 0x4F9B,
+    data4: [0xD2, 0x65, 0x40, 0x7E, 0x91, 0xB3, 0xC2, 0xD5],
+};
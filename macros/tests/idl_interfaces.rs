@@ -0,0 +1,149 @@
+use com::interfaces::IUnknown;
+use com::sys::{BOOL, E_INVALIDARG, E_POINTER, HRESULT, S_OK};
+use com::{co_class, ComPtr, ComRc};
+
+use com_macros_support::metadata::Metadata;
+
+use std::cell::Cell;
+
+mod zoo {
+    include!("idl/zoo.rs");
+}
+
+use zoo::*;
+
+#[co_class(implements(IAviary))]
+pub struct Aviary {
+    grams: Cell<u32>,
+}
+
+impl Aviary {
+    fn new() -> Box<Aviary> {
+        Aviary::allocate(Cell::new(0))
+    }
+}
+
+impl IEnclosure for Aviary {
+    unsafe fn feed(&self, feedings: *const FEEDING, count: u32) -> HRESULT {
+        if feedings.is_null() {
+            return E_POINTER;
+        }
+        let feedings = std::slice::from_raw_parts(feedings, count as usize);
+        if feedings.iter().any(|feeding| feeding.kind != AK_BIRD) {
+            return E_INVALIDARG;
+        }
+        let grams = feedings.iter().map(|feeding| feeding.grams).sum::<u32>();
+        self.grams.set(self.grams.get() + grams);
+        S_OK
+    }
+
+    unsafe fn get_grams(&self, grams: *mut u32) -> HRESULT {
+        *grams = self.grams.get();
+        S_OK
+    }
+}
+
+impl IAviary for Aviary {
+    unsafe fn wingspan(
+        &self,
+        kind: ANIMAL_KIND,
+        age: i16,
+        metres: *mut f64,
+        flies: *mut BOOL,
+    ) -> HRESULT {
+        *metres = if kind == AK_BIRD {
+            f64::from(age) * 0.25
+        } else {
+            0.0
+        };
+        *flies = (kind == AK_BIRD) as BOOL;
+        S_OK
+    }
+}
+
+#[co_class(implements(IZookeeper))]
+pub struct Zookeeper {}
+
+impl Zookeeper {
+    fn new() -> Box<Zookeeper> {
+        Zookeeper::allocate()
+    }
+}
+
+impl IZookeeper for Zookeeper {
+    unsafe fn inspect(&self, enclosure: *mut IEnclosureVPtr, total: *mut u32) -> HRESULT {
+        let enclosure = ComPtr::<dyn IEnclosure>::new(enclosure as *mut _);
+        let mut grams = 0;
+        let hr = enclosure.get_grams(&mut grams);
+        *total += grams;
+        hr
+    }
+}
+
+fn main() {
+    // The checked in module is what the generator makes of the IDL
+    let mut metadata = Metadata::new();
+    metadata.parse_idl(include_str!("idl/zoo.idl")).unwrap();
+    assert_eq!(
+        com_macros_support::rust::generate(&metadata),
+        include_str!("idl/zoo.rs")
+    );
+    assert_eq!(CLSID_AVIARY.data1, 0x6B5F_7C2E);
+
+    let aviary = Aviary::new();
+    let aviary = unsafe {
+        let mut ppv = std::ptr::null_mut();
+        assert_eq!(aviary.query_interface(&IID_IAVIARY, &mut ppv), S_OK);
+        let _ = Box::into_raw(aviary);
+        ComRc::<dyn IAviary>::from_raw(ppv as *mut _)
+    };
+
+    let feedings = [
+        FEEDING {
+            kind: AK_BIRD,
+            grams: 40,
+            hour: 8.0,
+        },
+        FEEDING {
+            kind: AK_BIRD,
+            grams: 25,
+            hour: 17.5,
+        },
+    ];
+    let mut metres = 0.0;
+    let mut flies = 0;
+    unsafe {
+        assert_eq!(aviary.feed(feedings.as_ptr(), feedings.len() as u32), S_OK);
+        assert_eq!(
+            aviary.feed(
+                &FEEDING {
+                    kind: AK_CAT,
+                    grams: 1,
+                    hour: 0.0
+                },
+                1
+            ),
+            E_INVALIDARG
+        );
+        assert_eq!(aviary.wingspan(AK_BIRD, 6, &mut metres, &mut flies), S_OK);
+    }
+    assert_eq!(metres, 1.5);
+    assert_eq!(flies, 1);
+
+    let keeper = Zookeeper::new();
+    let keeper = unsafe {
+        let mut ppv = std::ptr::null_mut();
+        assert_eq!(keeper.query_interface(&IID_IZOOKEEPER, &mut ppv), S_OK);
+        let _ = Box::into_raw(keeper);
+        ComRc::<dyn IZookeeper>::from_raw(ppv as *mut _)
+    };
+    let enclosure = aviary.get_interface::<dyn IEnclosure>().unwrap();
+    let mut total = 35;
+    unsafe {
+        assert_eq!(
+            keeper.inspect(enclosure.as_raw() as *mut _, &mut total),
+            S_OK
+        );
+    }
+    assert_eq!(total, 100);
+}
@@ -0,0 +1,107 @@
+use com::generations::{OneOf2, OneOf3};
+use com::interfaces::IUnknown;
+use com::sys::{HRESULT, S_OK};
+use com::{co_class, com_interface, ComInterface, ComRc};
+
+#[com_interface("7F8091A2-B3C4-45D6-A7E8-F90A1B2C3D4E")]
+pub trait IGauge: IUnknown {
+    unsafe fn level(&self) -> u32;
+}
+
+#[com_interface("8091A2B3-C4D5-46E7-B8F9-0A1B2C3D4E5F")]
+pub trait IGauge2: IGauge {
+    unsafe fn reset(&self) -> HRESULT;
+}
+
+#[com_interface("91A2B3C4-D5E6-47F8-C90A-1B2C3D4E5F60")]
+pub trait IGauge3: IGauge2 {
+    unsafe fn generation(&self) -> u32;
+}
+
+// Only the newest generation is listed
+#[co_class(implements(IGauge3))]
+pub struct Gauge {}
+
+impl Gauge {
+    fn new() -> Box<Gauge> {
+        Gauge::allocate()
+    }
+}
+
+impl IGauge for Gauge {
+    unsafe fn level(&self) -> u32 {
+        42
+    }
+}
+
+impl IGauge2 for Gauge {
+    unsafe fn reset(&self) -> HRESULT {
+        S_OK
+    }
+}
+
+impl IGauge3 for Gauge {
+    unsafe fn generation(&self) -> u32 {
+        3
+    }
+}
+
+#[co_class(implements(IGauge))]
+pub struct OldGauge {}
+
+impl OldGauge {
+    fn new() -> Box<OldGauge> {
+        OldGauge::allocate()
+    }
+}
+
+impl IGauge for OldGauge {
+    unsafe fn level(&self) -> u32 {
+        7
+    }
+}
+
+type Versions = OneOf3<dyn IGauge3, dyn IGauge2, dyn IGauge>;
+
+fn unknown<C: IUnknown>(instance: Box<C>) -> ComRc<dyn IUnknown> {
+    let unknown = unsafe {
+        let mut ppv = std::ptr::null_mut();
+        instance.query_interface(&<dyn IUnknown as ComInterface>::IID, &mut ppv);
+        ComRc::<dyn IUnknown>::from_raw(ppv as *mut *mut _)
+    };
+    let _ = Box::into_raw(instance);
+    unknown
+}
+
+fn main() {
+    let gauge = unknown(Gauge::new());
+    let gauge3 = gauge.get_interface::<dyn IGauge3>().unwrap();
+    let gauge2 = gauge.get_interface::<dyn IGauge2>().unwrap();
+    let gauge1 = gauge.get_interface::<dyn IGauge>().unwrap();
+    // The older generations are answered from the VTable of the newest
+    assert_eq!(gauge2.as_raw() as usize, gauge3.as_raw() as usize);
+    assert_eq!(gauge1.as_raw() as usize, gauge3.as_raw() as usize);
+    unsafe {
+        assert_eq!(gauge2.reset(), S_OK);
+        assert_eq!(gauge1.level(), 42);
+    }
+
+    match gauge.best_of::<Versions>() {
+        Some(OneOf3::First(gauge3)) => unsafe {
+            assert_eq!(gauge3.generation(), 3);
+            assert_eq!(gauge3.level(), 42);
+        },
+        _ => panic!("IGauge3 is the newest generation of Gauge"),
+    }
+
+    let old_gauge = unknown(OldGauge::new());
+    match old_gauge.best_of::<Versions>() {
+        Some(OneOf3::Third(gauge)) => unsafe {
+            assert_eq!(gauge.level(), 7);
+        },
+        _ => panic!("IGauge is the only generation of OldGauge"),
+    }
+    assert!(old_gauge
+        .best_of::<OneOf2<dyn IGauge3, dyn IGauge2>>()
+        .is_none());
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use com::interfaces::IUnknown;
+use com::sys::{BSTR, E_NOTIMPL, HRESULT, S_FALSE, S_OK};
+use com::ComRc;
+use common::{IAnimal, MockIAnimal};
+
+#[com::com_interface("F5353C58-CFD9-4204-8D92-D274C7578B53")]
+pub trait ICat: IAnimal {
+    unsafe fn ignore_humans(&self) -> HRESULT;
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Whiskers {
+    count: u32,
+    length: f32,
+}
+
+#[com::com_interface("0B6A2F43-8C1D-4E95-A7B2-3F4C5D6E7A81")]
+pub trait IGroom: IUnknown {
+    unsafe fn whiskers(&self, count: u32) -> Whiskers;
+    unsafe fn name(&self) -> BSTR;
+}
+
+fn main() {
+    let mock = MockICat::new();
+    mock.expect_ignore_humans().returning(|| S_OK).times(2);
+    mock.expect_eat()
+        .returning(|amount| if amount > 10 { S_FALSE } else { S_OK });
+
+    let cat: ComRc<dyn ICat> = mock.clone().into();
+    unsafe {
+        assert_eq!(cat.ignore_humans(), S_OK);
+        assert_eq!(cat.ignore_humans(), S_OK);
+        assert_eq!(cat.eat(1), S_OK);
+        assert_eq!(cat.eat(100), S_FALSE);
+    }
+
+    let animal = cat.get_interface::<dyn IAnimal>().expect("ICat is an IAnimal");
+    unsafe { assert_eq!(animal.eat(2), S_OK) };
+    assert_eq!(mock.calls("eat"), 3);
+    mock.checkpoint();
+
+    let other = MockIAnimal::new();
+    other.expect_eat().return_const(S_FALSE);
+    let animal: ComRc<dyn IAnimal> = other.into();
+    unsafe { assert_eq!(animal.eat(0), S_FALSE) };
+    assert!(animal.get_interface::<dyn ICat>().is_none());
+
+    let unexpected: ComRc<dyn ICat> = MockICat::new().into();
+    unsafe { assert_eq!(unexpected.ignore_humans(), E_NOTIMPL) };
+
+    // Return types without `UnexpectedCall` answer unexpected calls with their default
+    let groom = MockIGroom::new();
+    let groomed: ComRc<dyn IGroom> = groom.clone().into();
+    unsafe {
+        assert_eq!(groomed.whiskers(1), Whiskers::default());
+        assert!(groomed.name().is_null());
+    }
+    groom
+        .expect_whiskers()
+        .returning(|count| Whiskers { count, length: 2.5 });
+    unsafe { assert_eq!(groomed.whiskers(12).count, 12) };
+}
@@ -0,0 +1,34 @@
+use com::interfaces::IUnknown;
+use com::sys::HRESULT;
+use com::{co_class, com_interface, ComRc};
+
+use std::cell::Cell;
+
+#[com_interface("3C0E7B19-6A4D-4F52-9E83-1B2D7F6A0C58")]
+pub trait ICat: IUnknown {
+    unsafe fn eat(&self) -> HRESULT;
+}
+
+fn assert_send<T: Send>() {}
+
+#[co_class(implements(ICat), threading = "free")]
+pub struct Cat {
+    meals: Cell<u32>,
+}
+
+impl Cat {
+    fn new() -> Box<Cat> {
+        Cat::allocate(Cell::new(0))
+    }
+}
+
+impl ICat for Cat {
+    unsafe fn eat(&self) -> HRESULT {
+        self.meals.set(self.meals.get() + 1);
+        com::sys::S_OK
+    }
+}
+
+fn main() {
+    assert_send::<ComRc<dyn ICat>>();
+}
@@ -0,0 +1,81 @@
+use com::co_class;
+use com::interfaces::stream::create_memory_stream;
+use com::interfaces::{IPersist, IPersistStream, IStream, IUnknown};
+use com::persist::PersistedState;
+use com::sys::{CLSID, S_FALSE, S_OK, STREAM_SEEK_SET};
+use com::{ComInterface, ComRc};
+
+use serde::{Deserialize, Serialize};
+
+pub const CLSID_CAT_CLASS: CLSID = CLSID {
+    data1: 0xC5F45CBC,
+    data2: 0x4439,
+    data3: 0x418C,
+    data4: [0xA9, 0xF9, 0x05, 0xAC, 0x67, 0x52, 0x5E, 0x43],
+};
+
+#[derive(Serialize, Deserialize)]
+struct CatState {
+    name: String,
+    lives: u32,
+}
+
+#[co_class(implements(IPersistStream), persist_stream(state))]
+pub struct Cat {
+    state: PersistedState<CatState>,
+}
+
+impl Cat {
+    fn new() -> Box<Cat> {
+        Cat::allocate(PersistedState::new(
+            CLSID_CAT_CLASS,
+            CatState {
+                name: "Tom".to_owned(),
+                lives: 9,
+            },
+        ))
+    }
+}
+
+fn persist_stream(cat: &Cat) -> ComRc<dyn IPersistStream> {
+    unsafe {
+        let mut ppv = std::ptr::null_mut();
+        cat.query_interface(&<dyn IPersistStream as ComInterface>::IID, &mut ppv);
+        ComRc::from_raw(ppv as *mut *mut _)
+    }
+}
+
+fn main() {
+    let cat = Cat::new();
+    let persist = persist_stream(&cat);
+    let stream = create_memory_stream(Vec::new());
+
+    unsafe {
+        let mut clsid = std::mem::zeroed();
+        assert_eq!(persist.get_class_id(&mut clsid), S_OK);
+        assert!(clsid == CLSID_CAT_CLASS);
+        assert_eq!(persist.is_dirty(), S_FALSE);
+
+        cat.state.update(|state| state.lives -= 1);
+        assert_eq!(persist.is_dirty(), S_OK);
+
+        let mut size = 0;
+        assert_eq!(persist.get_size_max(&mut size), S_OK);
+        assert_eq!(persist.save(stream.as_raw() as *mut _, 1), S_OK);
+        assert_eq!(persist.is_dirty(), S_FALSE);
+        assert_eq!(stream.seek(0, STREAM_SEEK_SET, std::ptr::null_mut()), S_OK);
+    }
+
+    let other = Cat::new();
+    let other_persist = persist_stream(&other);
+    unsafe {
+        assert_eq!(other_persist.load(stream.as_raw() as *mut _), S_OK);
+    }
+    assert_eq!(other.state.get().lives, 8);
+    assert_eq!(other.state.get().name, "Tom");
+    assert!(persist.get_interface::<dyn IPersist>().is_some());
+
+    // Leave the references held by the `IPersistStream` pointers as the only ones
+    let _ = Box::into_raw(cat);
+    let _ = Box::into_raw(other);
+}
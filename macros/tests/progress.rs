@@ -5,5 +5,32 @@ fn test_com_interface() {
     let t = trybuild::TestCases::new();
     t.compile_fail("tests/no_supertrait.rs");
     t.compile_fail("tests/non_string_guid.rs");
+    t.compile_fail("tests/non_agile_send.rs");
+    // The diagnostic lists the implementations of `FreeThreaded`, which include the
+    // proxies of the portable runtime
+    #[cfg(not(windows))]
+    t.compile_fail("tests/agile_apartment.rs");
+    t.compile_fail("tests/generations_chain.rs");
+    t.compile_fail("tests/wrong_slots.rs");
     t.pass("tests/supertrait_path.rs");
+    t.pass("tests/mock.rs");
+    t.pass("tests/connection_point.rs");
+    t.pass("tests/persist_stream.rs");
+    t.pass("tests/error_info.rs");
+    // Windows needs a registered proxy/stub to resolve `ICat` in another apartment
+    #[cfg(not(windows))]
+    t.pass("tests/agile_ref.rs");
+    t.pass("tests/agile_interface.rs");
+    t.pass("tests/agile_co_class.rs");
+    t.pass("tests/weak_reference.rs");
+    t.pass("tests/class_object_cache.rs");
+    t.pass("tests/class_factory2.rs");
+    t.pass("tests/create_instance_multi.rs");
+    t.pass("tests/create_options.rs");
+    t.pass("tests/wide_strings.rs");
+    t.pass("tests/c_header.rs");
+    t.pass("tests/idl_interfaces.rs");
+    t.pass("tests/type_library.rs");
+    t.pass("tests/skip_slots.rs");
+    t.pass("tests/interface_versions.rs");
 }
@@ -0,0 +1,105 @@
+use com::interfaces::IUnknown;
+use com::{co_class, com_interface, ComInterface, ComRc};
+
+#[com_interface("6E7F8091-A2B3-44C5-96D7-E8F90A1B2C3D")]
+pub trait IDial: IUnknown {
+    unsafe fn one(&self) -> u32;
+    unsafe fn two(&self) -> u32;
+    unsafe fn three(&self) -> u32;
+    unsafe fn four(&self) -> u32;
+    unsafe fn five(&self) -> u32;
+}
+
+// The methods of `IDial` a client calls, at the same slots
+#[com_interface("6E7F8091-A2B3-44C5-96D7-E8F90A1B2C3D", slots = 8)]
+pub trait IDialMirror: IUnknown {
+    #[skip_slots(1)]
+    unsafe fn two(&self) -> u32;
+    #[skip_slots(2)]
+    #[slot(7)]
+    unsafe fn five(&self) -> u32;
+}
+
+#[co_class(implements(IDial))]
+pub struct Dial {}
+
+impl Dial {
+    fn new() -> Box<Dial> {
+        Dial::allocate()
+    }
+}
+
+impl IDial for Dial {
+    unsafe fn one(&self) -> u32 {
+        1
+    }
+
+    unsafe fn two(&self) -> u32 {
+        2
+    }
+
+    unsafe fn three(&self) -> u32 {
+        3
+    }
+
+    unsafe fn four(&self) -> u32 {
+        4
+    }
+
+    unsafe fn five(&self) -> u32 {
+        5
+    }
+}
+
+// A server only implements the declared methods of a mirror
+#[co_class(implements(IDialMirror))]
+pub struct DialMirror {}
+
+impl DialMirror {
+    fn new() -> Box<DialMirror> {
+        DialMirror::allocate()
+    }
+}
+
+impl IDialMirror for DialMirror {
+    unsafe fn two(&self) -> u32 {
+        2
+    }
+
+    unsafe fn five(&self) -> u32 {
+        5
+    }
+}
+
+fn main() {
+    assert_eq!(
+        std::mem::size_of::<<dyn IDialMirror as ComInterface>::VTable>(),
+        std::mem::size_of::<<dyn IDial as ComInterface>::VTable>()
+    );
+
+    let dial = Dial::new();
+    let idial = unsafe {
+        let mut ppv = std::ptr::null_mut();
+        dial.query_interface(&<dyn IDial as ComInterface>::IID, &mut ppv);
+        ComRc::<dyn IDial>::from_raw(ppv as *mut *mut _)
+    };
+    let _ = Box::into_raw(dial);
+
+    let mirror = idial.get_interface::<dyn IDialMirror>().unwrap();
+    unsafe {
+        assert_eq!(mirror.two(), 2);
+        assert_eq!(mirror.five(), 5);
+    }
+
+    let dial_mirror = DialMirror::new();
+    let mirror = unsafe {
+        let mut ppv = std::ptr::null_mut();
+        dial_mirror.query_interface(&<dyn IDialMirror as ComInterface>::IID, &mut ppv);
+        ComRc::<dyn IDialMirror>::from_raw(ppv as *mut *mut _)
+    };
+    let _ = Box::into_raw(dial_mirror);
+    unsafe {
+        assert_eq!(mirror.two(), 2);
+        assert_eq!(mirror.five(), 5);
+    }
+}
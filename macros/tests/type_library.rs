@@ -0,0 +1,114 @@
+use com::interfaces::IUnknown;
+use com::sys::{BSTR, CLSID, FAILED, GUID, HRESULT, S_OK};
+use com::{co_class, com_interface};
+
+use com_macros_support::metadata::{Library, Metadata};
+use com_macros_support::tlb::{self, HRef, TypeKind, TypeLib};
+
+pub const LIBID_GARDEN_LIB: GUID = GUID {
+    data1: 0x0A1B_2C3D,
+    data2: 0x4E5F,
+    data3: 0x4061,
+    data4: [0x82, 0x93, 0xA4, 0xB5, 0xC6, 0xD7, 0xE8, 0xF9],
+};
+
+pub const CLSID_ROSE_CLASS: CLSID = CLSID {
+    data1: 0x1B2C_3D4E,
+    data2: 0x5F60,
+    data3: 0x4172,
+    data4: [0x93, 0xA4, 0xB5, 0xC6, 0xD7, 0xE8, 0xF9, 0x0A],
+};
+
+#[com_interface("2C3D4E5F-6071-4283-A4B5-C6D7E8F90A1B")]
+pub trait IPlant: IUnknown {
+    unsafe fn water(&self, litres: f64) -> HRESULT;
+}
+
+#[com_interface("3D4E5F60-7182-4394-B5C6-D7E8F90A1B2C")]
+pub trait IFlower: IPlant {
+    unsafe fn colour(&self, colour: *mut BSTR) -> HRESULT;
+    unsafe fn petals(&self) -> u32;
+}
+
+#[co_class(implements(IFlower, IPlant))]
+pub struct Rose {}
+
+impl Rose {
+    fn new() -> Box<Rose> {
+        Rose::allocate()
+    }
+}
+
+impl IPlant for Rose {
+    unsafe fn water(&self, _litres: f64) -> HRESULT {
+        S_OK
+    }
+}
+
+impl IFlower for Rose {
+    unsafe fn colour(&self, colour: *mut BSTR) -> HRESULT {
+        *colour = std::ptr::null_mut();
+        S_OK
+    }
+
+    unsafe fn petals(&self) -> u32 {
+        5
+    }
+}
+
+com::inproc_dll_module![
+    type_lib(
+        LIBID_GARDEN_LIB,
+        "Garden",
+        1,
+        0,
+        interfaces(IPlant, IFlower)
+    ),
+    (CLSID_ROSE_CLASS, Rose),
+];
+
+fn main() {
+    let mut metadata = Metadata::new();
+    metadata
+        .parse_source(include_str!("type_library.rs"))
+        .unwrap();
+    let library = Library::new("GardenLib", "0A1B2C3D-4E5F-4061-8293-A4B5C6D7E8F9");
+    let type_lib = TypeLib::parse(&tlb::generate(&metadata, &library).unwrap()).unwrap();
+
+    let kinds = type_lib
+        .types
+        .iter()
+        .map(|t| (t.name.as_str(), t.kind))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            ("IPlant", TypeKind::Interface),
+            ("IFlower", TypeKind::Interface),
+            ("Rose", TypeKind::CoClass),
+        ]
+    );
+    let rose = &type_lib.types[2];
+    assert_eq!(
+        rose.guid.as_deref(),
+        Some("1B2C3D4E-5F60-4172-93A4-B5C6D7E8F90A")
+    );
+    assert_eq!(rose.impl_types[0].href, HRef::Local(1));
+    let slots = type_lib.types[1]
+        .functions
+        .iter()
+        .map(|f| (f.name.as_str(), f.vtable_slot))
+        .collect::<Vec<_>>();
+    assert_eq!(slots, [("Colour", 4), ("Petals", 5)]);
+
+    let rose = Rose::new();
+    assert_eq!(unsafe { rose.petals() }, 5);
+
+    // The registry of other platforms is emulated in memory, where the keys of the type
+    // library and interfaces must come and go along with those of the class
+    if cfg!(not(windows)) {
+        assert_eq!(DllRegisterServer(), S_OK);
+        assert_eq!(DllUnregisterServer(), S_OK);
+        assert!(FAILED(DllUnregisterServer()));
+    }
+}
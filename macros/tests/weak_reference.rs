@@ -0,0 +1,136 @@
+mod common;
+
+use com::interfaces::IUnknown;
+use com::sys::{E_NOINTERFACE, HRESULT, S_OK};
+use com::{co_class, com_interface, ComRc, ComWeak};
+use common::{count_drop, dropped, into_interface};
+
+use std::cell::RefCell;
+use std::mem::ManuallyDrop;
+
+#[com_interface("C2D6E1A4-7F38-4B95-A0E3-5B8C9D1F2A64")]
+pub trait INode: IUnknown {
+    unsafe fn add_child(&self, child: *mut INodeVPtr) -> HRESULT;
+    unsafe fn set_parent(&self, parent: *mut INodeVPtr) -> HRESULT;
+    unsafe fn parent_count(&self) -> u32;
+}
+
+#[co_class(implements(INode), weak)]
+pub struct Node {
+    parent: RefCell<Option<ComWeak<dyn INode>>>,
+    children: RefCell<Vec<ComRc<dyn INode>>>,
+}
+
+impl Node {
+    fn new() -> Box<Node> {
+        Node::allocate(RefCell::new(None), RefCell::new(Vec::new()))
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        count_drop();
+    }
+}
+
+impl INode for Node {
+    unsafe fn add_child(&self, child: *mut INodeVPtr) -> HRESULT {
+        let child = ManuallyDrop::new(ComRc::<dyn INode>::from_raw(child as *mut *mut _));
+        self.children.borrow_mut().push((*child).clone());
+        S_OK
+    }
+
+    unsafe fn set_parent(&self, parent: *mut INodeVPtr) -> HRESULT {
+        let parent = ManuallyDrop::new(ComRc::<dyn INode>::from_raw(parent as *mut *mut _));
+        match parent.downgrade() {
+            Ok(parent) => {
+                *self.parent.borrow_mut() = Some(parent);
+                S_OK
+            }
+            Err(hr) => hr,
+        }
+    }
+
+    unsafe fn parent_count(&self) -> u32 {
+        let parent = self.parent.borrow();
+        match parent.as_ref().and_then(ComWeak::upgrade) {
+            Some(_) => 1,
+            None => 0,
+        }
+    }
+}
+
+#[com_interface("8F4A2B6C-1D3E-4F57-9A0B-C7D8E9F1A2B3", agile)]
+pub trait ICounter: IUnknown {
+    unsafe fn count(&self) -> u32;
+}
+
+#[co_class(implements(ICounter), threading = "free", weak)]
+pub struct Counter {}
+
+impl Counter {
+    fn new() -> Box<Counter> {
+        Counter::allocate()
+    }
+}
+
+impl ICounter for Counter {
+    unsafe fn count(&self) -> u32 {
+        0
+    }
+}
+
+#[co_class(implements(ICounter), threading = "free")]
+pub struct StrongCounter {}
+
+impl StrongCounter {
+    fn new() -> Box<StrongCounter> {
+        StrongCounter::allocate()
+    }
+}
+
+impl ICounter for StrongCounter {
+    unsafe fn count(&self) -> u32 {
+        0
+    }
+}
+
+fn create_node() -> ComRc<dyn INode> {
+    into_interface(Node::new())
+}
+
+fn main() {
+    // A child only holding a weak reference to its parent does not keep it alive
+    let parent = create_node();
+    let child = create_node();
+    unsafe {
+        assert_eq!(parent.add_child(child.as_raw() as *mut _), S_OK);
+        assert_eq!(child.set_parent(parent.as_raw() as *mut _), S_OK);
+        assert_eq!(child.parent_count(), 1);
+    }
+
+    let weak = parent.downgrade().unwrap();
+    let other = weak.clone();
+    assert_eq!(weak.upgrade().unwrap().as_raw(), parent.as_raw());
+
+    drop(parent);
+    assert_eq!(dropped(), 1);
+    assert!(weak.upgrade().is_none());
+    assert!(other.upgrade().is_none());
+    unsafe {
+        assert_eq!(child.parent_count(), 0);
+    }
+    drop(child);
+    assert_eq!(dropped(), 2);
+
+    // Free threaded classes count atomically
+    let counter_rc = into_interface::<_, dyn ICounter>(Counter::new());
+    let weak = ComWeak::new(&counter_rc).unwrap();
+    assert!(weak.upgrade().is_some());
+    drop(counter_rc);
+    assert!(weak.upgrade().is_none());
+
+    // Classes without the option have no weak references
+    let strong_rc = into_interface::<_, dyn ICounter>(StrongCounter::new());
+    assert!(strong_rc.downgrade().err() == Some(E_NOINTERFACE));
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use com::alloc::CoTaskMemString;
+#[cfg(not(windows))]
+use com::alloc::LeakCheck;
+use com::interfaces::IUnknown;
+use com::sys::{E_POINTER, HRESULT, PCWSTR, PWSTR, S_OK};
+use com::{co_class, com_interface};
+use common::into_interface;
+
+use std::cell::RefCell;
+
+#[com_interface("7A8B9CAD-BECF-40D1-E2F3-6E7F8091A2B3")]
+pub trait IGreeter: IUnknown {
+    unsafe fn set_name(&self, name: PCWSTR, repeat: u32) -> HRESULT;
+    unsafe fn greeting(&self, greeting: *mut PWSTR) -> HRESULT;
+    /// The first argument is a locale, which is ignored
+    unsafe fn set_nickname(&self, _: PCWSTR, nickname: PCWSTR) -> HRESULT;
+}
+
+#[co_class(implements(IGreeter))]
+pub struct Greeter {
+    name: RefCell<String>,
+}
+
+impl Greeter {
+    fn new() -> Box<Greeter> {
+        Greeter::allocate(RefCell::new(String::new()))
+    }
+}
+
+impl IGreeter for Greeter {
+    unsafe fn set_name(&self, name: PCWSTR, repeat: u32) -> HRESULT {
+        if name.is_null() {
+            return E_POINTER;
+        }
+        let mut len = 0;
+        while *name.add(len) != 0 {
+            len += 1;
+        }
+        let name = String::from_utf16_lossy(std::slice::from_raw_parts(name, len));
+        *self.name.borrow_mut() = name.repeat(repeat as usize);
+        S_OK
+    }
+
+    unsafe fn greeting(&self, greeting: *mut PWSTR) -> HRESULT {
+        if greeting.is_null() {
+            return E_POINTER;
+        }
+        let value = format!("Hello, {}!", self.name.borrow());
+        *greeting = CoTaskMemString::new(&value).into_raw();
+        S_OK
+    }
+
+    unsafe fn set_nickname(&self, _locale: PCWSTR, nickname: PCWSTR) -> HRESULT {
+        self.set_name(nickname, 1)
+    }
+}
+
+fn main() {
+    // Task memory is only tracked by the portable runtime
+    #[cfg(not(windows))]
+    let check = LeakCheck::start();
+    let greeter = into_interface::<_, dyn IGreeter>(Greeter::new());
+
+    let mut greeting = None;
+    unsafe {
+        assert_eq!(greeter.set_name_str("wörld", 2), S_OK);
+        assert_eq!(greeter.greeting_str(&mut greeting), S_OK);
+    }
+    let greeting = greeting.expect("a greeting");
+    assert_eq!(greeting.to_string(), "Hello, wörldwörld!");
+
+    // The owned string frees its task memory once converted
+    #[cfg(not(windows))]
+    assert_eq!(check.leaks().len(), 1);
+    assert_eq!(String::from(greeting), "Hello, wörldwörld!");
+
+    // Parameters declared with a pattern are converted as well
+    let mut greeting = None;
+    unsafe {
+        assert_eq!(greeter.set_nickname_str("en-GB", "mate"), S_OK);
+        assert_eq!(greeter.greeting_str(&mut greeting), S_OK);
+    }
+    assert_eq!(greeting.unwrap().to_string(), "Hello, mate!");
+    #[cfg(not(windows))]
+    check.assert_no_leaks();
+}
@@ -0,0 +1,12 @@
+use com::com_interface;
+use com::interfaces::IUnknown;
+use com::sys::HRESULT;
+
+#[com_interface("5D1E2F3A-4B5C-4D6E-8F90-A1B2C3D4E5F6", slots = 6)]
+pub trait IPrinter: IUnknown {
+    unsafe fn print(&self) -> HRESULT;
+    #[slot(5)]
+    unsafe fn cancel(&self) -> HRESULT;
+}
+
+fn main() {}
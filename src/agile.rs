@@ -0,0 +1,278 @@
+//! Passing interfaces between apartments
+//!
+//! A [`ComRc`] is only valid in the apartment it was obtained in. An [`AgileRef`] registers
+//! the object in the global interface table and can be sent to another thread, where
+//! [`AgileRef::resolve`] returns a `ComRc` that is valid in that thread's apartment:
+//!
+//! ```rust,ignore
+//! let sta = StaExecutor::new()?;
+//! let agile = sta.handle().run(|| AgileRef::new(&create_cat()))??;
+//!
+//! // Calls made through `cat` run on the STA thread
+//! let cat: ComRc<dyn ICat> = agile.resolve()?;
+//! ```
+//!
+//! On Windows this uses the system `IGlobalInterfaceTable`. The portable runtime keeps the
+//! table itself and hands out a [`Proxy`] when an object owned by an STA is resolved on a
+//! different thread. The proxy runs every call on the owning thread through its message
+//! queue, so that thread must pump messages, e.g. with [`run_message_loop`].
+//!
+//! A co_class with the `agile` option answers `IAgileObject` and implements `IMarshal`
+//! with the [`FreeThreadedMarshaler`], so its interface pointers are handed to other
+//! apartments as they are instead of through a proxy.
+//!
+//! [`ComRc`]: ../struct.ComRc.html
+//! [`AgileRef`]: struct.AgileRef.html
+//! [`AgileRef::resolve`]: struct.AgileRef.html#method.resolve
+//! [`Proxy`]: struct.Proxy.html
+//! [`run_message_loop`]: ../runtime/fn.run_message_loop.html
+//! [`FreeThreadedMarshaler`]: struct.FreeThreadedMarshaler.html
+
+#[cfg(not(windows))]
+mod proxy;
+
+#[cfg(not(windows))]
+#[doc(hidden)]
+pub use proxy::AssertSend;
+#[cfg(not(windows))]
+pub use proxy::Proxy;
+
+use crate::interfaces::iglobal_interface_table::{
+    IGlobalInterfaceTable, CLSID_STD_GLOBAL_INTERFACE_TABLE,
+};
+use crate::interfaces::imarshal::CLSID_IN_PROC_FREE_MARSHALER;
+use crate::interfaces::istream::{IStream, IStreamVPtr};
+use crate::interfaces::iunknown::IUnknown;
+use crate::interfaces::stream::ComStream;
+use crate::runtime::create_instance;
+use crate::sys::{
+    CLSID, E_FAIL, E_POINTER, FAILED, HRESULT, IID, MSHCTX_CROSSCTX, MSHCTX_INPROC,
+    MSHLFLAGS_NORMAL, MSHLFLAGS_TABLEWEAK, STG_E_READFAULT, STG_E_WRITEFAULT, S_OK,
+};
+use crate::{ComInterface, ComPtr, ComRc};
+
+use std::ffi::c_void;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+
+/// A reference to a COM object that can be sent to and resolved in any apartment
+///
+/// The object stays registered in the global interface table until the `AgileRef`
+/// is dropped.
+pub struct AgileRef<T: ComInterface + ?Sized> {
+    cookie: u32,
+    _marker: PhantomData<*const T>,
+}
+
+// The cookie is valid in every apartment of the process. Only `resolve` hands out
+// interface pointers, and those are valid for the apartment of the calling thread.
+unsafe impl<T: ComInterface + ?Sized> Send for AgileRef<T> {}
+unsafe impl<T: ComInterface + ?Sized> Sync for AgileRef<T> {}
+
+impl<T: ProxyInterface + ?Sized> AgileRef<T> {
+    /// Register `object`, which belongs to the apartment of the current thread
+    pub fn new(object: &ComRc<T>) -> Result<Self, HRESULT> {
+        #[cfg(not(windows))]
+        crate::sys::register_proxy(&T::IID, Proxy::<T>::create_raw);
+
+        let table = global_interface_table()?;
+        let mut cookie = 0;
+        let hr = unsafe {
+            table.register_interface_in_global(object.as_raw() as *mut _, &T::IID, &mut cookie)
+        };
+        if FAILED(hr) {
+            return Err(hr);
+        }
+        Ok(AgileRef {
+            cookie,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T: ComInterface + ?Sized> AgileRef<T> {
+    /// Get the object for use in the apartment of the current thread
+    ///
+    /// This is the object itself in the apartment that created the `AgileRef`, and a
+    /// proxy to it in other apartments.
+    pub fn resolve(&self) -> Result<ComRc<T>, HRESULT> {
+        let table = global_interface_table()?;
+        let mut ppv = std::ptr::null_mut::<c_void>();
+        let hr = unsafe { table.get_interface_from_global(self.cookie, &T::IID, &mut ppv) };
+        if FAILED(hr) {
+            return Err(hr);
+        }
+        Ok(unsafe { ComRc::from_raw(ppv as *mut *mut _) })
+    }
+}
+
+impl<T: ComInterface + ?Sized> Drop for AgileRef<T> {
+    fn drop(&mut self) {
+        // Without an apartment on this thread the registration is leaked
+        if let Ok(table) = global_interface_table() {
+            unsafe {
+                table.revoke_interface_from_global(self.cookie);
+            }
+        }
+    }
+}
+
+fn global_interface_table() -> Result<ComRc<dyn IGlobalInterfaceTable>, HRESULT> {
+    create_instance::<dyn IGlobalInterfaceTable>(&CLSID_STD_GLOBAL_INTERFACE_TABLE)
+}
+
+/// An interface that can be called through a [`Proxy`](struct.Proxy.html)
+///
+/// This is implemented for every interface defined with `#[com_interface]`.
+#[cfg(not(windows))]
+pub trait ProxyInterface: ComInterface + crate::ProductionComInterface<Proxy<Self>> {}
+
+#[cfg(not(windows))]
+impl<T: ComInterface + crate::ProductionComInterface<Proxy<T>> + ?Sized> ProxyInterface for T {}
+
+/// An interface that can be registered in the global interface table
+///
+/// Windows marshals the interface itself, so this is implemented for every interface.
+#[cfg(windows)]
+pub trait ProxyInterface: ComInterface {}
+
+#[cfg(windows)]
+impl<T: ComInterface + ?Sized> ProxyInterface for T {}
+
+/// The `IMarshal` implementation of co_classes with the `agile` option
+///
+/// Interface pointers are only marshaled within the process, where the marshaled data is
+/// the pointer itself. Other destination contexts fail with `E_FAIL`.
+#[doc(hidden)]
+pub struct FreeThreadedMarshaler;
+
+impl FreeThreadedMarshaler {
+    /// The marshal flags followed by the interface pointer
+    const DATA_SIZE: u32 = 12;
+
+    fn is_in_process(dest_context: u32) -> bool {
+        dest_context == MSHCTX_INPROC || dest_context == MSHCTX_CROSSCTX
+    }
+
+    unsafe fn stream(stream: *mut IStreamVPtr) -> ComStream {
+        ComStream::new(
+            ComPtr::<dyn IStream>::new(stream as *mut _)
+                .clone()
+                .upgrade(),
+        )
+    }
+
+    /// Read back the marshal flags and interface pointer written by `marshal_interface`
+    unsafe fn read(stream: *mut IStreamVPtr) -> Result<(u32, *mut c_void), HRESULT> {
+        if stream.is_null() {
+            return Err(E_POINTER);
+        }
+        let mut data = [0u8; Self::DATA_SIZE as usize];
+        Self::stream(stream)
+            .read_exact(&mut data)
+            .map_err(|_| STG_E_READFAULT)?;
+        let mut flags = [0u8; 4];
+        let mut pointer = [0u8; 8];
+        flags.copy_from_slice(&data[..4]);
+        pointer.copy_from_slice(&data[4..]);
+        Ok((
+            u32::from_le_bytes(flags),
+            u64::from_le_bytes(pointer) as usize as *mut c_void,
+        ))
+    }
+
+    /// Implementation of `IMarshal::GetUnmarshalClass`
+    pub unsafe fn get_unmarshal_class(dest_context: u32, pclsid: *mut CLSID) -> HRESULT {
+        if pclsid.is_null() {
+            return E_POINTER;
+        }
+        if !Self::is_in_process(dest_context) {
+            return E_FAIL;
+        }
+        *pclsid = CLSID_IN_PROC_FREE_MARSHALER;
+        S_OK
+    }
+
+    /// Implementation of `IMarshal::GetMarshalSizeMax`
+    pub unsafe fn get_marshal_size_max(dest_context: u32, psize: *mut u32) -> HRESULT {
+        if psize.is_null() {
+            return E_POINTER;
+        }
+        if !Self::is_in_process(dest_context) {
+            return E_FAIL;
+        }
+        *psize = Self::DATA_SIZE;
+        S_OK
+    }
+
+    /// Implementation of `IMarshal::MarshalInterface`
+    ///
+    /// Unless the data is marshaled with `MSHLFLAGS_TABLEWEAK` it holds a reference to
+    /// `object`, which is released when the data is unmarshaled with `MSHLFLAGS_NORMAL`
+    /// or by `release_marshal_data`.
+    pub unsafe fn marshal_interface<T: IUnknown + ?Sized>(
+        object: &T,
+        stream: *mut IStreamVPtr,
+        riid: *const IID,
+        dest_context: u32,
+        mshlflags: u32,
+    ) -> HRESULT {
+        if stream.is_null() || riid.is_null() {
+            return E_POINTER;
+        }
+        if !Self::is_in_process(dest_context) {
+            return E_FAIL;
+        }
+        let mut pointer = std::ptr::null_mut::<c_void>();
+        let hr = object.query_interface(riid, &mut pointer);
+        if FAILED(hr) {
+            return hr;
+        }
+
+        let mut data = [0u8; Self::DATA_SIZE as usize];
+        data[..4].copy_from_slice(&mshlflags.to_le_bytes());
+        data[4..].copy_from_slice(&(pointer as usize as u64).to_le_bytes());
+        let written = Self::stream(stream).write_all(&data);
+        if mshlflags == MSHLFLAGS_TABLEWEAK || written.is_err() {
+            ComPtr::<dyn IUnknown>::new(pointer as *mut _).release();
+        }
+        match written {
+            Ok(()) => S_OK,
+            Err(_) => STG_E_WRITEFAULT,
+        }
+    }
+
+    /// Implementation of `IMarshal::UnmarshalInterface`
+    pub unsafe fn unmarshal_interface(
+        stream: *mut IStreamVPtr,
+        riid: *const IID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT {
+        if riid.is_null() || ppv.is_null() {
+            return E_POINTER;
+        }
+        *ppv = std::ptr::null_mut();
+        let (mshlflags, pointer) = match Self::read(stream) {
+            Ok(data) => data,
+            Err(hr) => return hr,
+        };
+        let object = ComPtr::<dyn IUnknown>::new(pointer as *mut _);
+        let hr = object.query_interface(riid, ppv);
+        if mshlflags == MSHLFLAGS_NORMAL {
+            object.release();
+        }
+        hr
+    }
+
+    /// Implementation of `IMarshal::ReleaseMarshalData`
+    pub unsafe fn release_marshal_data(stream: *mut IStreamVPtr) -> HRESULT {
+        match Self::read(stream) {
+            Ok((MSHLFLAGS_TABLEWEAK, _)) => S_OK,
+            Ok((_, pointer)) => {
+                ComPtr::<dyn IUnknown>::new(pointer as *mut _).release();
+                S_OK
+            }
+            Err(hr) => hr,
+        }
+    }
+}
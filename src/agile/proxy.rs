@@ -0,0 +1,141 @@
+//! The proxies the portable runtime hands out for objects owned by an STA thread
+
+use crate::runtime::StaHandle;
+use crate::sys::{HRESULT, RPC_E_DISCONNECTED};
+use crate::{ComInterface, ComRc, FreeThreaded, ProductionComInterface};
+
+use std::ffi::c_void;
+use std::mem::ManuallyDrop;
+use std::sync::atomic::AtomicU32;
+use std::sync::mpsc;
+
+/// Moves a value to another thread whose use of it is synchronized by the caller
+#[doc(hidden)]
+pub struct AssertSend<T>(T);
+
+unsafe impl<T> Send for AssertSend<T> {}
+
+impl<T> AssertSend<T> {
+    /// # Safety
+    ///
+    /// `value` must be safe to use from the thread it is moved to, e.g. an interface
+    /// pointer that only the owning thread dereferences.
+    pub unsafe fn new(value: T) -> Self {
+        AssertSend(value)
+    }
+
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+/// A call posted to the owning thread of a proxy
+///
+/// The fields are dropped in order, so the sender is dropped last and the caller waiting
+/// for the result can't return while the other thread still holds `f`.
+struct ProxyCall<T: ComInterface + ?Sized, F, R> {
+    f: F,
+    target: AssertSend<*mut *mut <T as ComInterface>::VTable>,
+    sender: mpsc::Sender<R>,
+}
+
+/// A proxy running every call to an object on the STA thread that owns it
+///
+/// `#[com_interface]` implements each interface for `Proxy<T>` by forwarding the
+/// call with [`Proxy::call`](#method.call). Calls block until the owning thread has
+/// handled them, and `QueryInterface` only answers the interfaces `T` inherits from.
+///
+/// Once the owning thread is gone, methods returning `HRESULT` return
+/// `RPC_E_DISCONNECTED`. Other methods have no way to report it and abort the process.
+///
+/// Only the portable runtime uses proxies, as it drops the queued calls of exited
+/// threads. On Windows the system marshals interfaces between apartments.
+#[repr(C)]
+pub struct Proxy<T: ComInterface + ?Sized> {
+    vptr: *const <T as ComInterface>::VTable,
+    ref_count: AtomicU32,
+    target: *mut *mut <T as ComInterface>::VTable,
+    owner: StaHandle,
+}
+
+impl<T: ComInterface + ?Sized> Proxy<T> {
+    /// Wrap the interface pointer `target` owned by the thread `owner`
+    ///
+    /// Takes over the reference held by `target` and returns a pointer to the proxy
+    /// with a reference count of one.
+    pub(crate) unsafe fn create_raw(target: *mut c_void, owner: StaHandle) -> *mut c_void
+    where
+        T: ProductionComInterface<Self>,
+    {
+        use crate::offset::Zero;
+
+        let vptr = Box::into_raw(Box::new(T::vtable::<Zero>()));
+        let proxy = Box::into_raw(Box::new(Proxy::<T> {
+            vptr,
+            ref_count: AtomicU32::new(1),
+            target: target as *mut *mut _,
+            owner,
+        }));
+        proxy as *mut c_void
+    }
+
+    /// Run `f` with the proxied object on the thread that owns it and wait for the result
+    ///
+    /// Fails with `RPC_E_DISCONNECTED` if the owning thread no longer pumps messages.
+    ///
+    /// # Safety
+    ///
+    /// `f` runs on another thread while this call is blocked and may borrow from the
+    /// caller. It must not use anything that is only valid on the calling thread, and
+    /// the object must be used as `T` on the owning thread only through `f`.
+    #[doc(hidden)]
+    pub unsafe fn call<F, R>(&self, f: F) -> Result<R, HRESULT>
+    where
+        F: FnOnce(&ComRc<T>) -> R + Send,
+        R: Send,
+    {
+        if self.owner.is_current() {
+            let target = ManuallyDrop::new(ComRc::from_raw(self.target));
+            return Ok(f(&target));
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let call = ProxyCall::<T, F, R> {
+            f,
+            target: AssertSend(self.target),
+            sender,
+        };
+        let task: Box<dyn FnOnce() + Send + '_> = Box::new(move || {
+            let ProxyCall { f, target, sender } = call;
+            let target = ManuallyDrop::new(ComRc::from_raw(target.into_inner()));
+            let result = f(&target);
+            let _ = sender.send(result);
+        });
+        // The task either runs or is dropped before `recv` returns, since the sender is
+        // dropped last and the queue of the owning thread drops the tasks it never ran.
+        // It thus never outlives the borrows of this call.
+        let task: Box<dyn FnOnce() + Send + 'static> = std::mem::transmute(task);
+        self.owner.post(task)?;
+        receiver.recv().map_err(|_| RPC_E_DISCONNECTED)
+    }
+}
+
+impl<T: ComInterface + ?Sized> Drop for Proxy<T> {
+    fn drop(&mut self) {
+        let target = AssertSend(self.target);
+        let release = move || unsafe {
+            drop(ComRc::<T>::from_raw(target.into_inner()));
+        };
+        if self.owner.is_current() {
+            release();
+        } else {
+            // If the owning thread is gone the reference is leaked
+            let _ = self.owner.post(release);
+        }
+    }
+}
+
+impl_iunknown!([T: ComInterface + ?Sized] Proxy<T> => T);
+
+// Calls are run on the owning thread, and the reference count is atomic
+unsafe impl<T: ComInterface + ?Sized> FreeThreaded for Proxy<T> {}
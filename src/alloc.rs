@@ -0,0 +1,339 @@
+//! The COM task allocator
+//!
+//! Memory passed between a client and a server through out parameters, such as strings
+//! and arrays, is allocated by the callee with `CoTaskMemAlloc` and freed by the caller
+//! with `CoTaskMemFree`. [`CoTaskMemBox`] and [`CoTaskMemString`] own such memory and
+//! free it when dropped, or hand it over with `into_raw`:
+//!
+//! ```rust,ignore
+//! unsafe fn get_name(&self, name: *mut *mut u16) -> HRESULT {
+//!     *name = CoTaskMemString::new(&self.name).into_raw();
+//!     S_OK
+//! }
+//! ```
+//!
+//! With the portable runtime a [`LeakCheck`] finds task memory that was never freed.
+//!
+//! [`CoTaskMemBox`]: struct.CoTaskMemBox.html
+//! [`CoTaskMemString`]: struct.CoTaskMemString.html
+//! [`LeakCheck`]: struct.LeakCheck.html
+
+use crate::interfaces::imalloc::IMalloc;
+use crate::sys::{
+    CoGetMalloc, CoTaskMemAlloc, CoTaskMemFree, CoTaskMemRealloc, FAILED, HRESULT, MEMCTX_TASK,
+};
+use crate::ComRc;
+
+use std::alloc::{handle_alloc_error, Layout};
+use std::ffi::c_void;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::ptr::NonNull;
+
+#[cfg(not(windows))]
+#[doc(inline)]
+pub use crate::sys::{Leak, LeakCheck};
+
+/// The alignment of task memory on every platform
+const TASK_ALIGN: usize = 8;
+
+/// Allocate `size` bytes of task memory, returning null if out of memory
+///
+/// Calls `CoTaskMemAlloc` internally
+pub fn task_alloc(size: usize) -> *mut c_void {
+    unsafe { CoTaskMemAlloc(size) }
+}
+
+/// Change the size of task memory, returning null and leaving it untouched if out of
+/// memory
+///
+/// Calls `CoTaskMemRealloc` internally
+pub unsafe fn task_realloc(ptr: *mut c_void, size: usize) -> *mut c_void {
+    CoTaskMemRealloc(ptr, size)
+}
+
+/// Free task memory, doing nothing for null
+///
+/// Calls `CoTaskMemFree` internally
+pub unsafe fn task_free(ptr: *mut c_void) {
+    CoTaskMemFree(ptr)
+}
+
+/// Get the task allocator
+///
+/// Calls `CoGetMalloc` internally
+pub fn get_malloc() -> Result<ComRc<dyn IMalloc>, HRESULT> {
+    let mut malloc = std::ptr::null_mut::<c_void>();
+    let hr = unsafe { CoGetMalloc(MEMCTX_TASK, &mut malloc) };
+    if FAILED(hr) {
+        return Err(hr);
+    }
+    Ok(unsafe { ComRc::from_raw(malloc as *mut *mut _) })
+}
+
+/// Allocate task memory for `layout`, aborting if out of memory like `Box`
+fn alloc_layout(layout: Layout) -> *mut c_void {
+    assert!(
+        layout.align() <= TASK_ALIGN,
+        "task memory is only aligned to {} bytes",
+        TASK_ALIGN
+    );
+    // Allocate at least one byte so that the pointer is unique
+    let ptr = task_alloc(layout.size().max(1));
+    if ptr.is_null() {
+        handle_alloc_error(layout);
+    }
+    ptr
+}
+
+/// A value in task memory, freed with `CoTaskMemFree` when dropped
+pub struct CoTaskMemBox<T> {
+    ptr: NonNull<T>,
+}
+
+impl<T> CoTaskMemBox<T> {
+    /// Move `value` to task memory
+    ///
+    /// Panics if `T` needs an alignment above 8 bytes.
+    pub fn new(value: T) -> Self {
+        let ptr = alloc_layout(Layout::new::<T>()) as *mut T;
+        unsafe {
+            ptr.write(value);
+            CoTaskMemBox {
+                ptr: NonNull::new_unchecked(ptr),
+            }
+        }
+    }
+
+    /// Take ownership of a value in task memory, e.g. returned through an out parameter
+    ///
+    /// Panics if `ptr` is null.
+    pub unsafe fn from_raw(ptr: *mut T) -> Self {
+        CoTaskMemBox {
+            ptr: NonNull::new(ptr).expect("null task memory"),
+        }
+    }
+
+    /// Give up ownership of the value, e.g. to return it through an out parameter
+    pub fn into_raw(self) -> *mut T {
+        let ptr = self.ptr.as_ptr();
+        std::mem::forget(self);
+        ptr
+    }
+
+    /// A pointer to the value
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr.as_ptr()
+    }
+}
+
+impl<T> Deref for CoTaskMemBox<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T> DerefMut for CoTaskMemBox<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T> Drop for CoTaskMemBox<T> {
+    fn drop(&mut self) {
+        unsafe {
+            std::ptr::drop_in_place(self.ptr.as_ptr());
+            task_free(self.ptr.as_ptr() as *mut c_void);
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CoTaskMemBox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+unsafe impl<T: Send> Send for CoTaskMemBox<T> {}
+unsafe impl<T: Sync> Sync for CoTaskMemBox<T> {}
+
+/// A null terminated UTF-16 string in task memory, freed with `CoTaskMemFree` when
+/// dropped
+pub struct CoTaskMemString {
+    ptr: NonNull<u16>,
+    /// The length in UTF-16 code units, without the null terminator
+    len: usize,
+}
+
+impl CoTaskMemString {
+    /// Copy `value` to task memory as UTF-16
+    pub fn new(value: &str) -> Self {
+        let chars = value.encode_utf16().collect::<Vec<_>>();
+        Self::from_wide(&chars)
+    }
+
+    /// Copy UTF-16 code units without a null terminator to task memory
+    pub fn from_wide(chars: &[u16]) -> Self {
+        let size = chars
+            .len()
+            .checked_add(1)
+            .and_then(|len| len.checked_mul(2))
+            .expect("string is too long");
+        let layout = Layout::from_size_align(size, 2).expect("string is too long");
+        let ptr = alloc_layout(layout) as *mut u16;
+        unsafe {
+            std::ptr::copy_nonoverlapping(chars.as_ptr(), ptr, chars.len());
+            *ptr.add(chars.len()) = 0;
+            CoTaskMemString {
+                ptr: NonNull::new_unchecked(ptr),
+                len: chars.len(),
+            }
+        }
+    }
+
+    /// Take ownership of a null terminated string in task memory, e.g. returned through
+    /// an out parameter
+    ///
+    /// Returns `None` for null.
+    pub unsafe fn from_raw(ptr: *mut u16) -> Option<Self> {
+        let ptr = NonNull::new(ptr)?;
+        let mut len = 0;
+        while *ptr.as_ptr().add(len) != 0 {
+            len += 1;
+        }
+        Some(CoTaskMemString { ptr, len })
+    }
+
+    /// Give up ownership of the string, e.g. to return it through an out parameter
+    pub fn into_raw(self) -> *mut u16 {
+        let ptr = self.ptr.as_ptr();
+        std::mem::forget(self);
+        ptr
+    }
+
+    /// A pointer to the null terminated string
+    pub fn as_ptr(&self) -> *const u16 {
+        self.ptr.as_ptr()
+    }
+
+    /// The UTF-16 code units without the null terminator
+    pub fn as_wide(&self) -> &[u16] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// The length in UTF-16 code units
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the string is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Convert to a `String`, replacing invalid UTF-16 with the replacement character
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(self.as_wide())
+    }
+}
+
+impl<'a> From<&'a str> for CoTaskMemString {
+    fn from(value: &'a str) -> Self {
+        CoTaskMemString::new(value)
+    }
+}
+
+impl From<CoTaskMemString> for String {
+    fn from(value: CoTaskMemString) -> Self {
+        value.to_string_lossy()
+    }
+}
+
+impl Clone for CoTaskMemString {
+    fn clone(&self) -> Self {
+        CoTaskMemString::from_wide(self.as_wide())
+    }
+}
+
+impl PartialEq for CoTaskMemString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_wide() == other.as_wide()
+    }
+}
+
+impl fmt::Display for CoTaskMemString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.to_string_lossy(), f)
+    }
+}
+
+impl fmt::Debug for CoTaskMemString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string_lossy(), f)
+    }
+}
+
+impl Drop for CoTaskMemString {
+    fn drop(&mut self) {
+        unsafe { task_free(self.ptr.as_ptr() as *mut c_void) }
+    }
+}
+
+unsafe impl Send for CoTaskMemString {}
+unsafe impl Sync for CoTaskMemString {}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boxes_are_freed_unless_returned() {
+        let check = LeakCheck::start();
+        let value = CoTaskMemBox::new([1u64, 2, 3]);
+        assert_eq!(value[1], 2);
+        drop(value);
+        check.assert_no_leaks();
+
+        let raw = CoTaskMemBox::new(7u32).into_raw();
+        assert_eq!(
+            check.leaks(),
+            vec![Leak {
+                address: raw as usize,
+                size: 4
+            }]
+        );
+        assert_eq!(*unsafe { CoTaskMemBox::from_raw(raw) }, 7);
+        check.assert_no_leaks();
+    }
+
+    #[test]
+    fn strings_round_trip_through_raw_pointers() {
+        let check = LeakCheck::start();
+        let raw = CoTaskMemString::new("héllo").into_raw();
+        let string = unsafe { CoTaskMemString::from_raw(raw) }.unwrap();
+        assert_eq!(string.len(), 5);
+        assert_eq!(unsafe { *string.as_ptr().add(5) }, 0);
+        assert_eq!(String::from(string.clone()), "héllo");
+        drop(string);
+        assert!(unsafe { CoTaskMemString::from_raw(std::ptr::null_mut()) }.is_none());
+        check.assert_no_leaks();
+    }
+
+    #[test]
+    fn malloc_shares_the_task_allocator() {
+        let check = LeakCheck::start();
+        let malloc = get_malloc().unwrap();
+        unsafe {
+            let ptr = malloc.alloc(10);
+            assert_eq!(malloc.get_size(ptr), 10);
+            assert_eq!(malloc.did_alloc(ptr), 1);
+            let ptr = task_realloc(ptr, 20);
+            assert_eq!(malloc.get_size(ptr), 20);
+            assert_eq!(check.leaks().len(), 1);
+            task_free(ptr);
+        }
+        check.assert_no_leaks();
+    }
+}
@@ -0,0 +1,374 @@
+//! Rich error information attached to failed COM calls
+//!
+//! A server describes a failure with [`runtime::set_error_info`] before returning a failing
+//! `HRESULT`. A co_class with the `support_error_info` option implements `ISupportErrorInfo`
+//! so that clients know to look for it:
+//!
+//! ```rust,ignore
+//! #[co_class(implements(ICat, ISupportErrorInfo), support_error_info)]
+//! pub struct BritishShortHairCat {}
+//!
+//! impl ICat for BritishShortHairCat {
+//!     unsafe fn ignore_humans(&self) -> HRESULT {
+//!         ErrorInfo::new("Humans can not be ignored").with_source("Cat").into_hresult(E_FAIL)
+//!     }
+//! }
+//! ```
+//!
+//! Each method returning a `HRESULT` gets a `try_` wrapper returning a [`ComError`] carrying
+//! the description on failure:
+//!
+//! ```rust,ignore
+//! let err = unsafe { cat.try_ignore_humans() }.unwrap_err();
+//! assert_eq!(err.description(), Some("Humans can not be ignored"));
+//! ```
+//!
+//! Any other call can be wrapped with [`ComRc::call`] to the same effect.
+//!
+//! [`runtime::set_error_info`]: ../runtime/fn.set_error_info.html
+//! [`ComRc::call`]: ../struct.ComRc.html#method.call
+//! [`ComError`]: struct.ComError.html
+
+use crate::interfaces::icreate_error_info::ICreateErrorInfo;
+use crate::interfaces::ierror_info::IErrorInfo;
+use crate::interfaces::isupport_error_info::ISupportErrorInfo;
+use crate::interfaces::iunknown::{IUnknown, IID_IUNKNOWN};
+use crate::sys::{
+    SysAllocStringLen, SysFreeString, SysStringLen, BSTR, E_NOINTERFACE, E_OUTOFMEMORY, E_POINTER,
+    FAILED, GUID, GUID_NULL, HRESULT, IID, NOERROR, S_OK,
+};
+use crate::{ComInterface, ComRc};
+
+use std::cell::{Cell, RefCell};
+use std::ffi::c_void;
+
+/// The error information set by a server with `SetErrorInfo`
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorInfo {
+    /// The IID of the interface that defined the error
+    pub guid: IID,
+    /// The programmatic ID of the class or application that raised the error
+    pub source: String,
+    /// A description of the error
+    pub description: String,
+    /// The path of the help file describing the error
+    pub help_file: String,
+    /// The help context ID of the error in the help file
+    pub help_context: u32,
+}
+
+impl ErrorInfo {
+    /// Create error information with a description and all other fields empty
+    pub fn new<S: Into<String>>(description: S) -> Self {
+        ErrorInfo {
+            guid: GUID_NULL,
+            source: String::new(),
+            description: description.into(),
+            help_file: String::new(),
+            help_context: 0,
+        }
+    }
+
+    /// Set the source of the error
+    pub fn with_source<S: Into<String>>(mut self, source: S) -> Self {
+        self.source = source.into();
+        self
+    }
+
+    /// Set the IID of the interface that defined the error
+    pub fn with_guid(mut self, guid: IID) -> Self {
+        self.guid = guid;
+        self
+    }
+
+    /// Set this as the error information of the current thread and return `hr`
+    ///
+    /// This is a shorthand for servers failing a call. `hr` is returned even if the error
+    /// information could not be set, as it is the result callers must see.
+    pub fn into_hresult(self, hr: HRESULT) -> HRESULT {
+        let _ = crate::runtime::set_error_info(self);
+        hr
+    }
+
+    /// Read the fields of an `IErrorInfo` object
+    pub(crate) fn from_interface(info: &ComRc<dyn IErrorInfo>) -> Self {
+        let mut guid = GUID_NULL;
+        let mut help_context = 0;
+        unsafe {
+            info.get_guid(&mut guid);
+            info.get_help_context(&mut help_context);
+            ErrorInfo {
+                guid,
+                source: read_bstr(|bstr| info.get_source(bstr)),
+                description: read_bstr(|bstr| info.get_description(bstr)),
+                help_file: read_bstr(|bstr| info.get_help_file(bstr)),
+                help_context,
+            }
+        }
+    }
+
+    /// Create an `IErrorInfo` object holding these fields
+    pub(crate) fn into_interface(self) -> ComRc<dyn IErrorInfo> {
+        ErrorInfoObject::create(self)
+    }
+}
+
+/// Call a getter returning a `BSTR`, converting and freeing the result
+unsafe fn read_bstr(get: impl FnOnce(*mut BSTR) -> HRESULT) -> String {
+    let mut bstr = std::ptr::null_mut();
+    if FAILED(get(&mut bstr)) || bstr.is_null() {
+        return String::new();
+    }
+    let chars = std::slice::from_raw_parts(bstr, SysStringLen(bstr) as usize);
+    let value = String::from_utf16_lossy(chars);
+    SysFreeString(bstr);
+    value
+}
+
+/// Write `value` to `out` as a newly allocated `BSTR`
+pub(crate) unsafe fn write_bstr(value: &str, out: *mut BSTR) -> HRESULT {
+    if out.is_null() {
+        return E_POINTER;
+    }
+    let chars: Vec<u16> = value.encode_utf16().collect();
+    *out = SysAllocStringLen(chars.as_ptr(), chars.len() as u32);
+    if (*out).is_null() {
+        E_OUTOFMEMORY
+    } else {
+        S_OK
+    }
+}
+
+/// The error returned by a failed COM call
+///
+/// Holds the `HRESULT` of the call and, if the object supports it, the
+/// [`ErrorInfo`] the server set for it.
+///
+/// [`ErrorInfo`]: struct.ErrorInfo.html
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComError {
+    hresult: HRESULT,
+    info: Option<ErrorInfo>,
+}
+
+impl ComError {
+    /// An error with only a `HRESULT`
+    pub fn new(hresult: HRESULT) -> Self {
+        ComError {
+            hresult,
+            info: None,
+        }
+    }
+
+    /// An error for a call through the interface `iid` of `object` that failed with `hresult`
+    ///
+    /// The error information of the current thread is only taken and attached if `object`
+    /// reports through `ISupportErrorInfo` that it supports it for `iid`. Otherwise it is
+    /// left untouched, as it was not set for this call.
+    pub fn from_call(object: &dyn IUnknown, iid: &IID, hresult: HRESULT) -> Self {
+        let support_iid = <dyn ISupportErrorInfo as ComInterface>::IID;
+        let mut support = std::ptr::null_mut::<c_void>();
+        let supported = unsafe {
+            if FAILED(object.query_interface(&support_iid, &mut support)) || support.is_null() {
+                false
+            } else {
+                let support = ComRc::<dyn ISupportErrorInfo>::from_raw(support as *mut *mut _);
+                support.interface_supports_error_info(iid) == S_OK
+            }
+        };
+        ComError {
+            hresult,
+            info: if supported {
+                crate::runtime::take_error_info()
+            } else {
+                None
+            },
+        }
+    }
+
+    /// The `HRESULT` of the failed call
+    pub fn hresult(&self) -> HRESULT {
+        self.hresult
+    }
+
+    /// The error information set by the server
+    pub fn info(&self) -> Option<&ErrorInfo> {
+        self.info.as_ref()
+    }
+
+    /// The description of the error set by the server
+    pub fn description(&self) -> Option<&str> {
+        self.info.as_ref().map(|info| info.description.as_str())
+    }
+
+    /// The source of the error set by the server
+    pub fn source(&self) -> Option<&str> {
+        self.info.as_ref().map(|info| info.source.as_str())
+    }
+}
+
+impl From<HRESULT> for ComError {
+    fn from(hresult: HRESULT) -> Self {
+        ComError::new(hresult)
+    }
+}
+
+impl From<ComError> for HRESULT {
+    fn from(error: ComError) -> Self {
+        error.hresult
+    }
+}
+
+impl std::fmt::Display for ComError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HRESULT 0x{:08X}", self.hresult)?;
+        if let Some(info) = &self.info {
+            if !info.source.is_empty() {
+                write!(f, " from {}", info.source)?;
+            }
+            if !info.description.is_empty() {
+                write!(f, ": {}", info.description)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ComError {}
+
+/// An `IErrorInfo` object holding an [`ErrorInfo`](struct.ErrorInfo.html), which can be
+/// filled in through its `ICreateErrorInfo` interface
+#[repr(C)]
+struct ErrorInfoObject {
+    vptr: *const <dyn IErrorInfo as ComInterface>::VTable,
+    create_vptr: *const <dyn ICreateErrorInfo as ComInterface>::VTable,
+    ref_count: Cell<u32>,
+    info: RefCell<ErrorInfo>,
+}
+
+impl ErrorInfoObject {
+    fn create(info: ErrorInfo) -> ComRc<dyn IErrorInfo> {
+        use crate::offset::{One, Zero};
+        use crate::ProductionComInterface;
+
+        let vptr = Box::into_raw(Box::new(
+            <dyn IErrorInfo as ProductionComInterface<Self>>::vtable::<Zero>(),
+        ));
+        let create_vtable = <dyn ICreateErrorInfo as ProductionComInterface<Self>>::vtable::<One>();
+        let create_vptr = Box::into_raw(Box::new(create_vtable));
+        let object = Box::into_raw(Box::new(ErrorInfoObject {
+            vptr,
+            create_vptr,
+            ref_count: Cell::new(1),
+            info: RefCell::new(info),
+        }));
+        unsafe { ComRc::from_raw(object as *mut *mut _) }
+    }
+}
+
+unsafe impl crate::CoClass for ErrorInfoObject {}
+
+impl IUnknown for ErrorInfoObject {
+    unsafe fn query_interface(&self, riid: *const IID, ppv: *mut *mut c_void) -> HRESULT {
+        let riid = &*riid;
+        *ppv = if riid == &IID_IUNKNOWN || <dyn IErrorInfo as ComInterface>::IID == *riid {
+            &self.vptr as *const _ as *mut c_void
+        } else if <dyn ICreateErrorInfo as ComInterface>::IID == *riid {
+            &self.create_vptr as *const _ as *mut c_void
+        } else {
+            std::ptr::null_mut()
+        };
+        if (*ppv).is_null() {
+            return E_NOINTERFACE;
+        }
+        self.add_ref();
+        NOERROR
+    }
+
+    unsafe fn add_ref(&self) -> u32 {
+        crate::refcount::RefCount::increment(&self.ref_count)
+    }
+
+    unsafe fn release(&self) -> u32 {
+        let value = crate::refcount::RefCount::decrement(&self.ref_count);
+        if value == 0 {
+            drop(Box::from_raw(
+                self.vptr as *mut <dyn IErrorInfo as ComInterface>::VTable,
+            ));
+            drop(Box::from_raw(
+                self.create_vptr as *mut <dyn ICreateErrorInfo as ComInterface>::VTable,
+            ));
+            drop(Box::from_raw(self as *const _ as *mut Self));
+        }
+        value
+    }
+}
+
+impl IErrorInfo for ErrorInfoObject {
+    unsafe fn get_guid(&self, guid: *mut GUID) -> HRESULT {
+        if guid.is_null() {
+            return E_POINTER;
+        }
+        *guid = self.info.borrow().guid;
+        S_OK
+    }
+
+    unsafe fn get_source(&self, source: *mut BSTR) -> HRESULT {
+        write_bstr(&self.info.borrow().source, source)
+    }
+
+    unsafe fn get_description(&self, description: *mut BSTR) -> HRESULT {
+        write_bstr(&self.info.borrow().description, description)
+    }
+
+    unsafe fn get_help_file(&self, help_file: *mut BSTR) -> HRESULT {
+        write_bstr(&self.info.borrow().help_file, help_file)
+    }
+
+    unsafe fn get_help_context(&self, help_context: *mut u32) -> HRESULT {
+        if help_context.is_null() {
+            return E_POINTER;
+        }
+        *help_context = self.info.borrow().help_context;
+        S_OK
+    }
+}
+
+/// Read a null terminated wide string, treating a null pointer as empty
+unsafe fn read_wide(chars: *const u16) -> String {
+    if chars.is_null() {
+        return String::new();
+    }
+    let len = (0..).take_while(|&i| *chars.add(i) != 0).count();
+    String::from_utf16_lossy(std::slice::from_raw_parts(chars, len))
+}
+
+impl ICreateErrorInfo for ErrorInfoObject {
+    unsafe fn set_guid(&self, guid: *const GUID) -> HRESULT {
+        if guid.is_null() {
+            return E_POINTER;
+        }
+        self.info.borrow_mut().guid = *guid;
+        S_OK
+    }
+
+    unsafe fn set_source(&self, source: *mut u16) -> HRESULT {
+        self.info.borrow_mut().source = read_wide(source);
+        S_OK
+    }
+
+    unsafe fn set_description(&self, description: *mut u16) -> HRESULT {
+        self.info.borrow_mut().description = read_wide(description);
+        S_OK
+    }
+
+    unsafe fn set_help_file(&self, help_file: *mut u16) -> HRESULT {
+        self.info.borrow_mut().help_file = read_wide(help_file);
+        S_OK
+    }
+
+    unsafe fn set_help_context(&self, help_context: u32) -> HRESULT {
+        self.info.borrow_mut().help_context = help_context;
+        S_OK
+    }
+}
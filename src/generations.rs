@@ -0,0 +1,120 @@
+//! Picking the newest generation of an interface an object supports
+//!
+//! An interface can evolve into generations deriving from the previous one, e.g.
+//! `IFoo3: IFoo2` and `IFoo2: IFoo`. [`ComRc::best_of`] queries an object for them from
+//! the newest and returns the first one it supports as a variant of a [`OneOf3`]:
+//!
+//! ```rust,ignore
+//! match unknown.best_of::<OneOf3<dyn IFoo3, dyn IFoo2, dyn IFoo>>() {
+//!     Some(OneOf3::First(foo3)) => unsafe { foo3.baz() },
+//!     Some(OneOf3::Second(foo2)) => unsafe { foo2.bar() },
+//!     Some(OneOf3::Third(foo)) => unsafe { foo.foo() },
+//!     None => E_NOINTERFACE,
+//! }
+//! ```
+//!
+//! Each interface must directly inherit from the one listed after it.
+//!
+//! [`ComRc::best_of`]: ../struct.ComRc.html#method.best_of
+//! [`OneOf3`]: enum.OneOf3.html
+
+use crate::{ComInterface, ComRc};
+
+/// An enum of the generations of an interface, from the newest
+pub trait Generations: Sized {
+    /// Query `rc` for the interfaces in order until one is supported
+    #[doc(hidden)]
+    fn query<T: ComInterface + ?Sized>(rc: &ComRc<T>) -> Option<Self>;
+}
+
+macro_rules! one_of {
+    (
+        $(#[$attr:meta])*
+        $name:ident {
+            $($variant:ident($interface:ident: $super:ident)),+;
+            $last_variant:ident($last:ident)
+        }
+    ) => {
+        $(#[$attr])*
+        pub enum $name<$($interface,)+ $last>
+        where
+            $($interface: ComInterface + ?Sized,)+
+            $last: ComInterface + ?Sized,
+        {
+            $(
+                #[allow(missing_docs)]
+                $variant(ComRc<$interface>),
+            )+
+            #[allow(missing_docs)]
+            $last_variant(ComRc<$last>),
+        }
+
+        impl<$($interface,)+ $last> Generations for $name<$($interface,)+ $last>
+        where
+            $($interface: ComInterface<Super = $super> + ?Sized,)+
+            $last: ComInterface + ?Sized,
+        {
+            fn query<T: ComInterface + ?Sized>(rc: &ComRc<T>) -> Option<Self> {
+                $(
+                    if let Some(interface) = rc.get_interface::<$interface>() {
+                        return Some($name::$variant(interface));
+                    }
+                )+
+                rc.get_interface::<$last>().map($name::$last_variant)
+            }
+        }
+    };
+}
+
+one_of!(
+    /// One of two generations of an interface, from the newest
+    OneOf2 { First(A: B); Second(B) }
+);
+one_of!(
+    /// One of three generations of an interface, from the newest
+    OneOf3 { First(A: B), Second(B: C); Third(C) }
+);
+one_of!(
+    /// One of four generations of an interface, from the newest
+    OneOf4 { First(A: B), Second(B: C), Third(C: D); Fourth(D) }
+);
+one_of!(
+    /// One of five generations of an interface, from the newest
+    OneOf5 { First(A: B), Second(B: C), Third(C: D), Fourth(D: E); Fifth(E) }
+);
+one_of!(
+    /// One of six generations of an interface, from the newest
+    OneOf6 {
+        First(A: B),
+        Second(B: C),
+        Third(C: D),
+        Fourth(D: E),
+        Fifth(E: F);
+        Sixth(F)
+    }
+);
+one_of!(
+    /// One of seven generations of an interface, from the newest
+    OneOf7 {
+        First(A: B),
+        Second(B: C),
+        Third(C: D),
+        Fourth(D: E),
+        Fifth(E: F),
+        Sixth(F: G);
+        Seventh(G)
+    }
+);
+one_of!(
+    /// One of eight generations of an interface, from the newest
+    OneOf8 {
+        First(A: B),
+        Second(B: C),
+        Third(C: D),
+        Fourth(D: E),
+        Fifth(E: F),
+        Sixth(F: G),
+        Seventh(G: H);
+        Eighth(H)
+    }
+);
@@ -0,0 +1,332 @@
+//! Support for COM events through connection points
+//!
+//! A co_class firing events implements [`IConnectionPointContainer`] and embeds a
+//! [`ConnectionPoint`] for each of its outgoing interfaces:
+//!
+//! ```rust,ignore
+//! #[co_class(implements(ICat, IConnectionPointContainer))]
+//! pub struct BritishShortHairCat {
+//!     events: ConnectionPoint<dyn ICatEvents>,
+//! }
+//!
+//! impl IConnectionPointContainer for BritishShortHairCat {
+//!     unsafe fn find_connection_point(&self, riid: *const IID, ppcp: *mut *mut IConnectionPointVPtr) -> HRESULT {
+//!         self.events.find(self, riid, ppcp)
+//!     }
+//!     // ...
+//! }
+//!
+//! // Somewhere in the implementation of ICat
+//! self.events.fire(|sink| unsafe { sink.on_meow(3); });
+//! ```
+//!
+//! Clients advise a sink through [`ComRc<dyn IConnectionPointContainer>::advise`] or
+//! [`ComRc<dyn IConnectionPointContainer>::advise_with`] and stay connected until the
+//! returned [`Connection`] is dropped.
+//!
+//! [`IConnectionPointContainer`]: ../iconnection_point_container/trait.IConnectionPointContainer.html
+//! [`ComRc<dyn IConnectionPointContainer>::advise`]: ../../struct.ComRc.html#method.advise
+//! [`ComRc<dyn IConnectionPointContainer>::advise_with`]: ../../struct.ComRc.html#method.advise_with
+
+use crate::interfaces::enumerator::Enumerator;
+use crate::interfaces::iconnection_point::{IConnectionPoint, IConnectionPointVPtr};
+use crate::interfaces::iconnection_point_container::{
+    IConnectionPointContainer, IConnectionPointContainerVPtr,
+};
+use crate::interfaces::ienum_connections::{ConnectData, IEnumConnections, IEnumConnectionsVPtr};
+use crate::interfaces::iunknown::{IUnknown, IUnknownVPtr};
+use crate::offset::Zero;
+use crate::sys::{
+    CONNECT_E_CANNOTCONNECT, CONNECT_E_NOCONNECTION, E_POINTER, FAILED, HRESULT, IID, S_OK,
+};
+use crate::{ComInterface, ComPtr, ComRc, ProductionComInterface};
+
+use std::cell::{Cell, RefCell};
+use std::ffi::c_void;
+use std::rc::Rc;
+
+struct Connections<I: ComInterface + ?Sized> {
+    next_cookie: Cell<u32>,
+    sinks: RefCell<Vec<(u32, ComRc<I>)>>,
+}
+
+/// The sinks connected to one outgoing interface `I` of a co_class
+///
+/// The co_class hands out the connection point through [`ConnectionPoint::find`] and
+/// calls the connected sinks with [`ConnectionPoint::fire`].
+pub struct ConnectionPoint<I: ComInterface + ?Sized> {
+    connections: Rc<Connections<I>>,
+}
+
+impl<I: ComInterface + ?Sized> ConnectionPoint<I> {
+    /// Create a connection point without any connected sinks
+    pub fn new() -> Self {
+        ConnectionPoint {
+            connections: Rc::new(Connections {
+                next_cookie: Cell::new(1),
+                sinks: RefCell::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Implementation of `IConnectionPointContainer::FindConnectionPoint`
+    ///
+    /// Returns `CONNECT_E_NOCONNECTION` if `riid` is not `I` so that a container with
+    /// several connection points can try each of them in turn.
+    pub unsafe fn find<C: IUnknown>(
+        &self,
+        container: &C,
+        riid: *const IID,
+        ppcp: *mut *mut IConnectionPointVPtr,
+    ) -> HRESULT {
+        if ppcp.is_null() {
+            return E_POINTER;
+        }
+        *ppcp = std::ptr::null_mut();
+        if riid.is_null() {
+            return E_POINTER;
+        }
+        if *riid != I::IID {
+            return CONNECT_E_NOCONNECTION;
+        }
+
+        let mut ppv = std::ptr::null_mut::<c_void>();
+        let hr = container.query_interface(
+            &<dyn IConnectionPointContainer as ComInterface>::IID as *const IID,
+            &mut ppv,
+        );
+        if FAILED(hr) {
+            return hr;
+        }
+        let container = ComRc::<dyn IConnectionPointContainer>::from_raw(ppv as *mut *mut _);
+
+        let vptr = Box::into_raw(Box::new(<dyn IConnectionPoint as ProductionComInterface<
+            ConnectionPointObject<I>,
+        >>::vtable::<Zero>()));
+        let object = Box::into_raw(Box::new(ConnectionPointObject {
+            vptr,
+            ref_count: Cell::new(1),
+            container,
+            connections: self.connections.clone(),
+        }));
+        *ppcp = object as *mut IConnectionPointVPtr;
+        S_OK
+    }
+
+    /// The currently connected sinks
+    pub fn sinks(&self) -> Vec<ComRc<I>> {
+        self.connections
+            .sinks
+            .borrow()
+            .iter()
+            .map(|(_, sink)| sink.clone())
+            .collect()
+    }
+
+    /// Call `f` for every connected sink
+    ///
+    /// Sinks may be advised or unadvised while the event is being fired. Those changes
+    /// take effect the next time an event is fired.
+    pub fn fire<F: FnMut(&ComRc<I>)>(&self, mut f: F) {
+        for sink in self.sinks() {
+            f(&sink);
+        }
+    }
+}
+
+impl<I: ComInterface + ?Sized> Default for ConnectionPoint<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The `IConnectionPoint` object handed out by [`ConnectionPoint::find`]
+///
+/// It keeps its container alive and shares the list of sinks with the embedded
+/// `ConnectionPoint`.
+#[repr(C)]
+struct ConnectionPointObject<I: ComInterface + ?Sized> {
+    vptr: *const <dyn IConnectionPoint as ComInterface>::VTable,
+    ref_count: Cell<u32>,
+    container: ComRc<dyn IConnectionPointContainer>,
+    connections: Rc<Connections<I>>,
+}
+
+impl_iunknown!([I: ComInterface + ?Sized] ConnectionPointObject<I> => dyn IConnectionPoint);
+
+impl<I: ComInterface + ?Sized> IConnectionPoint for ConnectionPointObject<I> {
+    unsafe fn get_connection_interface(&self, piid: *mut IID) -> HRESULT {
+        if piid.is_null() {
+            return E_POINTER;
+        }
+        *piid = I::IID;
+        S_OK
+    }
+
+    unsafe fn get_connection_point_container(
+        &self,
+        ppcpc: *mut *mut IConnectionPointContainerVPtr,
+    ) -> HRESULT {
+        if ppcpc.is_null() {
+            return E_POINTER;
+        }
+        *ppcpc = ComPtr::from(self.container.clone()).as_raw() as *mut _;
+        S_OK
+    }
+
+    unsafe fn advise(&self, sink: *mut IUnknownVPtr, cookie: *mut u32) -> HRESULT {
+        if sink.is_null() || cookie.is_null() {
+            return E_POINTER;
+        }
+        let unknown = ComPtr::<dyn IUnknown>::new(sink as *mut _);
+        let sink = match unknown.get_interface::<I>() {
+            Some(sink) => sink.upgrade(),
+            None => return CONNECT_E_CANNOTCONNECT,
+        };
+
+        let next_cookie = self.connections.next_cookie.get();
+        self.connections
+            .next_cookie
+            .set(next_cookie.wrapping_add(1).max(1));
+        self.connections
+            .sinks
+            .borrow_mut()
+            .push((next_cookie, sink));
+        *cookie = next_cookie;
+        S_OK
+    }
+
+    unsafe fn unadvise(&self, cookie: u32) -> HRESULT {
+        let removed = {
+            let mut sinks = self.connections.sinks.borrow_mut();
+            sinks
+                .iter()
+                .position(|(c, _)| *c == cookie)
+                .map(|index| sinks.remove(index))
+        };
+        // The sink is released after the borrow ends as releasing it may call back
+        match removed {
+            Some(_) => S_OK,
+            None => CONNECT_E_NOCONNECTION,
+        }
+    }
+
+    unsafe fn enum_connections(&self, ppenum: *mut *mut IEnumConnectionsVPtr) -> HRESULT {
+        if ppenum.is_null() {
+            return E_POINTER;
+        }
+        let connections: Vec<_> = self
+            .connections
+            .sinks
+            .borrow()
+            .iter()
+            .filter_map(|(cookie, sink)| {
+                Some(ConnectData {
+                    sink: sink.get_interface::<dyn IUnknown>()?,
+                    cookie: *cookie,
+                })
+            })
+            .collect();
+        let enumerator: ComRc<dyn IEnumConnections> =
+            Enumerator::create(ConnectDataIter(connections.into_iter()));
+        *ppenum = ComPtr::from(enumerator).as_raw() as *mut _;
+        S_OK
+    }
+}
+
+/// Yields clones of the connections so that the enumerator can be reset
+struct ConnectDataIter(std::vec::IntoIter<ConnectData>);
+
+impl Clone for ConnectDataIter {
+    fn clone(&self) -> Self {
+        let connections: Vec<_> = self
+            .0
+            .as_slice()
+            .iter()
+            .map(|connection| ConnectData {
+                sink: connection.sink.clone(),
+                cookie: connection.cookie,
+            })
+            .collect();
+        ConnectDataIter(connections.into_iter())
+    }
+}
+
+impl Iterator for ConnectDataIter {
+    type Item = ConnectData;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+/// A sink answering each method of the interface `I` with a closure
+///
+/// `F` is a tuple with one closure per method of `I`, in declaration order. The
+/// implementation of `I` is generated by `#[com_interface]` for every interface that
+/// derives directly from `IUnknown`.
+#[repr(C)]
+pub struct ClosureSink<I: ComInterface + ?Sized, F> {
+    vptr: *const <I as ComInterface>::VTable,
+    ref_count: Cell<u32>,
+    handlers: F,
+}
+
+impl<I: ComInterface + ?Sized, F> ClosureSink<I, F> {
+    /// Create a new sink calling `handlers`
+    pub fn create(handlers: F) -> ComRc<I>
+    where
+        I: ProductionComInterface<Self>,
+    {
+        let vptr = Box::into_raw(Box::new(I::vtable::<Zero>()));
+        let object = Box::into_raw(Box::new(ClosureSink::<I, F> {
+            vptr,
+            ref_count: Cell::new(1),
+            handlers,
+        }));
+        unsafe { ComRc::from_raw(object as *mut *mut <I as ComInterface>::VTable) }
+    }
+
+    #[doc(hidden)]
+    pub fn handlers(&self) -> &F {
+        &self.handlers
+    }
+}
+
+impl_iunknown!([I: ComInterface + ?Sized, F] ClosureSink<I, F> => I);
+
+/// A sink connected to a connection point
+///
+/// The sink is unadvised when the connection is dropped.
+pub struct Connection {
+    point: ComRc<dyn IConnectionPoint>,
+    cookie: u32,
+}
+
+impl Connection {
+    /// Advise `sink` on the connection point `point`
+    pub fn advise<I: ComInterface + ?Sized>(
+        point: ComRc<dyn IConnectionPoint>,
+        sink: &ComRc<I>,
+    ) -> Result<Connection, HRESULT> {
+        let mut cookie = 0;
+        let hr = unsafe { point.advise(sink.as_raw() as *mut IUnknownVPtr, &mut cookie) };
+        if FAILED(hr) {
+            return Err(hr);
+        }
+        Ok(Connection { point, cookie })
+    }
+
+    /// The cookie identifying the connection
+    pub fn cookie(&self) -> u32 {
+        self.cookie
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe {
+            self.point.unadvise(self.cookie);
+        }
+    }
+}
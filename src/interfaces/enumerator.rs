@@ -0,0 +1,414 @@
+//! Generic support for `IEnum*` style enumerator interfaces
+//!
+//! Enumerator interfaces all share the same shape (`Next`, `Skip`, `Reset` and `Clone`)
+//! and only differ in the type of element they hand out. Such an interface is declared
+//! with [`enum_interface!`] and any `Clone + Iterator` can then be exposed through it
+//! with [`Enumerator::create`]:
+//!
+//! ```rust,ignore
+//! com::enum_interface! {
+//!     /// Enumerates the names of the animals in a zoo
+//!     pub trait IEnumAnimalIds("C5F45CBC-4439-418C-A9F9-05AC67525E43"): Iterator<Item = u32>;
+//! }
+//!
+//! let ids: ComRc<dyn IEnumAnimalIds> = Enumerator::create(vec![1, 2, 3].into_iter());
+//! for id in ids {
+//!     println!("{}", id);
+//! }
+//! ```
+//!
+//! [`enum_interface!`]: ../../macro.enum_interface.html
+
+use crate::offset::Zero;
+use crate::sys::{CLSID, E_INVALIDARG, E_POINTER, FAILED, HRESULT, S_FALSE, S_OK};
+use crate::{ComInterface, ComPtr, ComRc, ProductionComInterface};
+
+use std::cell::{Cell, RefCell};
+use std::ffi::c_void;
+use std::mem::MaybeUninit;
+
+/// A value that can be handed out by an enumerator
+pub trait EnumItem: Sized {
+    /// The raw element written to the array passed to `Next`
+    type Element: Copy;
+
+    /// Convert into a raw element, transferring ownership to the caller of `Next`
+    fn into_element(self) -> Self::Element;
+
+    /// Take ownership of a raw element returned from `Next`
+    ///
+    /// # Safety
+    ///
+    /// `element` must have been returned from a successful call to `Next`.
+    unsafe fn from_element(element: Self::Element) -> Self;
+}
+
+impl<T: ComInterface + ?Sized> EnumItem for ComRc<T> {
+    type Element = *mut *mut <T as ComInterface>::VTable;
+
+    fn into_element(self) -> Self::Element {
+        ComPtr::from(self).as_raw()
+    }
+
+    unsafe fn from_element(element: Self::Element) -> Self {
+        ComRc::from_raw(element)
+    }
+}
+
+macro_rules! impl_enum_item_copy {
+    ($($ty:ty),*) => {
+        $(
+            impl EnumItem for $ty {
+                type Element = $ty;
+
+                fn into_element(self) -> Self::Element {
+                    self
+                }
+
+                unsafe fn from_element(element: Self::Element) -> Self {
+                    element
+                }
+            }
+        )*
+    };
+}
+
+impl_enum_item_copy!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64, CLSID);
+
+/// An enumerator interface declared with [`enum_interface!`]
+///
+/// [`enum_interface!`]: ../../macro.enum_interface.html
+pub trait EnumInterface: ComInterface {
+    /// The type of value enumerated
+    type Item: EnumItem;
+
+    /// Call the `Next` method of the enumerator
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    unsafe fn next(
+        this: &ComRc<Self>,
+        celt: u32,
+        rgelt: *mut <Self::Item as EnumItem>::Element,
+        fetched: *mut u32,
+    ) -> HRESULT;
+
+    /// Call the `Skip` method of the enumerator
+    unsafe fn skip(this: &ComRc<Self>, celt: u32) -> HRESULT;
+
+    /// Call the `Reset` method of the enumerator
+    unsafe fn reset(this: &ComRc<Self>) -> HRESULT;
+
+    /// Call the `Clone` method of the enumerator
+    unsafe fn clone_enum(this: &ComRc<Self>, ppenum: *mut *mut c_void) -> HRESULT;
+}
+
+/// A COM object exposing a Rust iterator through an enumerator interface `I`
+///
+/// The iterator is cloned when the enumerator is created so that `Reset` can start
+/// over, and `Clone` creates a new enumerator at the current position.
+#[repr(C)]
+pub struct Enumerator<I: ComInterface + ?Sized, It> {
+    vptr: *const <I as ComInterface>::VTable,
+    ref_count: Cell<u32>,
+    source: It,
+    current: RefCell<It>,
+}
+
+impl<I, It> Enumerator<I, It>
+where
+    I: EnumInterface + ProductionComInterface<Self> + ?Sized,
+    It: Iterator + Clone + 'static,
+    It::Item: Into<I::Item>,
+{
+    /// Create a new enumerator handing out the items of `iter`
+    pub fn create(iter: It) -> ComRc<I> {
+        let current = RefCell::new(iter.clone());
+        Self::allocate(iter, current)
+    }
+
+    fn allocate(source: It, current: RefCell<It>) -> ComRc<I> {
+        let vptr = Box::into_raw(Box::new(I::vtable::<Zero>()));
+        let object = Box::into_raw(Box::new(Enumerator::<I, It> {
+            vptr,
+            ref_count: Cell::new(1),
+            source,
+            current,
+        }));
+        unsafe { ComRc::from_raw(object as *mut *mut <I as ComInterface>::VTable) }
+    }
+
+    /// The implementation of `Next`
+    #[doc(hidden)]
+    pub unsafe fn next_elements(
+        &self,
+        celt: u32,
+        rgelt: *mut <I::Item as EnumItem>::Element,
+        fetched: *mut u32,
+    ) -> HRESULT {
+        if rgelt.is_null() {
+            return E_POINTER;
+        }
+        if fetched.is_null() && celt != 1 {
+            return E_INVALIDARG;
+        }
+
+        let mut current = self.current.borrow_mut();
+        let mut count = 0;
+        while count < celt {
+            match current.next() {
+                Some(item) => {
+                    *rgelt.add(count as usize) = item.into().into_element();
+                    count += 1;
+                }
+                None => break,
+            }
+        }
+
+        if !fetched.is_null() {
+            *fetched = count;
+        }
+        if count == celt {
+            S_OK
+        } else {
+            S_FALSE
+        }
+    }
+
+    /// The implementation of `Skip`
+    #[doc(hidden)]
+    pub fn skip_elements(&self, celt: u32) -> HRESULT {
+        let mut current = self.current.borrow_mut();
+        for _ in 0..celt {
+            if current.next().is_none() {
+                return S_FALSE;
+            }
+        }
+        S_OK
+    }
+
+    /// The implementation of `Reset`
+    #[doc(hidden)]
+    pub fn reset_elements(&self) -> HRESULT {
+        *self.current.borrow_mut() = self.source.clone();
+        S_OK
+    }
+
+    /// The implementation of `Clone`
+    #[doc(hidden)]
+    pub unsafe fn clone_elements(&self, ppenum: *mut *mut c_void) -> HRESULT {
+        if ppenum.is_null() {
+            return E_POINTER;
+        }
+        let current = RefCell::new(self.current.borrow().clone());
+        let clone = Self::allocate(self.source.clone(), current);
+        *ppenum = ComPtr::from(clone).as_raw() as *mut c_void;
+        S_OK
+    }
+}
+
+impl_iunknown!([I: ComInterface + ?Sized, It] Enumerator<I, It> => I);
+
+/// An iterator over the items of a COM enumerator
+///
+/// Created through the `IntoIterator` implementation of `ComRc<I>`.
+pub struct EnumIterator<I: EnumInterface + ?Sized> {
+    enumerator: ComRc<I>,
+}
+
+impl<I: EnumInterface + ?Sized> EnumIterator<I> {
+    /// Get back the enumerator being iterated over
+    pub fn into_inner(self) -> ComRc<I> {
+        self.enumerator
+    }
+}
+
+impl<I: EnumInterface + ?Sized> Iterator for EnumIterator<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut element = MaybeUninit::<<I::Item as EnumItem>::Element>::uninit();
+        let mut fetched = 0;
+        let hr = unsafe { I::next(&self.enumerator, 1, element.as_mut_ptr(), &mut fetched) };
+        if FAILED(hr) || hr == S_FALSE || fetched != 1 {
+            return None;
+        }
+        Some(unsafe { EnumItem::from_element(element.assume_init()) })
+    }
+}
+
+impl<I: EnumInterface + ?Sized> IntoIterator for ComRc<I> {
+    type Item = I::Item;
+    type IntoIter = EnumIterator<I>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        EnumIterator { enumerator: self }
+    }
+}
+
+impl<I: EnumInterface + ?Sized> ComRc<I> {
+    /// Create an independent copy of the enumerator at its current position
+    pub fn clone_enum(&self) -> Result<ComRc<I>, HRESULT> {
+        let mut ppenum = std::ptr::null_mut::<c_void>();
+        let hr = unsafe { I::clone_enum(self, &mut ppenum) };
+        if FAILED(hr) {
+            return Err(hr);
+        }
+        Ok(unsafe { ComRc::from_raw(ppenum as *mut *mut _) })
+    }
+}
+
+/// Declare an `IEnum*` style enumerator interface
+///
+/// This declares a COM interface with the `Next`, `Skip`, `Reset` and `Clone` methods
+/// (the latter is named `clone_enum` in Rust) handing out raw elements of `Item`.
+/// The interface can be implemented by [`Enumerator`] and consumed as a Rust
+/// iterator through `IntoIterator` on `ComRc`.
+///
+/// ```rust,ignore
+/// com::enum_interface! {
+///     /// Enumerates IDs
+///     pub trait IEnumIds("C5F45CBC-4439-418C-A9F9-05AC67525E43"): Iterator<Item = u32>;
+/// }
+/// ```
+///
+/// [`Enumerator`]: interfaces/enumerator/struct.Enumerator.html
+#[macro_export]
+macro_rules! enum_interface {
+    ($(#[$attr:meta])* $vis:vis trait $name:ident($iid:tt): Iterator<Item = $item:ty>;) => {
+        $(#[$attr])*
+        #[com::com_interface($iid)]
+        $vis trait $name: com::interfaces::IUnknown {
+            /// Retrieve the next `celt` items. `S_FALSE` is returned if fewer items
+            /// were left.
+            unsafe fn next(
+                &self,
+                celt: u32,
+                rgelt: *mut <$item as com::interfaces::enumerator::EnumItem>::Element,
+                fetched: *mut u32,
+            ) -> com::sys::HRESULT;
+
+            /// Skip over the next `celt` items. `S_FALSE` is returned if fewer items
+            /// were left.
+            unsafe fn skip(&self, celt: u32) -> com::sys::HRESULT;
+
+            /// Reset the enumeration to the beginning
+            unsafe fn reset(&self) -> com::sys::HRESULT;
+
+            /// Create a new enumerator with the same state as this one
+            unsafe fn clone_enum(&self, ppenum: *mut *mut std::ffi::c_void) -> com::sys::HRESULT;
+        }
+
+        impl com::interfaces::enumerator::EnumInterface for dyn $name {
+            type Item = $item;
+
+            unsafe fn next(
+                this: &com::ComRc<Self>,
+                celt: u32,
+                rgelt: *mut <$item as com::interfaces::enumerator::EnumItem>::Element,
+                fetched: *mut u32,
+            ) -> com::sys::HRESULT {
+                $name::next(this, celt, rgelt, fetched)
+            }
+
+            unsafe fn skip(this: &com::ComRc<Self>, celt: u32) -> com::sys::HRESULT {
+                $name::skip(this, celt)
+            }
+
+            unsafe fn reset(this: &com::ComRc<Self>) -> com::sys::HRESULT {
+                $name::reset(this)
+            }
+
+            unsafe fn clone_enum(
+                this: &com::ComRc<Self>,
+                ppenum: *mut *mut std::ffi::c_void,
+            ) -> com::sys::HRESULT {
+                $name::clone_enum(this, ppenum)
+            }
+        }
+
+        impl<It> $name for com::interfaces::enumerator::Enumerator<dyn $name, It>
+        where
+            It: Iterator + Clone + 'static,
+            It::Item: Into<$item>,
+        {
+            unsafe fn next(
+                &self,
+                celt: u32,
+                rgelt: *mut <$item as com::interfaces::enumerator::EnumItem>::Element,
+                fetched: *mut u32,
+            ) -> com::sys::HRESULT {
+                self.next_elements(celt, rgelt, fetched)
+            }
+
+            unsafe fn skip(&self, celt: u32) -> com::sys::HRESULT {
+                self.skip_elements(celt)
+            }
+
+            unsafe fn reset(&self) -> com::sys::HRESULT {
+                self.reset_elements()
+            }
+
+            unsafe fn clone_enum(&self, ppenum: *mut *mut std::ffi::c_void) -> com::sys::HRESULT {
+                self.clone_elements(ppenum)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interfaces::{IEnumUnknown, IUnknown};
+
+    enum_interface! {
+        /// Enumerates numbers
+        trait IEnumNumbers("7A8C5D8E-2C0B-4E3B-9D1F-6E0F4B2A9C11"): Iterator<Item = u32>;
+    }
+
+    #[test]
+    fn next_reports_fetched_count() {
+        let numbers: ComRc<dyn IEnumNumbers> = Enumerator::create(1..=3u32);
+        let mut elements = [0u32; 2];
+        let mut fetched = 0;
+        unsafe {
+            assert_eq!(numbers.next(2, elements.as_mut_ptr(), &mut fetched), S_OK);
+            assert_eq!((fetched, elements), (2, [1, 2]));
+            assert_eq!(
+                numbers.next(2, elements.as_mut_ptr(), &mut fetched),
+                S_FALSE
+            );
+            assert_eq!((fetched, elements[0]), (1, 3));
+            assert_eq!(
+                numbers.next(2, elements.as_mut_ptr(), std::ptr::null_mut()),
+                E_INVALIDARG
+            );
+            assert_eq!(numbers.reset(), S_OK);
+            assert_eq!(numbers.skip(2), S_OK);
+            assert_eq!(numbers.skip(2), S_FALSE);
+        }
+    }
+
+    #[test]
+    fn clone_and_iterate() {
+        let numbers: ComRc<dyn IEnumNumbers> = Enumerator::create(vec![5u32, 6, 7].into_iter());
+        unsafe { numbers.skip(1) };
+        let clone = numbers.clone_enum().unwrap();
+        assert_eq!(clone.into_iter().collect::<Vec<_>>(), vec![6, 7]);
+        assert_eq!(numbers.into_iter().collect::<Vec<_>>(), vec![6, 7]);
+    }
+
+    #[test]
+    fn interface_elements_are_add_refed() {
+        let inner: ComRc<dyn IEnumNumbers> = Enumerator::create(0..1u32);
+        let unknown = inner.get_interface::<dyn IUnknown>().unwrap();
+        let unknowns: ComRc<dyn IEnumUnknown> =
+            Enumerator::create(vec![unknown.clone(), unknown.clone()].into_iter());
+        let items: Vec<_> = unknowns.into_iter().collect();
+        assert_eq!(items.len(), 2);
+        drop(items);
+        // Both the enumerator and the handed out items have released their references
+        unsafe {
+            assert_eq!(unknown.add_ref(), 3);
+            unknown.release();
+        }
+    }
+}
@@ -0,0 +1,9 @@
+//! Everything related to the [IAgileObject](https://docs.microsoft.com/en-us/windows/win32/api/objidlbase/nn-objidlbase-iagileobject) COM interface
+use crate::com_interface;
+use crate::interfaces::iunknown::IUnknown;
+
+/// [IAgileObject](https://docs.microsoft.com/en-us/windows/win32/api/objidlbase/nn-objidlbase-iagileobject) COM interface
+///
+/// A marker answered by objects that can be called directly from any apartment.
+#[com_interface("94EA2B94-E9CC-49E0-C0FF-EE64CA8F5B90")]
+pub trait IAgileObject: IUnknown {}
@@ -0,0 +1,94 @@
+//! Everything related to the [IClassFactory2](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nn-ocidl-iclassfactory2) COM interface
+use crate::com_interface;
+use crate::interfaces::iclass_factory::IClassFactory;
+use crate::interfaces::iunknown::IUnknownVPtr;
+use crate::sys::{
+    SysAllocStringLen, SysFreeString, SysStringLen, BSTR, E_OUTOFMEMORY, FAILED, GUID, HRESULT,
+    LICINFO,
+};
+use crate::{ComInterface, ComRc};
+
+use std::ffi::c_void;
+
+/// [IClassFactory2](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nn-ocidl-iclassfactory2) COM interface
+///
+/// The class factory of a co_class with the `licensed` option implements this interface.
+#[com_interface("B196B28F-BAB4-101A-B69C-00AA00341D07")]
+pub trait IClassFactory2: IClassFactory {
+    /// the [GetLicInfo](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nf-ocidl-iclassfactory2-getlicinfo) COM method
+    unsafe fn get_lic_info(&self, lic_info: *mut LICINFO) -> HRESULT;
+    /// the [RequestLicKey](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nf-ocidl-iclassfactory2-requestlickey) COM method
+    unsafe fn request_lic_key(&self, reserved: u32, key: *mut BSTR) -> HRESULT;
+    /// the [CreateInstanceLic](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nf-ocidl-iclassfactory2-createinstancelic) COM method
+    unsafe fn create_instance_lic(
+        &self,
+        aggr: *mut IUnknownVPtr,
+        reserved: *mut IUnknownVPtr,
+        riid: *const GUID,
+        key: BSTR,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT;
+}
+
+impl ComRc<dyn IClassFactory2> {
+    /// Get the licensing information of the class
+    pub fn lic_info(&self) -> Result<LICINFO, HRESULT> {
+        let mut lic_info = LICINFO {
+            cb_lic_info: std::mem::size_of::<LICINFO>() as i32,
+            ..LICINFO::default()
+        };
+        let hr = unsafe { self.get_lic_info(&mut lic_info) };
+        if FAILED(hr) {
+            return Err(hr);
+        }
+        Ok(lic_info)
+    }
+
+    /// Get a runtime key that creates instances on machines without a license
+    pub fn request_key(&self) -> Result<String, HRESULT> {
+        let mut key = std::ptr::null_mut();
+        let hr = unsafe { self.request_lic_key(0, &mut key) };
+        if FAILED(hr) {
+            return Err(hr);
+        }
+        if key.is_null() {
+            return Ok(String::new());
+        }
+        unsafe {
+            let chars = std::slice::from_raw_parts(key, SysStringLen(key) as usize);
+            let value = String::from_utf16_lossy(chars);
+            SysFreeString(key);
+            Ok(value)
+        }
+    }
+
+    /// Get an instance of the associated Co Class, created with the runtime key `key`
+    ///
+    /// Fails with `CLASS_E_NOTLICENSED` if the key is wrong.
+    pub fn get_instance_lic<T: ComInterface + ?Sized>(
+        &self,
+        key: &str,
+    ) -> Result<ComRc<T>, HRESULT> {
+        let chars: Vec<u16> = key.encode_utf16().collect();
+        let mut ppv = std::ptr::null_mut::<c_void>();
+        let hr = unsafe {
+            let key = SysAllocStringLen(chars.as_ptr(), chars.len() as u32);
+            if key.is_null() {
+                return Err(E_OUTOFMEMORY);
+            }
+            let hr = self.create_instance_lic(
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+                &T::IID as *const GUID,
+                key,
+                &mut ppv,
+            );
+            SysFreeString(key);
+            hr
+        };
+        if FAILED(hr) {
+            return Err(hr);
+        }
+        Ok(unsafe { ComRc::from_raw(ppv as *mut _) })
+    }
+}
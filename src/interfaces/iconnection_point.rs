@@ -0,0 +1,24 @@
+//! Everything related to the [IConnectionPoint](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nn-ocidl-iconnectionpoint) COM interface
+use crate::com_interface;
+use crate::interfaces::iconnection_point_container::IConnectionPointContainerVPtr;
+use crate::interfaces::ienum_connections::IEnumConnectionsVPtr;
+use crate::interfaces::iunknown::{IUnknown, IUnknownVPtr};
+use crate::sys::{HRESULT, IID};
+
+/// [IConnectionPoint](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nn-ocidl-iconnectionpoint) COM interface
+#[com_interface("B196B286-BAB4-101A-B69C-00AA00341D07")]
+pub trait IConnectionPoint: IUnknown {
+    /// the [GetConnectionInterface](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nf-ocidl-iconnectionpoint-getconnectioninterface) COM method
+    unsafe fn get_connection_interface(&self, piid: *mut IID) -> HRESULT;
+    /// the [GetConnectionPointContainer](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nf-ocidl-iconnectionpoint-getconnectionpointcontainer) COM method
+    unsafe fn get_connection_point_container(
+        &self,
+        ppcpc: *mut *mut IConnectionPointContainerVPtr,
+    ) -> HRESULT;
+    /// the [Advise](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nf-ocidl-iconnectionpoint-advise) COM method
+    unsafe fn advise(&self, sink: *mut IUnknownVPtr, cookie: *mut u32) -> HRESULT;
+    /// the [Unadvise](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nf-ocidl-iconnectionpoint-unadvise) COM method
+    unsafe fn unadvise(&self, cookie: u32) -> HRESULT;
+    /// the [EnumConnections](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nf-ocidl-iconnectionpoint-enumconnections) COM method
+    unsafe fn enum_connections(&self, ppenum: *mut *mut IEnumConnectionsVPtr) -> HRESULT;
+}
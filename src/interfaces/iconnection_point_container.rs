@@ -0,0 +1,61 @@
+//! Everything related to the [IConnectionPointContainer](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nn-ocidl-iconnectionpointcontainer) COM interface
+use crate::com_interface;
+use crate::interfaces::connection_point::{ClosureSink, Connection};
+use crate::interfaces::iconnection_point::{IConnectionPoint, IConnectionPointVPtr};
+use crate::interfaces::ienum_connection_points::IEnumConnectionPointsVPtr;
+use crate::interfaces::iunknown::IUnknown;
+use crate::sys::{FAILED, HRESULT, IID};
+use crate::{ComInterface, ComRc, ProductionComInterface};
+
+/// [IConnectionPointContainer](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nn-ocidl-iconnectionpointcontainer) COM interface
+#[com_interface("B196B284-BAB4-101A-B69C-00AA00341D07")]
+pub trait IConnectionPointContainer: IUnknown {
+    /// the [EnumConnectionPoints](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nf-ocidl-iconnectionpointcontainer-enumconnectionpoints) COM method
+    unsafe fn enum_connection_points(&self, ppenum: *mut *mut IEnumConnectionPointsVPtr)
+        -> HRESULT;
+    /// the [FindConnectionPoint](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nf-ocidl-iconnectionpointcontainer-findconnectionpoint) COM method
+    unsafe fn find_connection_point(
+        &self,
+        riid: *const IID,
+        ppcp: *mut *mut IConnectionPointVPtr,
+    ) -> HRESULT;
+}
+
+impl ComRc<dyn IConnectionPointContainer> {
+    /// Get the connection point for the outgoing interface `I`
+    pub fn connection_point<I: ComInterface + ?Sized>(
+        &self,
+    ) -> Result<ComRc<dyn IConnectionPoint>, HRESULT> {
+        let mut ppcp = std::ptr::null_mut();
+        let hr = unsafe { self.find_connection_point(&I::IID as *const IID, &mut ppcp) };
+        if FAILED(hr) {
+            return Err(hr);
+        }
+        Ok(unsafe { ComRc::from_raw(ppcp as *mut *mut _) })
+    }
+
+    /// Advise `sink` of the events of its interface `I`
+    ///
+    /// The sink stays connected until the returned [`Connection`] is dropped.
+    ///
+    /// [`Connection`]: ../connection_point/struct.Connection.html
+    pub fn advise<I: ComInterface + ?Sized>(&self, sink: &ComRc<I>) -> Result<Connection, HRESULT> {
+        Connection::advise(self.connection_point::<I>()?, sink)
+    }
+
+    /// Advise a sink for `I` that answers each method with the closure at the same
+    /// position of the `handlers` tuple
+    ///
+    /// ```rust,ignore
+    /// let connection = container.advise_with::<dyn ICatEvents, _>((
+    ///     |volume: u32| { println!("meow at {}", volume); S_OK },
+    /// ))?;
+    /// ```
+    pub fn advise_with<I, F>(&self, handlers: F) -> Result<Connection, HRESULT>
+    where
+        I: ProductionComInterface<ClosureSink<I, F>> + ?Sized,
+        F: 'static,
+    {
+        self.advise(&ClosureSink::<I, F>::create(handlers))
+    }
+}
@@ -0,0 +1,19 @@
+//! Everything related to the [ICreateErrorInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-icreateerrorinfo) COM interface
+use crate::com_interface;
+use crate::interfaces::iunknown::IUnknown;
+use crate::sys::{GUID, HRESULT};
+
+/// [ICreateErrorInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-icreateerrorinfo) COM interface
+#[com_interface("22F03340-547D-101B-8E65-08002B2BD119")]
+pub trait ICreateErrorInfo: IUnknown {
+    /// the [SetGUID](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-icreateerrorinfo-setguid) COM method
+    unsafe fn set_guid(&self, guid: *const GUID) -> HRESULT;
+    /// the [SetSource](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-icreateerrorinfo-setsource) COM method
+    unsafe fn set_source(&self, source: *mut u16) -> HRESULT;
+    /// the [SetDescription](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-icreateerrorinfo-setdescription) COM method
+    unsafe fn set_description(&self, description: *mut u16) -> HRESULT;
+    /// the [SetHelpFile](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-icreateerrorinfo-sethelpfile) COM method
+    unsafe fn set_help_file(&self, help_file: *mut u16) -> HRESULT;
+    /// the [SetHelpContext](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-icreateerrorinfo-sethelpcontext) COM method
+    unsafe fn set_help_context(&self, help_context: u32) -> HRESULT;
+}
@@ -0,0 +1,9 @@
+//! Everything related to the [IEnumConnectionPoints](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nn-ocidl-ienumconnectionpoints) COM interface
+
+use crate::interfaces::IConnectionPoint;
+use crate::ComRc;
+
+crate::enum_interface! {
+    /// [IEnumConnectionPoints](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nn-ocidl-ienumconnectionpoints) COM interface
+    pub trait IEnumConnectionPoints("B196B285-BAB4-101A-B69C-00AA00341D07"): Iterator<Item = ComRc<dyn IConnectionPoint>>;
+}
@@ -0,0 +1,49 @@
+//! Everything related to the [IEnumConnections](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nn-ocidl-ienumconnections) COM interface
+
+use crate::interfaces::enumerator::EnumItem;
+use crate::interfaces::iunknown::{IUnknown, IUnknownVPtr};
+use crate::{ComPtr, ComRc};
+
+/// The raw [CONNECTDATA](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/ns-ocidl-connectdata) structure
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[allow(non_camel_case_types)]
+pub struct CONNECTDATA {
+    /// The connected sink
+    pub unk: *mut IUnknownVPtr,
+    /// The cookie returned from `Advise`
+    pub cookie: u32,
+}
+
+/// A connection enumerated by [`IEnumConnections`]
+///
+/// [`IEnumConnections`]: trait.IEnumConnections.html
+pub struct ConnectData {
+    /// The connected sink
+    pub sink: ComRc<dyn IUnknown>,
+    /// The cookie returned from `Advise`
+    pub cookie: u32,
+}
+
+impl EnumItem for ConnectData {
+    type Element = CONNECTDATA;
+
+    fn into_element(self) -> Self::Element {
+        CONNECTDATA {
+            unk: ComPtr::from(self.sink).as_raw() as *mut IUnknownVPtr,
+            cookie: self.cookie,
+        }
+    }
+
+    unsafe fn from_element(element: Self::Element) -> Self {
+        ConnectData {
+            sink: ComRc::from_raw(element.unk as *mut _),
+            cookie: element.cookie,
+        }
+    }
+}
+
+crate::enum_interface! {
+    /// [IEnumConnections](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nn-ocidl-ienumconnections) COM interface
+    pub trait IEnumConnections("B196B287-BAB4-101A-B69C-00AA00341D07"): Iterator<Item = ConnectData>;
+}
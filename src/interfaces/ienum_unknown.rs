@@ -0,0 +1,9 @@
+//! Everything related to the [IEnumUnknown](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-ienumunknown) COM interface
+
+use crate::interfaces::IUnknown;
+use crate::ComRc;
+
+crate::enum_interface! {
+    /// [IEnumUnknown](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-ienumunknown) COM interface
+    pub trait IEnumUnknown("00000100-0000-0000-C000-000000000046"): Iterator<Item = ComRc<dyn IUnknown>>;
+}
@@ -0,0 +1,19 @@
+//! Everything related to the [IErrorInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-ierrorinfo) COM interface
+use crate::com_interface;
+use crate::interfaces::iunknown::IUnknown;
+use crate::sys::{BSTR, GUID, HRESULT};
+
+/// [IErrorInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-ierrorinfo) COM interface
+#[com_interface("1CF2B120-547D-101B-8E65-08002B2BD119")]
+pub trait IErrorInfo: IUnknown {
+    /// the [GetGUID](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-ierrorinfo-getguid) COM method
+    unsafe fn get_guid(&self, guid: *mut GUID) -> HRESULT;
+    /// the [GetSource](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-ierrorinfo-getsource) COM method
+    unsafe fn get_source(&self, source: *mut BSTR) -> HRESULT;
+    /// the [GetDescription](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-ierrorinfo-getdescription) COM method
+    unsafe fn get_description(&self, description: *mut BSTR) -> HRESULT;
+    /// the [GetHelpFile](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-ierrorinfo-gethelpfile) COM method
+    unsafe fn get_help_file(&self, help_file: *mut BSTR) -> HRESULT;
+    /// the [GetHelpContext](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-ierrorinfo-gethelpcontext) COM method
+    unsafe fn get_help_context(&self, help_context: *mut u32) -> HRESULT;
+}
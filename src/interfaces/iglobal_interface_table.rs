@@ -0,0 +1,37 @@
+//! Everything related to the [IGlobalInterfaceTable](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-iglobalinterfacetable) COM interface
+use crate::com_interface;
+use crate::interfaces::iunknown::{IUnknown, IUnknownVPtr};
+use crate::sys::{CLSID, HRESULT, IID};
+
+use std::ffi::c_void;
+
+/// The class ID of the process wide global interface table
+pub const CLSID_STD_GLOBAL_INTERFACE_TABLE: CLSID = CLSID {
+    data1: 0x0000_0323,
+    data2: 0x0000,
+    data3: 0x0000,
+    data4: [0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46],
+};
+
+/// [IGlobalInterfaceTable](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-iglobalinterfacetable) COM interface
+///
+/// Most code should use [`AgileRef`](../../agile/struct.AgileRef.html) instead.
+#[com_interface("00000146-0000-0000-C000-000000000046")]
+pub trait IGlobalInterfaceTable: IUnknown {
+    /// the [RegisterInterfaceInGlobal](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-iglobalinterfacetable-registerinterfaceinglobal) COM method
+    unsafe fn register_interface_in_global(
+        &self,
+        unk: *mut IUnknownVPtr,
+        riid: *const IID,
+        cookie: *mut u32,
+    ) -> HRESULT;
+    /// the [RevokeInterfaceFromGlobal](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-iglobalinterfacetable-revokeinterfacefromglobal) COM method
+    unsafe fn revoke_interface_from_global(&self, cookie: u32) -> HRESULT;
+    /// the [GetInterfaceFromGlobal](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-iglobalinterfacetable-getinterfacefromglobal) COM method
+    unsafe fn get_interface_from_global(
+        &self,
+        cookie: u32,
+        riid: *const IID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT;
+}
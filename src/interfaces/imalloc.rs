@@ -0,0 +1,25 @@
+//! Everything related to the [IMalloc](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-imalloc) COM interface
+use crate::com_interface;
+use crate::interfaces::iunknown::IUnknown;
+
+use std::ffi::c_void;
+
+/// [IMalloc](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-imalloc) COM interface
+///
+/// The task allocator returned by `CoGetMalloc`. Most code should use the functions in
+/// [`com::alloc`](../../alloc/index.html) instead.
+#[com_interface("00000002-0000-0000-C000-000000000046")]
+pub trait IMalloc: IUnknown {
+    /// the [Alloc](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-imalloc-alloc) COM method
+    unsafe fn alloc(&self, cb: usize) -> *mut c_void;
+    /// the [Realloc](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-imalloc-realloc) COM method
+    unsafe fn realloc(&self, pv: *mut c_void, cb: usize) -> *mut c_void;
+    /// the [Free](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-imalloc-free) COM method
+    unsafe fn free(&self, pv: *mut c_void);
+    /// the [GetSize](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-imalloc-getsize) COM method
+    unsafe fn get_size(&self, pv: *mut c_void) -> usize;
+    /// the [DidAlloc](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-imalloc-didalloc) COM method
+    unsafe fn did_alloc(&self, pv: *mut c_void) -> i32;
+    /// the [HeapMinimize](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-imalloc-heapminimize) COM method
+    unsafe fn heap_minimize(&self);
+}
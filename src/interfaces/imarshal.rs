@@ -0,0 +1,64 @@
+//! Everything related to the [IMarshal](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-imarshal) COM interface
+use crate::com_interface;
+use crate::interfaces::istream::IStreamVPtr;
+use crate::interfaces::iunknown::IUnknown;
+use crate::sys::{CLSID, HRESULT, IID};
+
+use std::ffi::c_void;
+
+/// The class ID of the free-threaded marshaler, which unmarshals in-process interface pointers
+pub const CLSID_IN_PROC_FREE_MARSHALER: CLSID = CLSID {
+    data1: 0x0000_033A,
+    data2: 0x0000,
+    data3: 0x0000,
+    data4: [0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46],
+};
+
+/// [IMarshal](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-imarshal) COM interface
+///
+/// A co_class with the `agile` option implements this interface by passing its
+/// interface pointers directly to other apartments of the same process.
+#[com_interface("00000003-0000-0000-C000-000000000046")]
+pub trait IMarshal: IUnknown {
+    /// the [GetUnmarshalClass](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-imarshal-getunmarshalclass) COM method
+    unsafe fn get_unmarshal_class(
+        &self,
+        riid: *const IID,
+        pv: *mut c_void,
+        dest_context: u32,
+        pv_dest_context: *mut c_void,
+        mshlflags: u32,
+        pclsid: *mut CLSID,
+    ) -> HRESULT;
+    /// the [GetMarshalSizeMax](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-imarshal-getmarshalsizemax) COM method
+    unsafe fn get_marshal_size_max(
+        &self,
+        riid: *const IID,
+        pv: *mut c_void,
+        dest_context: u32,
+        pv_dest_context: *mut c_void,
+        mshlflags: u32,
+        psize: *mut u32,
+    ) -> HRESULT;
+    /// the [MarshalInterface](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-imarshal-marshalinterface) COM method
+    unsafe fn marshal_interface(
+        &self,
+        stream: *mut IStreamVPtr,
+        riid: *const IID,
+        pv: *mut c_void,
+        dest_context: u32,
+        pv_dest_context: *mut c_void,
+        mshlflags: u32,
+    ) -> HRESULT;
+    /// the [UnmarshalInterface](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-imarshal-unmarshalinterface) COM method
+    unsafe fn unmarshal_interface(
+        &self,
+        stream: *mut IStreamVPtr,
+        riid: *const IID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT;
+    /// the [ReleaseMarshalData](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-imarshal-releasemarshaldata) COM method
+    unsafe fn release_marshal_data(&self, stream: *mut IStreamVPtr) -> HRESULT;
+    /// the [DisconnectObject](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-imarshal-disconnectobject) COM method
+    unsafe fn disconnect_object(&self, reserved: u32) -> HRESULT;
+}
@@ -0,0 +1,11 @@
+//! Everything related to the [IPersist](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-ipersist) COM interface
+use crate::com_interface;
+use crate::interfaces::iunknown::IUnknown;
+use crate::sys::{CLSID, HRESULT};
+
+/// [IPersist](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-ipersist) COM interface
+#[com_interface("0000010C-0000-0000-C000-000000000046")]
+pub trait IPersist: IUnknown {
+    /// the [GetClassID](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-ipersist-getclassid) COM method
+    unsafe fn get_class_id(&self, class_id: *mut CLSID) -> HRESULT;
+}
@@ -0,0 +1,18 @@
+//! Everything related to the [IPersistStream](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-ipersiststream) COM interface
+use crate::com_interface;
+use crate::interfaces::ipersist::IPersist;
+use crate::interfaces::istream::IStreamVPtr;
+use crate::sys::{BOOL, HRESULT};
+
+/// [IPersistStream](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-ipersiststream) COM interface
+#[com_interface("00000109-0000-0000-C000-000000000046")]
+pub trait IPersistStream: IPersist {
+    /// the [IsDirty](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-ipersiststream-isdirty) COM method
+    unsafe fn is_dirty(&self) -> HRESULT;
+    /// the [Load](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-ipersiststream-load) COM method
+    unsafe fn load(&self, stream: *mut IStreamVPtr) -> HRESULT;
+    /// the [Save](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-ipersiststream-save) COM method
+    unsafe fn save(&self, stream: *mut IStreamVPtr, clear_dirty: BOOL) -> HRESULT;
+    /// the [GetSizeMax](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-ipersiststream-getsizemax) COM method
+    unsafe fn get_size_max(&self, pcb_size: *mut u64) -> HRESULT;
+}
@@ -0,0 +1,20 @@
+//! Everything related to the [IPersistStreamInit](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nn-ocidl-ipersiststreaminit) COM interface
+use crate::com_interface;
+use crate::interfaces::ipersist::IPersist;
+use crate::interfaces::istream::IStreamVPtr;
+use crate::sys::{BOOL, HRESULT};
+
+/// [IPersistStreamInit](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nn-ocidl-ipersiststreaminit) COM interface
+#[com_interface("7FD52380-4E07-101B-AE2D-08002B2EC713")]
+pub trait IPersistStreamInit: IPersist {
+    /// the [IsDirty](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nf-ocidl-ipersiststreaminit-isdirty) COM method
+    unsafe fn is_dirty(&self) -> HRESULT;
+    /// the [Load](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nf-ocidl-ipersiststreaminit-load) COM method
+    unsafe fn load(&self, stream: *mut IStreamVPtr) -> HRESULT;
+    /// the [Save](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nf-ocidl-ipersiststreaminit-save) COM method
+    unsafe fn save(&self, stream: *mut IStreamVPtr, clear_dirty: BOOL) -> HRESULT;
+    /// the [GetSizeMax](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nf-ocidl-ipersiststreaminit-getsizemax) COM method
+    unsafe fn get_size_max(&self, pcb_size: *mut u64) -> HRESULT;
+    /// the [InitNew](https://docs.microsoft.com/en-us/windows/win32/api/ocidl/nf-ocidl-ipersiststreaminit-initnew) COM method
+    unsafe fn init_new(&self) -> HRESULT;
+}
@@ -0,0 +1,14 @@
+//! Everything related to the [ISequentialStream](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-isequentialstream) COM interface
+use crate::com_interface;
+use crate::interfaces::iunknown::IUnknown;
+use crate::sys::HRESULT;
+use std::ffi::c_void;
+
+/// [ISequentialStream](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-isequentialstream) COM interface
+#[com_interface("0C733A30-2A1C-11CE-ADE5-00AA0044773D")]
+pub trait ISequentialStream: IUnknown {
+    /// the [Read](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-isequentialstream-read) COM method
+    unsafe fn read(&self, pv: *mut c_void, cb: u32, pcb_read: *mut u32) -> HRESULT;
+    /// the [Write](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-isequentialstream-write) COM method
+    unsafe fn write(&self, pv: *const c_void, cb: u32, pcb_written: *mut u32) -> HRESULT;
+}
@@ -0,0 +1,33 @@
+//! Everything related to the [IStream](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-istream) COM interface
+use crate::com_interface;
+use crate::interfaces::isequential_stream::ISequentialStream;
+use crate::sys::{HRESULT, STATSTG};
+
+/// [IStream](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nn-objidl-istream) COM interface
+#[com_interface("0000000C-0000-0000-C000-000000000046")]
+pub trait IStream: ISequentialStream {
+    /// the [Seek](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-istream-seek) COM method
+    unsafe fn seek(&self, dlib_move: i64, origin: u32, plib_new_position: *mut u64) -> HRESULT;
+    /// the [SetSize](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-istream-setsize) COM method
+    unsafe fn set_size(&self, lib_new_size: u64) -> HRESULT;
+    /// the [CopyTo](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-istream-copyto) COM method
+    unsafe fn copy_to(
+        &self,
+        pstm: *mut IStreamVPtr,
+        cb: u64,
+        pcb_read: *mut u64,
+        pcb_written: *mut u64,
+    ) -> HRESULT;
+    /// the [Commit](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-istream-commit) COM method
+    unsafe fn commit(&self, commit_flags: u32) -> HRESULT;
+    /// the [Revert](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-istream-revert) COM method
+    unsafe fn revert(&self) -> HRESULT;
+    /// the [LockRegion](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-istream-lockregion) COM method
+    unsafe fn lock_region(&self, lib_offset: u64, cb: u64, lock_type: u32) -> HRESULT;
+    /// the [UnlockRegion](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-istream-unlockregion) COM method
+    unsafe fn unlock_region(&self, lib_offset: u64, cb: u64, lock_type: u32) -> HRESULT;
+    /// the [Stat](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-istream-stat) COM method
+    unsafe fn stat(&self, pstatstg: *mut STATSTG, stat_flag: u32) -> HRESULT;
+    /// the [Clone](https://docs.microsoft.com/en-us/windows/win32/api/objidl/nf-objidl-istream-clone) COM method
+    unsafe fn clone_stream(&self, ppstm: *mut *mut IStreamVPtr) -> HRESULT;
+}
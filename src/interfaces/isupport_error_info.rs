@@ -0,0 +1,14 @@
+//! Everything related to the [ISupportErrorInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-isupporterrorinfo) COM interface
+use crate::com_interface;
+use crate::interfaces::iunknown::IUnknown;
+use crate::sys::{HRESULT, IID};
+
+/// [ISupportErrorInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-isupporterrorinfo) COM interface
+///
+/// A co_class implements it with the `support_error_info` option. It then reports
+/// rich error information for every interface listed in `implements(...)`.
+#[com_interface("DF0B3D60-548F-101B-8E65-08002B2BD119")]
+pub trait ISupportErrorInfo: IUnknown {
+    /// the [InterfaceSupportsErrorInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-isupporterrorinfo-interfacesupportserrorinfo) COM method
+    unsafe fn interface_supports_error_info(&self, riid: *const IID) -> HRESULT;
+}
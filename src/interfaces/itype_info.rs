@@ -0,0 +1,108 @@
+//! Everything related to the [ITypeInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-itypeinfo) COM interface
+use crate::com_interface;
+use crate::interfaces::itype_lib::ITypeLibVPtr;
+use crate::interfaces::iunknown::{IUnknown, IUnknownVPtr};
+use crate::sys::{BSTR, FUNCDESC, HREFTYPE, HRESULT, IID, MEMBERID, PCWSTR, TYPEATTR};
+
+use std::ffi::c_void;
+
+/// [ITypeInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-itypeinfo) COM interface
+///
+/// The `ITypeComp`, `VARDESC`, `DISPPARAMS`, `VARIANT` and `EXCEPINFO` parameters are
+/// untyped pointers. Type libraries can also be read without `oleaut32` with the `tlb`
+/// module of `com_macros_support`.
+#[com_interface("00020401-0000-0000-C000-000000000046", slots = 22)]
+pub trait ITypeInfo: IUnknown {
+    /// the [GetTypeAttr](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-gettypeattr) COM method
+    unsafe fn get_type_attr(&self, type_attr: *mut *mut TYPEATTR) -> HRESULT;
+    /// the [GetTypeComp](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-gettypecomp) COM method
+    unsafe fn get_type_comp(&self, type_comp: *mut *mut c_void) -> HRESULT;
+    /// the [GetFuncDesc](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-getfuncdesc) COM method
+    unsafe fn get_func_desc(&self, index: u32, func_desc: *mut *mut FUNCDESC) -> HRESULT;
+    /// the [GetVarDesc](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-getvardesc) COM method
+    unsafe fn get_var_desc(&self, index: u32, var_desc: *mut *mut c_void) -> HRESULT;
+    /// the [GetNames](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-getnames) COM method
+    unsafe fn get_names(
+        &self,
+        memid: MEMBERID,
+        names: *mut BSTR,
+        max_names: u32,
+        count: *mut u32,
+    ) -> HRESULT;
+    /// the [GetRefTypeOfImplType](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-getreftypeofimpltype) COM method
+    unsafe fn get_ref_type_of_impl_type(&self, index: u32, ref_type: *mut HREFTYPE) -> HRESULT;
+    /// the [GetImplTypeFlags](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-getimpltypeflags) COM method
+    unsafe fn get_impl_type_flags(&self, index: u32, impl_type_flags: *mut i32) -> HRESULT;
+    /// the [GetIDsOfNames](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-getidsofnames) COM method
+    unsafe fn get_ids_of_names(
+        &self,
+        names: *const PCWSTR,
+        count: u32,
+        memids: *mut MEMBERID,
+    ) -> HRESULT;
+    /// the [Invoke](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-invoke) COM method
+    #[allow(clippy::too_many_arguments)]
+    #[slot(11)]
+    unsafe fn invoke(
+        &self,
+        instance: *mut c_void,
+        memid: MEMBERID,
+        flags: u16,
+        disp_params: *mut c_void,
+        var_result: *mut c_void,
+        excep_info: *mut c_void,
+        arg_err: *mut u32,
+    ) -> HRESULT;
+    /// the [GetDocumentation](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-getdocumentation) COM method
+    unsafe fn get_documentation(
+        &self,
+        memid: MEMBERID,
+        name: *mut BSTR,
+        doc_string: *mut BSTR,
+        help_context: *mut u32,
+        help_file: *mut BSTR,
+    ) -> HRESULT;
+    /// the [GetDllEntry](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-getdllentry) COM method
+    unsafe fn get_dll_entry(
+        &self,
+        memid: MEMBERID,
+        inv_kind: u32,
+        dll_name: *mut BSTR,
+        name: *mut BSTR,
+        ordinal: *mut u16,
+    ) -> HRESULT;
+    /// the [GetRefTypeInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-getreftypeinfo) COM method
+    unsafe fn get_ref_type_info(
+        &self,
+        ref_type: HREFTYPE,
+        type_info: *mut *mut ITypeInfoVPtr,
+    ) -> HRESULT;
+    /// the [AddressOfMember](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-addressofmember) COM method
+    unsafe fn address_of_member(
+        &self,
+        memid: MEMBERID,
+        inv_kind: u32,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT;
+    /// the [CreateInstance](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-createinstance) COM method
+    unsafe fn create_instance(
+        &self,
+        outer: *mut IUnknownVPtr,
+        riid: *const IID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT;
+    /// the [GetMops](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-getmops) COM method
+    unsafe fn get_mops(&self, memid: MEMBERID, mops: *mut BSTR) -> HRESULT;
+    /// the [GetContainingTypeLib](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-getcontainingtypelib) COM method
+    unsafe fn get_containing_type_lib(
+        &self,
+        type_lib: *mut *mut ITypeLibVPtr,
+        index: *mut u32,
+    ) -> HRESULT;
+    /// the [ReleaseTypeAttr](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-releasetypeattr) COM method
+    unsafe fn release_type_attr(&self, type_attr: *mut TYPEATTR);
+    /// the [ReleaseFuncDesc](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-releasefuncdesc) COM method
+    unsafe fn release_func_desc(&self, func_desc: *mut FUNCDESC);
+    /// the [ReleaseVarDesc](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypeinfo-releasevardesc) COM method
+    unsafe fn release_var_desc(&self, var_desc: *mut c_void);
+}
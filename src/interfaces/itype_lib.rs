@@ -0,0 +1,50 @@
+//! Everything related to the [ITypeLib](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-itypelib) COM interface
+use crate::com_interface;
+use crate::interfaces::itype_info::ITypeInfoVPtr;
+use crate::interfaces::iunknown::IUnknown;
+use crate::sys::{BOOL, BSTR, GUID, HRESULT, MEMBERID, PWSTR, TLIBATTR};
+
+use std::ffi::c_void;
+
+/// [ITypeLib](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nn-oaidl-itypelib) COM interface
+#[com_interface("00020402-0000-0000-C000-000000000046", slots = 13)]
+pub trait ITypeLib: IUnknown {
+    /// the [GetTypeInfoCount](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypelib-gettypeinfocount) COM method
+    unsafe fn get_type_info_count(&self) -> u32;
+    /// the [GetTypeInfo](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypelib-gettypeinfo) COM method
+    unsafe fn get_type_info(&self, index: u32, type_info: *mut *mut ITypeInfoVPtr) -> HRESULT;
+    /// the [GetTypeInfoType](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypelib-gettypeinfotype) COM method
+    unsafe fn get_type_info_type(&self, index: u32, kind: *mut u32) -> HRESULT;
+    /// the [GetTypeInfoOfGuid](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypelib-gettypeinfoofguid) COM method
+    unsafe fn get_type_info_of_guid(
+        &self,
+        guid: *const GUID,
+        type_info: *mut *mut ITypeInfoVPtr,
+    ) -> HRESULT;
+    /// the [GetLibAttr](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypelib-getlibattr) COM method
+    unsafe fn get_lib_attr(&self, lib_attr: *mut *mut TLIBATTR) -> HRESULT;
+    /// the [GetTypeComp](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypelib-gettypecomp) COM method
+    unsafe fn get_type_comp(&self, type_comp: *mut *mut c_void) -> HRESULT;
+    /// the [GetDocumentation](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypelib-getdocumentation) COM method
+    unsafe fn get_documentation(
+        &self,
+        index: i32,
+        name: *mut BSTR,
+        doc_string: *mut BSTR,
+        help_context: *mut u32,
+        help_file: *mut BSTR,
+    ) -> HRESULT;
+    /// the [IsName](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypelib-isname) COM method
+    unsafe fn is_name(&self, name: PWSTR, hash: u32, found: *mut BOOL) -> HRESULT;
+    /// the [FindName](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypelib-findname) COM method
+    unsafe fn find_name(
+        &self,
+        name: PWSTR,
+        hash: u32,
+        type_infos: *mut *mut ITypeInfoVPtr,
+        memids: *mut MEMBERID,
+        found: *mut u16,
+    ) -> HRESULT;
+    /// the [ReleaseTLibAttr](https://docs.microsoft.com/en-us/windows/win32/api/oaidl/nf-oaidl-itypelib-releasetlibattr) COM method
+    unsafe fn release_tlib_attr(&self, lib_attr: *mut TLIBATTR);
+}
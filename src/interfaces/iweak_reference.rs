@@ -0,0 +1,17 @@
+//! Everything related to the [IWeakReference](https://docs.microsoft.com/en-us/windows/win32/api/weakreference/nn-weakreference-iweakreference) COM interface
+use crate::com_interface;
+use crate::interfaces::iunknown::IUnknown;
+use crate::sys::{HRESULT, IID};
+
+use std::ffi::c_void;
+
+/// [IWeakReference](https://docs.microsoft.com/en-us/windows/win32/api/weakreference/nn-weakreference-iweakreference) COM interface
+///
+/// Most code should use [`ComWeak`](../../struct.ComWeak.html) instead.
+#[com_interface("00000037-0000-0000-C000-000000000046")]
+pub trait IWeakReference: IUnknown {
+    /// the [Resolve](https://docs.microsoft.com/en-us/windows/win32/api/weakreference/nf-weakreference-iweakreference-resolve(refiid_iinspectable)) COM method
+    ///
+    /// Succeeds with a null pointer once the object is gone.
+    unsafe fn resolve(&self, riid: *const IID, object_reference: *mut *mut c_void) -> HRESULT;
+}
@@ -0,0 +1,14 @@
+//! Everything related to the [IWeakReferenceSource](https://docs.microsoft.com/en-us/windows/win32/api/weakreference/nn-weakreference-iweakreferencesource) COM interface
+use crate::com_interface;
+use crate::interfaces::iunknown::IUnknown;
+use crate::interfaces::iweak_reference::IWeakReferenceVPtr;
+use crate::sys::HRESULT;
+
+/// [IWeakReferenceSource](https://docs.microsoft.com/en-us/windows/win32/api/weakreference/nn-weakreference-iweakreferencesource) COM interface
+///
+/// A co_class with the `weak` option implements this interface.
+#[com_interface("00000038-0000-0000-C000-000000000046")]
+pub trait IWeakReferenceSource: IUnknown {
+    /// the [GetWeakReference](https://docs.microsoft.com/en-us/windows/win32/api/weakreference/nf-weakreference-iweakreferencesource-getweakreference) COM method
+    unsafe fn get_weak_reference(&self, weak_reference: *mut *mut IWeakReferenceVPtr) -> HRESULT;
+}
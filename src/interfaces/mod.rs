@@ -1,9 +1,82 @@
 //! Common COM interfaces including IUknown and IClassFactory
 
+pub mod connection_point;
+pub mod enumerator;
+pub mod iagile_object;
 pub mod iclass_factory;
+pub mod iclass_factory2;
+pub mod iconnection_point;
+pub mod iconnection_point_container;
+pub mod icreate_error_info;
+pub mod ienum_connection_points;
+pub mod ienum_connections;
+pub mod ienum_unknown;
+pub mod ierror_info;
+pub mod iglobal_interface_table;
+pub mod imalloc;
+pub mod imarshal;
+pub mod ipersist;
+pub mod ipersist_stream;
+pub mod ipersist_stream_init;
+pub mod isequential_stream;
+pub mod istream;
+pub mod isupport_error_info;
+pub mod itype_info;
+pub mod itype_lib;
 pub mod iunknown;
+pub mod iweak_reference;
+pub mod iweak_reference_source;
+pub mod stream;
 
+#[doc(inline)]
+pub use connection_point::ConnectionPoint;
+#[doc(inline)]
+pub use enumerator::Enumerator;
+#[doc(inline)]
+pub use iagile_object::IAgileObject;
 #[doc(inline)]
 pub use iclass_factory::IClassFactory;
 #[doc(inline)]
+pub use iclass_factory2::IClassFactory2;
+#[doc(inline)]
+pub use iconnection_point::IConnectionPoint;
+#[doc(inline)]
+pub use iconnection_point_container::IConnectionPointContainer;
+#[doc(inline)]
+pub use icreate_error_info::ICreateErrorInfo;
+#[doc(inline)]
+pub use ienum_connection_points::IEnumConnectionPoints;
+#[doc(inline)]
+pub use ienum_connections::IEnumConnections;
+#[doc(inline)]
+pub use ienum_unknown::IEnumUnknown;
+#[doc(inline)]
+pub use ierror_info::IErrorInfo;
+#[doc(inline)]
+pub use iglobal_interface_table::IGlobalInterfaceTable;
+#[doc(inline)]
+pub use imalloc::IMalloc;
+#[doc(inline)]
+pub use imarshal::IMarshal;
+#[doc(inline)]
+pub use ipersist::IPersist;
+#[doc(inline)]
+pub use ipersist_stream::IPersistStream;
+#[doc(inline)]
+pub use ipersist_stream_init::IPersistStreamInit;
+#[doc(inline)]
+pub use isequential_stream::ISequentialStream;
+#[doc(inline)]
+pub use istream::IStream;
+#[doc(inline)]
+pub use isupport_error_info::ISupportErrorInfo;
+#[doc(inline)]
+pub use itype_info::ITypeInfo;
+#[doc(inline)]
+pub use itype_lib::ITypeLib;
+#[doc(inline)]
 pub use iunknown::IUnknown;
+#[doc(inline)]
+pub use iweak_reference::IWeakReference;
+#[doc(inline)]
+pub use iweak_reference_source::IWeakReferenceSource;
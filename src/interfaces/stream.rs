@@ -0,0 +1,486 @@
+//! Adapters between COM streams and the `std::io` traits
+//!
+//! [`IoStream`] exposes any `Read + Write + Seek` value as an [`IStream`] and
+//! [`ComStream`] implements `Read`, `Write` and `Seek` on top of an [`IStream`].
+//!
+//! ```rust,ignore
+//! let mut stream = ComStream::new(create_memory_stream(Vec::new()));
+//! stream.write_all(b"payload")?;
+//! stream.seek(SeekFrom::Start(0))?;
+//! ```
+//!
+//! [`IoStream`]: struct.IoStream.html
+//! [`ComStream`]: struct.ComStream.html
+//! [`IStream`]: ../istream/trait.IStream.html
+
+use crate::interfaces::isequential_stream::ISequentialStream;
+use crate::interfaces::istream::{IStream, IStreamVPtr};
+use crate::offset::Zero;
+use crate::sys::{
+    FAILED, HRESULT, STATSTG, STGTY_STREAM, STG_E_INVALIDFUNCTION, STG_E_INVALIDPOINTER,
+    STG_E_READFAULT, STG_E_WRITEFAULT, STREAM_SEEK_CUR, STREAM_SEEK_END, STREAM_SEEK_SET, S_OK,
+};
+use crate::{ComInterface, ComPtr, ComRc, ProductionComInterface};
+
+use std::cell::{Cell, RefCell};
+use std::ffi::c_void;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::rc::Rc;
+
+/// Create a stream backed by a growable buffer in memory
+///
+/// This is the equivalent of `CreateStreamOnHGlobal`. The stream starts at the beginning
+/// of `data` and clones of it share the same buffer.
+pub fn create_memory_stream(data: Vec<u8>) -> ComRc<dyn IStream> {
+    IoStream::create_resizable(Cursor::new(data))
+}
+
+/// A `Read + Write + Seek` value whose length can be set, which lets an [`IoStream`]
+/// truncate it through `SetSize`
+///
+/// [`IoStream`]: struct.IoStream.html
+pub trait SetLen {
+    /// Truncate or extend the value to `len` bytes
+    fn set_len(&mut self, len: u64) -> io::Result<()>;
+}
+
+impl SetLen for Cursor<Vec<u8>> {
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        self.get_mut().resize(len as usize, 0);
+        Ok(())
+    }
+}
+
+impl SetLen for std::fs::File {
+    fn set_len(&mut self, len: u64) -> io::Result<()> {
+        std::fs::File::set_len(self, len)
+    }
+}
+
+/// A COM object exposing a `Read + Write + Seek` value as an `IStream`
+///
+/// Clones created through `IStream::Clone` share the value but have their own seek
+/// position. `SetSize` can only truncate streams created with [`create_resizable`],
+/// region locking is not supported and `Stat` never returns a name.
+///
+/// [`create_resizable`]: #method.create_resizable
+#[repr(C)]
+pub struct IoStream<T> {
+    vptr: *const <dyn IStream as ComInterface>::VTable,
+    ref_count: Cell<u32>,
+    inner: Rc<RefCell<T>>,
+    position: Cell<u64>,
+    set_len: Option<fn(&mut T, u64) -> io::Result<()>>,
+}
+
+impl<T: Read + Write + Seek + 'static> IoStream<T> {
+    /// Create a new stream starting at the current position of `inner`
+    pub fn create(inner: T) -> ComRc<dyn IStream> {
+        Self::create_with(inner, None)
+    }
+
+    /// Create a new stream starting at the current position of `inner`, which `SetSize`
+    /// can also truncate
+    pub fn create_resizable(inner: T) -> ComRc<dyn IStream>
+    where
+        T: SetLen,
+    {
+        Self::create_with(inner, Some(<T as SetLen>::set_len))
+    }
+
+    fn create_with(
+        mut inner: T,
+        set_len: Option<fn(&mut T, u64) -> io::Result<()>>,
+    ) -> ComRc<dyn IStream> {
+        let position = inner.seek(SeekFrom::Current(0)).unwrap_or(0);
+        Self::allocate(Rc::new(RefCell::new(inner)), position, set_len)
+    }
+
+    fn allocate(
+        inner: Rc<RefCell<T>>,
+        position: u64,
+        set_len: Option<fn(&mut T, u64) -> io::Result<()>>,
+    ) -> ComRc<dyn IStream> {
+        let vptr = Box::into_raw(Box::new(
+            <dyn IStream as ProductionComInterface<Self>>::vtable::<Zero>(),
+        ));
+        let object = Box::into_raw(Box::new(IoStream {
+            vptr,
+            ref_count: Cell::new(1),
+            inner,
+            position: Cell::new(position),
+            set_len,
+        }));
+        unsafe { ComRc::from_raw(object as *mut *mut _) }
+    }
+
+    /// Run `f` at the seek position of this stream and keep the position it ends at
+    fn with_inner<R>(&self, f: impl FnOnce(&mut T) -> io::Result<R>) -> io::Result<R> {
+        let mut inner = self.inner.borrow_mut();
+        inner.seek(SeekFrom::Start(self.position.get()))?;
+        let result = f(&mut inner);
+        self.position.set(inner.seek(SeekFrom::Current(0))?);
+        result
+    }
+
+    fn len(&self) -> io::Result<u64> {
+        let position = self.position.get();
+        let len = self.with_inner(|inner| inner.seek(SeekFrom::End(0)));
+        self.position.set(position);
+        len
+    }
+}
+
+impl_iunknown!([T] IoStream<T> => dyn IStream);
+
+impl<T: Read + Write + Seek + 'static> ISequentialStream for IoStream<T> {
+    unsafe fn read(&self, pv: *mut c_void, cb: u32, pcb_read: *mut u32) -> HRESULT {
+        if pv.is_null() {
+            return STG_E_INVALIDPOINTER;
+        }
+        let buf = std::slice::from_raw_parts_mut(pv as *mut u8, cb as usize);
+        let result = self.with_inner(|inner| {
+            let mut total = 0;
+            while total < buf.len() {
+                match inner.read(&mut buf[total..]) {
+                    Ok(0) => break,
+                    Ok(n) => total += n,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(total)
+        });
+        match result {
+            Ok(read) => {
+                if !pcb_read.is_null() {
+                    *pcb_read = read as u32;
+                }
+                S_OK
+            }
+            Err(_) => STG_E_READFAULT,
+        }
+    }
+
+    unsafe fn write(&self, pv: *const c_void, cb: u32, pcb_written: *mut u32) -> HRESULT {
+        if pv.is_null() {
+            return STG_E_INVALIDPOINTER;
+        }
+        let buf = std::slice::from_raw_parts(pv as *const u8, cb as usize);
+        match self.with_inner(|inner| inner.write_all(buf)) {
+            Ok(()) => {
+                if !pcb_written.is_null() {
+                    *pcb_written = cb;
+                }
+                S_OK
+            }
+            Err(_) => STG_E_WRITEFAULT,
+        }
+    }
+}
+
+impl<T: Read + Write + Seek + 'static> IStream for IoStream<T> {
+    unsafe fn seek(&self, dlib_move: i64, origin: u32, plib_new_position: *mut u64) -> HRESULT {
+        let pos = match origin {
+            STREAM_SEEK_SET if dlib_move >= 0 => SeekFrom::Start(dlib_move as u64),
+            STREAM_SEEK_CUR => SeekFrom::Current(dlib_move),
+            STREAM_SEEK_END => SeekFrom::End(dlib_move),
+            _ => return STG_E_INVALIDFUNCTION,
+        };
+        match self.with_inner(|inner| inner.seek(pos)) {
+            Ok(position) => {
+                if !plib_new_position.is_null() {
+                    *plib_new_position = position;
+                }
+                S_OK
+            }
+            Err(_) => STG_E_INVALIDFUNCTION,
+        }
+    }
+
+    unsafe fn set_size(&self, lib_new_size: u64) -> HRESULT {
+        let len = match self.len() {
+            Ok(len) => len,
+            Err(_) => return STG_E_READFAULT,
+        };
+        if lib_new_size < len {
+            // The seek position is left as is, even past the new end
+            return match self.set_len {
+                Some(set_len) => match set_len(&mut self.inner.borrow_mut(), lib_new_size) {
+                    Ok(()) => S_OK,
+                    Err(_) => STG_E_WRITEFAULT,
+                },
+                None => STG_E_INVALIDFUNCTION,
+            };
+        }
+        let position = self.position.get();
+        self.position.set(len);
+        let result = self.with_inner(|inner| {
+            io::copy(&mut io::repeat(0).take(lib_new_size - len), inner).map(|_| ())
+        });
+        self.position.set(position);
+        match result {
+            Ok(()) => S_OK,
+            Err(_) => STG_E_WRITEFAULT,
+        }
+    }
+
+    unsafe fn copy_to(
+        &self,
+        pstm: *mut IStreamVPtr,
+        cb: u64,
+        pcb_read: *mut u64,
+        pcb_written: *mut u64,
+    ) -> HRESULT {
+        if pstm.is_null() {
+            return STG_E_INVALIDPOINTER;
+        }
+        let target = ComPtr::<dyn IStream>::new(pstm as *mut _);
+        let mut buf = [0u8; 8192];
+        let mut total_read = 0u64;
+        let mut total_written = 0u64;
+        let mut hr = S_OK;
+        while total_read < cb {
+            let chunk = std::cmp::min(buf.len() as u64, cb - total_read) as u32;
+            let mut read = 0;
+            hr = self.read(buf.as_mut_ptr() as *mut c_void, chunk, &mut read);
+            if FAILED(hr) || read == 0 {
+                break;
+            }
+            total_read += u64::from(read);
+            let mut written = 0;
+            hr = target.write(buf.as_ptr() as *const c_void, read, &mut written);
+            total_written += u64::from(written);
+            if FAILED(hr) {
+                break;
+            }
+        }
+        if !pcb_read.is_null() {
+            *pcb_read = total_read;
+        }
+        if !pcb_written.is_null() {
+            *pcb_written = total_written;
+        }
+        if FAILED(hr) {
+            hr
+        } else {
+            S_OK
+        }
+    }
+
+    unsafe fn commit(&self, _commit_flags: u32) -> HRESULT {
+        match self.inner.borrow_mut().flush() {
+            Ok(()) => S_OK,
+            Err(_) => STG_E_WRITEFAULT,
+        }
+    }
+
+    unsafe fn revert(&self) -> HRESULT {
+        S_OK
+    }
+
+    unsafe fn lock_region(&self, _lib_offset: u64, _cb: u64, _lock_type: u32) -> HRESULT {
+        STG_E_INVALIDFUNCTION
+    }
+
+    unsafe fn unlock_region(&self, _lib_offset: u64, _cb: u64, _lock_type: u32) -> HRESULT {
+        STG_E_INVALIDFUNCTION
+    }
+
+    unsafe fn stat(&self, pstatstg: *mut STATSTG, _stat_flag: u32) -> HRESULT {
+        if pstatstg.is_null() {
+            return STG_E_INVALIDPOINTER;
+        }
+        let size = match self.len() {
+            Ok(len) => len,
+            Err(_) => return STG_E_READFAULT,
+        };
+        let mut statstg: STATSTG = std::mem::zeroed();
+        statstg.ty = STGTY_STREAM;
+        statstg.size = size;
+        *pstatstg = statstg;
+        S_OK
+    }
+
+    unsafe fn clone_stream(&self, ppstm: *mut *mut IStreamVPtr) -> HRESULT {
+        if ppstm.is_null() {
+            return STG_E_INVALIDPOINTER;
+        }
+        let clone = Self::allocate(self.inner.clone(), self.position.get(), self.set_len);
+        *ppstm = ComPtr::from(clone).as_raw() as *mut IStreamVPtr;
+        S_OK
+    }
+}
+
+/// Implements `Read`, `Write` and `Seek` for an `IStream`
+///
+/// Failed calls are reported as an `io::Error` of kind `Other` carrying the `HRESULT`.
+pub struct ComStream {
+    stream: ComRc<dyn IStream>,
+}
+
+impl ComStream {
+    /// Wrap `stream`
+    pub fn new(stream: ComRc<dyn IStream>) -> Self {
+        ComStream { stream }
+    }
+
+    /// Get back the wrapped stream
+    pub fn into_inner(self) -> ComRc<dyn IStream> {
+        self.stream
+    }
+}
+
+impl From<ComRc<dyn IStream>> for ComStream {
+    fn from(stream: ComRc<dyn IStream>) -> Self {
+        ComStream::new(stream)
+    }
+}
+
+fn hresult_error(hr: HRESULT) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        format!("stream call failed with HRESULT 0x{:08X}", hr),
+    )
+}
+
+impl Read for ComStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let cb = std::cmp::min(buf.len(), u32::max_value() as usize) as u32;
+        let mut read = 0;
+        let hr = unsafe {
+            ISequentialStream::read(&self.stream, buf.as_mut_ptr() as *mut c_void, cb, &mut read)
+        };
+        if FAILED(hr) {
+            return Err(hresult_error(hr));
+        }
+        Ok(read as usize)
+    }
+}
+
+impl Write for ComStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let cb = std::cmp::min(buf.len(), u32::max_value() as usize) as u32;
+        let mut written = 0;
+        let hr = unsafe {
+            ISequentialStream::write(
+                &self.stream,
+                buf.as_ptr() as *const c_void,
+                cb,
+                &mut written,
+            )
+        };
+        if FAILED(hr) {
+            return Err(hresult_error(hr));
+        }
+        Ok(written as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let hr = unsafe { self.stream.commit(0) };
+        if FAILED(hr) {
+            return Err(hresult_error(hr));
+        }
+        Ok(())
+    }
+}
+
+impl Seek for ComStream {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (dlib_move, origin) = match pos {
+            SeekFrom::Start(offset) => (offset as i64, STREAM_SEEK_SET),
+            SeekFrom::Current(offset) => (offset, STREAM_SEEK_CUR),
+            SeekFrom::End(offset) => (offset, STREAM_SEEK_END),
+        };
+        let mut position = 0;
+        let hr = unsafe { IStream::seek(&self.stream, dlib_move, origin, &mut position) };
+        if FAILED(hr) {
+            return Err(hresult_error(hr));
+        }
+        Ok(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_write_and_seek() {
+        let mut stream = ComStream::new(create_memory_stream(b"hello".to_vec()));
+        stream.seek(SeekFrom::End(0)).unwrap();
+        stream.write_all(b" world").unwrap();
+        assert!(stream.seek(SeekFrom::Current(-100)).is_err());
+
+        let mut contents = String::new();
+        stream.seek(SeekFrom::Start(0)).unwrap();
+        stream.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "hello world");
+
+        let stream = stream.into_inner();
+        let mut statstg: STATSTG = unsafe { std::mem::zeroed() };
+        unsafe {
+            assert_eq!(stream.stat(&mut statstg, 0), S_OK);
+            assert_eq!(stream.set_size(16), S_OK);
+        }
+        assert_eq!((statstg.ty, statstg.size), (STGTY_STREAM, 11));
+    }
+
+    #[test]
+    fn set_size_truncates_resizable_streams() {
+        let stream = create_memory_stream(b"hello world".to_vec());
+        let mut statstg: STATSTG = unsafe { std::mem::zeroed() };
+        unsafe {
+            assert_eq!(stream.set_size(5), S_OK);
+            assert_eq!(stream.stat(&mut statstg, 0), S_OK);
+        }
+        assert_eq!(statstg.size, 5);
+        let mut stream = ComStream::new(stream);
+        let mut contents = String::new();
+        stream.read_to_string(&mut contents).unwrap();
+        assert_eq!(contents, "hello");
+
+        let stream = IoStream::create(Cursor::new(b"hello".to_vec()));
+        unsafe {
+            assert_eq!(stream.set_size(3), STG_E_INVALIDFUNCTION);
+        }
+    }
+
+    #[test]
+    fn clones_share_data_but_not_position() {
+        let stream = create_memory_stream(b"abcdef".to_vec());
+        let clone = unsafe {
+            let mut ppstm = std::ptr::null_mut();
+            assert_eq!(stream.clone_stream(&mut ppstm), S_OK);
+            ComRc::<dyn IStream>::from_raw(ppstm as *mut *mut _)
+        };
+        let mut stream = ComStream::new(stream);
+        let mut clone = ComStream::new(clone);
+
+        let mut buf = [0; 3];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"abc");
+        clone.write_all(b"XY").unwrap();
+        stream.seek(SeekFrom::Start(0)).unwrap();
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"XYc");
+    }
+
+    #[test]
+    fn copy_to_another_stream() {
+        let source = create_memory_stream(b"payload".to_vec());
+        let target = create_memory_stream(Vec::new());
+        let (mut read, mut written) = (0, 0);
+        unsafe {
+            let hr = source.copy_to(target.as_raw() as *mut _, 100, &mut read, &mut written);
+            assert_eq!(hr, S_OK);
+        }
+        assert_eq!((read, written), (7, 7));
+
+        let mut target = ComStream::new(target);
+        let mut contents = Vec::new();
+        target.seek(SeekFrom::Start(0)).unwrap();
+        target.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, b"payload");
+    }
+}
@@ -14,22 +14,87 @@
 //!
 
 #![deny(missing_docs)]
+// Every COM interface method is unsafe to call for the same reasons, which are
+// documented on `ComPtr::new`.
+#![allow(clippy::missing_safety_doc)]
 
+/// Implement `IUnknown` for a hand written COM object exposing a single interface
+///
+/// The object must be `#[repr(C)]` with a `vptr: *const <$interface>::VTable` field
+/// followed by a `ref_count` field of a [`RefCount`] type. It answers `QueryInterface` for the
+/// inheritance chain of `$interface` only.
+///
+/// [`RefCount`]: refcount/trait.RefCount.html
+macro_rules! impl_iunknown {
+    ([$($generics:tt)*] $ty:ty => $interface:ty) => {
+        unsafe impl<$($generics)*> crate::CoClass for $ty {}
+
+        impl<$($generics)*> crate::interfaces::IUnknown for $ty {
+            unsafe fn query_interface(
+                &self,
+                riid: *const crate::sys::IID,
+                ppv: *mut *mut std::ffi::c_void,
+            ) -> crate::sys::HRESULT {
+                let riid = &*riid;
+                if riid == &crate::interfaces::iunknown::IID_IUNKNOWN
+                    || <$interface as crate::ComInterface>::is_iid_in_inheritance_chain(riid)
+                {
+                    *ppv = &self.vptr as *const _ as *mut std::ffi::c_void;
+                    self.add_ref();
+                    crate::sys::NOERROR
+                } else {
+                    *ppv = std::ptr::null_mut();
+                    crate::sys::E_NOINTERFACE
+                }
+            }
+
+            unsafe fn add_ref(&self) -> u32 {
+                crate::refcount::RefCount::increment(&self.ref_count)
+            }
+
+            unsafe fn release(&self) -> u32 {
+                let value = crate::refcount::RefCount::decrement(&self.ref_count);
+                if value == 0 {
+                    drop(Box::from_raw(
+                        self.vptr as *mut <$interface as crate::ComInterface>::VTable,
+                    ));
+                    drop(Box::from_raw(self as *const _ as *mut Self));
+                }
+                value
+            }
+        }
+    };
+}
+
+pub mod agile;
+pub mod alloc;
+pub mod error;
+pub mod generations;
 pub mod interfaces;
+pub mod license;
+#[cfg(feature = "mock")]
+pub mod mock;
 #[doc(hidden)]
 pub mod offset;
+#[cfg(feature = "persist")]
+pub mod persist;
 mod ptr;
 mod rc;
 #[doc(hidden)]
+pub mod refcount;
+#[doc(hidden)]
 pub mod registration;
 pub mod runtime;
 pub mod sys;
+pub mod weak;
 
 use interfaces::IUnknown;
 pub use ptr::ComPtr;
 pub use rc::ComRc;
 #[doc(inline)]
 pub use sys::{CLSID, IID};
+#[doc(inline)]
+pub use weak::ComWeak;
 
 /// A COM compliant interface
 ///
@@ -38,7 +103,25 @@ pub use sys::{CLSID, IID};
 /// The trait or struct implementing this trait must provide a valid vtable as the
 /// associated VTable type. A vtable is valid if:
 /// * it is `#[repr(C)]`
-/// * the type only contains `extern "stdcall" fn" definitions
+/// * the type only contains `extern "system" fn` definitions
+///
+/// `#[com_interface]` asserts at compile time that the VTable has a slot for every
+/// inherited and declared method. An interface mirroring one declared elsewhere can also
+/// state its number of slots with `#[com_interface("...", slots = N)]`, and the slot of
+/// a method with `#[slot(N)]`, both counting the three slots of `IUnknown`.
+///
+/// Such a mirror only needs to declare the methods it calls: `#[skip_slots(N)]` on a
+/// method reserves the `N` slots before it, which co_classes implementing the
+/// interface fill with a function aborting the process.
+///
+/// An interface can evolve into generations deriving from the previous one, e.g.
+/// `IFoo3: IFoo2` and `IFoo2: IFoo`. A co_class only lists the newest it implements and
+/// answers `QueryInterface` for the older ones with the same VTable, whose layout
+/// starts with theirs. Clients pick the newest generation an object supports with
+/// [`ComRc::best_of`], see [`generations`].
+///
+/// [`ComRc::best_of`]: struct.ComRc.html#method.best_of
+/// [`generations`]: generations/index.html
 pub unsafe trait ComInterface: IUnknown + 'static {
     /// A COM compatible V-Table
     type VTable;
@@ -55,6 +138,33 @@ pub unsafe trait ComInterface: IUnknown + 'static {
     }
 }
 
+/// A COM interface that is safe to call from any thread
+///
+/// `#[com_interface("...", agile)]` implements this for the interface. [`ComRc`]s of
+/// agile interfaces are `Send` and `Sync`.
+///
+/// Only [`FreeThreaded`] classes can implement agile interfaces and the interfaces
+/// inheriting from them.
+///
+/// # Safety
+///
+/// Every object implementing the interface must be callable from any thread, e.g. a
+/// co_class with `threading = "free"`.
+///
+/// [`ComRc`]: struct.ComRc.html
+/// [`FreeThreaded`]: trait.FreeThreaded.html
+pub unsafe trait AgileInterface: ComInterface {}
+
+/// A class whose objects can be called from any thread
+///
+/// `#[co_class]` implements this for classes with `threading = "free"`, `"both"` or
+/// `"neutral"` once it has checked that all their fields are `Send` and `Sync`.
+///
+/// # Safety
+///
+/// The reference count and all state of the objects must be thread safe.
+pub unsafe trait FreeThreaded: CoClass {}
+
 /// A COM compliant class
 ///
 /// # Safety
@@ -62,8 +172,52 @@ pub unsafe trait ComInterface: IUnknown + 'static {
 /// The implementing struct must have the following properties:
 /// * it is `#[repr(C)]`
 /// * The first fields of the struct are pointers to the backing VTables for
-/// each of the COM Interfaces the class implements
-pub unsafe trait CoClass: IUnknown {}
+///   each of the COM Interfaces the class implements
+pub unsafe trait CoClass: IUnknown {
+    /// The threading model the class is registered with, set with the `threading`
+    /// option of `#[co_class]`
+    ///
+    /// Classes without one are created in the main STA of the process.
+    const THREADING_MODEL: Option<ThreadingModel> = None;
+
+    /// The cache of the class object of a class with the `cache_class_object` option
+    #[doc(hidden)]
+    fn class_object_cache() -> Option<&'static registration::ObjectCache> {
+        None
+    }
+
+    /// The cache of the only instance of a class with the `singleton` option
+    #[doc(hidden)]
+    fn instance_cache() -> Option<&'static registration::ObjectCache> {
+        None
+    }
+}
+
+/// The [threading model](https://docs.microsoft.com/en-us/windows/win32/com/inprocserver32)
+/// of a CoClass
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ThreadingModel {
+    /// Objects are created in and called from a single-threaded apartment
+    Apartment,
+    /// Objects are created in and called from the multithreaded apartment
+    Free,
+    /// Objects are created in the apartment of the caller
+    Both,
+    /// Objects are created in the neutral apartment and called from any thread
+    Neutral,
+}
+
+impl ThreadingModel {
+    /// The value of the `ThreadingModel` registry key
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ThreadingModel::Apartment => "Apartment",
+            ThreadingModel::Free => "Free",
+            ThreadingModel::Both => "Both",
+            ThreadingModel::Neutral => "Neutral",
+        }
+    }
+}
 
 /// A COM interface that will be exposed in a COM server
 pub trait ProductionComInterface<T: IUnknown>: ComInterface {
@@ -71,6 +225,18 @@ pub trait ProductionComInterface<T: IUnknown>: ComInterface {
     fn vtable<O: offset::Offset>() -> Self::VTable;
 }
 
+/// A VTable slot reserved with `#[skip_slots(N)]`
+#[doc(hidden)]
+pub type ReservedSlot = unsafe extern "system" fn();
+
+/// The function of the reserved slots in the VTables of a co_class, which clients only
+/// call when they disagree with it about the layout of the interface
+#[doc(hidden)]
+pub unsafe extern "system" fn reserved_slot() {
+    // Unwinding out of an `extern "system"` function is undefined behavior
+    std::process::abort()
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! vtable {
@@ -79,6 +245,15 @@ macro_rules! vtable {
             $crate::offset::$offset,
         >();
     };
+    ($class:ident: $interface:ident, 7usize) => {
+        $crate::vtable!($class: $interface, Seven)
+    };
+    ($class:ident: $interface:ident, 6usize) => {
+        $crate::vtable!($class: $interface, Six)
+    };
+    ($class:ident: $interface:ident, 5usize) => {
+        $crate::vtable!($class: $interface, Five)
+    };
     ($class:ident: $interface:ident, 4usize) => {
         $crate::vtable!($class: $interface, Four)
     };
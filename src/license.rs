@@ -0,0 +1,109 @@
+//! Licensing of co_classes through `IClassFactory2`
+//!
+//! A co_class with the `licensed` option gets a class factory implementing
+//! `IClassFactory2`, which leaves the licensing decisions to the class's [`License`]
+//! implementation:
+//!
+//! ```rust,ignore
+//! #[co_class(implements(IChart), licensed)]
+//! pub struct Chart {}
+//!
+//! impl License for Chart {
+//!     fn is_licensed() -> bool {
+//!         license_file_installed()
+//!     }
+//!
+//!     fn runtime_key() -> Option<String> {
+//!         Some(RUNTIME_KEY.to_owned())
+//!     }
+//! }
+//! ```
+//!
+//! `CreateInstance` fails with `CLASS_E_NOTLICENSED` on machines that are not licensed,
+//! and so does `CreateInstanceLic` when given a wrong key.
+//!
+//! [`License`]: trait.License.html
+
+use crate::error::write_bstr;
+use crate::sys::{
+    SysStringLen, BSTR, CLASS_E_NOTLICENSED, E_NOTIMPL, E_POINTER, HRESULT, LICINFO, S_OK,
+};
+
+/// Decides whether instances of a licensed co_class can be created
+pub trait License {
+    /// Whether instances can be created on this machine without a key, e.g. because a
+    /// design time license is installed
+    fn is_licensed() -> bool;
+
+    /// The key handed out by `RequestLicKey` on licensed machines, to create instances
+    /// on machines without a license
+    ///
+    /// The default has no runtime key.
+    fn runtime_key() -> Option<String> {
+        None
+    }
+
+    /// Whether `key` passed to `CreateInstanceLic` permits creating an instance
+    ///
+    /// The default accepts the runtime key.
+    fn validate_key(key: &str) -> bool {
+        Self::runtime_key().map_or(false, |runtime_key| runtime_key == key)
+    }
+}
+
+/// Fail with `CLASS_E_NOTLICENSED` unless the machine is licensed
+#[doc(hidden)]
+pub fn check_license<T: License>() -> HRESULT {
+    if T::is_licensed() {
+        S_OK
+    } else {
+        CLASS_E_NOTLICENSED
+    }
+}
+
+/// Fail with `CLASS_E_NOTLICENSED` unless `key` is valid
+#[doc(hidden)]
+pub unsafe fn check_key<T: License>(key: BSTR) -> HRESULT {
+    let key = if key.is_null() {
+        String::new()
+    } else {
+        String::from_utf16_lossy(std::slice::from_raw_parts(key, SysStringLen(key) as usize))
+    };
+    if T::validate_key(&key) {
+        S_OK
+    } else {
+        CLASS_E_NOTLICENSED
+    }
+}
+
+/// Implementation of `IClassFactory2::GetLicInfo`
+#[doc(hidden)]
+pub unsafe fn get_lic_info<T: License>(lic_info: *mut LICINFO) -> HRESULT {
+    if lic_info.is_null() {
+        return E_POINTER;
+    }
+    *lic_info = LICINFO {
+        cb_lic_info: std::mem::size_of::<LICINFO>() as i32,
+        f_runtime_key_avail: T::runtime_key().is_some() as i32,
+        f_licence_verified: T::is_licensed() as i32,
+    };
+    S_OK
+}
+
+/// Implementation of `IClassFactory2::RequestLicKey`
+///
+/// Only licensed machines get the runtime key.
+#[doc(hidden)]
+pub unsafe fn request_lic_key<T: License>(key: *mut BSTR) -> HRESULT {
+    if key.is_null() {
+        return E_POINTER;
+    }
+    *key = std::ptr::null_mut();
+    if !T::is_licensed() {
+        return CLASS_E_NOTLICENSED;
+    }
+    match T::runtime_key() {
+        Some(runtime_key) => write_bstr(&runtime_key, key),
+        None => E_NOTIMPL,
+    }
+}
@@ -0,0 +1,333 @@
+//! Mock objects for COM interfaces
+//!
+//! When the `mock` feature is enabled, every interface declared with `#[com_interface]`
+//! also gets a `Mock{Interface}` type. Expectations are set on the mock and it is then
+//! converted into a [`ComRc`] which can be handed to the code under test:
+//!
+//! ```rust,ignore
+//! let mock = MockICat::new();
+//! mock.expect_ignore_humans().returning(|| S_OK);
+//! let cat: ComRc<dyn ICat> = mock.clone().into();
+//!
+//! unsafe { cat.ignore_humans() };
+//! assert_eq!(mock.calls("ignore_humans"), 1);
+//! ```
+//!
+//! Calls that have no expectation are recorded and answered with
+//! [`UnexpectedCall::unexpected_call`], or with the `Default` value of return types that
+//! don't implement it. [`Mock::checkpoint`] panics if any such call happened. Handlers must not panic as they are called across an FFI boundary.
+//!
+//! Mock objects may only be called from the thread that created them, so mocks of agile
+//! interfaces can't be converted into a `ComRc`.
+//!
+//! [`ComRc`]: ../struct.ComRc.html
+
+use crate::interfaces::iunknown::IUnknown;
+use crate::offset::Zero;
+use crate::sys::{E_NOTIMPL, HRESULT};
+use crate::{ComInterface, ComRc, ProductionComInterface};
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+/// An interface that has a generated mock type
+pub trait MockInterface: ComInterface {
+    /// The generated `Mock{Interface}` type
+    type Mock: MockHandle;
+}
+
+impl MockInterface for dyn IUnknown {
+    type Mock = Mock;
+}
+
+/// A handle to the state shared between a mock and the COM objects created from it
+pub trait MockHandle: Clone {
+    /// Wrap the base mock state
+    fn from_mock(mock: Mock) -> Self;
+}
+
+impl MockHandle for Mock {
+    fn from_mock(mock: Mock) -> Self {
+        mock
+    }
+}
+
+/// The value returned from a mocked method that was called without an expectation
+pub trait UnexpectedCall {
+    /// The value to return
+    fn unexpected_call() -> Self;
+}
+
+impl UnexpectedCall for () {
+    fn unexpected_call() -> Self {}
+}
+
+impl UnexpectedCall for HRESULT {
+    fn unexpected_call() -> Self {
+        E_NOTIMPL
+    }
+}
+
+impl<T> UnexpectedCall for *mut T {
+    fn unexpected_call() -> Self {
+        std::ptr::null_mut()
+    }
+}
+
+impl<T> UnexpectedCall for *const T {
+    fn unexpected_call() -> Self {
+        std::ptr::null()
+    }
+}
+
+macro_rules! impl_unexpected_call_default {
+    ($($ty:ty),*) => {
+        $(
+            impl UnexpectedCall for $ty {
+                fn unexpected_call() -> Self {
+                    Default::default()
+                }
+            }
+        )*
+    };
+}
+
+impl_unexpected_call_default!(bool, u8, u16, u32, u64, usize, i8, i16, i64, isize, f32, f64);
+
+/// Picks the value answering an unexpected call to a method returning `R`
+///
+/// Generated mocks call `(&Unexpected::<R>(PhantomData)).value()`, which resolves to
+/// [`UnexpectedValue`] if `R` implements [`UnexpectedCall`] and to [`DefaultValue`]
+/// otherwise, since method resolution tries the receiver before borrowing it again.
+#[doc(hidden)]
+pub struct Unexpected<R>(pub PhantomData<R>);
+
+#[doc(hidden)]
+pub trait UnexpectedValue<R> {
+    fn value(&self) -> R;
+}
+
+impl<R: UnexpectedCall> UnexpectedValue<R> for Unexpected<R> {
+    fn value(&self) -> R {
+        R::unexpected_call()
+    }
+}
+
+#[doc(hidden)]
+pub trait DefaultValue<R> {
+    fn value(&self) -> R;
+}
+
+impl<R: Default> DefaultValue<R> for &Unexpected<R> {
+    fn value(&self) -> R {
+        R::default()
+    }
+}
+
+#[derive(Default)]
+struct Method {
+    handler: Option<Box<dyn Any>>,
+    calls: usize,
+    expected_calls: Option<usize>,
+}
+
+#[derive(Default)]
+struct MockState {
+    methods: RefCell<HashMap<&'static str, Method>>,
+    unexpected_calls: RefCell<Vec<&'static str>>,
+}
+
+/// The expectations and recorded calls of a mock object
+///
+/// All generated `Mock{Interface}` types dereference to this type.
+#[derive(Clone, Default)]
+pub struct Mock {
+    state: Rc<MockState>,
+}
+
+impl Mock {
+    /// Create a mock without any expectations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start an expectation for `method`
+    #[doc(hidden)]
+    pub fn expect<F: ?Sized + 'static>(&self, method: &'static str) -> Expectation<'_, F> {
+        self.state.methods.borrow_mut().entry(method).or_default();
+        Expectation {
+            mock: self,
+            method,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Record a call to `method` and forward it to its handler
+    #[doc(hidden)]
+    pub fn call<F: ?Sized + 'static, R>(
+        &self,
+        method: &'static str,
+        unexpected: impl FnOnce() -> R,
+        call: impl FnOnce(&mut F) -> R,
+    ) -> R {
+        let handler = {
+            let mut methods = self.state.methods.borrow_mut();
+            let entry = methods.entry(method).or_default();
+            entry.calls += 1;
+            entry.handler.take()
+        };
+        let mut handler = match handler {
+            Some(handler) => handler,
+            None => {
+                self.state.unexpected_calls.borrow_mut().push(method);
+                return unexpected();
+            }
+        };
+
+        let result = match handler.downcast_mut::<Box<F>>() {
+            Some(f) => call(&mut **f),
+            None => panic!(
+                "the handler for the mocked method `{}` does not match its signature",
+                method
+            ),
+        };
+
+        // The handler may have been replaced while it was running
+        let mut methods = self.state.methods.borrow_mut();
+        let entry = methods.entry(method).or_default();
+        if entry.handler.is_none() {
+            entry.handler = Some(handler);
+        }
+        result
+    }
+
+    /// The number of times `method` has been called
+    pub fn calls(&self, method: &str) -> usize {
+        self.state
+            .methods
+            .borrow()
+            .get(method)
+            .map(|m| m.calls)
+            .unwrap_or(0)
+    }
+
+    /// Verify that every expectation was met so far
+    ///
+    /// # Panics
+    ///
+    /// Panics if a method without an expectation was called or if a method was not
+    /// called the number of times given to [`Expectation::times`].
+    pub fn checkpoint(&self) {
+        let unexpected = self.state.unexpected_calls.borrow();
+        if !unexpected.is_empty() {
+            panic!("unexpected calls to mocked methods: {:?}", &*unexpected);
+        }
+        for (name, method) in self.state.methods.borrow().iter() {
+            if let Some(expected) = method.expected_calls {
+                assert_eq!(
+                    method.calls, expected,
+                    "expected `{}` to be called {} times, but it was called {} times",
+                    name, expected, method.calls
+                );
+            }
+        }
+    }
+}
+
+/// An expectation on a single mocked method
+///
+/// `F` is the signature of the method, e.g. `dyn FnMut(u32) -> HRESULT`.
+pub struct Expectation<'a, F: ?Sized> {
+    mock: &'a Mock,
+    method: &'static str,
+    phantom: PhantomData<Box<F>>,
+}
+
+impl<'a, F: ?Sized + 'static> Expectation<'a, F> {
+    /// Expect the method to be called exactly `count` times
+    ///
+    /// This is verified by [`Mock::checkpoint`].
+    pub fn times(self, count: usize) -> Self {
+        if let Some(method) = self.mock.state.methods.borrow_mut().get_mut(self.method) {
+            method.expected_calls = Some(count);
+        }
+        self
+    }
+
+    fn set_handler(&self, handler: Box<F>) {
+        if let Some(method) = self.mock.state.methods.borrow_mut().get_mut(self.method) {
+            method.handler = Some(Box::new(handler));
+        }
+    }
+}
+
+macro_rules! impl_expectation {
+    ($($arg:ident),*) => {
+        impl<'a, $($arg: 'static,)* R: 'static> Expectation<'a, dyn FnMut($($arg),*) -> R> {
+            /// Answer calls to the method with the given closure
+            pub fn returning<F: FnMut($($arg),*) -> R + 'static>(self, f: F) -> Self {
+                self.set_handler(Box::new(f));
+                self
+            }
+
+            /// Answer calls to the method with a clone of `value`
+            pub fn return_const(self, value: R) -> Self
+            where
+                R: Clone,
+            {
+                self.returning(move |$(_: $arg),*| value.clone())
+            }
+        }
+    };
+}
+
+impl_expectation!();
+impl_expectation!(A);
+impl_expectation!(A, B);
+impl_expectation!(A, B, C);
+impl_expectation!(A, B, C, D);
+impl_expectation!(A, B, C, D, E);
+impl_expectation!(A, B, C, D, E, G);
+impl_expectation!(A, B, C, D, E, G, H);
+impl_expectation!(A, B, C, D, E, G, H, I);
+impl_expectation!(A, B, C, D, E, G, H, I, J);
+impl_expectation!(A, B, C, D, E, G, H, I, J, K);
+impl_expectation!(A, B, C, D, E, G, H, I, J, K, L);
+impl_expectation!(A, B, C, D, E, G, H, I, J, K, L, M);
+
+/// The COM object behind a mock
+///
+/// It implements every interface in the inheritance chain of `I` by forwarding calls to
+/// the expectations of its [`Mock`].
+#[repr(C)]
+pub struct MockObject<I: ComInterface + ?Sized> {
+    vptr: *const <I as ComInterface>::VTable,
+    ref_count: Cell<u32>,
+    mock: Mock,
+}
+
+impl<I: ComInterface + ?Sized> MockObject<I> {
+    /// Create a new COM object answering calls with the expectations of `mock`
+    pub fn create(mock: &Mock) -> ComRc<I>
+    where
+        I: ProductionComInterface<Self>,
+    {
+        let vptr = Box::into_raw(Box::new(I::vtable::<Zero>()));
+        let object = Box::into_raw(Box::new(MockObject::<I> {
+            vptr,
+            ref_count: Cell::new(1),
+            mock: mock.clone(),
+        }));
+        unsafe { ComRc::from_raw(object as *mut *mut <I as ComInterface>::VTable) }
+    }
+
+    #[doc(hidden)]
+    pub fn mock(&self) -> &Mock {
+        &self.mock
+    }
+}
+
+impl_iunknown!([I: ComInterface + ?Sized] MockObject<I> => I);
@@ -14,4 +14,6 @@ macro_rules! declare_offset {
     };
 }
 
-declare_offset!(Zero => 0, One => 1, Two => 2, Three => 3, Four => 4);
+declare_offset!(
+    Zero => 0, One => 1, Two => 2, Three => 3, Four => 4, Five => 5, Six => 6, Seven => 7
+);
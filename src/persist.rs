@@ -0,0 +1,144 @@
+//! Persisting the state of a co_class with serde
+//!
+//! A co_class opts in with the `persist_stream` option naming a [`PersistedState`] field.
+//! `IPersist` and `IPersistStream` are then generated for the class:
+//!
+//! ```rust,ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct CatState {
+//!     lives: u32,
+//! }
+//!
+//! #[co_class(implements(ICat, IPersistStream), persist_stream(state))]
+//! pub struct BritishShortHairCat {
+//!     state: PersistedState<CatState>,
+//! }
+//! ```
+//!
+//! The state is written to the stream with `bincode`. Changes made through
+//! [`PersistedState::update`] mark the object as dirty until it is saved or loaded.
+//!
+//! [`PersistedState`]: struct.PersistedState.html
+//! [`PersistedState::update`]: struct.PersistedState.html#method.update
+
+use crate::interfaces::istream::{IStream, IStreamVPtr};
+use crate::interfaces::stream::ComStream;
+use crate::sys::{
+    BOOL, CLSID, E_POINTER, HRESULT, STG_E_READFAULT, STG_E_WRITEFAULT, S_FALSE, S_OK,
+};
+use crate::ComPtr;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use std::cell::{Cell, Ref, RefCell};
+
+/// The serializable state of a co_class implementing `IPersistStream`
+pub struct PersistedState<T> {
+    clsid: CLSID,
+    value: RefCell<T>,
+    dirty: Cell<bool>,
+}
+
+impl<T: Serialize + DeserializeOwned> PersistedState<T> {
+    /// Create the state of an object of the class `clsid`
+    ///
+    /// `clsid` is returned from `IPersist::GetClassID`. A new state is not dirty.
+    pub fn new(clsid: CLSID, value: T) -> Self {
+        PersistedState {
+            clsid,
+            value: RefCell::new(value),
+            dirty: Cell::new(false),
+        }
+    }
+
+    /// Borrow the current state
+    pub fn get(&self) -> Ref<'_, T> {
+        self.value.borrow()
+    }
+
+    /// Change the state and mark it as dirty
+    pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.dirty.set(true);
+        f(&mut self.value.borrow_mut())
+    }
+
+    /// Whether the state changed since it was last saved or loaded
+    pub fn dirty(&self) -> bool {
+        self.dirty.get()
+    }
+
+    /// Implementation of `IPersist::GetClassID`
+    #[doc(hidden)]
+    pub unsafe fn get_class_id(&self, class_id: *mut CLSID) -> HRESULT {
+        if class_id.is_null() {
+            return E_POINTER;
+        }
+        *class_id = self.clsid;
+        S_OK
+    }
+
+    /// Implementation of `IPersistStream::IsDirty`
+    #[doc(hidden)]
+    pub fn is_dirty(&self) -> HRESULT {
+        if self.dirty.get() {
+            S_OK
+        } else {
+            S_FALSE
+        }
+    }
+
+    /// Implementation of `IPersistStream::Load`
+    #[doc(hidden)]
+    pub unsafe fn load(&self, stream: *mut IStreamVPtr) -> HRESULT {
+        if stream.is_null() {
+            return E_POINTER;
+        }
+        let stream = ComPtr::<dyn IStream>::new(stream as *mut _)
+            .clone()
+            .upgrade();
+        match bincode::deserialize_from(ComStream::new(stream)) {
+            Ok(value) => {
+                *self.value.borrow_mut() = value;
+                self.dirty.set(false);
+                S_OK
+            }
+            Err(_) => STG_E_READFAULT,
+        }
+    }
+
+    /// Implementation of `IPersistStream::Save`
+    #[doc(hidden)]
+    pub unsafe fn save(&self, stream: *mut IStreamVPtr, clear_dirty: BOOL) -> HRESULT {
+        if stream.is_null() {
+            return E_POINTER;
+        }
+        let stream = ComPtr::<dyn IStream>::new(stream as *mut _)
+            .clone()
+            .upgrade();
+        match bincode::serialize_into(ComStream::new(stream), &*self.value.borrow()) {
+            Ok(()) => {
+                if clear_dirty != 0 {
+                    self.dirty.set(false);
+                }
+                S_OK
+            }
+            Err(_) => STG_E_WRITEFAULT,
+        }
+    }
+
+    /// Implementation of `IPersistStream::GetSizeMax`
+    #[doc(hidden)]
+    pub unsafe fn get_size_max(&self, pcb_size: *mut u64) -> HRESULT {
+        if pcb_size.is_null() {
+            return E_POINTER;
+        }
+        match bincode::serialized_size(&*self.value.borrow()) {
+            Ok(size) => {
+                *pcb_size = size;
+                S_OK
+            }
+            Err(_) => STG_E_WRITEFAULT,
+        }
+    }
+}
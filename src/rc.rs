@@ -1,4 +1,7 @@
-use crate::{interfaces::IUnknown, ComInterface, ComPtr};
+use crate::error::ComError;
+use crate::generations::Generations;
+use crate::sys::{FAILED, HRESULT};
+use crate::{interfaces::IUnknown, AgileInterface, ComInterface, ComPtr, ComWeak};
 
 /// A reference counted COM interface.
 ///
@@ -10,7 +13,12 @@ use crate::{interfaces::IUnknown, ComInterface, ComPtr};
 /// automatically performing `AddRef` and `Release`, you can use the [`ComPtr`]
 /// type.
 ///
+/// A `ComRc` can only be sent to or shared with other threads if `T` is an
+/// [`AgileInterface`]. Use an [`AgileRef`] for other interfaces.
+///
 /// [`ComPtr`]: struct.ComPtr.html
+/// [`AgileInterface`]: trait.AgileInterface.html
+/// [`AgileRef`]: agile/struct.AgileRef.html
 pub struct ComRc<T: ComInterface + ?Sized> {
     ptr: ComPtr<T>,
 }
@@ -42,8 +50,45 @@ impl<T: ComInterface + ?Sized> ComRc<T> {
     pub fn get_interface<I: ComInterface + ?Sized>(&self) -> Option<ComRc<I>> {
         self.ptr.get_interface().map(|ptr| ptr.upgrade())
     }
+
+    /// Query the object for the newest of several generations of an interface
+    ///
+    /// The generations are listed from the newest as the type parameters of one of the
+    /// enums in [`generations`], whose variant holds the first supported interface.
+    /// Returns `None` if the object supports none of them.
+    ///
+    /// [`generations`]: generations/index.html
+    pub fn best_of<G: Generations>(&self) -> Option<G> {
+        G::query(self)
+    }
+
+    /// Get a weak reference to the object, which must implement `IWeakReferenceSource`
+    ///
+    /// See [`ComWeak`](struct.ComWeak.html).
+    pub fn downgrade(&self) -> Result<ComWeak<T>, HRESULT> {
+        ComWeak::new(self)
+    }
+
+    /// Make a call through this interface, turning a failing `HRESULT` into a [`ComError`]
+    ///
+    /// The error carries the description and source the server set for the call if the
+    /// object implements `ISupportErrorInfo` for `T`. Success codes are returned as is.
+    ///
+    /// [`ComError`]: error/struct.ComError.html
+    pub fn call<F: FnOnce(&Self) -> HRESULT>(&self, f: F) -> Result<HRESULT, ComError> {
+        let hr = f(self);
+        if FAILED(hr) {
+            Err(ComError::from_call(self, &T::IID, hr))
+        } else {
+            Ok(hr)
+        }
+    }
 }
 
+// The objects behind agile interfaces can be called and released from any thread
+unsafe impl<T: AgileInterface + ?Sized> Send for ComRc<T> {}
+unsafe impl<T: AgileInterface + ?Sized> Sync for ComRc<T> {}
+
 impl<T: ComInterface + ?Sized> Drop for ComRc<T> {
     fn drop(&mut self) {
         unsafe {
@@ -0,0 +1,80 @@
+//! Reference counts of generated co_classes
+//!
+//! Apartment threaded classes count with a `Cell<u32>`. Classes that may be called from
+//! any thread (`threading = "free"`, `"both"` or `"neutral"`) count with an `AtomicU32`.
+use std::cell::Cell;
+use std::sync::atomic::{fence, AtomicU32, Ordering};
+
+/// A reference count starting at zero
+pub trait RefCount: Default {
+    /// Add a reference and return the new count
+    fn increment(&self) -> u32;
+    /// Remove a reference and return the new count
+    fn decrement(&self) -> u32;
+    /// Add a reference unless the count already dropped to zero
+    ///
+    /// Weak references use this to upgrade only while the object is alive.
+    fn try_increment(&self) -> bool;
+}
+
+impl RefCount for Cell<u32> {
+    fn increment(&self) -> u32 {
+        let value = self
+            .get()
+            .checked_add(1)
+            .expect("Overflow of reference count");
+        self.set(value);
+        value
+    }
+
+    fn decrement(&self) -> u32 {
+        let value = self
+            .get()
+            .checked_sub(1)
+            .expect("Underflow of reference count");
+        self.set(value);
+        value
+    }
+
+    fn try_increment(&self) -> bool {
+        if self.get() == 0 {
+            return false;
+        }
+        self.increment();
+        true
+    }
+}
+
+impl RefCount for AtomicU32 {
+    fn increment(&self) -> u32 {
+        self.fetch_add(1, Ordering::Relaxed)
+            .checked_add(1)
+            .expect("Overflow of reference count")
+    }
+
+    fn decrement(&self) -> u32 {
+        let value = self
+            .fetch_sub(1, Ordering::Release)
+            .checked_sub(1)
+            .expect("Underflow of reference count");
+        if value == 0 {
+            // Synchronize with the other releases before the object is dropped
+            fence(Ordering::Acquire);
+        }
+        value
+    }
+
+    fn try_increment(&self) -> bool {
+        let mut current = self.load(Ordering::Relaxed);
+        loop {
+            if current == 0 {
+                return false;
+            }
+            let value = current.checked_add(1).expect("Overflow of reference count");
+            match self.compare_exchange_weak(current, value, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
@@ -3,20 +3,23 @@
 use crate::interfaces::IUnknown;
 use crate::sys::{
     GetModuleFileNameA, GetModuleHandleA, RegCloseKey, RegCreateKeyExA, RegDeleteKeyA,
-    RegSetValueExA, CLSID, ERROR_SUCCESS, FAILED, GUID, HKEY, HRESULT, IID, LSTATUS,
-    SELFREG_E_CLASS, S_OK,
+    RegSetValueExA, BOOL, CLSID, ERROR_SUCCESS, E_UNEXPECTED, FAILED, GUID, HKEY, HRESULT, IID,
+    LSTATUS, SELFREG_E_CLASS, S_FALSE, S_OK,
 };
+use crate::{ComPtr, ThreadingModel};
 
+use std::cell::UnsafeCell;
 use std::convert::TryInto;
 use std::ffi::c_void;
 use std::ffi::CString;
+use std::path::Path;
 use std::str;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
 
 #[doc(hidden)]
 pub struct RegistryKeyInfo {
     key_path: CString,
-    key_value_name: CString,
-    key_value_data: CString,
+    key_values: Vec<(CString, CString)>,
 }
 
 #[doc(hidden)]
@@ -24,16 +27,33 @@ impl RegistryKeyInfo {
     pub fn new(key_path: &str, key_value_name: &str, key_value_data: &str) -> RegistryKeyInfo {
         RegistryKeyInfo {
             key_path: CString::new(key_path).unwrap(),
-            key_value_name: CString::new(key_value_name).unwrap(),
-            key_value_data: CString::new(key_value_data).unwrap(),
+            key_values: Vec::new(),
         }
+        .with_value(key_value_name, key_value_data)
+    }
+
+    /// A key without values, e.g. one holding only other keys
+    pub fn empty(key_path: &str) -> RegistryKeyInfo {
+        RegistryKeyInfo {
+            key_path: CString::new(key_path).unwrap(),
+            key_values: Vec::new(),
+        }
+    }
+
+    /// Set another value of the same key
+    pub fn with_value(mut self, key_value_name: &str, key_value_data: &str) -> RegistryKeyInfo {
+        self.key_values.push((
+            CString::new(key_value_name).unwrap(),
+            CString::new(key_value_data).unwrap(),
+        ));
+        self
     }
 }
 
 #[doc(hidden)]
-pub fn register_keys(registry_keys_to_add: &Vec<RegistryKeyInfo>) -> HRESULT {
+pub fn register_keys(registry_keys_to_add: &[RegistryKeyInfo]) -> HRESULT {
     for key_info in registry_keys_to_add.iter() {
-        let result = add_class_key(&key_info);
+        let result = add_class_key(key_info);
         if result as u32 != ERROR_SUCCESS {
             return SELFREG_E_CLASS;
         }
@@ -43,10 +63,10 @@ pub fn register_keys(registry_keys_to_add: &Vec<RegistryKeyInfo>) -> HRESULT {
 }
 
 #[doc(hidden)]
-pub fn unregister_keys(registry_keys_to_remove: &Vec<RegistryKeyInfo>) -> HRESULT {
+pub fn unregister_keys(registry_keys_to_remove: &[RegistryKeyInfo]) -> HRESULT {
     let mut hr = S_OK;
     for key_info in registry_keys_to_remove.iter() {
-        let result = remove_class_key(&key_info);
+        let result = remove_class_key(key_info);
         if result as u32 != ERROR_SUCCESS {
             hr = SELFREG_E_CLASS;
         }
@@ -85,23 +105,20 @@ fn create_class_key(key_info: &RegistryKeyInfo) -> Result<HKEY, LSTATUS> {
 
 const REG_SZ: u32 = 1;
 fn set_class_key(key_handle: HKEY, key_info: &RegistryKeyInfo) -> Result<HKEY, LSTATUS> {
-    let result = unsafe {
-        RegSetValueExA(
-            key_handle,
-            key_info.key_value_name.as_ptr(),
-            0,
-            REG_SZ,
-            key_info.key_value_data.as_ptr() as *const u8,
-            key_info
-                .key_value_data
-                .to_bytes_with_nul()
-                .len()
-                .try_into()
-                .unwrap(),
-        )
-    };
-    if result as u32 != ERROR_SUCCESS {
-        return Err(result);
+    for (key_value_name, key_value_data) in &key_info.key_values {
+        let result = unsafe {
+            RegSetValueExA(
+                key_handle,
+                key_value_name.as_ptr(),
+                0,
+                REG_SZ,
+                key_value_data.as_ptr() as *const u8,
+                key_value_data.to_bytes_with_nul().len().try_into().unwrap(),
+            )
+        };
+        if result as u32 != ERROR_SUCCESS {
+            return Err(result);
+        }
     }
 
     Ok(key_handle)
@@ -156,9 +173,97 @@ pub fn class_inproc_key_path(clsid: CLSID) -> String {
     format!("CLSID\\{}\\InprocServer32", guid_to_string(&clsid))
 }
 
+/// The `InprocServer32` key of a class, including its threading model if it has one
+#[doc(hidden)]
+pub fn class_inproc_key_info(
+    clsid: CLSID,
+    file_path: &str,
+    threading_model: Option<ThreadingModel>,
+) -> RegistryKeyInfo {
+    let key_info = RegistryKeyInfo::new(&class_inproc_key_path(clsid), "", file_path);
+    match threading_model {
+        Some(model) => key_info.with_value("ThreadingModel", model.as_str()),
+        None => key_info,
+    }
+}
+
+/// The CLSID of the proxy/stub the type library marshaler provides for interfaces
+/// described by a registered type library
+const PSOA_INTERFACE: &str = "{00020424-0000-0000-C000-000000000046}";
+
+/// The path of the type library shipped next to the DLL at `file_path`, with the same
+/// name and a `.tlb` extension
+#[doc(hidden)]
+pub fn type_lib_path(file_path: &str) -> String {
+    Path::new(file_path)
+        .with_extension("tlb")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The `TypeLib` keys registering the type library at `tlb_path`, parents first
+#[doc(hidden)]
+pub fn type_lib_key_infos(
+    libid: GUID,
+    version: (u16, u16),
+    description: &str,
+    tlb_path: &str,
+) -> Vec<RegistryKeyInfo> {
+    let platform = if cfg!(target_pointer_width = "64") {
+        "win64"
+    } else {
+        "win32"
+    };
+    let help_dir = Path::new(tlb_path)
+        .parent()
+        .map(|dir| dir.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let library_key = format!("TypeLib\\{}", guid_to_string(&libid));
+    let version_key = format!("{}\\{:x}.{:x}", library_key, version.0, version.1);
+    vec![
+        RegistryKeyInfo::empty(&library_key),
+        RegistryKeyInfo::new(&version_key, "", description),
+        RegistryKeyInfo::empty(&format!("{}\\0", version_key)),
+        RegistryKeyInfo::new(&format!("{}\\0\\{}", version_key, platform), "", tlb_path),
+        RegistryKeyInfo::new(&format!("{}\\FLAGS", version_key), "", "0"),
+        RegistryKeyInfo::new(&format!("{}\\HELPDIR", version_key), "", &help_dir),
+    ]
+}
+
+/// The `Interface` keys of an interface described by a registered type library, which
+/// let COM marshal it through the type library marshaler, parents first
+#[doc(hidden)]
+pub fn interface_key_infos(
+    iid: IID,
+    name: &str,
+    libid: GUID,
+    version: (u16, u16),
+) -> Vec<RegistryKeyInfo> {
+    let interface_key = format!("Interface\\{}", guid_to_string(&iid));
+    vec![
+        RegistryKeyInfo::new(&interface_key, "", name),
+        RegistryKeyInfo::new(
+            &format!("{}\\ProxyStubClsid32", interface_key),
+            "",
+            PSOA_INTERFACE,
+        ),
+        RegistryKeyInfo::new(
+            &format!("{}\\ProxyStubClsid", interface_key),
+            "",
+            PSOA_INTERFACE,
+        ),
+        RegistryKeyInfo::new(
+            &format!("{}\\TypeLib", interface_key),
+            "",
+            &guid_to_string(&libid),
+        )
+        .with_value("Version", &format!("{:x}.{:x}", version.0, version.1)),
+    ]
+}
+
 fn guid_to_string(guid: &GUID) -> String {
     format!(
-        "{{{:04X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}}}",
+        "{{{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}}}",
         guid.data1,
         guid.data2,
         guid.data3,
@@ -175,26 +280,225 @@ fn guid_to_string(guid: &GUID) -> String {
 
 #[doc(hidden)]
 #[inline]
-pub fn initialize_class_object<T: IUnknown>(
+pub unsafe fn initialize_class_object<T: IUnknown>(
     instance: Box<T>,
     riid: *const IID,
     result: *mut *mut c_void,
 ) -> HRESULT {
-    let hr = unsafe {
-        instance.add_ref();
-        let hr = instance.query_interface(riid, result);
-        instance.release();
-        hr
-    };
-    Box::into_raw(instance);
+    instance.add_ref();
+    let hr = instance.query_interface(riid, result);
+    instance.release();
+    std::mem::forget(instance);
 
     hr
 }
 
+/// Get the class object of a co_class for `DllGetClassObject`
+///
+/// The class object is created by `create`, or taken from `cache` if the class has one.
+#[doc(hidden)]
+pub unsafe fn get_class_object<T: IUnknown>(
+    cache: Option<&ObjectCache>,
+    create: fn() -> Box<T>,
+    riid: *const IID,
+    result: *mut *mut c_void,
+) -> HRESULT {
+    match cache {
+        Some(cache) => cache.query(create, riid, result),
+        None => initialize_class_object(create(), riid, result),
+    }
+}
+
+/// The number of live co_class instances and server locks of this module
+static MODULE_LOCKS: AtomicU32 = AtomicU32::new(0);
+
+/// Keep the module loaded, for every co_class instance and `LockServer(TRUE)`
+#[doc(hidden)]
+pub fn lock_module() {
+    MODULE_LOCKS.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Undo `lock_module`
+#[doc(hidden)]
+pub fn unlock_module() {
+    MODULE_LOCKS.fetch_sub(1, Ordering::SeqCst);
+}
+
+/// Implementation of `IClassFactory::LockServer`
+#[doc(hidden)]
+pub fn lock_server(lock: BOOL) -> HRESULT {
+    if lock != 0 {
+        lock_module();
+    } else {
+        unlock_module();
+    }
+    S_OK
+}
+
+/// An object that is created once and shared until the module is unloaded
+///
+/// Holds the class object of a co_class with the `cache_class_object` option, or the
+/// instance of a co_class with the `singleton` option.
+#[doc(hidden)]
+pub struct ObjectCache {
+    /// Whether the object is a co_class instance, which keeps the module loaded
+    instance: bool,
+    /// The thread holding the lock, see `current_thread`, or zero
+    owner: AtomicUsize,
+    /// The object, whose first field is an interface pointer, holding a reference
+    object: UnsafeCell<*mut c_void>,
+}
+
+// The object is only accessed while holding the lock
+unsafe impl Sync for ObjectCache {}
+
+struct ObjectCacheGuard<'a>(&'a AtomicUsize);
+
+impl Drop for ObjectCacheGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(0, Ordering::Release);
+    }
+}
+
+/// A non-zero number identifying the current thread among the running ones
+fn current_thread() -> usize {
+    thread_local! {
+        static THREAD: u8 = 0;
+    }
+    THREAD.with(|thread| thread as *const u8 as usize)
+}
+
+impl ObjectCache {
+    /// A cache for a class object
+    pub const fn class_object() -> Self {
+        Self::new(false)
+    }
+
+    /// A cache for a co_class instance
+    pub const fn instance() -> Self {
+        Self::new(true)
+    }
+
+    const fn new(instance: bool) -> Self {
+        ObjectCache {
+            instance,
+            owner: AtomicUsize::new(0),
+            object: UnsafeCell::new(std::ptr::null_mut()),
+        }
+    }
+
+    /// Lock the cache, or return `None` if the current thread already holds the lock
+    ///
+    /// The lock is held while the object is created, which may call back into the
+    /// module, e.g. to create an instance of the same class.
+    fn lock(&self) -> Option<ObjectCacheGuard<'_>> {
+        let thread = current_thread();
+        loop {
+            match self
+                .owner
+                .compare_exchange_weak(0, thread, Ordering::Acquire, Ordering::Relaxed)
+            {
+                Ok(_) => return Some(ObjectCacheGuard(&self.owner)),
+                Err(owner) if owner == thread => return None,
+                Err(_) => std::thread::yield_now(),
+            }
+        }
+    }
+
+    /// Lock the cache if no thread holds the lock
+    fn try_lock(&self) -> Option<ObjectCacheGuard<'_>> {
+        self.owner
+            .compare_exchange(0, current_thread(), Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| ObjectCacheGuard(&self.owner))
+    }
+
+    /// Query the cached object for `riid`, creating it with `create` first if needed
+    ///
+    /// Fails with `E_UNEXPECTED` if `create` queries the cache it is creating the
+    /// object for, which would otherwise never return.
+    pub unsafe fn query<T: IUnknown>(
+        &self,
+        create: fn() -> Box<T>,
+        riid: *const IID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT {
+        let _guard = match self.lock() {
+            Some(guard) => guard,
+            None => {
+                *ppv = std::ptr::null_mut();
+                return E_UNEXPECTED;
+            }
+        };
+        let object = &mut *self.object.get();
+        if object.is_null() {
+            let instance = create();
+            instance.add_ref();
+            *object = Box::into_raw(instance) as *mut c_void;
+        }
+        ComPtr::<dyn IUnknown>::new(*object as *mut _).query_interface(riid, ppv)
+    }
+
+    /// Whether the cached object exists and the cache holds its only reference
+    ///
+    /// The caller must hold the lock.
+    unsafe fn is_idle(&self) -> bool {
+        let object = *self.object.get();
+        if object.is_null() {
+            return false;
+        }
+        let object = ComPtr::<dyn IUnknown>::new(object as *mut _);
+        object.add_ref();
+        object.release() == 1
+    }
+
+    /// Take the cached object out of the cache, along with its reference
+    ///
+    /// The caller must hold the lock.
+    unsafe fn take(&self) -> *mut c_void {
+        std::mem::replace(&mut *self.object.get(), std::ptr::null_mut())
+    }
+}
+
+/// Implementation of `DllCanUnloadNow`
+///
+/// The module can be unloaded once the only live instances are singletons held by
+/// their cache and no server locks are left. The cached objects are then released.
+/// It can't while one of the caches is in use, e.g. while its object is being created.
+#[doc(hidden)]
+pub fn dll_can_unload_now(caches: &[Option<&ObjectCache>]) -> HRESULT {
+    let caches: Vec<&ObjectCache> = caches.iter().flatten().copied().collect();
+    // Every cache stays locked from the check until its object is taken, so none of them
+    // can hand out a new reference in between. Waiting for a cache could deadlock with
+    // the thread creating its object, which may use the other caches.
+    let guards: Option<Vec<_>> = caches.iter().map(|cache| cache.try_lock()).collect();
+    let guards = match guards {
+        Some(guards) => guards,
+        None => return S_FALSE,
+    };
+    let idle = caches
+        .iter()
+        .filter(|cache| cache.instance && unsafe { cache.is_idle() })
+        .count();
+    if MODULE_LOCKS.load(Ordering::SeqCst) as usize != idle {
+        return S_FALSE;
+    }
+    let objects: Vec<_> = caches.iter().map(|cache| unsafe { cache.take() }).collect();
+    drop(guards);
+
+    // Releasing runs the destructors, which must not find the caches locked
+    for object in objects {
+        if !object.is_null() {
+            unsafe { ComPtr::<dyn IUnknown>::new(object as *mut _).release() };
+        }
+    }
+    S_OK
+}
+
 /// Register the supplied keys with the registry
 #[doc(hidden)]
 #[inline]
-pub fn dll_register_server(relevant_keys: &mut Vec<RegistryKeyInfo>) -> HRESULT {
+pub fn dll_register_server(relevant_keys: &mut [RegistryKeyInfo]) -> HRESULT {
     let hr = register_keys(relevant_keys);
     if FAILED(hr) {
         dll_unregister_server(relevant_keys);
@@ -206,71 +510,132 @@ pub fn dll_register_server(relevant_keys: &mut Vec<RegistryKeyInfo>) -> HRESULT
 /// Unregister the supplied keys with the registry
 #[doc(hidden)]
 #[inline]
-pub fn dll_unregister_server(relevant_keys: &mut Vec<RegistryKeyInfo>) -> HRESULT {
+pub fn dll_unregister_server(relevant_keys: &mut [RegistryKeyInfo]) -> HRESULT {
     relevant_keys.reverse();
     unregister_keys(relevant_keys)
 }
 
 /// A macro for declaring a COM server to the COM runtime
 ///
-/// This implements the `DllGetClassObject`, `DllRegisterServer`, and `DllUnregisterServer`
-/// functions on behalf of the user.
+/// This implements the `DllGetClassObject`, `DllCanUnloadNow`, `DllRegisterServer`, and
+/// `DllUnregisterServer` functions on behalf of the user.
+///
+/// A leading `type_lib(LIBID, "description", major, minor, interfaces(IFoo, ...))` also
+/// registers the type library shipped next to the DLL as `<name>.tlb`, e.g. one written
+/// by `com_macros_support::tlb::write`, and the listed interfaces, so COM marshals them
+/// through the type library marshaler:
+///
+/// ```rust,ignore
+/// com::inproc_dll_module![
+///     type_lib(LIBID_ANIMAL_LIB, "Animals", 1, 0, interfaces(IAnimal, ICat)),
+///     (CLSID_CAT_CLASS, BritishShortHairCat),
+/// ];
+/// ```
 #[macro_export]
 macro_rules! inproc_dll_module {
+    (type_lib($lib_id:ident, $description:expr, $major:expr, $minor:expr, interfaces($($interface:ident),* $(,)?)), $(($class_id:ident, $class_type:ty)),+ $(,)?) => {
+        $crate::inproc_dll_module!(@keys [
+            com::registration::type_lib_key_infos(
+                $lib_id,
+                ($major, $minor),
+                $description,
+                &com::registration::type_lib_path(&com::registration::get_dll_file_path()),
+            )
+            $(, com::registration::interface_key_infos(
+                <dyn $interface as com::ComInterface>::IID,
+                stringify!($interface),
+                $lib_id,
+                ($major, $minor),
+            ))*
+        ] $(($class_id, $class_type)),+);
+    };
     (($class_id_one:ident, $class_type_one:ty), $(($class_id:ident, $class_type:ty)),*) => {
+        $crate::inproc_dll_module!(@keys [] ($class_id_one, $class_type_one), $(($class_id, $class_type)),*);
+    };
+    (@keys [$($extra_keys:expr),*] ($class_id_one:ident, $class_type_one:ty) $(, ($class_id:ident, $class_type:ty))* $(,)?) => {
         #[no_mangle]
-        extern "stdcall" fn DllGetClassObject(class_id: *const com::sys::CLSID, iid: *const com::sys::IID, result: *mut *mut std::ffi::c_void) -> com::sys::HRESULT {
-            use com::interfaces::IUnknown;
-            use com::registration::initialize_class_object;
+        extern "system" fn DllGetClassObject(class_id: *const com::sys::CLSID, iid: *const com::sys::IID, result: *mut *mut std::ffi::c_void) -> com::sys::HRESULT {
+            use com::registration::get_class_object;
             assert!(!class_id.is_null(), "class id passed to DllGetClassObject should never be null");
 
             let class_id = unsafe { &*class_id };
             if class_id == &$class_id_one {
-                let mut instance = <$class_type_one>::get_class_object();
-                initialize_class_object(instance, iid, result)
+                let cache = <$class_type_one as com::CoClass>::class_object_cache();
+                unsafe { get_class_object(cache, <$class_type_one>::get_class_object, iid, result) }
             } $(else if class_id == &$class_id {
-                let mut instance = <$class_type>::get_class_object();
-                initialize_class_object(instance, iid, result)
+                let cache = <$class_type as com::CoClass>::class_object_cache();
+                unsafe { get_class_object(cache, <$class_type>::get_class_object, iid, result) }
             })* else {
                 com::sys::CLASS_E_CLASSNOTAVAILABLE
             }
         }
 
         #[no_mangle]
-        extern "stdcall" fn DllRegisterServer() -> com::sys::HRESULT {
+        extern "system" fn DllCanUnloadNow() -> com::sys::HRESULT {
+            com::registration::dll_can_unload_now(&[
+                <$class_type_one as com::CoClass>::class_object_cache(),
+                <$class_type_one as com::CoClass>::instance_cache(),
+                $(<$class_type as com::CoClass>::class_object_cache(),
+                <$class_type as com::CoClass>::instance_cache(),)*
+            ])
+        }
+
+        #[no_mangle]
+        extern "system" fn DllRegisterServer() -> com::sys::HRESULT {
             com::registration::dll_register_server(&mut get_relevant_registry_keys())
         }
 
         #[no_mangle]
-        extern "stdcall" fn DllUnregisterServer() -> com::sys::HRESULT {
+        extern "system" fn DllUnregisterServer() -> com::sys::HRESULT {
             com::registration::dll_unregister_server(&mut get_relevant_registry_keys())
         }
 
         fn get_relevant_registry_keys() -> Vec<com::registration::RegistryKeyInfo> {
             use com::registration::RegistryKeyInfo;
             let file_path = com::registration::get_dll_file_path();
-            vec![
+            let mut keys = vec![
                 RegistryKeyInfo::new(
                     &com::registration::class_key_path($class_id_one),
                     "",
                     stringify!($class_type_one),
                 ),
-                RegistryKeyInfo::new(
-                    &com::registration::class_inproc_key_path($class_id_one),
-                    "",
+                com::registration::class_inproc_key_info(
+                    $class_id_one,
                     &file_path,
+                    <$class_type_one as com::CoClass>::THREADING_MODEL,
                 ),
                 $(RegistryKeyInfo::new(
                     &com::registration::class_key_path($class_id),
                     "",
                     stringify!($class_type),
                 ),
-                RegistryKeyInfo::new(
-                    &com::registration::class_inproc_key_path($class_id),
-                    "",
+                com::registration::class_inproc_key_info(
+                    $class_id,
                     &file_path,
+                    <$class_type as com::CoClass>::THREADING_MODEL,
                 )),*
-            ]
+            ];
+            $(keys.extend($extra_keys);)*
+            keys
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guid_to_string_pads_every_group() {
+        let guid = GUID {
+            data1: 0x00C0_FFEE,
+            data2: 0x0042,
+            data3: 0x4B1D,
+            data4: [0x80, 0x00, 0x01, 0x23, 0x45, 0x67, 0x89, 0xAB],
+        };
+        assert_eq!(
+            guid_to_string(&guid),
+            "{00C0FFEE-0042-4B1D-8000-0123456789AB}"
+        );
+    }
+}
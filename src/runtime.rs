@@ -1,12 +1,24 @@
 //! COM runtime facilities
 //!
 //! This includes initializing the COM runtime as well as creating instances of CoClasses
+use crate::error::ErrorInfo;
+use crate::interfaces::icreate_error_info::ICreateErrorInfo;
+use crate::interfaces::ierror_info::IErrorInfo;
+use crate::interfaces::{IClassFactory, IUnknown};
 use crate::sys::{
-    CoCreateInstance, CoGetClassObject, CoIncrementMTAUsage, CoInitializeEx, CoUninitialize,
-    CLSCTX_INPROC_SERVER, CLSID, COINIT_APARTMENTTHREADED, COINIT_MULTITHREADED, FAILED, HRESULT,
-    IID, S_FALSE, S_OK,
+    CoCreateInstance, CoCreateInstanceEx, CoGetClassObject, CoIncrementMTAUsage, CoInitializeEx,
+    CoRegisterClassObject, CoRevokeClassObject, CoUninitialize, DispatchMessageW,
+    GetCurrentThreadId, GetErrorInfo, GetMessageW, PeekMessageW, PostQuitMessage,
+    PostThreadMessageW, SetErrorInfo, TranslateMessage, CLASS_E_CLASSNOTAVAILABLE,
+    CLSCTX_ACTIVATE_32_BIT_SERVER, CLSCTX_ACTIVATE_64_BIT_SERVER, CLSCTX_INPROC_HANDLER,
+    CLSCTX_INPROC_SERVER, CLSCTX_LOCAL_SERVER, CLSCTX_REMOTE_SERVER, CLSID,
+    COINIT_APARTMENTTHREADED, COINIT_MULTITHREADED, COSERVERINFO, CO_E_SERVER_EXEC_FAILURE, E_FAIL,
+    FAILED, HRESULT, IID, MSG, MULTI_QI, PM_NOREMOVE, PM_REMOVE, REGCLS_MULTIPLEUSE,
+    REGDB_E_CLASSNOTREG, RPC_E_DISCONNECTED, S_FALSE, S_OK, WM_APP,
 };
 use std::ffi::c_void;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
 
 use crate::{CoClass, ComInterface, ComPtr, ComRc};
 
@@ -45,10 +57,8 @@ pub enum ApartmentType {
 /// In  general this should only be called on threads created by the user.
 ///
 /// This wraps `CoInitializeEx`. The user is still responsible for establishing
-/// a message pump in the case of an STA
-// TODO: create a special `spawn` function for spawning a thread
-// with a specific apartment type.
-// TODO: add helpers for establishing a message pump
+/// a message pump in the case of an STA, e.g. with [`run_message_loop`]. [`spawn`]
+/// and [`StaExecutor`] take care of both for new threads.
 pub fn init_apartment(apartment_type: ApartmentType) -> Result<(), HRESULT> {
     match unsafe { CoInitializeEx(std::ptr::null_mut::<c_void>(), apartment_type as u32) } {
         // S_OK indicates the runtime was initialized
@@ -92,37 +102,223 @@ impl Drop for ApartmentRuntime {
     }
 }
 
-/// Get the class object with the associated [`CLSID`]
+/// Spawn a thread in a new apartment of the given type
 ///
-/// Calls `CoGetClassObject` internally
-pub fn get_class_object<T: ComInterface + ?Sized>(class_id: &CLSID) -> Result<ComRc<T>, HRESULT> {
-    let mut class = std::ptr::null_mut::<c_void>();
-    let hr = unsafe {
-        CoGetClassObject(
-            class_id as *const CLSID,
-            CLSCTX_INPROC_SERVER,
-            std::ptr::null_mut::<c_void>(),
-            &T::IID as *const IID,
-            &mut class as *mut *mut c_void,
-        )
+/// The apartment is initialized before `f` runs and uninitialized once it returns. The
+/// thread returns the error of `CoInitializeEx` if the apartment could not be initialized.
+///
+/// An STA thread must pump messages to receive calls from other apartments, e.g. by
+/// ending `f` with [`run_message_loop`].
+pub fn spawn<F, T>(apartment_type: ApartmentType, f: F) -> JoinHandle<Result<T, HRESULT>>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    std::thread::spawn(move || {
+        let _runtime = ApartmentRuntime::new(apartment_type)?;
+        Ok(f())
+    })
+}
+
+/// The thread message carrying a closure posted with [`StaHandle::post`]
+const WM_RUN_TASK: u32 = WM_APP + 0x0C0D;
+
+/// A closure posted to a thread, boxed twice to pass it as a thin pointer in `lParam`
+type Task = Box<dyn FnOnce() + Send + 'static>;
+
+/// Pump the messages of the current thread until `PostQuitMessage` is called
+///
+/// Closures posted with [`StaHandle::post`] run on this thread in the order they were
+/// posted. Other messages are translated and dispatched as usual. Closures that are still
+/// queued once the loop ends are dropped without running.
+///
+/// Returns the exit code passed to `PostQuitMessage`.
+pub fn run_message_loop() -> i32 {
+    let mut msg = unsafe { std::mem::zeroed::<MSG>() };
+    let exit_code = loop {
+        match unsafe { GetMessageW(&mut msg, std::ptr::null_mut(), 0, 0) } {
+            0 => break msg.w_param as i32,
+            -1 => break -1,
+            _ => unsafe { handle_message(&msg) },
+        }
     };
-    if FAILED(hr) {
-        return Err(hr);
+
+    while unsafe { PeekMessageW(&mut msg, std::ptr::null_mut(), 0, 0, PM_REMOVE) } != 0 {
+        if msg.hwnd.is_null() {
+            unsafe { discard_thread_message(msg.message, msg.l_param) };
+        }
+    }
+    exit_code
+}
+
+/// Free what a thread message that will never be handled owns
+///
+/// Only closures posted with [`StaHandle::post`] are owned by their message.
+pub(crate) unsafe fn discard_thread_message(message: u32, l_param: isize) {
+    if message == WM_RUN_TASK {
+        drop(Box::from_raw(l_param as *mut Task));
     }
+}
+
+unsafe fn handle_message(msg: &MSG) {
+    if msg.hwnd.is_null() && msg.message == WM_RUN_TASK {
+        let task = Box::from_raw(msg.l_param as *mut Task);
+        task();
+    } else {
+        TranslateMessage(msg);
+        DispatchMessageW(msg);
+    }
+}
 
-    Ok(unsafe { ComRc::from_raw(class as *mut *mut _) })
+/// A handle for posting closures to a thread running [`run_message_loop`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct StaHandle {
+    thread_id: u32,
 }
 
-/// Create an instance of a CoClass with the associated class id
+impl StaHandle {
+    /// The handle of the current thread
+    ///
+    /// This creates the message queue of the thread, so closures can be posted as soon
+    /// as this returns. They run once the thread pumps messages.
+    pub fn current() -> Self {
+        unsafe {
+            let mut msg = std::mem::zeroed::<MSG>();
+            PeekMessageW(&mut msg, std::ptr::null_mut(), 0, 0, PM_NOREMOVE);
+            StaHandle {
+                thread_id: GetCurrentThreadId(),
+            }
+        }
+    }
+
+    /// Whether the handle belongs to the current thread
+    pub fn is_current(&self) -> bool {
+        self.thread_id == unsafe { GetCurrentThreadId() }
+    }
+
+    /// Queue `f` to run on the thread
+    ///
+    /// Fails with `RPC_E_DISCONNECTED` if the thread has exited.
+    pub fn post<F: FnOnce() + Send + 'static>(&self, f: F) -> Result<(), HRESULT> {
+        let task: *mut Task = Box::into_raw(Box::new(Box::new(f)));
+        if unsafe { PostThreadMessageW(self.thread_id, WM_RUN_TASK, 0, task as isize) } == 0 {
+            drop(unsafe { Box::from_raw(task) });
+            return Err(RPC_E_DISCONNECTED);
+        }
+        Ok(())
+    }
+
+    /// Run `f` on the thread and wait for its result
+    ///
+    /// `f` runs immediately if this is the current thread. Fails with `RPC_E_DISCONNECTED`
+    /// if the thread exits before running `f`.
+    pub fn run<F, R>(&self, f: F) -> Result<R, HRESULT>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        if self.is_current() {
+            return Ok(f());
+        }
+        let (sender, receiver) = mpsc::channel();
+        self.post(move || {
+            let _ = sender.send(f());
+        })?;
+        receiver.recv().map_err(|_| RPC_E_DISCONNECTED)
+    }
+
+    /// Ask the thread to leave its message loop once the closures posted so far have run
+    pub fn quit(&self) -> Result<(), HRESULT> {
+        self.post(|| unsafe { PostQuitMessage(0) })
+    }
+}
+
+/// A thread in a single-threaded apartment that runs posted closures
 ///
-/// Calls `CoCreateInstance` internally
-pub fn create_instance<T: ComInterface + ?Sized>(class_id: &CLSID) -> Result<ComRc<T>, HRESULT> {
-    unsafe {
-        Ok(ComRc::new(create_raw_instance::<T>(
-            class_id,
-            std::ptr::null_mut(),
-        )?))
+/// The thread runs [`run_message_loop`] until the executor is joined or dropped.
+///
+/// ```rust,no_run
+/// use com::runtime::StaExecutor;
+///
+/// let sta = StaExecutor::new().unwrap();
+/// sta.post(|| println!("Hello from the STA")).unwrap();
+/// assert_eq!(sta.handle().run(|| 1 + 1), Ok(2));
+/// sta.join().unwrap();
+/// ```
+pub struct StaExecutor {
+    handle: StaHandle,
+    thread: Option<JoinHandle<Result<i32, HRESULT>>>,
+}
+
+impl StaExecutor {
+    /// Spawn the STA thread
+    pub fn new() -> Result<Self, HRESULT> {
+        let (sender, receiver) = mpsc::channel();
+        let thread = spawn(ApartmentType::SingleThreaded, move || {
+            let _ = sender.send(StaHandle::current());
+            run_message_loop()
+        });
+        match receiver.recv() {
+            Ok(handle) => Ok(StaExecutor {
+                handle,
+                thread: Some(thread),
+            }),
+            Err(_) => match thread.join() {
+                Ok(Err(hr)) => Err(hr),
+                _ => Err(E_FAIL),
+            },
+        }
+    }
+
+    /// A handle for posting closures to the thread
+    pub fn handle(&self) -> StaHandle {
+        self.handle.clone()
     }
+
+    /// Queue `f` to run on the thread
+    pub fn post<F: FnOnce() + Send + 'static>(&self, f: F) -> Result<(), HRESULT> {
+        self.handle.post(f)
+    }
+
+    /// Wait for the closures posted so far to run, then stop the thread
+    ///
+    /// A panic on the thread is propagated to the caller.
+    pub fn join(mut self) -> Result<(), HRESULT> {
+        match self.stop() {
+            Ok(result) => result,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    fn stop(&mut self) -> std::thread::Result<Result<(), HRESULT>> {
+        let thread = match self.thread.take() {
+            Some(thread) => thread,
+            None => return Ok(Ok(())),
+        };
+        // The thread may already have exited after a panic, which `join` reports
+        let _ = self.handle.quit();
+        thread.join().map(|result| result.map(|_| ()))
+    }
+}
+
+impl Drop for StaExecutor {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+/// Get the class object with the associated [`CLSID`] from an in process server
+///
+/// Calls `CoGetClassObject` internally
+pub fn get_class_object<T: ComInterface + ?Sized>(class_id: &CLSID) -> Result<ComRc<T>, HRESULT> {
+    CreateOptions::new().get_class_object(class_id)
+}
+
+/// Create an instance of a CoClass with the associated class id from an in process server
+///
+/// Calls `CoCreateInstanceEx` internally
+pub fn create_instance<T: ComInterface + ?Sized>(class_id: &CLSID) -> Result<ComRc<T>, HRESULT> {
+    CreateOptions::new().create_instance(class_id)
 }
 
 /// Created an aggreated instance
@@ -154,3 +350,431 @@ unsafe fn create_raw_instance<T: ComInterface + ?Sized>(
 
     Ok(ComPtr::new(instance as *mut _))
 }
+
+/// An interface pointer queried for an IID, viewed as `IUnknown`, or the error of
+/// querying for it
+pub type QueriedInterface = Result<ComRc<dyn IUnknown>, HRESULT>;
+
+/// Create an instance of a CoClass from an in process server and query it for each of
+/// `iids` in one call
+///
+/// Each result is for the IID at the same index. Fails if the instance could not be
+/// created or supports none of the interfaces.
+///
+/// Calls `CoCreateInstanceEx` internally
+pub fn create_instance_multi(
+    class_id: &CLSID,
+    iids: &[IID],
+) -> Result<Vec<QueriedInterface>, HRESULT> {
+    CreateOptions::new().create_instance_multi(class_id, iids)
+}
+
+/// Create an instance of a CoClass from an in process server and query it for every
+/// interface of a tuple of [`ComRc`]s in one call
+///
+/// Fails with the first error if any of the interfaces is not supported.
+///
+/// ```rust,ignore
+/// let (animal, cat): (ComRc<dyn IAnimal>, ComRc<dyn ICat>) =
+///     create_instance_tuple(&CLSID_CAT_CLASS)?;
+/// ```
+///
+/// Calls `CoCreateInstanceEx` internally
+pub fn create_instance_tuple<T: ComRcTuple>(class_id: &CLSID) -> Result<T, HRESULT> {
+    CreateOptions::new().create_instance_tuple(class_id)
+}
+
+/// A tuple of [`ComRc`]s of up to eight interfaces, as created by [`create_instance_tuple`]
+pub trait ComRcTuple: Sized {
+    /// The IIDs of the interfaces in order
+    #[doc(hidden)]
+    fn iids() -> Vec<IID>;
+
+    /// Take the interfaces in order from the results of [`create_instance_multi`]
+    #[doc(hidden)]
+    fn from_results(results: &mut dyn Iterator<Item = QueriedInterface>) -> Result<Self, HRESULT>;
+}
+
+/// Take the next interface from the results of [`create_instance_multi`]
+fn next_interface<T: ComInterface + ?Sized>(
+    results: &mut dyn Iterator<Item = QueriedInterface>,
+) -> Result<ComRc<T>, HRESULT> {
+    let unknown = results.next().unwrap_or(Err(E_FAIL))?;
+    let raw = unknown.as_raw();
+    std::mem::forget(unknown);
+    // The pointer was queried for `T::IID` and its reference moves to the new `ComRc`
+    Ok(unsafe { ComRc::from_raw(raw as *mut _) })
+}
+
+macro_rules! impl_com_rc_tuple {
+    ($($interface:ident),+) => {
+        impl<$($interface: ComInterface + ?Sized),+> ComRcTuple for ($(ComRc<$interface>,)+) {
+            fn iids() -> Vec<IID> {
+                vec![$($interface::IID),+]
+            }
+
+            fn from_results(
+                results: &mut dyn Iterator<Item = QueriedInterface>,
+            ) -> Result<Self, HRESULT> {
+                Ok(($(next_interface::<$interface>(results)?,)+))
+            }
+        }
+    };
+}
+
+impl_com_rc_tuple!(A);
+impl_com_rc_tuple!(A, B);
+impl_com_rc_tuple!(A, B, C);
+impl_com_rc_tuple!(A, B, C, D);
+impl_com_rc_tuple!(A, B, C, D, E);
+impl_com_rc_tuple!(A, B, C, D, E, F);
+impl_com_rc_tuple!(A, B, C, D, E, F, G);
+impl_com_rc_tuple!(A, B, C, D, E, F, G, H);
+
+/// Make a class object available to `CoGetClassObject` and `CoCreateInstance` in this
+/// process as an in process server until it is revoked
+///
+/// Returns the cookie to pass to [`revoke_class_object`].
+///
+/// Calls `CoRegisterClassObject` internally
+pub fn register_class_object(
+    class_id: &CLSID,
+    class_object: &ComRc<dyn IClassFactory>,
+) -> Result<u32, HRESULT> {
+    CreateOptions::new().register_class_object(class_id, class_object)
+}
+
+/// Revoke a class object registered with [`register_class_object`]
+///
+/// Calls `CoRevokeClassObject` internally
+pub fn revoke_class_object(cookie: u32) -> Result<(), HRESULT> {
+    match unsafe { CoRevokeClassObject(cookie) } {
+        S_OK => Ok(()),
+        hr => Err(hr),
+    }
+}
+
+/// The kinds of server to create instances and get class objects from, in the order
+/// they are tried
+///
+/// The next server type is tried if the class is not registered for, or not available
+/// from, the previous one. Without any server types only in process servers are used.
+///
+/// ```rust,ignore
+/// let options = CreateOptions::new()
+///     .inproc_server()
+///     .local_server()
+///     .activate_64_bit_server();
+/// let cat = options.create_instance::<dyn ICat>(&CLSID_CAT_CLASS)?;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CreateOptions {
+    /// The `CLSCTX_*` server types in the order they are tried
+    contexts: Vec<u32>,
+    /// The null terminated name of the computer for `CLSCTX_REMOTE_SERVER`
+    server_name: Option<Vec<u16>>,
+    /// The `CLSCTX_*` flags added to every server type
+    flags: u32,
+}
+
+impl CreateOptions {
+    /// Options without any server types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Try in process servers (`CLSCTX_INPROC_SERVER`) next
+    pub fn inproc_server(self) -> Self {
+        self.context(CLSCTX_INPROC_SERVER)
+    }
+
+    /// Try in process handlers (`CLSCTX_INPROC_HANDLER`) next
+    pub fn inproc_handler(self) -> Self {
+        self.context(CLSCTX_INPROC_HANDLER)
+    }
+
+    /// Try servers in another process on this computer (`CLSCTX_LOCAL_SERVER`) next
+    pub fn local_server(self) -> Self {
+        self.context(CLSCTX_LOCAL_SERVER)
+    }
+
+    /// Try the server on the computer `name` (`CLSCTX_REMOTE_SERVER`) next
+    pub fn remote_server(mut self, name: &str) -> Self {
+        self.server_name = Some(name.encode_utf16().chain(Some(0)).collect());
+        self.context(CLSCTX_REMOTE_SERVER)
+    }
+
+    /// Try any combination of `CLSCTX_*` server types next, as a single step
+    pub fn context(mut self, context: u32) -> Self {
+        self.contexts.push(context);
+        self
+    }
+
+    /// Prefer the 32-bit version of local servers (`CLSCTX_ACTIVATE_32_BIT_SERVER`)
+    pub fn activate_32_bit_server(mut self) -> Self {
+        self.flags |= CLSCTX_ACTIVATE_32_BIT_SERVER;
+        self
+    }
+
+    /// Prefer the 64-bit version of local servers (`CLSCTX_ACTIVATE_64_BIT_SERVER`)
+    pub fn activate_64_bit_server(mut self) -> Self {
+        self.flags |= CLSCTX_ACTIVATE_64_BIT_SERVER;
+        self
+    }
+
+    /// Get the class object with the associated [`CLSID`]
+    ///
+    /// Calls `CoGetClassObject` internally
+    pub fn get_class_object<T: ComInterface + ?Sized>(
+        &self,
+        class_id: &CLSID,
+    ) -> Result<ComRc<T>, HRESULT> {
+        self.try_contexts(|context, server_info| {
+            let mut class = std::ptr::null_mut::<c_void>();
+            let hr = unsafe {
+                CoGetClassObject(
+                    class_id as *const CLSID,
+                    context,
+                    server_info as *mut c_void,
+                    &T::IID as *const IID,
+                    &mut class as *mut *mut c_void,
+                )
+            };
+            if FAILED(hr) {
+                return Err(hr);
+            }
+
+            Ok(unsafe { ComRc::from_raw(class as *mut *mut _) })
+        })
+    }
+
+    /// Create an instance of a CoClass with the associated class id
+    ///
+    /// Calls `CoCreateInstanceEx` internally
+    pub fn create_instance<T: ComInterface + ?Sized>(
+        &self,
+        class_id: &CLSID,
+    ) -> Result<ComRc<T>, HRESULT> {
+        let (instance,) = self.create_instance_tuple::<(ComRc<T>,)>(class_id)?;
+        Ok(instance)
+    }
+
+    /// Create an instance of a CoClass and query it for each of `iids` in one call, see
+    /// [`create_instance_multi`]
+    ///
+    /// Calls `CoCreateInstanceEx` internally
+    pub fn create_instance_multi(
+        &self,
+        class_id: &CLSID,
+        iids: &[IID],
+    ) -> Result<Vec<QueriedInterface>, HRESULT> {
+        self.try_contexts(|context, server_info| {
+            let mut results = iids
+                .iter()
+                .map(|iid| MULTI_QI {
+                    iid,
+                    itf: std::ptr::null_mut(),
+                    hr: S_OK,
+                })
+                .collect::<Vec<_>>();
+            let hr = unsafe {
+                CoCreateInstanceEx(
+                    class_id as *const CLSID,
+                    std::ptr::null_mut(),
+                    context,
+                    server_info,
+                    results.len() as u32,
+                    results.as_mut_ptr(),
+                )
+            };
+            if FAILED(hr) {
+                return Err(hr);
+            }
+
+            Ok(results
+                .into_iter()
+                .map(|result| {
+                    if FAILED(result.hr) || result.itf.is_null() {
+                        Err(result.hr)
+                    } else {
+                        Ok(unsafe { ComRc::from_raw(result.itf as *mut _) })
+                    }
+                })
+                .collect())
+        })
+    }
+
+    /// Create an instance of a CoClass and query it for every interface of a tuple of
+    /// [`ComRc`]s in one call, see [`create_instance_tuple`]
+    ///
+    /// Calls `CoCreateInstanceEx` internally
+    pub fn create_instance_tuple<T: ComRcTuple>(&self, class_id: &CLSID) -> Result<T, HRESULT> {
+        let mut results = self
+            .create_instance_multi(class_id, &T::iids())?
+            .into_iter();
+        T::from_results(&mut results)
+    }
+
+    /// Make a class object available for all of the server types until it is revoked
+    /// with [`revoke_class_object`]
+    ///
+    /// Calls `CoRegisterClassObject` internally
+    pub fn register_class_object(
+        &self,
+        class_id: &CLSID,
+        class_object: &ComRc<dyn IClassFactory>,
+    ) -> Result<u32, HRESULT> {
+        let context = match self.contexts.iter().fold(0, |all, context| all | context) {
+            0 => CLSCTX_INPROC_SERVER,
+            context => context,
+        };
+        let mut cookie = 0;
+        let hr = unsafe {
+            CoRegisterClassObject(
+                class_id as *const CLSID,
+                class_object.as_raw() as *mut c_void,
+                context,
+                REGCLS_MULTIPLEUSE,
+                &mut cookie,
+            )
+        };
+        if FAILED(hr) {
+            return Err(hr);
+        }
+        Ok(cookie)
+    }
+
+    /// Call `f` with each server type and its server info until it succeeds or fails
+    /// with an error other than the class not being available
+    fn try_contexts<R>(
+        &self,
+        mut f: impl FnMut(u32, *mut COSERVERINFO) -> Result<R, HRESULT>,
+    ) -> Result<R, HRESULT> {
+        let contexts = if self.contexts.is_empty() {
+            &[CLSCTX_INPROC_SERVER][..]
+        } else {
+            &self.contexts[..]
+        };
+        let mut server_name = self.server_name.clone();
+        let mut hr = REGDB_E_CLASSNOTREG;
+        for &context in contexts {
+            let mut server_info = COSERVERINFO {
+                reserved1: 0,
+                name: std::ptr::null_mut(),
+                auth_info: std::ptr::null_mut(),
+                reserved2: 0,
+            };
+            let server_info = match &mut server_name {
+                Some(name) if context & CLSCTX_REMOTE_SERVER != 0 => {
+                    server_info.name = name.as_mut_ptr();
+                    &mut server_info as *mut COSERVERINFO
+                }
+                _ => std::ptr::null_mut(),
+            };
+            match f(context | self.flags, server_info) {
+                Err(error)
+                    if error == REGDB_E_CLASSNOTREG
+                        || error == CLASS_E_CLASSNOTAVAILABLE
+                        || error == CO_E_SERVER_EXEC_FAILURE =>
+                {
+                    hr = error
+                }
+                result => return result,
+            }
+        }
+        Err(hr)
+    }
+}
+
+/// Create an empty error object to fill in through `ICreateErrorInfo`
+///
+/// The object also implements `IErrorInfo`, which can be queried for and passed to
+/// `SetErrorInfo`. Like `CreateErrorInfo`, but the object is created by this crate on
+/// every platform.
+pub fn create_error_info() -> ComRc<dyn ICreateErrorInfo> {
+    ErrorInfo::new("")
+        .into_interface()
+        .get_interface::<dyn ICreateErrorInfo>()
+        .expect("error objects implement ICreateErrorInfo")
+}
+
+/// Set the error information of the current thread
+///
+/// Servers call this before returning a failing `HRESULT` to describe the failure.
+/// It replaces any error information that has not been taken yet.
+///
+/// Calls `SetErrorInfo` internally
+pub fn set_error_info(info: ErrorInfo) -> Result<(), HRESULT> {
+    let info = info.into_interface();
+    match unsafe { SetErrorInfo(0, info.as_raw() as *mut c_void) } {
+        S_OK => Ok(()),
+        hr => Err(hr),
+    }
+}
+
+/// Take the error information of the current thread, leaving none behind
+///
+/// Calls `GetErrorInfo` internally
+pub fn take_error_info() -> Option<ErrorInfo> {
+    let mut info = std::ptr::null_mut::<c_void>();
+    let hr = unsafe { GetErrorInfo(0, &mut info as *mut *mut c_void) };
+    if hr != S_OK || info.is_null() {
+        return None;
+    }
+
+    let info = unsafe { ComRc::<dyn IErrorInfo>::from_raw(info as *mut *mut _) };
+    Some(ErrorInfo::from_interface(&info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn spawn_initializes_apartment() {
+        let result = spawn(ApartmentType::Multithreaded, || {
+            // The thread is already in the MTA
+            init_apartment(ApartmentType::Multithreaded).map(|_| deinit_apartment())
+        });
+        assert_eq!(result.join().unwrap(), Ok(Ok(())));
+    }
+
+    #[test]
+    fn sta_runs_posted_closures_in_order() {
+        let sta = StaExecutor::new().unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        for i in 0..3 {
+            let seen = seen.clone();
+            sta.post(move || seen.lock().unwrap().push(i)).unwrap();
+        }
+        let handle = sta.handle();
+        assert_eq!(handle.run(StaHandle::current), Ok(handle.clone()));
+        assert!(!handle.is_current());
+
+        sta.join().unwrap();
+        assert_eq!(*seen.lock().unwrap(), vec![0, 1, 2]);
+        assert_eq!(handle.post(|| {}), Err(RPC_E_DISCONNECTED));
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn closures_are_dropped_when_the_thread_exits() {
+        let (handle_sender, handle_receiver) = mpsc::channel();
+        let (exit_sender, exit_receiver) = mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || {
+            handle_sender.send(StaHandle::current()).unwrap();
+            // Exit without pumping messages
+            let _ = exit_receiver.recv();
+        });
+        let handle = handle_receiver.recv().unwrap();
+
+        let (sender, receiver) = mpsc::channel::<()>();
+        handle.post(move || sender.send(()).unwrap()).unwrap();
+        drop(exit_sender);
+        thread.join().unwrap();
+        assert!(receiver.recv().is_err());
+        assert_eq!(handle.post(|| {}), Err(RPC_E_DISCONNECTED));
+    }
+}
@@ -1,6 +1,12 @@
 //! Types for interacting with COM related system APIs
 use std::ffi::c_void;
 
+// The portable runtime standing in for ole32 on non-Windows targets
+#[cfg(not(windows))]
+mod portable;
+#[cfg(not(windows))]
+pub use portable::*;
+
 /// A Windows result code
 pub type HRESULT = i32;
 
@@ -16,6 +22,28 @@ pub type BOOL = i32;
 pub type LSTATUS = i32;
 /// HKEY type
 pub type HKEY = *mut c_void;
+/// HWND type
+pub type HWND = *mut c_void;
+/// A length prefixed UTF-16 string allocated with `SysAllocString`
+pub type BSTR = *mut u16;
+/// An Automation boolean, [`VARIANT_TRUE`](constant.VARIANT_TRUE.html) or
+/// [`VARIANT_FALSE`](constant.VARIANT_FALSE.html)
+#[allow(non_camel_case_types)]
+pub type VARIANT_BOOL = i16;
+/// A borrowed null terminated UTF-16 string, usually an in-param
+///
+/// `#[com_interface]` methods taking one get a `{method}_str` wrapper taking `&str`.
+pub type PCWSTR = *const u16;
+/// A null terminated UTF-16 string, usually an out-param allocated with `CoTaskMemAlloc`
+///
+/// `#[com_interface]` methods taking a `*mut PWSTR` get a `{method}_str` wrapper filling
+/// in a [`CoTaskMemString`](../alloc/struct.CoTaskMemString.html) instead.
+pub type PWSTR = *mut u16;
+
+/// True as a [`VARIANT_BOOL`](type.VARIANT_BOOL.html)
+pub const VARIANT_TRUE: VARIANT_BOOL = -1;
+/// False as a [`VARIANT_BOOL`](type.VARIANT_BOOL.html)
+pub const VARIANT_FALSE: VARIANT_BOOL = 0;
 
 /// No error
 pub const S_OK: HRESULT = 0;
@@ -30,24 +58,134 @@ pub const E_INVALIDARG: HRESULT = -0x7FF8_FFA9;
 pub const E_NOINTERFACE: HRESULT = -0x7FFF_BFFE;
 /// Invalid pointer
 pub const E_POINTER: HRESULT = -0x7FFF_BFFD;
+/// Not implemented
+pub const E_NOTIMPL: HRESULT = -0x7FFF_BFFF;
+/// Unspecified failure
+pub const E_FAIL: HRESULT = -0x7FFF_BFFB;
+/// Ran out of memory
+pub const E_OUTOFMEMORY: HRESULT = -0x7FF8_FFF2;
+/// Catastrophic failure
+pub const E_UNEXPECTED: HRESULT = -0x7FFF_0001;
 
 /// No aggregation for CoClass
 pub const CLASS_E_NOAGGREGATION: HRESULT = -0x7FFB_FEF0;
 /// Class is not available
 pub const CLASS_E_CLASSNOTAVAILABLE: HRESULT = -0x7FFB_FEEF;
+/// The class is not licensed on this machine or the license key is wrong
+pub const CLASS_E_NOTLICENSED: HRESULT = -0x7FFB_FEEE;
+/// The server of the class could not be started
+pub const CO_E_SERVER_EXEC_FAILURE: HRESULT = -0x7FF7_FFFB;
+/// Class is not registered
+pub const REGDB_E_CLASSNOTREG: HRESULT = -0x7FFB_FEAC;
+/// Interface is not registered
+pub const REGDB_E_IIDNOTREG: HRESULT = -0x7FFB_FEAB;
+/// The COM library has not been initialized on this thread
+pub const CO_E_NOTINITIALIZED: HRESULT = -0x7FFB_FE10;
+/// The thread has already been initialized with a different apartment type
+pub const RPC_E_CHANGED_MODE: HRESULT = -0x7FFE_FEFA;
+/// The object invoked has disconnected from its clients
+pub const RPC_E_DISCONNECTED: HRESULT = -0x7FFE_FEF8;
+
+/// Only some of the interfaces requested from `CoCreateInstanceEx` were found
+pub const CO_S_NOTALLINTERFACES: HRESULT = 0x0008_0012;
 
 /// No error
 pub const ERROR_SUCCESS: u32 = 0;
+/// The connection point does not support the requested interface
+pub const CONNECT_E_NOCONNECTION: HRESULT = -0x7FFB_FE00;
+/// The sink does not support the interface of the connection point
+pub const CONNECT_E_CANNOTCONNECT: HRESULT = -0x7FFB_FDFE;
+
+/// The function is not supported by the storage object
+pub const STG_E_INVALIDFUNCTION: HRESULT = -0x7FFC_FFFF;
+/// An invalid pointer was passed to a storage function
+pub const STG_E_INVALIDPOINTER: HRESULT = -0x7FFC_FFF7;
+/// The storage object could not be written to
+pub const STG_E_WRITEFAULT: HRESULT = -0x7FFC_FFE3;
+/// The storage object could not be read from
+pub const STG_E_READFAULT: HRESULT = -0x7FFC_FFE2;
+
 /// Registration error
 pub const SELFREG_E_CLASS: HRESULT = -0x7FFB_FDFF;
 /// A in process server
 pub const CLSCTX_INPROC_SERVER: u32 = 0x1;
+/// An in process handler for a local server
+pub const CLSCTX_INPROC_HANDLER: u32 = 0x2;
+/// A server in another process on the same computer
+pub const CLSCTX_LOCAL_SERVER: u32 = 0x4;
+/// A server on another computer
+pub const CLSCTX_REMOTE_SERVER: u32 = 0x10;
+/// Any kind of server
+pub const CLSCTX_SERVER: u32 = CLSCTX_INPROC_SERVER | CLSCTX_LOCAL_SERVER | CLSCTX_REMOTE_SERVER;
+/// Any kind of server or handler
+pub const CLSCTX_ALL: u32 = CLSCTX_SERVER | CLSCTX_INPROC_HANDLER;
+/// Prefer the 32-bit version of a local server
+pub const CLSCTX_ACTIVATE_32_BIT_SERVER: u32 = 0x40000;
+/// Prefer the 64-bit version of a local server
+pub const CLSCTX_ACTIVATE_64_BIT_SERVER: u32 = 0x80000;
+
+/// A registered class object creates one object and is then hidden from other clients
+pub const REGCLS_SINGLEUSE: u32 = 0;
+/// A registered class object can create any number of objects
+pub const REGCLS_MULTIPLEUSE: u32 = 1;
 
 /// An single threaded apartment (STA)
 pub const COINIT_APARTMENTTHREADED: u32 = 0x2;
 /// An multi threaded apartment (STA)
 pub const COINIT_MULTITHREADED: u32 = 0x0;
 
+/// Indicates a request to terminate an application
+pub const WM_QUIT: u32 = 0x0012;
+/// The first message number available for use by applications
+pub const WM_APP: u32 = 0x8000;
+/// Messages are not removed from the queue by `PeekMessage`
+pub const PM_NOREMOVE: u32 = 0x0000;
+/// Messages are removed from the queue by `PeekMessage`
+pub const PM_REMOVE: u32 = 0x0001;
+
+/// The task allocator returned by `CoGetMalloc`
+pub const MEMCTX_TASK: u32 = 1;
+
+/// Seek relative to the beginning of a stream
+pub const STREAM_SEEK_SET: u32 = 0;
+/// Seek relative to the current position of a stream
+pub const STREAM_SEEK_CUR: u32 = 1;
+/// Seek relative to the end of a stream
+pub const STREAM_SEEK_END: u32 = 2;
+/// The storage object is a stream
+pub const STGTY_STREAM: u32 = 2;
+/// Do not return the name of a storage object from `Stat`
+pub const STATFLAG_NONAME: u32 = 1;
+
+/// Unmarshaling happens in another process on the same computer
+pub const MSHCTX_LOCAL: u32 = 0;
+/// Unmarshaling happens in a process without shared memory access
+pub const MSHCTX_NOSHAREDMEM: u32 = 1;
+/// Unmarshaling happens on another computer
+pub const MSHCTX_DIFFERENTMACHINE: u32 = 2;
+/// Unmarshaling happens in another apartment of the same process
+pub const MSHCTX_INPROC: u32 = 3;
+/// Unmarshaling happens in another context of the same apartment
+pub const MSHCTX_CROSSCTX: u32 = 4;
+/// The marshaled data is unmarshaled once
+pub const MSHLFLAGS_NORMAL: u32 = 0;
+/// The marshaled data keeps the object alive and can be unmarshaled many times
+pub const MSHLFLAGS_TABLESTRONG: u32 = 1;
+/// The marshaled data can be unmarshaled many times without keeping the object alive
+pub const MSHLFLAGS_TABLEWEAK: u32 = 2;
+
+/// The licensing information returned by `IClassFactory2::GetLicInfo`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LICINFO {
+    /// The size of the structure in bytes
+    pub cb_lic_info: i32,
+    /// Whether `RequestLicKey` can hand out a runtime key
+    pub f_runtime_key_avail: BOOL,
+    /// Whether the machine is licensed to create objects without a key
+    pub f_licence_verified: BOOL,
+}
+
 /// A globally unique identifier
 #[repr(C)]
 #[derive(Copy, Clone, PartialEq)]
@@ -62,6 +200,14 @@ pub struct GUID {
     pub data4: [u8; 8],
 }
 
+/// The all zero GUID
+pub const GUID_NULL: GUID = GUID {
+    data1: 0,
+    data2: 0,
+    data3: 0,
+    data4: [0; 8],
+};
+
 /// An interface ID
 pub type IID = GUID;
 /// A class ID
@@ -87,7 +233,373 @@ impl std::fmt::Debug for GUID {
     }
 }
 
+/// A point in time as the number of 100 nanosecond intervals since January 1, 1601
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct FILETIME {
+    #[allow(missing_docs)]
+    pub low_date_time: u32,
+    #[allow(missing_docs)]
+    pub high_date_time: u32,
+}
+
+/// Information about a storage object returned from `IStream::Stat`
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[allow(non_camel_case_types)]
+pub struct STATSTG {
+    /// The name of the storage object, allocated with `CoTaskMemAlloc`
+    pub name: *mut u16,
+    /// The type of storage object (`STGTY_*`)
+    pub ty: u32,
+    /// The size in bytes of the stream
+    pub size: u64,
+    /// The last modification time
+    pub mtime: FILETIME,
+    /// The creation time
+    pub ctime: FILETIME,
+    /// The last access time
+    pub atime: FILETIME,
+    /// The access mode the object was opened with
+    pub mode: u32,
+    /// The region locking supported by the stream
+    pub locks_supported: u32,
+    /// The class ID of a storage object
+    pub clsid: CLSID,
+    /// The current state bits of a storage object
+    pub state_bits: u32,
+    #[allow(missing_docs)]
+    pub reserved: u32,
+}
+
+/// An interface requested from `CoCreateInstanceEx` and the result of querying for it
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+#[allow(non_camel_case_types)]
+pub struct MULTI_QI {
+    /// The requested interface
+    pub iid: *const IID,
+    /// The interface pointer, set on success
+    pub itf: *mut c_void,
+    /// The result of querying for the interface
+    pub hr: HRESULT,
+}
+
+/// The computer a class is created on by `CoCreateInstanceEx`
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct COSERVERINFO {
+    #[allow(missing_docs)]
+    pub reserved1: u32,
+    /// The name of the computer, null for the local one
+    pub name: *mut u16,
+    /// The authentication settings, null for the defaults
+    pub auth_info: *mut c_void,
+    #[allow(missing_docs)]
+    pub reserved2: u32,
+}
+
+/// A point in screen coordinates
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct POINT {
+    #[allow(missing_docs)]
+    pub x: i32,
+    #[allow(missing_docs)]
+    pub y: i32,
+}
+
+/// A message from a thread's message queue
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct MSG {
+    /// The window the message is for, null for thread messages
+    pub hwnd: HWND,
+    /// The message identifier
+    pub message: u32,
+    /// Additional information about the message
+    pub w_param: usize,
+    /// Additional information about the message
+    pub l_param: isize,
+    /// The time the message was posted
+    pub time: u32,
+    /// The cursor position when the message was posted
+    pub pt: POINT,
+}
+
+/// The ID of a member of a type
+pub type MEMBERID = i32;
+/// A handle to a type referenced by another type of a type library
+pub type HREFTYPE = u32;
+/// A locale identifier
+pub type LCID = u32;
+/// The ID of no member
+pub const MEMBERID_NIL: MEMBERID = -1;
+
+/// An enumeration
+pub const TKIND_ENUM: u32 = 0;
+/// A structure
+pub const TKIND_RECORD: u32 = 1;
+/// A module of static functions and data
+pub const TKIND_MODULE: u32 = 2;
+/// An interface with a VTable
+pub const TKIND_INTERFACE: u32 = 3;
+/// A dispinterface, called through `IDispatch::Invoke`
+pub const TKIND_DISPATCH: u32 = 4;
+/// A creatable class
+pub const TKIND_COCLASS: u32 = 5;
+/// Another name for a type
+pub const TKIND_ALIAS: u32 = 6;
+/// A union
+pub const TKIND_UNION: u32 = 7;
+
+/// A dispinterface which can also be called through its VTable
+pub const TYPEFLAG_FDUAL: u16 = 0x40;
+/// The interface is the default of a coclass
+pub const IMPLTYPEFLAG_FDEFAULT: i32 = 0x1;
+/// The interface is an outgoing interface of a coclass
+pub const IMPLTYPEFLAG_FSOURCE: i32 = 0x2;
+
+/// A parameter passed by the caller
+pub const PARAMFLAG_FIN: u16 = 0x1;
+/// A parameter written by the callee
+pub const PARAMFLAG_FOUT: u16 = 0x2;
+/// A parameter which is the return value of the member
+pub const PARAMFLAG_FRETVAL: u16 = 0x8;
+
+/// A method
+pub const INVOKE_FUNC: u32 = 1;
+/// A property getter
+pub const INVOKE_PROPERTYGET: u32 = 2;
+/// A property setter
+pub const INVOKE_PROPERTYPUT: u32 = 4;
+/// A property setter taking a reference
+pub const INVOKE_PROPERTYPUTREF: u32 = 8;
+
+/// A function called through the VTable, with an implementation
+pub const FUNC_VIRTUAL: u32 = 0;
+/// A function called through the VTable, without an implementation
+pub const FUNC_PUREVIRTUAL: u32 = 1;
+/// A non-virtual function
+pub const FUNC_NONVIRTUAL: u32 = 2;
+/// A static function
+pub const FUNC_STATIC: u32 = 3;
+/// A function called through `IDispatch::Invoke`
+pub const FUNC_DISPATCH: u32 = 4;
+
+/// No value
+pub const VT_EMPTY: u16 = 0;
+/// `i16`
+pub const VT_I2: u16 = 2;
+/// `i32`
+pub const VT_I4: u16 = 3;
+/// `f32`
+pub const VT_R4: u16 = 4;
+/// `f64`
+pub const VT_R8: u16 = 5;
+/// A currency amount
+pub const VT_CY: u16 = 6;
+/// A date
+pub const VT_DATE: u16 = 7;
+/// A [`BSTR`](type.BSTR.html)
+pub const VT_BSTR: u16 = 8;
+/// An `IDispatch` pointer
+pub const VT_DISPATCH: u16 = 9;
+/// An `SCODE`
+pub const VT_ERROR: u16 = 10;
+/// A `VARIANT_BOOL`, -1 for true and 0 for false
+pub const VT_BOOL: u16 = 11;
+/// A `VARIANT`
+pub const VT_VARIANT: u16 = 12;
+/// An `IUnknown` pointer
+pub const VT_UNKNOWN: u16 = 13;
+/// A decimal number
+pub const VT_DECIMAL: u16 = 14;
+/// `i8`
+pub const VT_I1: u16 = 16;
+/// `u8`
+pub const VT_UI1: u16 = 17;
+/// `u16`
+pub const VT_UI2: u16 = 18;
+/// `u32`
+pub const VT_UI4: u16 = 19;
+/// `i64`
+pub const VT_I8: u16 = 20;
+/// `u64`
+pub const VT_UI8: u16 = 21;
+/// A C `int`
+pub const VT_INT: u16 = 22;
+/// A C `unsigned int`
+pub const VT_UINT: u16 = 23;
+/// `void`
+pub const VT_VOID: u16 = 24;
+/// An [`HRESULT`](type.HRESULT.html)
+pub const VT_HRESULT: u16 = 25;
+/// A pointer, to the type in `lptdesc`
+pub const VT_PTR: u16 = 26;
+/// A `SAFEARRAY`, of the type in `lptdesc`
+pub const VT_SAFEARRAY: u16 = 27;
+/// A C array, described by `lpadesc`
+pub const VT_CARRAY: u16 = 28;
+/// A type of a type library, referenced by `hreftype`
+pub const VT_USERDEFINED: u16 = 29;
+/// A null terminated ANSI string
+pub const VT_LPSTR: u16 = 30;
+/// A null terminated UTF-16 string
+pub const VT_LPWSTR: u16 = 31;
+/// `isize`
+pub const VT_INT_PTR: u16 = 37;
+/// `usize`
+pub const VT_UINT_PTR: u16 = 38;
+
+/// The type referred to by a [`TYPEDESC`](struct.TYPEDESC.html), depending on its `vt`
+#[repr(C)]
+#[derive(Copy, Clone)]
+#[allow(non_camel_case_types)]
+pub union TYPEDESC_U {
+    /// The type pointed to for `VT_PTR` and `VT_SAFEARRAY`
+    pub lptdesc: *mut TYPEDESC,
+    /// The `ARRAYDESC` of a `VT_CARRAY`
+    pub lpadesc: *mut c_void,
+    /// The type referenced by a `VT_USERDEFINED`
+    pub hreftype: HREFTYPE,
+}
+
+/// The type of a parameter, field or alias in a type library
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct TYPEDESC {
+    /// The type referred to, if any
+    pub u: TYPEDESC_U,
+    /// The variant type (`VT_*`)
+    pub vt: u16,
+}
+
+/// Information for marshaling a type, no longer used
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct IDLDESC {
+    #[allow(missing_docs)]
+    pub reserved: usize,
+    #[allow(missing_docs)]
+    pub idl_flags: u16,
+}
+
+/// The flags and default value of a parameter
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct PARAMDESC {
+    /// The default value, if `PARAMFLAG_FHASDEFAULT` is set
+    pub paramdescex: *mut c_void,
+    /// The direction of the parameter (`PARAMFLAG_*`)
+    pub param_flags: u16,
+}
+
+/// The type and flags of a parameter or return value
+///
+/// Only the `PARAMDESC` variant of the union is used by current type libraries.
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct ELEMDESC {
+    /// The type
+    pub tdesc: TYPEDESC,
+    /// The flags
+    pub paramdesc: PARAMDESC,
+}
+
+/// The attributes of a type returned by `ITypeInfo::GetTypeAttr`
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct TYPEATTR {
+    /// The GUID of the type
+    pub guid: GUID,
+    /// The locale of the names and documentation
+    pub lcid: LCID,
+    #[allow(missing_docs)]
+    pub reserved: u32,
+    /// The ID of the constructor, or `MEMBERID_NIL`
+    pub memid_constructor: MEMBERID,
+    /// The ID of the destructor, or `MEMBERID_NIL`
+    pub memid_destructor: MEMBERID,
+    #[allow(missing_docs)]
+    pub schema: PWSTR,
+    /// The size of an instance of the type
+    pub size_instance: u32,
+    /// The kind of type (`TKIND_*`)
+    pub typekind: u32,
+    /// The number of functions
+    pub funcs: u16,
+    /// The number of variables and data fields
+    pub vars: u16,
+    /// The number of implemented interfaces
+    pub impl_types: u16,
+    /// The size of the VTable in bytes
+    pub size_vft: u16,
+    /// The alignment of an instance of the type
+    pub alignment: u16,
+    /// The `TYPEFLAG_*` flags
+    pub type_flags: u16,
+    /// The major version
+    pub major_ver_num: u16,
+    /// The minor version
+    pub minor_ver_num: u16,
+    /// The type named by a `TKIND_ALIAS`
+    pub tdesc_alias: TYPEDESC,
+    #[allow(missing_docs)]
+    pub idldesc_type: IDLDESC,
+}
+
+/// A function of a type returned by `ITypeInfo::GetFuncDesc`
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct FUNCDESC {
+    /// The ID of the function
+    pub memid: MEMBERID,
+    /// The error codes the function can return
+    pub scodes: *mut i32,
+    /// The `params` parameters
+    pub elemdesc_param: *mut ELEMDESC,
+    /// Whether the function is virtual, static or dispatch only (`FUNC_*`)
+    pub funckind: u32,
+    /// Whether it is a method or property accessor (`INVOKE_*`)
+    pub invkind: u32,
+    /// The calling convention
+    pub callconv: u32,
+    /// The number of parameters
+    pub params: i16,
+    /// The number of optional parameters
+    pub params_opt: i16,
+    /// The offset of the function in the VTable in bytes
+    pub vft: i16,
+    /// The number of error codes
+    pub count_scodes: i16,
+    /// The return type
+    pub elemdesc_func: ELEMDESC,
+    /// The `FUNCFLAG_*` flags
+    pub func_flags: u16,
+}
+
+/// The attributes of a type library returned by `ITypeLib::GetLibAttr`
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct TLIBATTR {
+    /// The LIBID
+    pub guid: GUID,
+    /// The locale of the names and documentation
+    pub lcid: LCID,
+    /// The platform the library was built for
+    pub syskind: u32,
+    /// The major version
+    pub major_ver_num: u16,
+    /// The minor version
+    pub minor_ver_num: u16,
+    /// The `LIBFLAG_*` flags
+    pub lib_flags: u16,
+}
+
+#[cfg(windows)]
 #[link(name = "ole32")]
+#[allow(missing_docs)]
 extern "system" {
     pub fn CoIncrementMTAUsage(cookie: *mut c_void) -> HRESULT;
     pub fn RegCreateKeyExA(
@@ -128,5 +640,62 @@ extern "system" {
         riid: *const IID,
         ppv: *mut *mut c_void,
     ) -> HRESULT;
+    pub fn CoCreateInstanceEx(
+        rclsid: *const IID,
+        pUnkOuter: *mut c_void,
+        dwClsCtx: u32,
+        pServerInfo: *mut COSERVERINFO,
+        dwCount: u32,
+        pResults: *mut MULTI_QI,
+    ) -> HRESULT;
+    pub fn CoRegisterClassObject(
+        rclsid: *const IID,
+        pUnk: *mut c_void,
+        dwClsContext: u32,
+        flags: u32,
+        lpdwRegister: *mut u32,
+    ) -> HRESULT;
+    pub fn CoRevokeClassObject(dwRegister: u32) -> HRESULT;
     pub fn CoUninitialize();
+    pub fn CoTaskMemAlloc(cb: usize) -> *mut c_void;
+    pub fn CoTaskMemRealloc(pv: *mut c_void, cb: usize) -> *mut c_void;
+    pub fn CoTaskMemFree(pv: *mut c_void);
+    pub fn CoGetMalloc(dwMemContext: u32, ppMalloc: *mut *mut c_void) -> HRESULT;
+}
+
+#[cfg(windows)]
+#[link(name = "oleaut32")]
+#[allow(missing_docs)]
+extern "system" {
+    pub fn SysAllocStringLen(strIn: *const u16, ui: u32) -> BSTR;
+    pub fn SysFreeString(bstrString: BSTR);
+    pub fn SysStringLen(pbstr: BSTR) -> u32;
+    pub fn SetErrorInfo(dwReserved: u32, perrinfo: *mut c_void) -> HRESULT;
+    pub fn GetErrorInfo(dwReserved: u32, pperrinfo: *mut *mut c_void) -> HRESULT;
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+#[allow(missing_docs)]
+extern "system" {
+    pub fn GetCurrentThreadId() -> u32;
+}
+
+#[cfg(windows)]
+#[link(name = "user32")]
+#[allow(missing_docs)]
+extern "system" {
+    pub fn PostThreadMessageW(idThread: u32, Msg: u32, wParam: usize, lParam: isize) -> BOOL;
+    pub fn GetMessageW(lpMsg: *mut MSG, hWnd: HWND, wMsgFilterMin: u32, wMsgFilterMax: u32)
+        -> BOOL;
+    pub fn PeekMessageW(
+        lpMsg: *mut MSG,
+        hWnd: HWND,
+        wMsgFilterMin: u32,
+        wMsgFilterMax: u32,
+        wRemoveMsg: u32,
+    ) -> BOOL;
+    pub fn TranslateMessage(lpMsg: *const MSG) -> BOOL;
+    pub fn DispatchMessageW(lpMsg: *const MSG) -> isize;
+    pub fn PostQuitMessage(nExitCode: i32);
 }
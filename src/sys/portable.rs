@@ -0,0 +1,786 @@
+//! A pure Rust stand-in for the parts of the COM runtime used by this crate.
+//!
+//! On platforms other than Windows there is no `ole32` to link against. This module
+//! provides functions with the same names and signatures so that COM objects can be
+//! produced and consumed in-process, e.g. when unit testing on Linux.
+#![allow(non_snake_case)]
+
+use super::{
+    BOOL, BSTR, CLSCTX_ALL, CLSID, COINIT_APARTMENTTHREADED, COSERVERINFO, CO_E_NOTINITIALIZED,
+    CO_S_NOTALLINTERFACES, E_INVALIDARG, E_NOINTERFACE, E_NOTIMPL, E_POINTER, FAILED, HKEY,
+    HRESULT, HWND, IID, LSTATUS, MEMCTX_TASK, MSG, MULTI_QI, PM_REMOVE, POINT, REGDB_E_CLASSNOTREG,
+    RPC_E_CHANGED_MODE, S_FALSE, S_OK, WM_QUIT,
+};
+
+use crate::interfaces::iglobal_interface_table::CLSID_STD_GLOBAL_INTERFACE_TABLE;
+use crate::interfaces::{IClassFactory, IUnknown};
+use crate::{ComInterface, ComPtr, ComRc};
+
+use std::cell::{Cell, RefCell, UnsafeCell};
+use std::collections::{BTreeMap, VecDeque};
+use std::ffi::{c_void, CStr};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Once};
+
+/// A static that is initialized on first use
+///
+/// `Mutex::new` and `BTreeMap::new` can only initialize a static since Rust 1.63 and 1.66,
+/// which is newer than the minimum supported version. Declared with `lazy_static!`.
+struct Lazy<T> {
+    once: Once,
+    value: UnsafeCell<Option<T>>,
+    init: fn() -> T,
+}
+
+// The value is only written once, by `call_once`
+unsafe impl<T: Send + Sync> Sync for Lazy<T> {}
+
+impl<T> std::ops::Deref for Lazy<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.once
+            .call_once(|| unsafe { *self.value.get() = Some((self.init)()) });
+        match unsafe { &*self.value.get() } {
+            Some(value) => value,
+            None => unreachable!("initialized by `call_once`"),
+        }
+    }
+}
+
+macro_rules! lazy_static {
+    ($($(#[$attr:meta])* static $name:ident: $ty:ty = $init:expr;)+) => {$(
+        $(#[$attr])*
+        static $name: crate::sys::portable::Lazy<$ty> = crate::sys::portable::Lazy {
+            once: std::sync::Once::new(),
+            value: std::cell::UnsafeCell::new(None),
+            init: {
+                fn init() -> $ty {
+                    $init
+                }
+                init
+            },
+        };
+    )+};
+}
+
+mod git;
+mod task_alloc;
+
+pub(crate) use git::register_proxy;
+pub use task_alloc::{Leak, LeakCheck};
+
+const ERROR_SUCCESS: LSTATUS = 0;
+const ERROR_FILE_NOT_FOUND: LSTATUS = 2;
+const ERROR_ACCESS_DENIED: LSTATUS = 5;
+const ERROR_INVALID_HANDLE: LSTATUS = 6;
+
+thread_local! {
+    /// The apartment type of the current thread and how often it was initialized
+    static APARTMENT: Cell<Option<(u32, u32)>> = Cell::new(None);
+}
+
+/// The number of outstanding `CoIncrementMTAUsage` calls
+static MTA_USAGE: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the apartment type (`COINIT_*`) of the current thread if it has one.
+///
+/// Threads that were never initialized are implicitly part of the MTA as long as
+/// `CoIncrementMTAUsage` has been called.
+pub(crate) fn current_apartment() -> Option<u32> {
+    APARTMENT
+        .with(|apartment| apartment.get())
+        .map(|(ty, _)| ty)
+        .or_else(|| {
+            if MTA_USAGE.load(Ordering::SeqCst) > 0 {
+                Some(super::COINIT_MULTITHREADED)
+            } else {
+                None
+            }
+        })
+}
+
+/// Portable version of [`CoIncrementMTAUsage`](https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-coincrementmtausage)
+pub unsafe extern "system" fn CoIncrementMTAUsage(cookie: *mut c_void) -> HRESULT {
+    let usage = MTA_USAGE.fetch_add(1, Ordering::SeqCst) + 1;
+    if !cookie.is_null() {
+        *(cookie as *mut *mut c_void) = usage as *mut c_void;
+    }
+    S_OK
+}
+
+/// Portable version of [`CoInitializeEx`](https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-coinitializeex)
+pub unsafe extern "system" fn CoInitializeEx(_pvReserved: *mut c_void, dwCoInit: u32) -> HRESULT {
+    let apartment_type = dwCoInit & COINIT_APARTMENTTHREADED;
+    APARTMENT.with(|apartment| match apartment.get() {
+        None => {
+            apartment.set(Some((apartment_type, 1)));
+            S_OK
+        }
+        Some((current, count)) if current == apartment_type => {
+            apartment.set(Some((current, count + 1)));
+            S_FALSE
+        }
+        Some(_) => RPC_E_CHANGED_MODE,
+    })
+}
+
+/// Portable version of [`CoUninitialize`](https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-couninitialize)
+pub unsafe extern "system" fn CoUninitialize() {
+    APARTMENT.with(|apartment| match apartment.get() {
+        Some((current, count)) if count > 1 => apartment.set(Some((current, count - 1))),
+        _ => apartment.set(None),
+    })
+}
+
+/// A class object registered with `CoRegisterClassObject`
+struct ClassObject {
+    cookie: u32,
+    clsid: CLSID,
+    /// The `CLSCTX_*` server types the class object is registered for
+    context: u32,
+    /// The registered class object, holding a reference
+    object: usize,
+}
+
+lazy_static! {
+    /// The class objects registered with `CoRegisterClassObject`
+    static CLASS_OBJECTS: Mutex<Vec<ClassObject>> = Mutex::new(Vec::new());
+}
+/// The next cookie returned by `CoRegisterClassObject`
+static NEXT_CLASS_COOKIE: AtomicU32 = AtomicU32::new(1);
+
+/// Portable version of [`CoRegisterClassObject`](https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-coregisterclassobject)
+///
+/// The class object is available to the whole process until revoked, for requests
+/// including one of the server types in `dwClsContext`, whatever the flags.
+pub unsafe extern "system" fn CoRegisterClassObject(
+    rclsid: *const CLSID,
+    pUnk: *mut c_void,
+    dwClsContext: u32,
+    _flags: u32,
+    lpdwRegister: *mut u32,
+) -> HRESULT {
+    if rclsid.is_null() || pUnk.is_null() || lpdwRegister.is_null() {
+        return E_POINTER;
+    }
+    if current_apartment().is_none() {
+        return CO_E_NOTINITIALIZED;
+    }
+    ComPtr::<dyn IUnknown>::new(pUnk as *mut _).add_ref();
+    let cookie = NEXT_CLASS_COOKIE.fetch_add(1, Ordering::SeqCst);
+    class_objects().push(ClassObject {
+        cookie,
+        clsid: *rclsid,
+        context: dwClsContext & CLSCTX_ALL,
+        object: pUnk as usize,
+    });
+    *lpdwRegister = cookie;
+    S_OK
+}
+
+/// Portable version of [`CoRevokeClassObject`](https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-corevokeclassobject)
+pub unsafe extern "system" fn CoRevokeClassObject(dwRegister: u32) -> HRESULT {
+    let object = {
+        let mut class_objects = class_objects();
+        match class_objects
+            .iter()
+            .position(|class_object| class_object.cookie == dwRegister)
+        {
+            Some(index) => class_objects.remove(index).object,
+            None => return E_INVALIDARG,
+        }
+    };
+    ComPtr::<dyn IUnknown>::new(object as *mut _).release();
+    S_OK
+}
+
+fn class_objects() -> std::sync::MutexGuard<'static, Vec<ClassObject>> {
+    CLASS_OBJECTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Portable version of [`CoGetClassObject`](https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-cogetclassobject)
+///
+/// Only class objects registered with `CoRegisterClassObject` for one of the server
+/// types in `dwClsContext` are found. Servers on other computers are not supported.
+pub unsafe extern "system" fn CoGetClassObject(
+    rclsid: *const CLSID,
+    dwClsContext: u32,
+    pvReserved: *mut c_void,
+    riid: *const IID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    *ppv = std::ptr::null_mut();
+    if current_apartment().is_none() {
+        return CO_E_NOTINITIALIZED;
+    }
+    let server_info = pvReserved as *const COSERVERINFO;
+    if !server_info.is_null() && !(*server_info).name.is_null() {
+        return E_NOTIMPL;
+    }
+    let object = {
+        let class_objects = class_objects();
+        let found = class_objects.iter().find(|class_object| {
+            class_object.clsid == *rclsid && class_object.context & dwClsContext != 0
+        });
+        match found {
+            Some(class_object) => {
+                let object = ComPtr::<dyn IUnknown>::new(class_object.object as *mut _);
+                object.add_ref();
+                object
+            }
+            None => return REGDB_E_CLASSNOTREG,
+        }
+    };
+    // Query and release outside of the lock in case the class object is revoked meanwhile
+    let hr = object.query_interface(riid, ppv);
+    object.release();
+    hr
+}
+
+/// Portable version of [`CoCreateInstance`](https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-cocreateinstance)
+pub unsafe extern "system" fn CoCreateInstance(
+    rclsid: *const CLSID,
+    pUnkOuter: *mut c_void,
+    dwClsContext: u32,
+    riid: *const IID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    create_instance(
+        rclsid,
+        pUnkOuter,
+        dwClsContext,
+        std::ptr::null_mut(),
+        riid,
+        ppv,
+    )
+}
+
+/// Create an instance with the class object registered for `rclsid` and `context`
+unsafe fn create_instance(
+    rclsid: *const CLSID,
+    outer: *mut c_void,
+    context: u32,
+    server_info: *mut COSERVERINFO,
+    riid: *const IID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    *ppv = std::ptr::null_mut();
+    if *rclsid == CLSID_STD_GLOBAL_INTERFACE_TABLE {
+        if current_apartment().is_none() {
+            return CO_E_NOTINITIALIZED;
+        }
+        return git::GlobalInterfaceTable::create(riid, ppv);
+    }
+
+    let mut factory = std::ptr::null_mut::<c_void>();
+    let hr = CoGetClassObject(
+        rclsid,
+        context,
+        server_info as *mut c_void,
+        &<dyn IClassFactory as ComInterface>::IID,
+        &mut factory,
+    );
+    if FAILED(hr) {
+        return hr;
+    }
+    let factory = ComRc::<dyn IClassFactory>::from_raw(factory as *mut _);
+    factory.create_instance(outer as *mut _, riid, ppv)
+}
+
+/// Portable version of [`CoCreateInstanceEx`](https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-cocreateinstanceex)
+///
+/// The object is created once and then queried for each of the requested interfaces.
+pub unsafe extern "system" fn CoCreateInstanceEx(
+    rclsid: *const CLSID,
+    pUnkOuter: *mut c_void,
+    dwClsCtx: u32,
+    pServerInfo: *mut COSERVERINFO,
+    dwCount: u32,
+    pResults: *mut MULTI_QI,
+) -> HRESULT {
+    if dwCount == 0 || pResults.is_null() {
+        return E_INVALIDARG;
+    }
+    let results = std::slice::from_raw_parts_mut(pResults, dwCount as usize);
+    for result in results.iter_mut() {
+        result.itf = std::ptr::null_mut();
+    }
+
+    let mut object = std::ptr::null_mut::<c_void>();
+    let hr = create_instance(
+        rclsid,
+        pUnkOuter,
+        dwClsCtx,
+        pServerInfo,
+        &<dyn IUnknown as ComInterface>::IID,
+        &mut object,
+    );
+    if FAILED(hr) {
+        for result in results.iter_mut() {
+            result.hr = hr;
+        }
+        return hr;
+    }
+    let object = ComRc::<dyn IUnknown>::from_raw(object as *mut _);
+    for result in results.iter_mut() {
+        result.hr = object.query_interface(result.iid, &mut result.itf);
+    }
+
+    match results.iter().filter(|result| !FAILED(result.hr)).count() {
+        0 => E_NOINTERFACE,
+        found if found < results.len() => CO_S_NOTALLINTERFACES,
+        _ => S_OK,
+    }
+}
+
+lazy_static! {
+    /// The in-memory registry backing the `Reg*` functions, keyed by lower case key path
+    static REGISTRY: Mutex<BTreeMap<String, BTreeMap<String, Vec<u8>>>> = Mutex::new(BTreeMap::new());
+}
+
+fn registry() -> std::sync::MutexGuard<'static, BTreeMap<String, BTreeMap<String, Vec<u8>>>> {
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Resolve a key handle and a sub key to the full key path
+unsafe fn key_path(hKey: HKEY, lpSubKey: *const i8) -> Option<String> {
+    let base = match hKey as usize {
+        0x8000_0000 => "HKEY_CLASSES_ROOT".to_owned(),
+        0x8000_0001 => "HKEY_CURRENT_USER".to_owned(),
+        0x8000_0002 => "HKEY_LOCAL_MACHINE".to_owned(),
+        0x8000_0003 => "HKEY_USERS".to_owned(),
+        _ if hKey.is_null() => return None,
+        _ => (*(hKey as *const String)).clone(),
+    };
+    if lpSubKey.is_null() || *lpSubKey == 0 {
+        return Some(base.to_lowercase());
+    }
+    let sub_key = CStr::from_ptr(lpSubKey).to_string_lossy();
+    Some(format!("{}\\{}", base, sub_key).to_lowercase())
+}
+
+/// Portable version of [`RegCreateKeyExA`](https://docs.microsoft.com/en-us/windows/win32/api/winreg/nf-winreg-regcreatekeyexa)
+/// backed by an in-memory registry
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "system" fn RegCreateKeyExA(
+    hKey: HKEY,
+    lpSubKey: *const i8,
+    _Reserved: u32,
+    _lpClass: *mut u8,
+    _dwOptions: u32,
+    _samDesired: u32,
+    _lpSecurityAttributes: *mut c_void,
+    phkResult: *mut HKEY,
+    _lpdwDisposition: *mut u32,
+) -> LSTATUS {
+    let path = match key_path(hKey, lpSubKey) {
+        Some(path) => path,
+        None => return ERROR_INVALID_HANDLE,
+    };
+    let mut registry = registry();
+    let mut parent = String::new();
+    for segment in path.split('\\') {
+        if !parent.is_empty() {
+            parent.push('\\');
+        }
+        parent.push_str(segment);
+        registry.entry(parent.clone()).or_default();
+    }
+    *phkResult = Box::into_raw(Box::new(path)) as HKEY;
+    ERROR_SUCCESS
+}
+
+/// Portable version of [`RegSetValueExA`](https://docs.microsoft.com/en-us/windows/win32/api/winreg/nf-winreg-regsetvalueexa)
+/// backed by an in-memory registry
+pub unsafe extern "system" fn RegSetValueExA(
+    hKey: HKEY,
+    lpValueName: *const i8,
+    _Reserved: u32,
+    _dwType: u32,
+    lpData: *const u8,
+    cbData: u32,
+) -> LSTATUS {
+    let path = match key_path(hKey, std::ptr::null()) {
+        Some(path) => path,
+        None => return ERROR_INVALID_HANDLE,
+    };
+    let name = if lpValueName.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(lpValueName).to_string_lossy().to_lowercase()
+    };
+    let data = std::slice::from_raw_parts(lpData, cbData as usize).to_vec();
+    match registry().get_mut(&path) {
+        Some(values) => {
+            values.insert(name, data);
+            ERROR_SUCCESS
+        }
+        None => ERROR_FILE_NOT_FOUND,
+    }
+}
+
+/// Portable version of [`RegCloseKey`](https://docs.microsoft.com/en-us/windows/win32/api/winreg/nf-winreg-regclosekey)
+pub unsafe extern "system" fn RegCloseKey(hKey: HKEY) -> LSTATUS {
+    if hKey.is_null() {
+        return ERROR_INVALID_HANDLE;
+    }
+    if (hKey as usize) & !0xF != 0x8000_0000 {
+        drop(Box::from_raw(hKey as *mut String));
+    }
+    ERROR_SUCCESS
+}
+
+/// Portable version of [`RegDeleteKeyA`](https://docs.microsoft.com/en-us/windows/win32/api/winreg/nf-winreg-regdeletekeya)
+/// backed by an in-memory registry
+///
+/// Like its Windows counterpart this fails when the key still has sub keys.
+pub unsafe extern "system" fn RegDeleteKeyA(hKey: HKEY, lpSubKey: *const i8) -> LSTATUS {
+    let path = match key_path(hKey, lpSubKey) {
+        Some(path) => path,
+        None => return ERROR_INVALID_HANDLE,
+    };
+    let mut registry = registry();
+    if !registry.contains_key(&path) {
+        return ERROR_FILE_NOT_FOUND;
+    }
+    let prefix = format!("{}\\", path);
+    if registry.keys().any(|key| key.starts_with(&prefix)) {
+        return ERROR_ACCESS_DENIED;
+    }
+    registry.remove(&path);
+    ERROR_SUCCESS
+}
+
+/// Portable version of [`GetModuleHandleA`](https://docs.microsoft.com/en-us/windows/win32/api/libloaderapi/nf-libloaderapi-getmodulehandlea)
+///
+/// Modules are not tracked so this always returns null.
+pub unsafe extern "system" fn GetModuleHandleA(_lpModuleName: *const i8) -> *mut c_void {
+    std::ptr::null_mut()
+}
+
+/// Portable version of [`GetModuleFileNameA`](https://docs.microsoft.com/en-us/windows/win32/api/libloaderapi/nf-libloaderapi-getmodulefilenamea)
+///
+/// Writes the path of the current executable.
+pub unsafe extern "system" fn GetModuleFileNameA(
+    _hModule: *mut c_void,
+    lpFilename: *mut i8,
+    nSize: u32,
+) -> u32 {
+    let path = std::env::current_exe()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let len = std::cmp::min(path.len(), (nSize as usize).saturating_sub(1));
+    std::ptr::copy_nonoverlapping(path.as_ptr(), lpFilename as *mut u8, len);
+    if nSize > 0 {
+        *lpFilename.add(len) = 0;
+    }
+    len as u32
+}
+
+/// Layout of a `BSTR` holding `len` UTF-16 code units: a `u32` byte length, the
+/// characters and a null terminator
+fn bstr_layout(len: usize) -> std::alloc::Layout {
+    std::alloc::Layout::from_size_align(4 + 2 * (len + 1), 4).expect("BSTR is too long")
+}
+
+/// Portable version of [`SysAllocStringLen`](https://docs.microsoft.com/en-us/windows/win32/api/oleauto/nf-oleauto-sysallocstringlen)
+pub unsafe extern "system" fn SysAllocStringLen(strIn: *const u16, ui: u32) -> BSTR {
+    let len = ui as usize;
+    let prefix = std::alloc::alloc(bstr_layout(len)) as *mut u32;
+    if prefix.is_null() {
+        return std::ptr::null_mut();
+    }
+    *prefix = ui * 2;
+    let bstr = prefix.add(1) as *mut u16;
+    if strIn.is_null() {
+        std::ptr::write_bytes(bstr, 0, len);
+    } else {
+        std::ptr::copy_nonoverlapping(strIn, bstr, len);
+    }
+    *bstr.add(len) = 0;
+    bstr
+}
+
+/// Portable version of [`SysFreeString`](https://docs.microsoft.com/en-us/windows/win32/api/oleauto/nf-oleauto-sysfreestring)
+pub unsafe extern "system" fn SysFreeString(bstrString: BSTR) {
+    if bstrString.is_null() {
+        return;
+    }
+    let len = SysStringLen(bstrString) as usize;
+    std::alloc::dealloc((bstrString as *mut u32).sub(1) as *mut u8, bstr_layout(len));
+}
+
+/// Portable version of [`SysStringLen`](https://docs.microsoft.com/en-us/windows/win32/api/oleauto/nf-oleauto-sysstringlen)
+pub unsafe extern "system" fn SysStringLen(pbstr: BSTR) -> u32 {
+    if pbstr.is_null() {
+        return 0;
+    }
+    *(pbstr as *mut u32).sub(1) / 2
+}
+
+/// Portable version of [`CoTaskMemAlloc`](https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-cotaskmemalloc)
+pub unsafe extern "system" fn CoTaskMemAlloc(cb: usize) -> *mut c_void {
+    task_alloc::alloc(cb)
+}
+
+/// Portable version of [`CoTaskMemRealloc`](https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-cotaskmemrealloc)
+pub unsafe extern "system" fn CoTaskMemRealloc(pv: *mut c_void, cb: usize) -> *mut c_void {
+    task_alloc::realloc(pv, cb)
+}
+
+/// Portable version of [`CoTaskMemFree`](https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-cotaskmemfree)
+pub unsafe extern "system" fn CoTaskMemFree(pv: *mut c_void) {
+    task_alloc::free(pv)
+}
+
+/// Portable version of [`CoGetMalloc`](https://docs.microsoft.com/en-us/windows/win32/api/combaseapi/nf-combaseapi-cogetmalloc)
+pub unsafe extern "system" fn CoGetMalloc(
+    dwMemContext: u32,
+    ppMalloc: *mut *mut c_void,
+) -> HRESULT {
+    if ppMalloc.is_null() {
+        return E_POINTER;
+    }
+    if dwMemContext != MEMCTX_TASK {
+        *ppMalloc = std::ptr::null_mut();
+        return E_INVALIDARG;
+    }
+    *ppMalloc = task_alloc::TaskAllocator::create();
+    S_OK
+}
+
+thread_local! {
+    /// The error object set by the last `SetErrorInfo` on this thread
+    static ERROR_INFO: RefCell<Option<ComRc<dyn IUnknown>>> = RefCell::new(None);
+}
+
+/// Portable version of [`SetErrorInfo`](https://docs.microsoft.com/en-us/windows/win32/api/oleauto/nf-oleauto-seterrorinfo)
+///
+/// The error object is kept in a thread local until it is retrieved with `GetErrorInfo`.
+pub unsafe extern "system" fn SetErrorInfo(_dwReserved: u32, perrinfo: *mut c_void) -> HRESULT {
+    let info = if perrinfo.is_null() {
+        None
+    } else {
+        Some(
+            ComPtr::<dyn IUnknown>::new(perrinfo as *mut _)
+                .clone()
+                .upgrade(),
+        )
+    };
+    ERROR_INFO.with(|error_info| error_info.replace(info));
+    S_OK
+}
+
+/// Portable version of [`GetErrorInfo`](https://docs.microsoft.com/en-us/windows/win32/api/oleauto/nf-oleauto-geterrorinfo)
+///
+/// Transfers ownership of the error object set on this thread to the caller and clears it.
+pub unsafe extern "system" fn GetErrorInfo(
+    _dwReserved: u32,
+    pperrinfo: *mut *mut c_void,
+) -> HRESULT {
+    match ERROR_INFO.with(|error_info| error_info.borrow_mut().take()) {
+        Some(info) => {
+            *pperrinfo = ComPtr::from(info).as_raw() as *mut c_void;
+            S_OK
+        }
+        None => {
+            *pperrinfo = std::ptr::null_mut();
+            S_FALSE
+        }
+    }
+}
+
+/// A message posted to a thread, the parts of `MSG` that cross threads
+struct QueuedMessage {
+    message: u32,
+    w_param: usize,
+    l_param: isize,
+}
+
+/// The messages waiting in a thread's message queue
+#[derive(Default)]
+struct QueueState {
+    messages: VecDeque<QueuedMessage>,
+    /// The exit code of a pending `PostQuitMessage`
+    quit: Option<i32>,
+    /// Set once the owning thread has exited, after which nothing can be posted
+    closed: bool,
+}
+
+/// The message queue of a thread
+#[derive(Default)]
+struct MessageQueue {
+    state: Mutex<QueueState>,
+    posted: Condvar,
+}
+
+lazy_static! {
+    /// The message queues of all threads that created one, keyed by thread ID
+    static MESSAGE_QUEUES: Mutex<BTreeMap<u32, Arc<MessageQueue>>> = Mutex::new(BTreeMap::new());
+}
+/// The ID of the next thread that asks for its ID
+static NEXT_THREAD_ID: AtomicU32 = AtomicU32::new(1);
+
+/// Removes the message queue of a thread when it exits
+struct MessageQueueOwner {
+    thread_id: u32,
+    queue: Arc<MessageQueue>,
+}
+
+impl Drop for MessageQueueOwner {
+    fn drop(&mut self) {
+        MESSAGE_QUEUES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.thread_id);
+        let messages = {
+            let mut state = self.queue.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.closed = true;
+            std::mem::take(&mut state.messages)
+        };
+        // Unlike on Windows, closures posted with `StaHandle::post` are dropped instead
+        // of leaked, so that whoever waits for them is woken up
+        for message in messages {
+            unsafe { crate::runtime::discard_thread_message(message.message, message.l_param) };
+        }
+    }
+}
+
+thread_local! {
+    static THREAD_ID: u32 = NEXT_THREAD_ID.fetch_add(1, Ordering::SeqCst);
+    static MESSAGE_QUEUE: RefCell<Option<MessageQueueOwner>> = RefCell::new(None);
+}
+
+/// The message queue of the current thread, created on first use like the one of a
+/// Windows thread calling a message function
+fn current_message_queue() -> Arc<MessageQueue> {
+    MESSAGE_QUEUE.with(|owner| {
+        owner
+            .borrow_mut()
+            .get_or_insert_with(|| {
+                let thread_id = unsafe { GetCurrentThreadId() };
+                let queue = Arc::new(MessageQueue::default());
+                MESSAGE_QUEUES
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(thread_id, queue.clone());
+                MessageQueueOwner { thread_id, queue }
+            })
+            .queue
+            .clone()
+    })
+}
+
+/// Take the next message from a queue's state into `lpMsg`. Returns false if there is none.
+unsafe fn take_message(state: &mut QueueState, lpMsg: *mut MSG, remove: bool) -> bool {
+    let (message, w_param, l_param) = match state.messages.front() {
+        Some(message) => (message.message, message.w_param, message.l_param),
+        None => match state.quit {
+            // `WM_QUIT` is only retrieved once all posted messages are handled
+            Some(exit_code) => (WM_QUIT, exit_code as usize, 0),
+            None => return false,
+        },
+    };
+    if remove && state.messages.pop_front().is_none() {
+        state.quit = None;
+    }
+    *lpMsg = MSG {
+        hwnd: std::ptr::null_mut(),
+        message,
+        w_param,
+        l_param,
+        time: 0,
+        pt: POINT::default(),
+    };
+    true
+}
+
+/// Portable version of [`GetCurrentThreadId`](https://docs.microsoft.com/en-us/windows/win32/api/processthreadsapi/nf-processthreadsapi-getcurrentthreadid)
+pub unsafe extern "system" fn GetCurrentThreadId() -> u32 {
+    THREAD_ID.with(|id| *id)
+}
+
+/// Portable version of [`PostThreadMessageW`](https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-postthreadmessagew)
+///
+/// Fails if the thread has not created a message queue yet or has exited.
+pub unsafe extern "system" fn PostThreadMessageW(
+    idThread: u32,
+    Msg: u32,
+    wParam: usize,
+    lParam: isize,
+) -> BOOL {
+    let queue = match MESSAGE_QUEUES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&idThread)
+    {
+        Some(queue) => queue.clone(),
+        None => return 0,
+    };
+    let mut state = queue.state.lock().unwrap_or_else(|e| e.into_inner());
+    if state.closed {
+        return 0;
+    }
+    state.messages.push_back(QueuedMessage {
+        message: Msg,
+        w_param: wParam,
+        l_param: lParam,
+    });
+    queue.posted.notify_one();
+    1
+}
+
+/// Portable version of [`GetMessageW`](https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-getmessagew)
+///
+/// Only thread messages exist, so the window and filter arguments are ignored.
+pub unsafe extern "system" fn GetMessageW(
+    lpMsg: *mut MSG,
+    _hWnd: HWND,
+    _wMsgFilterMin: u32,
+    _wMsgFilterMax: u32,
+) -> BOOL {
+    let queue = current_message_queue();
+    let mut state = queue.state.lock().unwrap_or_else(|e| e.into_inner());
+    while !take_message(&mut state, lpMsg, true) {
+        state = queue.posted.wait(state).unwrap_or_else(|e| e.into_inner());
+    }
+    ((*lpMsg).message != WM_QUIT) as BOOL
+}
+
+/// Portable version of [`PeekMessageW`](https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-peekmessagew)
+///
+/// Only thread messages exist, so the window and filter arguments are ignored.
+pub unsafe extern "system" fn PeekMessageW(
+    lpMsg: *mut MSG,
+    _hWnd: HWND,
+    _wMsgFilterMin: u32,
+    _wMsgFilterMax: u32,
+    wRemoveMsg: u32,
+) -> BOOL {
+    let queue = current_message_queue();
+    let mut state = queue.state.lock().unwrap_or_else(|e| e.into_inner());
+    take_message(&mut state, lpMsg, wRemoveMsg & PM_REMOVE != 0) as BOOL
+}
+
+/// Portable version of [`TranslateMessage`](https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-translatemessage)
+///
+/// There are no keyboard messages to translate.
+pub unsafe extern "system" fn TranslateMessage(_lpMsg: *const MSG) -> BOOL {
+    0
+}
+
+/// Portable version of [`DispatchMessageW`](https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-dispatchmessagew)
+///
+/// There are no windows, so thread messages are not dispatched anywhere.
+pub unsafe extern "system" fn DispatchMessageW(_lpMsg: *const MSG) -> isize {
+    0
+}
+
+/// Portable version of [`PostQuitMessage`](https://docs.microsoft.com/en-us/windows/win32/api/winuser/nf-winuser-postquitmessage)
+pub unsafe extern "system" fn PostQuitMessage(nExitCode: i32) {
+    let queue = current_message_queue();
+    let mut state = queue.state.lock().unwrap_or_else(|e| e.into_inner());
+    state.quit = Some(nExitCode);
+    queue.posted.notify_one();
+}
@@ -0,0 +1,227 @@
+//! The global interface table of the portable runtime
+//!
+//! Objects registered from an STA thread are only called on that thread. Resolving them
+//! on another thread returns a proxy created by the factory registered for the IID,
+//! which stands in for the proxy/stub DLLs Windows finds through the registry.
+//! Objects answering `IAgileObject` are handed out directly in every apartment.
+//!
+//! Objects registered from the MTA are handed out directly as well, also to STA threads.
+//! There is no thread the portable runtime could marshal their calls to, and the object
+//! is thread-safe as it lives in the MTA, so calling it from an STA thread only loses
+//! the guarantee that it is never called on one.
+
+use super::{current_apartment, COINIT_APARTMENTTHREADED};
+use crate::interfaces::iagile_object::IAgileObject;
+use crate::interfaces::iglobal_interface_table::IGlobalInterfaceTable;
+use crate::interfaces::iunknown::{IUnknown, IUnknownVPtr};
+use crate::runtime::StaHandle;
+use crate::sys::{
+    CO_E_NOTINITIALIZED, E_INVALIDARG, E_POINTER, FAILED, HRESULT, IID, REGDB_E_IIDNOTREG,
+    RPC_E_DISCONNECTED, S_OK,
+};
+use crate::{ComInterface, ComPtr};
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::ffi::c_void;
+use std::sync::Mutex;
+
+/// Creates a proxy for an interface pointer owned by another thread
+pub(crate) type ProxyFactory = unsafe fn(*mut c_void, StaHandle) -> *mut c_void;
+
+/// A registered interface pointer
+struct Entry {
+    /// The registered interface pointer, holding a reference
+    object: usize,
+    /// The thread of the STA owning the object, `None` for the MTA
+    owner: Option<StaHandle>,
+}
+
+lazy_static! {
+    /// The registered interfaces keyed by cookie, and the next cookie to hand out
+    static TABLE: Mutex<(BTreeMap<u32, Entry>, u32)> = Mutex::new((BTreeMap::new(), 1));
+    /// The proxy factories for each IID
+    static PROXIES: Mutex<Vec<(IID, ProxyFactory)>> = Mutex::new(Vec::new());
+}
+
+/// Register how proxies for the interface `iid` are created
+pub(crate) fn register_proxy(iid: &IID, factory: ProxyFactory) {
+    let mut proxies = PROXIES.lock().unwrap_or_else(|e| e.into_inner());
+    if !proxies.iter().any(|(registered, _)| registered == iid) {
+        proxies.push((*iid, factory));
+    }
+}
+
+fn proxy_factory(iid: &IID) -> Option<ProxyFactory> {
+    PROXIES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|(registered, _)| registered == iid)
+        .map(|(_, factory)| *factory)
+}
+
+fn table() -> std::sync::MutexGuard<'static, (BTreeMap<u32, Entry>, u32)> {
+    TABLE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Query a registered object for `iid`, returning the result as an address
+unsafe fn query(object: usize, iid: &IID) -> Result<usize, HRESULT> {
+    let object = ComPtr::<dyn IUnknown>::new(object as *mut _);
+    let mut ppv = std::ptr::null_mut::<c_void>();
+    let hr = object.query_interface(iid, &mut ppv);
+    if FAILED(hr) {
+        Err(hr)
+    } else {
+        Ok(ppv as usize)
+    }
+}
+
+/// Release a registered object
+unsafe fn release(object: usize) {
+    ComPtr::<dyn IUnknown>::new(object as *mut _).release();
+}
+
+/// The object returned for `CLSID_STD_GLOBAL_INTERFACE_TABLE`. All instances share the table.
+#[repr(C)]
+pub(crate) struct GlobalInterfaceTable {
+    vptr: *const <dyn IGlobalInterfaceTable as ComInterface>::VTable,
+    ref_count: Cell<u32>,
+}
+
+impl GlobalInterfaceTable {
+    /// Create an instance and query it for `riid`
+    pub(crate) unsafe fn create(riid: *const IID, ppv: *mut *mut c_void) -> HRESULT {
+        use crate::offset::Zero;
+        use crate::ProductionComInterface;
+
+        let vptr = Box::into_raw(Box::new(
+            <dyn IGlobalInterfaceTable as ProductionComInterface<Self>>::vtable::<Zero>(),
+        ));
+        let table = Box::into_raw(Box::new(GlobalInterfaceTable {
+            vptr,
+            ref_count: Cell::new(1),
+        }));
+        let hr = (*table).query_interface(riid, ppv);
+        (*table).release();
+        hr
+    }
+}
+
+impl_iunknown!([] GlobalInterfaceTable => dyn IGlobalInterfaceTable);
+
+impl IGlobalInterfaceTable for GlobalInterfaceTable {
+    unsafe fn register_interface_in_global(
+        &self,
+        unk: *mut IUnknownVPtr,
+        riid: *const IID,
+        cookie: *mut u32,
+    ) -> HRESULT {
+        if unk.is_null() || riid.is_null() || cookie.is_null() {
+            return E_POINTER;
+        }
+        let owner = match current_apartment() {
+            None => return CO_E_NOTINITIALIZED,
+            Some(COINIT_APARTMENTTHREADED) => Some(StaHandle::current()),
+            Some(_) => None,
+        };
+        let object = match query(unk as usize, &*riid) {
+            Ok(object) => object,
+            Err(hr) => return hr,
+        };
+        let owner = match query(object, &<dyn IAgileObject as ComInterface>::IID) {
+            Ok(agile) => {
+                release(agile);
+                None
+            }
+            Err(_) => owner,
+        };
+
+        let mut table = table();
+        let next = table.1;
+        table.1 = next.wrapping_add(1).max(1);
+        table.0.insert(next, Entry { object, owner });
+        *cookie = next;
+        S_OK
+    }
+
+    unsafe fn revoke_interface_from_global(&self, cookie: u32) -> HRESULT {
+        // The lock is not held while releasing, as the object may revoke the entries it
+        // holds itself when it is destroyed
+        let entry = match table().0.remove(&cookie) {
+            Some(entry) => entry,
+            None => return E_INVALIDARG,
+        };
+        match entry.owner {
+            Some(owner) if !owner.is_current() => {
+                // If the owning thread is gone the reference is leaked
+                let object = entry.object;
+                let _ = owner.post(move || release(object));
+            }
+            _ => release(entry.object),
+        }
+        S_OK
+    }
+
+    unsafe fn get_interface_from_global(
+        &self,
+        cookie: u32,
+        riid: *const IID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT {
+        if riid.is_null() || ppv.is_null() {
+            return E_POINTER;
+        }
+        *ppv = std::ptr::null_mut();
+        let iid = *riid;
+
+        let owner = {
+            let table = table();
+            let entry = match table.0.get(&cookie) {
+                Some(entry) => entry,
+                None => return E_INVALIDARG,
+            };
+            match &entry.owner {
+                Some(owner) if !owner.is_current() => owner.clone(),
+                _ => {
+                    // Hold a reference so that the object can be queried after
+                    // unlocking, in case it calls the table itself
+                    let object = entry.object;
+                    ComPtr::<dyn IUnknown>::new(object as *mut _).add_ref();
+                    drop(table);
+                    let queried = query(object, &iid);
+                    release(object);
+                    return match queried {
+                        Ok(object) => {
+                            *ppv = object as *mut c_void;
+                            S_OK
+                        }
+                        Err(hr) => hr,
+                    };
+                }
+            }
+        };
+
+        let factory = match proxy_factory(&iid) {
+            Some(factory) => factory,
+            None => return REGDB_E_IIDNOTREG,
+        };
+        // Look the entry up again on the owning thread, which is where it is released
+        // when revoked
+        let queried = owner.run(move || {
+            let object = table().0.get(&cookie).map(|entry| entry.object);
+            match object {
+                Some(object) => query(object, &iid),
+                None => Err(E_INVALIDARG),
+            }
+        });
+        match queried {
+            Ok(Ok(object)) => {
+                *ppv = factory(object as *mut c_void, owner);
+                S_OK
+            }
+            Ok(Err(hr)) => hr,
+            Err(_) => RPC_E_DISCONNECTED,
+        }
+    }
+}
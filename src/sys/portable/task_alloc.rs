@@ -0,0 +1,260 @@
+//! The task allocator of the portable runtime
+//!
+//! Every allocation starts with a header holding its size, so freeing it only needs the
+//! pointer. While a [`LeakCheck`] is active, new allocations are also recorded until they
+//! are freed, which lets tests find task memory that was never freed.
+
+use crate::interfaces::imalloc::IMalloc;
+
+use std::alloc::Layout;
+use std::collections::BTreeMap;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread::ThreadId;
+
+/// The size of the header in front of every allocation, which is also its alignment
+const HEADER: usize = 16;
+
+/// A recorded allocation
+struct Allocation {
+    size: usize,
+    thread: ThreadId,
+    sequence: usize,
+}
+
+/// The number of active leak checks
+static LEAK_CHECKS: AtomicUsize = AtomicUsize::new(0);
+/// Whether any allocation was ever recorded, so frees only look them up after that
+static RECORDED: AtomicBool = AtomicBool::new(false);
+/// The sequence number of the next recorded allocation
+static SEQUENCE: AtomicUsize = AtomicUsize::new(0);
+lazy_static! {
+    /// The live recorded allocations by address
+    static ALLOCATIONS: Mutex<BTreeMap<usize, Allocation>> = Mutex::new(BTreeMap::new());
+}
+
+fn allocations() -> std::sync::MutexGuard<'static, BTreeMap<usize, Allocation>> {
+    ALLOCATIONS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn layout(size: usize) -> Option<Layout> {
+    Layout::from_size_align(HEADER.checked_add(size)?, HEADER).ok()
+}
+
+fn record(ptr: *mut c_void, size: usize) {
+    if LEAK_CHECKS.load(Ordering::SeqCst) == 0 {
+        return;
+    }
+    RECORDED.store(true, Ordering::SeqCst);
+    let allocation = Allocation {
+        size,
+        thread: std::thread::current().id(),
+        sequence: SEQUENCE.fetch_add(1, Ordering::SeqCst),
+    };
+    allocations().insert(ptr as usize, allocation);
+}
+
+fn forget(ptr: *mut c_void) {
+    if RECORDED.load(Ordering::SeqCst) {
+        allocations().remove(&(ptr as usize));
+    }
+}
+
+/// Allocate `size` bytes, returning null if out of memory
+pub(crate) unsafe fn alloc(size: usize) -> *mut c_void {
+    let layout = match layout(size) {
+        Some(layout) => layout,
+        None => return std::ptr::null_mut(),
+    };
+    let header = std::alloc::alloc(layout);
+    if header.is_null() {
+        return std::ptr::null_mut();
+    }
+    *(header as *mut usize) = size;
+    let ptr = header.add(HEADER) as *mut c_void;
+    record(ptr, size);
+    ptr
+}
+
+/// The size `ptr` was allocated with
+pub(crate) unsafe fn size(ptr: *mut c_void) -> usize {
+    *((ptr as *mut u8).sub(HEADER) as *mut usize)
+}
+
+/// Free an allocation, doing nothing for null
+pub(crate) unsafe fn free(ptr: *mut c_void) {
+    if ptr.is_null() {
+        return;
+    }
+    forget(ptr);
+    let size = size(ptr);
+    std::alloc::dealloc(
+        (ptr as *mut u8).sub(HEADER),
+        layout(size).expect("allocated with this size"),
+    );
+}
+
+/// Change the size of an allocation, leaving it untouched and returning null if out of
+/// memory
+pub(crate) unsafe fn realloc(ptr: *mut c_void, size: usize) -> *mut c_void {
+    if ptr.is_null() {
+        return alloc(size);
+    }
+    if size == 0 {
+        free(ptr);
+        return std::ptr::null_mut();
+    }
+    let old_size = self::size(ptr);
+    let new_layout = match layout(size) {
+        Some(layout) => layout,
+        None => return std::ptr::null_mut(),
+    };
+    let header = std::alloc::realloc(
+        (ptr as *mut u8).sub(HEADER),
+        layout(old_size).expect("allocated with this size"),
+        new_layout.size(),
+    );
+    if header.is_null() {
+        return std::ptr::null_mut();
+    }
+    forget(ptr);
+    *(header as *mut usize) = size;
+    let ptr = header.add(HEADER) as *mut c_void;
+    record(ptr, size);
+    ptr
+}
+
+/// Task memory allocated while a [`LeakCheck`] was active and not freed since
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Leak {
+    /// The address of the allocation
+    pub address: usize,
+    /// The size of the allocation in bytes
+    pub size: usize,
+}
+
+/// Records the task memory allocated on the current thread until dropped, to find the
+/// allocations that were never freed
+///
+/// Only available with the portable runtime.
+///
+/// ```rust
+/// use com::alloc::{task_alloc, task_free, LeakCheck};
+///
+/// let check = LeakCheck::start();
+/// let ptr = task_alloc(8);
+/// assert_eq!(check.leaks().len(), 1);
+/// unsafe { task_free(ptr) };
+/// check.assert_no_leaks();
+/// ```
+pub struct LeakCheck {
+    thread: ThreadId,
+    start: usize,
+    // Only allocations of the thread that started the check are reported
+    _not_send: PhantomData<*const ()>,
+}
+
+impl LeakCheck {
+    /// Start recording allocations
+    pub fn start() -> Self {
+        LEAK_CHECKS.fetch_add(1, Ordering::SeqCst);
+        LeakCheck {
+            thread: std::thread::current().id(),
+            start: SEQUENCE.load(Ordering::SeqCst),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// The allocations of this thread since the check started that were not freed, in
+    /// the order they were made
+    pub fn leaks(&self) -> Vec<Leak> {
+        let mut leaks = allocations()
+            .iter()
+            .filter(|(_, allocation)| {
+                allocation.thread == self.thread && allocation.sequence >= self.start
+            })
+            .map(|(address, allocation)| (allocation.sequence, *address, allocation.size))
+            .collect::<Vec<_>>();
+        leaks.sort_unstable();
+        leaks
+            .into_iter()
+            .map(|(_, address, size)| Leak { address, size })
+            .collect()
+    }
+
+    /// Panic if any allocation since the check started was not freed
+    pub fn assert_no_leaks(&self) {
+        let leaks = self.leaks();
+        assert!(leaks.is_empty(), "leaked task memory: {:?}", leaks);
+    }
+}
+
+impl Drop for LeakCheck {
+    fn drop(&mut self) {
+        LEAK_CHECKS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The object returned by `CoGetMalloc`
+#[repr(C)]
+pub(crate) struct TaskAllocator {
+    vptr: *const <dyn IMalloc as crate::ComInterface>::VTable,
+    ref_count: AtomicU32,
+}
+
+impl TaskAllocator {
+    /// Create an instance, returning it as an `IMalloc` interface pointer
+    pub(crate) fn create() -> *mut c_void {
+        use crate::offset::Zero;
+        use crate::ProductionComInterface;
+
+        let vptr = Box::into_raw(Box::new(
+            <dyn IMalloc as ProductionComInterface<Self>>::vtable::<Zero>(),
+        ));
+        let allocator = Box::into_raw(Box::new(TaskAllocator {
+            vptr,
+            ref_count: AtomicU32::new(1),
+        }));
+        allocator as *mut c_void
+    }
+}
+
+impl_iunknown!([] TaskAllocator => dyn IMalloc);
+
+impl IMalloc for TaskAllocator {
+    unsafe fn alloc(&self, cb: usize) -> *mut c_void {
+        alloc(cb)
+    }
+
+    unsafe fn realloc(&self, pv: *mut c_void, cb: usize) -> *mut c_void {
+        realloc(pv, cb)
+    }
+
+    unsafe fn free(&self, pv: *mut c_void) {
+        free(pv)
+    }
+
+    unsafe fn get_size(&self, pv: *mut c_void) -> usize {
+        if pv.is_null() {
+            // Documented to return -1 as a SIZE_T
+            return std::usize::MAX;
+        }
+        size(pv)
+    }
+
+    unsafe fn did_alloc(&self, pv: *mut c_void) -> i32 {
+        if pv.is_null() {
+            return -1;
+        }
+        // The header does not identify an allocation, unless it was recorded
+        if RECORDED.load(Ordering::SeqCst) && allocations().contains_key(&(pv as usize)) {
+            1
+        } else {
+            -1
+        }
+    }
+
+    unsafe fn heap_minimize(&self) {}
+}
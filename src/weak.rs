@@ -0,0 +1,195 @@
+//! Weak references to COM objects
+//!
+//! A [`ComWeak`] does not keep the object alive, which breaks reference cycles between
+//! parent and child objects. It is the COM counterpart of `std::rc::Weak`:
+//!
+//! ```rust,ignore
+//! let weak = parent.downgrade()?;
+//! drop(parent);
+//! assert!(weak.upgrade().is_none());
+//! ```
+//!
+//! The object must implement `IWeakReferenceSource`. A co_class with the `weak` option
+//! does so with a control block that is only allocated once the first weak reference
+//! is requested.
+//!
+//! [`ComWeak`]: ../struct.ComWeak.html
+
+use crate::interfaces::iunknown::IUnknown;
+use crate::interfaces::iweak_reference::{IWeakReference, IWeakReferenceVPtr};
+use crate::interfaces::iweak_reference_source::IWeakReferenceSource;
+use crate::offset::Zero;
+use crate::sys::{E_NOINTERFACE, E_POINTER, FAILED, HRESULT, IID, S_OK};
+use crate::{ComInterface, ComPtr, ComRc, ProductionComInterface};
+
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicPtr, AtomicU32, Ordering};
+use std::sync::Mutex;
+
+/// A reference to a COM object that does not keep it alive
+pub struct ComWeak<T: ComInterface + ?Sized> {
+    reference: ComRc<dyn IWeakReference>,
+    _marker: PhantomData<*const T>,
+}
+
+impl<T: ComInterface + ?Sized> ComWeak<T> {
+    /// Get a weak reference to `object` through its `IWeakReferenceSource`
+    ///
+    /// Fails with `E_NOINTERFACE` if the object does not implement it.
+    pub fn new(object: &ComRc<T>) -> Result<Self, HRESULT> {
+        let source = object
+            .get_interface::<dyn IWeakReferenceSource>()
+            .ok_or(E_NOINTERFACE)?;
+        let mut reference = std::ptr::null_mut::<IWeakReferenceVPtr>();
+        let hr = unsafe { source.get_weak_reference(&mut reference) };
+        if FAILED(hr) {
+            return Err(hr);
+        }
+        Ok(ComWeak {
+            reference: unsafe { ComRc::from_raw(reference as *mut *mut _) },
+            _marker: PhantomData,
+        })
+    }
+
+    /// Get a strong reference to the object, or `None` if it has been released
+    pub fn upgrade(&self) -> Option<ComRc<T>> {
+        let mut ppv = std::ptr::null_mut::<c_void>();
+        let hr = unsafe { self.reference.resolve(&T::IID, &mut ppv) };
+        if FAILED(hr) || ppv.is_null() {
+            None
+        } else {
+            Some(unsafe { ComRc::from_raw(ppv as *mut *mut _) })
+        }
+    }
+}
+
+impl<T: ComInterface + ?Sized> Clone for ComWeak<T> {
+    fn clone(&self) -> Self {
+        ComWeak {
+            reference: self.reference.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// The object a control block resolves to, while it is alive
+#[derive(Copy, Clone)]
+struct Target {
+    /// The object, whose first field is an interface pointer
+    object: *mut c_void,
+    /// Adds a reference to the object unless its reference count dropped to zero
+    try_add_ref: unsafe fn(*mut c_void) -> bool,
+}
+
+/// The control block shared by all weak references to an object
+#[repr(C)]
+struct WeakReference {
+    vptr: *const <dyn IWeakReference as ComInterface>::VTable,
+    ref_count: AtomicU32,
+    target: Mutex<Option<Target>>,
+}
+
+impl WeakReference {
+    fn create(target: Target) -> *mut WeakReference {
+        let vptr = Box::into_raw(Box::new(<dyn IWeakReference as ProductionComInterface<
+            Self,
+        >>::vtable::<Zero>()));
+        Box::into_raw(Box::new(WeakReference {
+            vptr,
+            ref_count: AtomicU32::new(1),
+            target: Mutex::new(Some(target)),
+        }))
+    }
+
+    /// Stop resolving to the object, which is being dropped
+    fn disconnect(&self) {
+        *self.target.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+impl_iunknown!([] WeakReference => dyn IWeakReference);
+
+impl IWeakReference for WeakReference {
+    unsafe fn resolve(&self, riid: *const IID, object_reference: *mut *mut c_void) -> HRESULT {
+        if riid.is_null() || object_reference.is_null() {
+            return E_POINTER;
+        }
+        *object_reference = std::ptr::null_mut();
+
+        // The object is disconnected while dropping it, so holding the lock keeps it
+        // from being dropped until the reference is added
+        let object = {
+            let target = self.target.lock().unwrap_or_else(|e| e.into_inner());
+            match *target {
+                Some(target) if (target.try_add_ref)(target.object) => target.object,
+                _ => return S_OK,
+            }
+        };
+        let object = ComPtr::<dyn IUnknown>::new(object as *mut _);
+        let hr = object.query_interface(riid, object_reference);
+        object.release();
+        hr
+    }
+}
+
+/// The field of a co_class with the `weak` option that holds its control block
+///
+/// The control block is allocated by the first call to `IWeakReferenceSource::GetWeakReference`
+/// and disconnected when the object is dropped.
+#[doc(hidden)]
+#[derive(Default)]
+pub struct WeakReferenceSlot {
+    block: AtomicPtr<WeakReference>,
+}
+
+impl WeakReferenceSlot {
+    /// Implementation of `IWeakReferenceSource::GetWeakReference`
+    ///
+    /// `object` is the co_class, whose first field is an interface pointer, and
+    /// `try_add_ref` adds a reference to it unless its reference count dropped to zero.
+    pub unsafe fn get_weak_reference(
+        &self,
+        object: *mut c_void,
+        try_add_ref: unsafe fn(*mut c_void) -> bool,
+        weak_reference: *mut *mut IWeakReferenceVPtr,
+    ) -> HRESULT {
+        if weak_reference.is_null() {
+            return E_POINTER;
+        }
+        let mut block = self.block.load(Ordering::Acquire);
+        if block.is_null() {
+            let created = WeakReference::create(Target {
+                object,
+                try_add_ref,
+            });
+            block = match self.block.compare_exchange(
+                std::ptr::null_mut(),
+                created,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => created,
+                Err(existing) => {
+                    (*created).release();
+                    existing
+                }
+            };
+        }
+        (*block).add_ref();
+        *weak_reference = block as *mut IWeakReferenceVPtr;
+        S_OK
+    }
+}
+
+impl Drop for WeakReferenceSlot {
+    fn drop(&mut self) {
+        let block = *self.block.get_mut();
+        if !block.is_null() {
+            unsafe {
+                (*block).disconnect();
+                (*block).release();
+            }
+        }
+    }
+}